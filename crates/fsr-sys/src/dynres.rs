@@ -0,0 +1,322 @@
+// ---- Dynamic resolution controller ----
+//
+// Games that create their upscaler with `FFX_UPSCALE_ENABLE_DYNAMIC_RESOLUTION`
+// may render at any size up to `max_render_size` and re-query the render
+// resolution every frame. This controller picks that size: it watches the
+// dispatch's `frame_time_delta` and moves the upscale ratio so the smoothed
+// frame time settles on a budget.
+//
+// Shading cost is roughly proportional to pixel count, i.e. to 1 / ratio^2,
+// so the ratio that would hit the budget is `ratio * sqrt(frame / target)`.
+// The controller moves a fraction of the way there each frame, ignores
+// errors inside a deadband, and quantizes the result so the render size does
+// not change by a pixel every frame.
+//
+// Configured from the `[dynamic_resolution]` section of `oxr.ini`:
+//
+//   [dynamic_resolution]
+//   enabled = true
+//   target_ms = 16.6       ; frame-time budget
+//   min_ratio = 1.0        ; sharpest allowed (display / render)
+//   max_ratio = 2.0        ; blurriest allowed
+//   smoothing = 0.1        ; EMA weight of the newest frame time
+//   gain = 0.25            ; fraction of the correction applied per frame
+//   deadband = 0.05        ; relative error ignored around the target
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DynResConfig {
+    pub target_frame_ms: f32,
+    pub min_ratio: f32,
+    pub max_ratio: f32,
+    pub smoothing: f32,
+    pub gain: f32,
+    pub deadband: f32,
+}
+
+impl Default for DynResConfig {
+    fn default() -> Self {
+        Self {
+            target_frame_ms: 1000.0 / 60.0,
+            min_ratio: 1.0,
+            max_ratio: 2.0,
+            smoothing: 0.1,
+            gain: 0.25,
+            deadband: 0.05,
+        }
+    }
+}
+
+/// Ratio granularity; render sizes only change when the ratio crosses a step.
+pub const DYNRES_RATIO_STEP: f32 = 0.01;
+
+/// Frame times above `target * DYNRES_OUTLIER_FACTOR` (loading hitches, alt-tab)
+/// are clamped before they reach the average.
+pub const DYNRES_OUTLIER_FACTOR: f32 = 4.0;
+
+impl DynResConfig {
+    /// Build the config from `[dynamic_resolution]` values. Returns `None` when
+    /// the section does not enable the controller. Invalid values keep their
+    /// defaults and are returned as `(key, value)` so the caller can log them.
+    pub fn parse<'a>(
+        get: impl Fn(&str) -> Option<&'a str>,
+    ) -> (Option<Self>, Vec<(String, String)>) {
+        let enabled = get("enabled").is_some_and(|v| {
+            matches!(
+                v.trim().to_ascii_lowercase().as_str(),
+                "1" | "true" | "yes" | "on"
+            )
+        });
+        if !enabled {
+            return (None, Vec::new());
+        }
+
+        let mut config = Self::default();
+        let mut rejected = Vec::new();
+        let mut field = |key: &str, slot: &mut f32, valid: fn(f32) -> bool| {
+            let Some(v) = get(key) else { return };
+            match v.trim().parse::<f32>() {
+                Ok(x) if x.is_finite() && valid(x) => *slot = x,
+                _ => rejected.push((key.to_string(), v.to_string())),
+            }
+        };
+        field("target_ms", &mut config.target_frame_ms, |x| x > 0.0);
+        field("min_ratio", &mut config.min_ratio, |x| x >= 1.0);
+        field("max_ratio", &mut config.max_ratio, |x| x >= 1.0);
+        field("smoothing", &mut config.smoothing, |x| x > 0.0 && x <= 1.0);
+        field("gain", &mut config.gain, |x| x > 0.0 && x <= 1.0);
+        field("deadband", &mut config.deadband, |x| {
+            (0.0..1.0).contains(&x)
+        });
+        if config.max_ratio < config.min_ratio {
+            rejected.push(("max_ratio".to_string(), config.max_ratio.to_string()));
+            config.max_ratio = config.min_ratio;
+        }
+        (Some(config), rejected)
+    }
+}
+
+/// Outcome of one controller step, for logs and the overlay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DynResDecision {
+    pub ratio: f32,
+    pub smoothed_frame_ms: f32,
+    /// Whether `ratio` differs from the previous frame's.
+    pub changed: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct DynResController {
+    pub config: DynResConfig,
+    ratio: f32,
+    smoothed_frame_ms: Option<f32>,
+}
+
+impl DynResController {
+    /// Starts at `min_ratio` (native-most) and backs off once frames run long.
+    pub fn new(config: DynResConfig) -> Self {
+        Self {
+            config,
+            ratio: config.min_ratio,
+            smoothed_frame_ms: None,
+        }
+    }
+
+    pub fn ratio(&self) -> f32 {
+        self.ratio
+    }
+
+    pub fn smoothed_frame_ms(&self) -> Option<f32> {
+        self.smoothed_frame_ms
+    }
+
+    /// Feed one frame's `frame_time_delta` (milliseconds). Non-positive or
+    /// non-finite values leave the state untouched.
+    pub fn update(&mut self, frame_time_ms: f32) -> DynResDecision {
+        let c = &self.config;
+        if !(frame_time_ms.is_finite() && frame_time_ms > 0.0) {
+            return DynResDecision {
+                ratio: self.ratio,
+                smoothed_frame_ms: self.smoothed_frame_ms.unwrap_or(0.0),
+                changed: false,
+            };
+        }
+
+        let sample = frame_time_ms.min(c.target_frame_ms * DYNRES_OUTLIER_FACTOR);
+        let smoothed = match self.smoothed_frame_ms {
+            Some(prev) => prev + c.smoothing * (sample - prev),
+            None => sample,
+        };
+        self.smoothed_frame_ms = Some(smoothed);
+
+        let error = smoothed / c.target_frame_ms - 1.0;
+        let previous = self.ratio;
+        if error.abs() > c.deadband {
+            let ideal = previous * (smoothed / c.target_frame_ms).sqrt();
+            let next = previous + c.gain * (ideal - previous);
+            let next = (next / DYNRES_RATIO_STEP).round() * DYNRES_RATIO_STEP;
+            self.ratio = next.clamp(c.min_ratio, c.max_ratio);
+        }
+
+        DynResDecision {
+            ratio: self.ratio,
+            smoothed_frame_ms: smoothed,
+            changed: self.ratio != previous,
+        }
+    }
+
+    /// Render size for the current ratio, truncated like the quality-mode
+    /// table and never below 1.
+    pub fn render_resolution(&self, display_width: u32, display_height: u32) -> (u32, u32) {
+        let scale = |d: u32| ((d as f32 / self.ratio) as u32).max(1);
+        (scale(display_width), scale(display_height))
+    }
+}
+
+/// Run a controller over a frame-time trace and return every decision. Handy
+/// for replaying captured `frame_time_delta` sequences offline.
+pub fn simulate_dynres(config: DynResConfig, frame_times_ms: &[f32]) -> Vec<DynResDecision> {
+    let mut controller = DynResController::new(config);
+    frame_times_ms
+        .iter()
+        .map(|&t| controller.update(t))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> DynResConfig {
+        DynResConfig {
+            target_frame_ms: 16.0,
+            min_ratio: 1.0,
+            max_ratio: 2.0,
+            smoothing: 0.5,
+            gain: 0.5,
+            deadband: 0.05,
+        }
+    }
+
+    /// Frame time of a GPU-bound scene that costs `native_ms` at ratio 1.0:
+    /// proportional to pixel count, i.e. to 1 / ratio^2.
+    fn gpu_bound(native_ms: f32, ratio: f32) -> f32 {
+        native_ms / (ratio * ratio)
+    }
+
+    fn run_closed_loop(native_ms: f32, frames: usize) -> DynResController {
+        let mut controller = DynResController::new(config());
+        for _ in 0..frames {
+            let t = gpu_bound(native_ms, controller.ratio());
+            controller.update(t);
+        }
+        controller
+    }
+
+    #[test]
+    fn parse_needs_enabled_and_reports_bad_values() {
+        let (config, rejected) = DynResConfig::parse(|_| None);
+        assert!(config.is_none() && rejected.is_empty());
+
+        let keys = [
+            ("enabled", "yes"),
+            ("target_ms", "8.3"),
+            ("min_ratio", "0.5"),
+            ("max_ratio", "nan"),
+            ("gain", "0.4"),
+        ];
+        let get = |k: &str| keys.iter().find(|(key, _)| *key == k).map(|(_, v)| *v);
+        let (config, rejected) = DynResConfig::parse(get);
+        let config = config.unwrap();
+        assert_eq!(config.target_frame_ms, 8.3);
+        assert_eq!(config.gain, 0.4);
+        assert_eq!(config.min_ratio, 1.0);
+        assert_eq!(config.max_ratio, 2.0);
+        let keys: Vec<_> = rejected.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, ["min_ratio", "max_ratio"]);
+    }
+
+    #[test]
+    fn max_below_min_is_clamped_up() {
+        let keys = [("enabled", "1"), ("min_ratio", "1.5"), ("max_ratio", "1.2")];
+        let get = |k: &str| keys.iter().find(|(key, _)| *key == k).map(|(_, v)| *v);
+        let (config, rejected) = DynResConfig::parse(get);
+        assert_eq!(config.unwrap().max_ratio, 1.5);
+        assert_eq!(rejected, [("max_ratio".to_string(), "1.2".to_string())]);
+    }
+
+    #[test]
+    fn converges_on_the_budget() {
+        // 24 ms native needs sqrt(24 / 16) = 1.22x to hit 16 ms
+        let controller = run_closed_loop(24.0, 200);
+        let ratio = controller.ratio();
+        assert!((ratio - 1.5f32.sqrt()).abs() < 0.05, "ratio {ratio}");
+        let t = gpu_bound(24.0, ratio);
+        assert!(
+            (t / 16.0 - 1.0).abs() <= config().deadband + 0.02,
+            "frame {t}"
+        );
+    }
+
+    #[test]
+    fn stays_native_under_budget() {
+        let controller = run_closed_loop(10.0, 100);
+        assert_eq!(controller.ratio(), 1.0);
+    }
+
+    #[test]
+    fn saturates_at_max_ratio() {
+        let controller = run_closed_loop(200.0, 200);
+        assert_eq!(controller.ratio(), 2.0);
+        assert_eq!(controller.render_resolution(1920, 1080), (960, 540));
+    }
+
+    #[test]
+    fn deadband_holds_the_ratio() {
+        let decisions = simulate_dynres(config(), &[16.5; 50]);
+        assert!(decisions.iter().all(|d| d.ratio == 1.0 && !d.changed));
+    }
+
+    #[test]
+    fn ratio_moves_in_steps() {
+        let decisions = simulate_dynres(config(), &[30.0; 20]);
+        for d in &decisions {
+            let steps = d.ratio / DYNRES_RATIO_STEP;
+            assert!((steps - steps.round()).abs() < 1e-3, "ratio {}", d.ratio);
+        }
+        assert!(decisions.iter().any(|d| d.changed));
+    }
+
+    #[test]
+    fn hitch_is_clamped_and_invalid_samples_ignored() {
+        let mut controller = DynResController::new(config());
+        controller.update(16.0);
+        let hitch = controller.update(5000.0);
+        // One outlier counts as at most 4x the budget
+        assert_eq!(hitch.smoothed_frame_ms, 16.0 + 0.5 * (64.0 - 16.0));
+
+        let before = (controller.ratio(), controller.smoothed_frame_ms());
+        for t in [0.0, -3.0, f32::NAN, f32::INFINITY] {
+            let d = controller.update(t);
+            assert!(!d.changed);
+        }
+        assert_eq!((controller.ratio(), controller.smoothed_frame_ms()), before);
+    }
+
+    #[test]
+    fn recovers_after_a_heavy_scene() {
+        let mut trace = vec![40.0; 60];
+        trace.extend([8.0; 120]);
+        let decisions = simulate_dynres(config(), &trace);
+        assert!(decisions[59].ratio > 1.4);
+        assert_eq!(decisions.last().unwrap().ratio, 1.0);
+    }
+
+    #[test]
+    fn render_resolution_never_hits_zero() {
+        let mut controller = DynResController::new(config());
+        for _ in 0..100 {
+            controller.update(1000.0);
+        }
+        assert_eq!(controller.render_resolution(1, 1), (1, 1));
+    }
+}
@@ -0,0 +1,273 @@
+// ---- Exposure (CPU reference) ----
+//
+// Temporal upscalers accumulate history in an exposure-normalized space so a
+// game ramping its exposure does not read as motion or ghosting. Colour goes
+// in as `color * exposure / pre_exposure` and comes back out with the inverse.
+//
+// `exposure` comes from one of three places:
+// - `FFX_UPSCALE_ENABLE_AUTO_EXPOSURE`: a log-luminance histogram of the
+//   frame, trimmed to a percentile window, averaged, and eased toward over
+//   time (`AutoExposure`);
+// - otherwise the game's 1x1 exposure texture;
+// - otherwise 1.0.
+//
+// `exposure_histogram_cs.hlsl` and `exposure_resolve_cs.hlsl` are the GPU
+// twins of the histogram and resolve steps; keep them in sync.
+
+/// Histogram bins. Bin 0 collects pixels darker than the range and is left
+/// out of the average.
+pub const EXPOSURE_HISTOGRAM_BINS: usize = 64;
+
+/// log2 luminance covered by bins 1..BINS.
+pub const EXPOSURE_MIN_LOG2_LUMINANCE: f32 = -12.0;
+pub const EXPOSURE_MAX_LOG2_LUMINANCE: f32 = 4.0;
+
+/// Fraction of the (non-black) pixels ignored at the dark and bright ends.
+pub const EXPOSURE_LOW_PERCENTILE: f32 = 0.1;
+pub const EXPOSURE_HIGH_PERCENTILE: f32 = 0.9;
+
+/// Adaptation rate in 1/s; the adapted value closes `1 - e^-(rate * dt)` of the
+/// gap to the frame's average each frame.
+pub const EXPOSURE_ADAPTATION_RATE: f32 = 1.5;
+
+/// Rec. 709 luminance.
+pub fn luminance(c: [f32; 3]) -> f32 {
+    0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]
+}
+
+/// Histogram bin for a luminance value.
+pub fn histogram_bin(luminance: f32) -> usize {
+    let range = EXPOSURE_MAX_LOG2_LUMINANCE - EXPOSURE_MIN_LOG2_LUMINANCE;
+    // NaN lands in bin 0 too
+    if luminance.is_nan() || luminance <= EXPOSURE_MIN_LOG2_LUMINANCE.exp2() {
+        return 0;
+    }
+    let t = ((luminance.log2() - EXPOSURE_MIN_LOG2_LUMINANCE) / range).clamp(0.0, 1.0);
+    (1 + (t * (EXPOSURE_HISTOGRAM_BINS - 1) as f32) as usize).min(EXPOSURE_HISTOGRAM_BINS - 1)
+}
+
+/// log2 luminance at the centre of `bin` (1..BINS).
+pub fn bin_center_log2(bin: usize) -> f32 {
+    let range = EXPOSURE_MAX_LOG2_LUMINANCE - EXPOSURE_MIN_LOG2_LUMINANCE;
+    EXPOSURE_MIN_LOG2_LUMINANCE + (bin as f32 - 0.5) / (EXPOSURE_HISTOGRAM_BINS - 1) as f32 * range
+}
+
+/// Histogram of row-major RGB pixels. `pre_exposure` is divided out first so
+/// the histogram sees the scene as the game lit it.
+pub fn build_histogram(pixels: &[[f32; 3]], pre_exposure: f32) -> [u32; EXPOSURE_HISTOGRAM_BINS] {
+    let rcp = 1.0 / sanitize_pre_exposure(pre_exposure);
+    let mut histogram = [0u32; EXPOSURE_HISTOGRAM_BINS];
+    for &p in pixels {
+        histogram[histogram_bin(luminance(p) * rcp)] += 1;
+    }
+    histogram
+}
+
+/// Mean log2 luminance of the pixels between the low and high percentiles.
+/// `None` when every pixel is black.
+pub fn average_log2_luminance(histogram: &[u32; EXPOSURE_HISTOGRAM_BINS]) -> Option<f32> {
+    let total: f32 = histogram[1..].iter().map(|&c| c as f32).sum();
+    if total == 0.0 {
+        return None;
+    }
+    let (low, high) = (
+        total * EXPOSURE_LOW_PERCENTILE,
+        total * EXPOSURE_HIGH_PERCENTILE,
+    );
+    let (mut below, mut sum, mut weight) = (0.0f32, 0.0f32, 0.0f32);
+    for (bin, &count) in histogram.iter().enumerate().skip(1) {
+        let end = below + count as f32;
+        let take = (end.min(high) - below.max(low)).max(0.0);
+        sum += take * bin_center_log2(bin);
+        weight += take;
+        below = end;
+    }
+    (weight > 0.0).then(|| sum / weight)
+}
+
+/// FSR's exposure for an average luminance: EV100 from a 12.5 calibration
+/// constant, then `1 / (1.2 * 2^ev100)`.
+pub fn exposure_from_log2_luminance(log2_luminance: f32) -> f32 {
+    let ev100 = log2_luminance + (100.0f32 / 12.5).log2();
+    1.0 / (1.2 * ev100.exp2())
+}
+
+/// Games pass 0 when they do not pre-expose.
+pub fn sanitize_pre_exposure(pre_exposure: f32) -> f32 {
+    if pre_exposure.is_finite() && pre_exposure > 0.0 {
+        pre_exposure
+    } else {
+        1.0
+    }
+}
+
+/// Eye adaptation over successive histogram averages.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AutoExposure {
+    adapted_log2: Option<f32>,
+}
+
+impl AutoExposure {
+    pub fn adapted_log2_luminance(&self) -> Option<f32> {
+        self.adapted_log2
+    }
+
+    /// Ease toward `average` (from [`average_log2_luminance`]) and return the
+    /// exposure. Matches the GPU resolve: a black frame keeps the previous
+    /// value, `reset` snaps to the new one (log2 luminance 0 when black).
+    pub fn update(&mut self, average: Option<f32>, frame_time_ms: f32, reset: bool) -> f32 {
+        let adapted = match (self.adapted_log2, average) {
+            (Some(prev), Some(target)) if !reset => {
+                let dt = (frame_time_ms.max(0.0) / 1000.0).min(1.0);
+                prev + (target - prev) * (1.0 - (-EXPOSURE_ADAPTATION_RATE * dt).exp())
+            }
+            (_, Some(target)) => target,
+            (Some(prev), None) if !reset => prev,
+            _ => 0.0,
+        };
+        self.adapted_log2 = Some(adapted);
+        exposure_from_log2_luminance(adapted)
+    }
+}
+
+/// What the upscalers read each frame (`ExposureState` in the shaders).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct ExposureState {
+    pub exposure: f32,
+    /// `exposure / pre_exposure`: game colour to accumulation space.
+    pub prepare: f32,
+    /// `pre_exposure / exposure`: accumulation space back to game colour.
+    pub unprepare: f32,
+    pub adapted_log2_luminance: f32,
+}
+
+impl ExposureState {
+    pub fn new(exposure: f32, pre_exposure: f32, adapted_log2_luminance: f32) -> Self {
+        let pre_exposure = sanitize_pre_exposure(pre_exposure);
+        let exposure = if exposure.is_finite() && exposure > 0.0 {
+            exposure
+        } else {
+            1.0
+        };
+        Self {
+            exposure,
+            prepare: exposure / pre_exposure,
+            unprepare: pre_exposure / exposure,
+            adapted_log2_luminance,
+        }
+    }
+
+    pub fn prepare_color(&self, c: [f32; 3]) -> [f32; 3] {
+        c.map(|v| v * self.prepare)
+    }
+
+    pub fn unprepare_color(&self, c: [f32; 3]) -> [f32; 3] {
+        c.map(|v| v * self.unprepare)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Half a histogram bin in log2 luminance: the averaging error bound.
+    const HALF_BIN: f32 = (EXPOSURE_MAX_LOG2_LUMINANCE - EXPOSURE_MIN_LOG2_LUMINANCE)
+        / (EXPOSURE_HISTOGRAM_BINS - 1) as f32
+        / 2.0;
+
+    /// 60 fps.
+    const FRAME_MS: f32 = 1000.0 / 60.0;
+
+    /// A 64x64 frame of one grey level (luminance `value`).
+    fn flat(value: f32) -> Vec<[f32; 3]> {
+        vec![[value; 3]; 64 * 64]
+    }
+
+    fn frame_average(pixels: &[[f32; 3]], pre_exposure: f32) -> Option<f32> {
+        average_log2_luminance(&build_histogram(pixels, pre_exposure))
+    }
+
+    #[test]
+    fn constant_luminance_holds_steady() {
+        let average = frame_average(&flat(0.18), 1.0).unwrap();
+        assert!((average - 0.18f32.log2()).abs() <= HALF_BIN, "{average}");
+
+        // Pre-exposure is divided out before binning
+        assert_eq!(frame_average(&flat(0.72), 4.0), Some(average));
+
+        let mut auto = AutoExposure::default();
+        let first = auto.update(Some(average), FRAME_MS, false);
+        assert_eq!(first, exposure_from_log2_luminance(average));
+        for _ in 0..120 {
+            assert_eq!(auto.update(Some(average), FRAME_MS, false), first);
+        }
+        assert_eq!(auto.adapted_log2_luminance(), Some(average));
+    }
+
+    #[test]
+    fn all_black_frames() {
+        let histogram = build_histogram(&flat(0.0), 1.0);
+        assert_eq!(histogram[0], 64 * 64);
+        assert_eq!(average_log2_luminance(&histogram), None);
+
+        // Nothing to adapt to yet: log2 luminance 0
+        let mut auto = AutoExposure::default();
+        let black = auto.update(None, FRAME_MS, false);
+        assert_eq!(black, exposure_from_log2_luminance(0.0));
+
+        // Once adapted, black frames keep the previous value until a reset
+        let average = frame_average(&flat(0.5), 1.0);
+        let lit = auto.update(average, FRAME_MS, true);
+        for _ in 0..30 {
+            assert_eq!(auto.update(None, FRAME_MS, false), lit);
+        }
+        assert_eq!(auto.update(None, FRAME_MS, true), black);
+    }
+
+    #[test]
+    fn step_change_adapts_over_frames() {
+        let dark = frame_average(&flat(0.05), 1.0).unwrap();
+        let bright = frame_average(&flat(2.0), 1.0).unwrap();
+        let mut auto = AutoExposure::default();
+        let start = auto.update(Some(dark), FRAME_MS, false);
+        let target = exposure_from_log2_luminance(bright);
+
+        // One frame closes 1 - e^(-rate * dt) of the gap
+        let step = 1.0 - (-EXPOSURE_ADAPTATION_RATE * FRAME_MS / 1000.0).exp();
+        let mut previous = start;
+        auto.update(Some(bright), FRAME_MS, false);
+        let adapted = auto.adapted_log2_luminance().unwrap();
+        assert!((adapted - (dark + (bright - dark) * step)).abs() < 1e-5);
+
+        // Exposure falls monotonically toward the bright frame's value
+        for _ in 0..180 {
+            let e = auto.update(Some(bright), FRAME_MS, false);
+            assert!(e < previous && e > target, "{e}");
+            previous = e;
+        }
+        // After three seconds less than 1.2% of the gap is left
+        let left = (auto.adapted_log2_luminance().unwrap() - bright) / (dark - bright);
+        assert!(left > 0.0 && left < 0.012, "{left}");
+
+        // A reset snaps to the new frame
+        assert_eq!(auto.update(Some(dark), FRAME_MS, true), start);
+    }
+
+    #[test]
+    fn prepare_round_trips() {
+        let state = ExposureState::new(0.25, 2.0, -2.0);
+        assert_eq!(state.prepare_color([4.0, 8.0, 16.0]), [0.5, 1.0, 2.0]);
+        assert_eq!(
+            state.unprepare_color(state.prepare_color([4.0, 8.0, 16.0])),
+            [4.0, 8.0, 16.0]
+        );
+
+        // Invalid inputs fall back to 1.0
+        let state = ExposureState::new(f32::NAN, 0.0, 0.0);
+        assert_eq!(
+            (state.exposure, state.prepare, state.unprepare),
+            (1.0, 1.0, 1.0)
+        );
+    }
+}
@@ -0,0 +1,330 @@
+use crate::types::*;
+use crate::upscale::*;
+use core::ffi::c_void;
+
+// ---- Backend-agnostic upscale frame ----
+//
+// Both proxies receive per-frame inputs through different ABI structs:
+// the legacy `ffxFsr3UpscalerContextDispatch` descriptor (game layout, no
+// `upscaleSize`, three extra dilated/internal resources) and the ffx API
+// `ffxDispatchDescUpscale`. `UpscaleFrame` is the common shape that
+// upscalers, post-processing and recording consume.
+//
+// Resource states match bit for bit between the two ABIs and are copied
+// through. Surface formats are copied through too, which is only right for
+// the header numbering (FfxSurfaceFormat 0..=37 of SDK v1.1.4 is a prefix of
+// FfxApiSurfaceFormat): games built against other SDK drops number the enum
+// differently (Cyberpunk 2077's is shifted), and the legacy proxy rewrites
+// a converted frame to header numbering with its game profile before
+// anything reads a format. The legacy `FfxResource::name` (a debug label)
+// has no counterpart in `FfxApiResource` and is dropped. Resources the
+// source ABI does not carry are `UpscaleFrame::NULL_RESOURCE`.
+
+#[derive(Debug, Clone, Copy)]
+pub struct UpscaleFrame {
+    pub command_list: *mut c_void,
+    pub color: FfxApiResource,
+    pub depth: FfxApiResource,
+    pub motion_vectors: FfxApiResource,
+    pub exposure: FfxApiResource,
+    pub reactive: FfxApiResource,
+    pub transparency_and_composition: FfxApiResource,
+    /// Legacy ABI only; null when converted from `ffxDispatchDescUpscale`.
+    pub dilated_depth: FfxApiResource,
+    /// Legacy ABI only; null when converted from `ffxDispatchDescUpscale`.
+    pub dilated_motion_vectors: FfxApiResource,
+    /// Legacy ABI only; null when converted from `ffxDispatchDescUpscale`.
+    pub reconstructed_prev_nearest_depth: FfxApiResource,
+    pub output: FfxApiResource,
+    pub jitter_offset: FfxApiFloatCoords2D,
+    pub motion_vector_scale: FfxApiFloatCoords2D,
+    pub render_size: FfxApiDimensions2D,
+    /// Legacy ABI has no `upscaleSize`; it is taken from the output description.
+    pub upscale_size: FfxApiDimensions2D,
+    pub enable_sharpening: bool,
+    pub sharpness: f32,
+    pub frame_time_delta: f32,
+    pub pre_exposure: f32,
+    pub reset: bool,
+    pub camera_near: f32,
+    pub camera_far: f32,
+    pub camera_fov_angle_vertical: f32,
+    pub view_space_to_meters_factor: f32,
+    pub flags: u32,
+}
+
+impl UpscaleFrame {
+    pub const NULL_RESOURCE: FfxApiResource = FfxApiResource {
+        resource: core::ptr::null_mut(),
+        description: FfxApiResourceDescription {
+            type_: FFX_API_RESOURCE_TYPE_BUFFER,
+            format: FFX_API_SURFACE_FORMAT_UNKNOWN,
+            width: 0,
+            height: 0,
+            depth: 0,
+            mip_count: 0,
+            flags: FFX_API_RESOURCE_FLAGS_NONE,
+            usage: FFX_API_RESOURCE_USAGE_READ_ONLY,
+        },
+        state: FFX_API_RESOURCE_STATE_COMMON,
+    };
+
+    /// Render size, falling back to the color input's dimensions when the
+    /// game leaves `render_size` zeroed.
+    pub fn effective_render_size(&self) -> (u32, u32) {
+        let w = if self.render_size.width > 0 {
+            self.render_size.width
+        } else {
+            self.color.description.width
+        };
+        let h = if self.render_size.height > 0 {
+            self.render_size.height
+        } else {
+            self.color.description.height
+        };
+        (w, h)
+    }
+
+    /// Jitter in render pixels, as the FSR API defines `jitterOffset`. Read
+    /// through here so every upscaler agrees on the unit.
+    pub fn jitter_pixels(&self) -> [f32; 2] {
+        [self.jitter_offset.x, self.jitter_offset.y]
+    }
+
+    /// Output size (`upscale_size`, falling back to the output description).
+    pub fn effective_upscale_size(&self) -> (u32, u32) {
+        if self.upscale_size.width > 0 && self.upscale_size.height > 0 {
+            (self.upscale_size.width, self.upscale_size.height)
+        } else {
+            (
+                self.output.description.width,
+                self.output.description.height,
+            )
+        }
+    }
+}
+
+impl From<&ffxDispatchDescUpscale> for UpscaleFrame {
+    fn from(d: &ffxDispatchDescUpscale) -> Self {
+        Self {
+            command_list: d.command_list,
+            color: d.color,
+            depth: d.depth,
+            motion_vectors: d.motion_vectors,
+            exposure: d.exposure,
+            reactive: d.reactive,
+            transparency_and_composition: d.transparency_and_composition,
+            dilated_depth: Self::NULL_RESOURCE,
+            dilated_motion_vectors: Self::NULL_RESOURCE,
+            reconstructed_prev_nearest_depth: Self::NULL_RESOURCE,
+            output: d.output,
+            jitter_offset: d.jitter_offset,
+            motion_vector_scale: d.motion_vector_scale,
+            render_size: d.render_size,
+            upscale_size: d.upscale_size,
+            enable_sharpening: d.enable_sharpening,
+            sharpness: d.sharpness,
+            frame_time_delta: d.frame_time_delta,
+            pre_exposure: d.pre_exposure,
+            reset: d.reset,
+            camera_near: d.camera_near,
+            camera_far: d.camera_far,
+            camera_fov_angle_vertical: d.camera_fov_angle_vertical,
+            view_space_to_meters_factor: d.view_space_to_meters_factor,
+            flags: d.flags,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ffxApiHeader;
+
+    /// A 2D texture with a recognisable handle, size and state.
+    fn texture(handle: usize, width: u32, height: u32, state: u32) -> FfxApiResource {
+        FfxApiResource {
+            resource: handle as *mut c_void,
+            description: FfxApiResourceDescription {
+                type_: FFX_API_RESOURCE_TYPE_TEXTURE2D,
+                format: FFX_API_SURFACE_FORMAT_R16G16B16A16_FLOAT,
+                width,
+                height,
+                depth: 1,
+                mip_count: 1,
+                flags: FFX_API_RESOURCE_FLAGS_NONE,
+                usage: FFX_API_RESOURCE_USAGE_READ_ONLY,
+            },
+            state,
+        }
+    }
+
+    fn desc() -> ffxDispatchDescUpscale {
+        ffxDispatchDescUpscale {
+            header: ffxApiHeader {
+                type_: FFX_API_DISPATCH_DESC_TYPE_UPSCALE,
+                p_next: core::ptr::null_mut(),
+            },
+            command_list: 0x10 as *mut c_void,
+            color: texture(0x20, 1280, 720, FFX_API_RESOURCE_STATE_COMPUTE_READ),
+            depth: texture(0x30, 1280, 720, FFX_API_RESOURCE_STATE_COMPUTE_READ),
+            motion_vectors: texture(0x40, 1280, 720, FFX_API_RESOURCE_STATE_COMPUTE_READ),
+            exposure: texture(0x50, 1, 1, FFX_API_RESOURCE_STATE_COMPUTE_READ),
+            reactive: texture(0x60, 1280, 720, FFX_API_RESOURCE_STATE_PIXEL_COMPUTE_READ),
+            transparency_and_composition: texture(0x70, 1280, 720, FFX_API_RESOURCE_STATE_COMMON),
+            output: texture(0x80, 2560, 1440, FFX_API_RESOURCE_STATE_UNORDERED_ACCESS),
+            jitter_offset: FfxApiFloatCoords2D { x: 0.25, y: -0.125 },
+            motion_vector_scale: FfxApiFloatCoords2D {
+                x: 1280.0,
+                y: -720.0,
+            },
+            render_size: FfxApiDimensions2D {
+                width: 1280,
+                height: 720,
+            },
+            upscale_size: FfxApiDimensions2D {
+                width: 2560,
+                height: 1440,
+            },
+            enable_sharpening: true,
+            sharpness: 0.4,
+            frame_time_delta: 16.6,
+            pre_exposure: 1.5,
+            reset: true,
+            camera_near: 0.1,
+            camera_far: 1000.0,
+            camera_fov_angle_vertical: 1.0,
+            view_space_to_meters_factor: 0.01,
+            flags: 0x3,
+        }
+    }
+
+    fn same_resource(a: &FfxApiResource, b: &FfxApiResource) -> bool {
+        a.resource == b.resource
+            && a.state == b.state
+            && a.description.type_ == b.description.type_
+            && a.description.format == b.description.format
+            && a.description.width == b.description.width
+            && a.description.height == b.description.height
+            && a.description.depth == b.description.depth
+            && a.description.mip_count == b.description.mip_count
+            && a.description.flags == b.description.flags
+            && a.description.usage == b.description.usage
+    }
+
+    fn is_null(r: &FfxApiResource) -> bool {
+        same_resource(r, &UpscaleFrame::NULL_RESOURCE)
+    }
+
+    #[test]
+    fn converts_the_api_dispatch_field_by_field() {
+        let d = desc();
+        let f = UpscaleFrame::from(&d);
+        assert_eq!(f.command_list, d.command_list);
+        assert!(same_resource(&f.color, &d.color));
+        assert!(same_resource(&f.depth, &d.depth));
+        assert!(same_resource(&f.motion_vectors, &d.motion_vectors));
+        assert!(same_resource(&f.exposure, &d.exposure));
+        assert!(same_resource(&f.reactive, &d.reactive));
+        assert!(same_resource(
+            &f.transparency_and_composition,
+            &d.transparency_and_composition
+        ));
+        assert!(same_resource(&f.output, &d.output));
+        assert_eq!(f.jitter_pixels(), [0.25, -0.125]);
+        assert_eq!(
+            (f.motion_vector_scale.x, f.motion_vector_scale.y),
+            (1280.0, -720.0)
+        );
+        assert_eq!(f.effective_render_size(), (1280, 720));
+        assert_eq!(f.effective_upscale_size(), (2560, 1440));
+        assert!(f.enable_sharpening && f.reset);
+        assert_eq!(f.sharpness, 0.4);
+        assert_eq!(f.frame_time_delta, 16.6);
+        assert_eq!(f.pre_exposure, 1.5);
+        assert_eq!((f.camera_near, f.camera_far), (0.1, 1000.0));
+        assert_eq!(f.camera_fov_angle_vertical, 1.0);
+        assert_eq!(f.view_space_to_meters_factor, 0.01);
+        assert_eq!(f.flags, 0x3);
+    }
+
+    #[test]
+    fn api_dispatch_has_no_legacy_resources() {
+        let f = UpscaleFrame::from(&desc());
+        assert!(is_null(&f.dilated_depth));
+        assert!(is_null(&f.dilated_motion_vectors));
+        assert!(is_null(&f.reconstructed_prev_nearest_depth));
+        assert!(!is_null(&f.color));
+    }
+
+    #[test]
+    fn null_resource_is_empty() {
+        let r = UpscaleFrame::NULL_RESOURCE;
+        assert!(r.resource.is_null());
+        assert_eq!((r.description.width, r.description.height), (0, 0));
+        assert_eq!(r.description.format, FFX_API_SURFACE_FORMAT_UNKNOWN);
+        assert_eq!(r.state, FFX_API_RESOURCE_STATE_COMMON);
+    }
+
+    #[test]
+    fn zero_render_size_falls_back_to_the_color_input() {
+        let mut d = desc();
+        d.color = texture(0x20, 1600, 900, FFX_API_RESOURCE_STATE_COMPUTE_READ);
+        d.render_size = FfxApiDimensions2D {
+            width: 0,
+            height: 0,
+        };
+        assert_eq!(UpscaleFrame::from(&d).effective_render_size(), (1600, 900));
+
+        // Each axis falls back on its own
+        d.render_size = FfxApiDimensions2D {
+            width: 1280,
+            height: 0,
+        };
+        assert_eq!(UpscaleFrame::from(&d).effective_render_size(), (1280, 900));
+        d.render_size = FfxApiDimensions2D {
+            width: 0,
+            height: 720,
+        };
+        assert_eq!(UpscaleFrame::from(&d).effective_render_size(), (1600, 720));
+    }
+
+    #[test]
+    fn zero_upscale_size_falls_back_to_the_output() {
+        let mut d = desc();
+        d.output = texture(0x80, 3840, 2160, FFX_API_RESOURCE_STATE_UNORDERED_ACCESS);
+        d.upscale_size = FfxApiDimensions2D {
+            width: 0,
+            height: 0,
+        };
+        assert_eq!(
+            UpscaleFrame::from(&d).effective_upscale_size(),
+            (3840, 2160)
+        );
+
+        // Unlike the render size, a half-filled upscale size is ignored whole
+        d.upscale_size = FfxApiDimensions2D {
+            width: 2560,
+            height: 0,
+        };
+        assert_eq!(
+            UpscaleFrame::from(&d).effective_upscale_size(),
+            (3840, 2160)
+        );
+    }
+
+    #[test]
+    fn sizes_are_zero_without_inputs() {
+        let mut d = desc();
+        d.color = UpscaleFrame::NULL_RESOURCE;
+        d.output = UpscaleFrame::NULL_RESOURCE;
+        d.render_size = FfxApiDimensions2D {
+            width: 0,
+            height: 0,
+        };
+        d.upscale_size = d.render_size;
+        let f = UpscaleFrame::from(&d);
+        assert_eq!(f.effective_render_size(), (0, 0));
+        assert_eq!(f.effective_upscale_size(), (0, 0));
+    }
+}
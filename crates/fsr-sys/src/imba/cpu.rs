@@ -0,0 +1,881 @@
+//! CPU reference for the IMBA v0 dispatches.
+//!
+//! Runs the exact `AAConstants` table `Graph::dispatch_table` compiles over the
+//! same feature, GroupNorm and weight buffers the GPU uses, one dispatch at a
+//! time. It is the golden reference for changes to the AA compute shaders and
+//! runs recorded frames offline. Each pass below states what its shader
+//! computes; keep the two in sync.
+//!
+//! Feature slots are CHW tensors at the dispatch's resolution: channel `c`,
+//! pixel `(x, y)` of slot `b` is `features[b * buf_stride + (c * h + y) * w + x]`.
+
+use super::graph::{DispatchTable, Graph, AA_GN_MAX_GROUPS};
+use super::*;
+use std::collections::VecDeque;
+
+/// Channels `PixelUnshuffle` packs per render pixel, see [`input_channels`].
+pub const AA_INPUT_CHANNELS: usize = 8;
+
+/// One frame's render-resolution inputs, row-major: colour, reverse-Z depth
+/// and UV motion vectors pointing to the previous position, without jitter
+/// (the convention `fsr_sys::inputs` normalizes to). `jitter` is in render
+/// pixels.
+#[derive(Debug, Clone, Copy)]
+pub struct AAFrameInputs<'a> {
+    pub color: &'a [[f32; 3]],
+    pub depth: &'a [f32],
+    pub motion_vectors: &'a [[f32; 2]],
+    pub jitter: [f32; 2],
+    /// How far each pixel rejects history, `reactive::history_rejection` of
+    /// the game's masks; `None` keeps all of it. Only the current frame's is
+    /// read.
+    pub history_mask: Option<&'a [f32]>,
+}
+
+impl AAFrameInputs<'_> {
+    fn check(&self, pixels: usize) -> Result<(), String> {
+        let lens = [
+            self.color.len(),
+            self.depth.len(),
+            self.motion_vectors.len(),
+            self.history_mask.map_or(pixels, <[f32]>::len),
+        ];
+        if lens.iter().any(|&n| n != pixels) {
+            return Err(format!(
+                "inputs hold {:?} pixels, expected {}",
+                lens, pixels
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Network input for render pixel `i`: RGB, depth, the motion vector in
+/// render pixels, and the frame's jitter.
+pub fn input_channels(
+    inputs: &AAFrameInputs,
+    i: usize,
+    jitter: [f32; 2],
+    render_w: u32,
+    render_h: u32,
+) -> [f32; AA_INPUT_CHANNELS] {
+    let [r, g, b] = inputs.color[i];
+    let [mx, my] = inputs.motion_vectors[i];
+    [
+        r,
+        g,
+        b,
+        inputs.depth[i],
+        mx * render_w as f32,
+        my * render_h as f32,
+        jitter[0],
+        jitter[1],
+    ]
+}
+
+fn activate(v: f32, activation: u32) -> f32 {
+    match activation {
+        ACT_SILU => v / (1.0 + (-v).exp()),
+        ACT_TANH => v.tanh(),
+        _ => v,
+    }
+}
+
+fn sigmoid(v: f32) -> f32 {
+    1.0 / (1.0 + (-v).exp())
+}
+
+/// Bilinear sample of a `w * h` plane at pixel coordinates, edges clamped.
+fn bilinear(plane: &[f32], w: usize, h: usize, x: f32, y: f32) -> f32 {
+    let x = x.clamp(0.0, (w - 1) as f32);
+    let y = y.clamp(0.0, (h - 1) as f32);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+    let top = plane[y0 * w + x0] * (1.0 - fx) + plane[y0 * w + x1] * fx;
+    let bottom = plane[y1 * w + x0] * (1.0 - fx) + plane[y1 * w + x1] * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+/// The AA network on the CPU, with the GPU path's buffers and caching.
+pub struct AAReference {
+    render_w: u32,
+    render_h: u32,
+    table: DispatchTable,
+    weights: Vec<f32>,
+    features: Vec<f32>,
+    gn_partials: Vec<f32>,
+    gn_stats: Vec<f32>,
+    output: Vec<[f32; 4]>,
+    /// Cached temporal features, newest first, at most `depth` frames.
+    history: VecDeque<Vec<f32>>,
+}
+
+impl AAReference {
+    /// `weights` as returned by [`pack_weights`] for `graph`; the output is at
+    /// render resolution.
+    pub fn new(
+        render_w: u32,
+        render_h: u32,
+        graph: &Graph,
+        weights: Vec<f32>,
+    ) -> Result<Self, String> {
+        Self::with_output_size(render_w, render_h, render_w, render_h, graph, weights)
+    }
+
+    /// Like [`new`](Self::new), upscaling to `output_w × output_h`.
+    pub fn with_output_size(
+        render_w: u32,
+        render_h: u32,
+        output_w: u32,
+        output_h: u32,
+        graph: &Graph,
+        weights: Vec<f32>,
+    ) -> Result<Self, String> {
+        if render_w < 4 || render_h < 4 {
+            return Err(format!("render size {}x{} too small", render_w, render_h));
+        }
+        if output_w < render_w || output_h < render_h {
+            return Err(format!(
+                "output size {}x{} smaller than the render size {}x{}",
+                output_w, output_h, render_w, render_h
+            ));
+        }
+        if weights.len() != graph.weight_count() as usize {
+            return Err(format!(
+                "{} weights, expected {}",
+                weights.len(),
+                graph.weight_count()
+            ));
+        }
+        let table = graph.dispatch_table(render_w, render_h, output_w, output_h);
+        Ok(Self {
+            render_w,
+            render_h,
+            weights,
+            features: vec![0.0; (table.slots * table.buf_stride) as usize],
+            gn_partials: vec![0.0; (table.gn_max_groups * AA_GN_TILES_PER_GROUP * 2) as usize],
+            gn_stats: vec![0.0; 2 * AA_GN_MAX_GROUPS as usize],
+            table,
+            output: vec![[0.0; 4]; (output_w * output_h) as usize],
+            history: VecDeque::new(),
+        })
+    }
+
+    pub fn dispatches(&self) -> &[AAConstants] {
+        &self.table.dispatches
+    }
+
+    /// The whole feature buffer, `slots * buf_stride` floats.
+    pub fn features(&self) -> &[f32] {
+        &self.features
+    }
+
+    pub fn buf_stride(&self) -> u32 {
+        self.table.buf_stride
+    }
+
+    /// Last output, RGBA at output resolution.
+    pub fn output(&self) -> &[[f32; 4]] {
+        &self.output
+    }
+
+    /// Forget the cached temporal features; the next frame encodes `prev` again.
+    pub fn reset(&mut self) {
+        self.history.clear();
+    }
+
+    /// Frames of temporal features cached.
+    pub fn history_frames(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Run one frame like `aa_pass::execute`: the previous-frame encoder is
+    /// skipped when its output is cached from the last call, and this frame's
+    /// temporal features are cached for the next. With nothing cached, the
+    /// encoded `prev` is cached as the frame before.
+    pub fn run(
+        &mut self,
+        curr: &AAFrameInputs,
+        prev: &AAFrameInputs,
+    ) -> Result<&[[f32; 4]], String> {
+        let pixels = (self.render_w * self.render_h) as usize;
+        curr.check(pixels)?;
+        prev.check(pixels)?;
+
+        let temporal = self.table.temporal;
+        let use_cache = temporal.is_some() && !self.history.is_empty();
+        if let (Some(t), Some(cache)) = (temporal, self.history.front()) {
+            let dst = self.slot(t.prev_slot);
+            self.features[dst..dst + cache.len()].copy_from_slice(cache);
+        }
+        if use_cache {
+            self.fill_history();
+        }
+
+        for i in 0..self.table.dispatches.len() {
+            if use_cache && self.table.prev_encoder.contains(&(i as u32)) {
+                continue;
+            }
+            let mut c = self.table.dispatches[i];
+            c.jitter_x = curr.jitter[0];
+            c.jitter_y = curr.jitter[1];
+            c.prev_jitter_x = prev.jitter[0];
+            c.prev_jitter_y = prev.jitter[1];
+            self.execute(&c, curr, prev);
+
+            if let Some(t) = temporal.filter(|_| !use_cache && !self.table.history.is_empty()) {
+                if i as u32 + 1 == self.table.prev_encoder.end {
+                    let src = self.slot(t.prev_slot);
+                    let encoded = self.features[src..src + t.len as usize].to_vec();
+                    self.history.push_front(encoded);
+                    self.fill_history();
+                }
+            }
+        }
+
+        if let Some(t) = temporal {
+            let src = self.slot(t.slot);
+            let current = self.features[src..src + t.len as usize].to_vec();
+            self.history.push_front(current);
+            self.history.truncate(t.depth as usize);
+        }
+        Ok(&self.output)
+    }
+
+    /// Copy the cached frames into the `history` tensors, the oldest standing
+    /// in for those not cached yet.
+    fn fill_history(&mut self) {
+        for h in self.table.history.clone() {
+            let age = (h.frames as usize).min(self.history.len());
+            let Some(frame) = age.checked_sub(1).and_then(|i| self.history.get(i)) else {
+                continue;
+            };
+            let dst = self.slot(h.slot);
+            self.features[dst..dst + frame.len()].copy_from_slice(frame);
+        }
+    }
+
+    fn slot(&self, buf: u32) -> usize {
+        (buf * self.table.buf_stride) as usize
+    }
+
+    /// Execute a single dispatch. Pass types outside `PASS_COUNT` are skipped,
+    /// as on the GPU.
+    pub fn execute(&mut self, c: &AAConstants, curr: &AAFrameInputs, prev: &AAFrameInputs) {
+        const UNSHUFFLE: u32 = PassType::PixelUnshuffle as u32;
+        const UNSHUFFLE_PADDED: u32 = PassType::PixelUnshufflePadded as u32;
+        const GN_STATS: u32 = PassType::GNStats as u32;
+        const GN_REDUCE: u32 = PassType::GNStatsReduce as u32;
+        const GN_APPLY: u32 = PassType::GNApply as u32;
+        const WARP: u32 = PassType::BackwardWarp as u32;
+        const ATTENTION: u32 = PassType::Attention as u32;
+        const UPSAMPLE: u32 = PassType::NearestUpsample as u32;
+        const SKIP_CONCAT: u32 = PassType::SkipConcatConv as u32;
+        const SHUFFLE: u32 = PassType::PixelShuffleOut as u32;
+        const SHUFFLE_UPSCALE: u32 = PassType::PixelShuffleUpscale as u32;
+        const DEBUG_VIEW: u32 = PassType::DebugView as u32;
+        const SCALE_MV: u32 = PassType::ScaleMV as u32;
+        const SCALE_MV_PADDED: u32 = PassType::ScaleMVPadded as u32;
+        const HISTORY_MASK: u32 = PassType::HistoryMask as u32;
+        const CONV: u32 = PassType::Conv as u32;
+        const CONV_LAST: u32 = PassType::Conv3x3S2_16x32 as u32;
+
+        match c.pass_type {
+            UNSHUFFLE | UNSHUFFLE_PADDED => {
+                if c.flags & FLAG_IS_PREV != 0 {
+                    self.pixel_unshuffle(c, prev, [c.prev_jitter_x, c.prev_jitter_y]);
+                } else {
+                    self.pixel_unshuffle(c, curr, [c.jitter_x, c.jitter_y]);
+                }
+            }
+            // The specialized convolutions compute the same thing
+            CONV => self.conv(c),
+            p if (PassType::Conv3x3_16x16 as u32..=CONV_LAST).contains(&p) => self.conv(c),
+            GN_STATS => self.gn_stats(c),
+            GN_REDUCE => self.gn_reduce(c),
+            GN_APPLY => self.gn_apply(c),
+            WARP => self.backward_warp(c),
+            ATTENTION => self.attention(c),
+            UPSAMPLE => self.nearest_upsample(c),
+            SKIP_CONCAT => self.skip_concat_conv(c),
+            SHUFFLE => self.pixel_shuffle_out(c, curr),
+            SHUFFLE_UPSCALE => self.pixel_shuffle_upscale(c, curr),
+            DEBUG_VIEW => self.debug_view(c),
+            SCALE_MV => self.scale_mv(c, curr),
+            SCALE_MV_PADDED => self.scale_mv_padded(c, curr),
+            HISTORY_MASK => self.history_mask(c, curr),
+            _ => {}
+        }
+    }
+
+    /// `[AA_INPUT_CHANNELS, in_h, in_w]` → `[4 * AA_INPUT_CHANNELS, h, w]`,
+    /// PyTorch's `pixel_unshuffle(2)`: out channel `c * 4 + dy * 2 + dx` at
+    /// `(x, y)` is input channel `c` at `(2x + dx, 2y + dy)`, clamped to the
+    /// input (only a padded table reads past it).
+    fn pixel_unshuffle(&mut self, c: &AAConstants, inputs: &AAFrameInputs, jitter: [f32; 2]) {
+        let (w, h) = (c.width as usize, c.height as usize);
+        let (in_w, in_h) = (c.in_width as usize, c.in_height as usize);
+        let base = self.slot(c.out_buf);
+        for y in 0..h {
+            for x in 0..w {
+                for dy in 0..2 {
+                    for dx in 0..2 {
+                        let sx = (2 * x + dx).min(in_w - 1);
+                        let sy = (2 * y + dy).min(in_h - 1);
+                        let i = sy * in_w + sx;
+                        let px = input_channels(inputs, i, jitter, c.in_width, c.in_height);
+                        for (ch, v) in px.into_iter().enumerate() {
+                            let oc = ch * 4 + dy * 2 + dx;
+                            self.features[base + (oc * h + y) * w + x] = v;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Zero-padded convolution, weights `[out, in, k, k]`, optional bias, then
+    /// the activation.
+    fn conv(&mut self, c: &AAConstants) {
+        let (w, h) = (c.width as usize, c.height as usize);
+        let (in_w, in_h) = (c.in_width as usize, c.in_height as usize);
+        let (in_ch, out_ch) = (c.in_channels as usize, c.out_channels as usize);
+        let ks = c.kernel_size as usize;
+        let pad = (ks / 2) as isize;
+        let stride = c.stride.max(1) as usize;
+        let src = self.slot(c.in_buf);
+        let weights = &self.weights[c.weight_off as usize..];
+
+        let mut out = vec![0.0f32; out_ch * h * w];
+        for oc in 0..out_ch {
+            let bias = if c.bias_off == AA_NO_OFFSET {
+                0.0
+            } else {
+                self.weights[c.bias_off as usize + oc]
+            };
+            for y in 0..h {
+                for x in 0..w {
+                    let mut acc = bias;
+                    for ic in 0..in_ch {
+                        let plane = &self.features[src + ic * in_h * in_w..][..in_h * in_w];
+                        let kernel = &weights[(oc * in_ch + ic) * ks * ks..][..ks * ks];
+                        for ky in 0..ks {
+                            let iy = (y * stride + ky) as isize - pad;
+                            if iy < 0 || iy >= in_h as isize {
+                                continue;
+                            }
+                            for kx in 0..ks {
+                                let ix = (x * stride + kx) as isize - pad;
+                                if ix < 0 || ix >= in_w as isize {
+                                    continue;
+                                }
+                                acc +=
+                                    kernel[ky * ks + kx] * plane[iy as usize * in_w + ix as usize];
+                            }
+                        }
+                    }
+                    out[(oc * h + y) * w + x] = activate(acc, c.activation);
+                }
+            }
+        }
+        let dst = self.slot(c.out_buf);
+        self.features[dst..dst + out.len()].copy_from_slice(&out);
+    }
+
+    /// GroupNorm pass A: each group's elements split into
+    /// `AA_GN_TILES_PER_GROUP` contiguous tiles; partial `(sum, sum of squares)`
+    /// per tile.
+    fn gn_stats(&mut self, c: &AAConstants) {
+        let plane = (c.width * c.height) as usize;
+        let per_group = (c.out_channels / c.num_groups) as usize * plane;
+        let tiles = AA_GN_TILES_PER_GROUP as usize;
+        let base = self.slot(c.in_buf);
+        for g in 0..c.num_groups as usize {
+            let group = &self.features[base + g * per_group..][..per_group];
+            for t in 0..tiles {
+                let tile = &group[t * per_group / tiles..(t + 1) * per_group / tiles];
+                let (mut sum, mut sq) = (0.0f32, 0.0f32);
+                for &v in tile {
+                    sum += v;
+                    sq += v * v;
+                }
+                self.gn_partials[(g * tiles + t) * 2] = sum;
+                self.gn_partials[(g * tiles + t) * 2 + 1] = sq;
+            }
+        }
+    }
+
+    /// GroupNorm pass B: `gn_stats[2g] = mean`, `gn_stats[2g + 1] = 1 / sqrt(var + eps)`.
+    fn gn_reduce(&mut self, c: &AAConstants) {
+        let n = ((c.out_channels / c.num_groups) * c.width * c.height) as f32;
+        let tiles = AA_GN_TILES_PER_GROUP as usize;
+        for g in 0..c.num_groups as usize {
+            let (mut sum, mut sq) = (0.0f32, 0.0f32);
+            for t in 0..tiles {
+                sum += self.gn_partials[(g * tiles + t) * 2];
+                sq += self.gn_partials[(g * tiles + t) * 2 + 1];
+            }
+            let mean = sum / n;
+            let var = (sq / n - mean * mean).max(0.0);
+            self.gn_stats[g * 2] = mean;
+            self.gn_stats[g * 2 + 1] = 1.0 / (var + AA_GN_EPSILON).sqrt();
+        }
+    }
+
+    /// GroupNorm pass C: normalize, scale by gamma, shift by beta, add the skip
+    /// slot when `FLAG_HAS_SKIP`, then the activation.
+    fn gn_apply(&mut self, c: &AAConstants) {
+        let plane = (c.width * c.height) as usize;
+        let per_group = (c.out_channels / c.num_groups) as usize;
+        let (src, dst, skip) = (
+            self.slot(c.in_buf),
+            self.slot(c.out_buf),
+            self.slot(c.aux_buf),
+        );
+        let has_skip = c.flags & FLAG_HAS_SKIP != 0;
+        for ch in 0..c.out_channels as usize {
+            let g = ch / per_group;
+            let (mean, rstd) = (self.gn_stats[g * 2], self.gn_stats[g * 2 + 1]);
+            let gamma = self.weights[c.gamma_off as usize + ch];
+            let beta = self.weights[c.beta_off as usize + ch];
+            for p in 0..plane {
+                let i = ch * plane + p;
+                let mut v = (self.features[src + i] - mean) * rstd * gamma + beta;
+                if has_skip {
+                    v += self.features[skip + i];
+                }
+                self.features[dst + i] = activate(v, c.activation);
+            }
+        }
+    }
+
+    /// Scaled motion vectors: for each output pixel, the render pixel under its
+    /// centre, its UV motion vector scaled to output pixels, as 2 channels.
+    fn scale_mv(&mut self, c: &AAConstants, curr: &AAFrameInputs) {
+        let (w, h) = (c.width as usize, c.height as usize);
+        let (in_w, in_h) = (c.in_width as usize, c.in_height as usize);
+        let base = self.slot(c.out_buf);
+        for y in 0..h {
+            let sy = (((y as f32 + 0.5) * in_h as f32 / h as f32) as usize).min(in_h - 1);
+            for x in 0..w {
+                let sx = (((x as f32 + 0.5) * in_w as f32 / w as f32) as usize).min(in_w - 1);
+                let [mx, my] = curr.motion_vectors[sy * in_w + sx];
+                self.features[base + y * w + x] = mx * w as f32;
+                self.features[base + (h + y) * w + x] = my * h as f32;
+            }
+        }
+    }
+
+    /// `ScaleMV` over a padded table: the motion vector at the centre of each
+    /// `stride × stride` render-pixel cell, clamped to the render size, in
+    /// render pixels over `stride`.
+    fn scale_mv_padded(&mut self, c: &AAConstants, curr: &AAFrameInputs) {
+        let (w, h) = (c.width as usize, c.height as usize);
+        let (in_w, in_h) = (c.in_width as usize, c.in_height as usize);
+        let cell = c.stride.max(1) as f32;
+        let (scale_x, scale_y) = (in_w as f32 / cell, in_h as f32 / cell);
+        let base = self.slot(c.out_buf);
+        for y in 0..h {
+            let sy = (((y as f32 + 0.5) * cell) as usize).min(in_h - 1);
+            for x in 0..w {
+                let sx = (((x as f32 + 0.5) * cell) as usize).min(in_w - 1);
+                let [mx, my] = curr.motion_vectors[sy * in_w + sx];
+                self.features[base + y * w + x] = mx * scale_x;
+                self.features[base + (h + y) * w + x] = my * scale_y;
+            }
+        }
+    }
+
+    /// Previous features (`in_buf`) sampled bilinearly at `(x, y) + mv`, with
+    /// `mv` from `aux_buf` (`ScaleMV` output), edges clamped.
+    fn backward_warp(&mut self, c: &AAConstants) {
+        let (w, h) = (c.width as usize, c.height as usize);
+        let plane = w * h;
+        let (src, mv, dst) = (
+            self.slot(c.in_buf),
+            self.slot(c.aux_buf),
+            self.slot(c.out_buf),
+        );
+        let mut out = vec![0.0f32; c.in_channels as usize * plane];
+        for y in 0..h {
+            for x in 0..w {
+                let p = y * w + x;
+                let sx = x as f32 + self.features[mv + p];
+                let sy = y as f32 + self.features[mv + plane + p];
+                for ch in 0..c.in_channels as usize {
+                    let prev = &self.features[src + ch * plane..][..plane];
+                    out[ch * plane + p] = bilinear(prev, w, h, sx, sy);
+                }
+            }
+        }
+        self.features[dst..dst + out.len()].copy_from_slice(&out);
+    }
+
+    /// `warped` (`aux_buf`) pulled toward `current` (`in_buf`) by the history
+    /// mask at the centre of each `stride × stride` render-pixel cell, clamped
+    /// to the render size, written back to `aux_buf`. A fully set mask leaves
+    /// the attention nothing but the current frame.
+    fn history_mask(&mut self, c: &AAConstants, curr: &AAFrameInputs) {
+        let Some(mask) = curr.history_mask else {
+            return;
+        };
+        let (w, h) = (c.width as usize, c.height as usize);
+        let (in_w, in_h) = (c.in_width as usize, c.in_height as usize);
+        let plane = w * h;
+        let cell = c.stride.max(1) as f32;
+        let (curr_buf, warped) = (self.slot(c.in_buf), self.slot(c.aux_buf));
+        for y in 0..h {
+            let sy = (((y as f32 + 0.5) * cell) as usize).min(in_h - 1);
+            for x in 0..w {
+                let sx = (((x as f32 + 0.5) * cell) as usize).min(in_w - 1);
+                let t = mask[sy * in_w + sx].clamp(0.0, 1.0);
+                let p = y * w + x;
+                for ch in 0..c.in_channels as usize {
+                    let a = self.features[warped + ch * plane + p];
+                    let b = self.features[curr_buf + ch * plane + p];
+                    self.features[warped + ch * plane + p] = a * (1.0 - t) + b * t;
+                }
+            }
+        }
+    }
+
+    /// Per-channel gate from a 1×1 conv over `concat(current, warped)` plus
+    /// bias, through a sigmoid: `out = lerp(current, warped, gate)`.
+    fn attention(&mut self, c: &AAConstants) {
+        let plane = (c.width * c.height) as usize;
+        let ch = c.in_channels as usize;
+        let (curr, warped, dst) = (
+            self.slot(c.in_buf),
+            self.slot(c.aux_buf),
+            self.slot(c.out_buf),
+        );
+        let mut out = vec![0.0f32; ch * plane];
+        for p in 0..plane {
+            for oc in 0..ch {
+                let row = &self.weights[c.weight_off as usize + oc * 2 * ch..][..2 * ch];
+                let mut logit = self.weights[c.bias_off as usize + oc];
+                for ic in 0..ch {
+                    logit += row[ic] * self.features[curr + ic * plane + p];
+                    logit += row[ch + ic] * self.features[warped + ic * plane + p];
+                }
+                let gate = sigmoid(logit);
+                let a = self.features[curr + oc * plane + p];
+                let b = self.features[warped + oc * plane + p];
+                out[oc * plane + p] = a + (b - a) * gate;
+            }
+        }
+        self.features[dst..dst + out.len()].copy_from_slice(&out);
+    }
+
+    /// 2× nearest-neighbour upsample of `in_channels` channels.
+    fn nearest_upsample(&mut self, c: &AAConstants) {
+        let (w, h) = (c.width as usize, c.height as usize);
+        let (in_w, in_h) = (c.in_width as usize, c.in_height as usize);
+        let (src, dst) = (self.slot(c.in_buf), self.slot(c.out_buf));
+        let mut out = vec![0.0f32; c.in_channels as usize * w * h];
+        for ch in 0..c.in_channels as usize {
+            for y in 0..h {
+                let sy = (y / 2).min(in_h - 1);
+                for x in 0..w {
+                    let sx = (x / 2).min(in_w - 1);
+                    out[(ch * h + y) * w + x] = self.features[src + (ch * in_h + sy) * in_w + sx];
+                }
+            }
+        }
+        self.features[dst..dst + out.len()].copy_from_slice(&out);
+    }
+
+    /// 1×1 conv over `concat(in_buf, aux_buf)`, `in_channels / 2` from each,
+    /// weights `[out, in, 1, 1]`, optional bias, no activation.
+    fn skip_concat_conv(&mut self, c: &AAConstants) {
+        let plane = (c.width * c.height) as usize;
+        let half = c.in_channels as usize / 2;
+        let (a, b, dst) = (
+            self.slot(c.in_buf),
+            self.slot(c.aux_buf),
+            self.slot(c.out_buf),
+        );
+        let mut out = vec![0.0f32; c.out_channels as usize * plane];
+        for oc in 0..c.out_channels as usize {
+            let row = &self.weights[c.weight_off as usize + oc * 2 * half..][..2 * half];
+            let bias = if c.bias_off == AA_NO_OFFSET {
+                0.0
+            } else {
+                self.weights[c.bias_off as usize + oc]
+            };
+            for p in 0..plane {
+                let mut acc = bias;
+                for ic in 0..half {
+                    acc += row[ic] * self.features[a + ic * plane + p];
+                    acc += row[half + ic] * self.features[b + ic * plane + p];
+                }
+                out[oc * plane + p] = acc;
+            }
+        }
+        self.features[dst..dst + out.len()].copy_from_slice(&out);
+    }
+
+    /// PyTorch's `pixel_shuffle(2)` of the 12-channel head output to an RGB
+    /// residual at render resolution, added to the current colour; alpha 1.
+    /// The head of a padded table covers more than the frame; the rest is
+    /// cropped.
+    fn pixel_shuffle_out(&mut self, c: &AAConstants, curr: &AAFrameInputs) {
+        let (w, h) = (c.width as usize, c.height as usize);
+        let (in_w, in_h) = (c.in_width as usize, c.in_height as usize);
+        let src = self.slot(c.in_buf);
+        for y in 0..h {
+            let (sy, dy) = ((y / 2).min(in_h - 1), y % 2);
+            for x in 0..w {
+                let (sx, dx) = ((x / 2).min(in_w - 1), x % 2);
+                let color = curr.color[y * w + x];
+                let mut rgba = [0.0, 0.0, 0.0, 1.0];
+                for ch in 0..3 {
+                    let ic = ch * 4 + dy * 2 + dx;
+                    rgba[ch] = color[ch] + self.features[src + (ic * in_h + sy) * in_w + sx];
+                }
+                self.output[y * w + x] = rgba;
+            }
+        }
+    }
+
+    /// `PixelShuffleOut` followed by a resample: the render-resolution frame
+    /// (colour plus the `pixel_shuffle(stride)` residual of `in_buf`) is
+    /// sampled bilinearly under each output pixel centre, edges clamped;
+    /// alpha 1. `in_width × in_height` is the head, `render_width ×
+    /// render_height` the frame.
+    fn pixel_shuffle_upscale(&mut self, c: &AAConstants, curr: &AAFrameInputs) {
+        let (w, h) = (c.width as usize, c.height as usize);
+        let (in_w, in_h) = (c.render_width as usize, c.render_height as usize);
+        let r = c.stride.max(1) as usize;
+        let (head_w, head_h) = (c.in_width as usize, c.in_height as usize);
+        let src = self.slot(c.in_buf);
+        let plane = in_w * in_h;
+        let mut frame = vec![0.0f32; 3 * plane];
+        for y in 0..in_h {
+            let (sy, dy) = ((y / r).min(head_h - 1), y % r);
+            for x in 0..in_w {
+                let (sx, dx) = ((x / r).min(head_w - 1), x % r);
+                let color = curr.color[y * in_w + x];
+                for ch in 0..3 {
+                    let ic = ch * r * r + dy * r + dx;
+                    frame[ch * plane + y * in_w + x] =
+                        color[ch] + self.features[src + (ic * head_h + sy) * head_w + sx];
+                }
+            }
+        }
+        let (scale_x, scale_y) = (in_w as f32 / w as f32, in_h as f32 / h as f32);
+        for y in 0..h {
+            let fy = (y as f32 + 0.5) * scale_y - 0.5;
+            for x in 0..w {
+                let fx = (x as f32 + 0.5) * scale_x - 0.5;
+                let mut rgba = [0.0, 0.0, 0.0, 1.0];
+                for ch in 0..3 {
+                    rgba[ch] = bilinear(&frame[ch * plane..][..plane], in_w, in_h, fx, fy);
+                }
+                self.output[y * w + x] = rgba;
+            }
+        }
+    }
+
+    /// Channel `in_channels` of slot `in_buf` (`in_width × in_height`) drawn
+    /// over the output, nearest-sampled: `t = clamp(v / range, -1, 1)`, red
+    /// `(t, t / 2, 0)` when positive, blue `(0, -t / 2, -t)` when negative;
+    /// alpha 1. `range` is `debug_mode` as f32 bits.
+    fn debug_view(&mut self, c: &AAConstants) {
+        let (w, h) = (c.width as usize, c.height as usize);
+        let (in_w, in_h) = (c.in_width as usize, c.in_height as usize);
+        let range = f32::from_bits(c.debug_mode).max(f32::MIN_POSITIVE);
+        let plane = self.slot(c.in_buf) + c.in_channels as usize * in_w * in_h;
+        for y in 0..h {
+            let sy = (y * in_h / h).min(in_h - 1);
+            for x in 0..w {
+                let sx = (x * in_w / w).min(in_w - 1);
+                let t = (self.features[plane + sy * in_w + sx] / range).clamp(-1.0, 1.0);
+                self.output[y * w + x] = if t >= 0.0 {
+                    [t, t * 0.5, 0.0, 1.0]
+                } else {
+                    [0.0, -t * 0.5, -t, 1.0]
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unshuffle, down to 1/4 and back: aligned to 4. Kept small, the
+    /// reference is slow at these sizes.
+    const PAD_GRAPH: &str = "\
+model pad-test
+x = unshuffle
+d = conv x k=1 stride=2 out=4 weight=d.weight
+dn = groupnorm d groups=2 act=silu gamma=dn.weight beta=dn.bias
+u = upsample dn
+head = conv u k=1 out=12 act=tanh weight=head.weight bias=head.bias
+output head
+";
+
+    /// One temporal fusion at 1/2 resolution.
+    const TEMPORAL_GRAPH: &str = "\
+model mask-test
+curr_in = unshuffle frame=current
+curr_t = conv curr_in k=1 out=4 weight=enc.weight
+prev_in = unshuffle frame=previous
+prev_t = conv prev_in k=1 out=4 weight=enc.weight
+temporal curr_t prev_t
+mv = scale_mv div=2
+warped = warp prev_t mv
+fused = attention curr_t warped weight=att.weight bias=att.bias
+head = conv fused k=1 out=12 act=tanh weight=head.weight bias=head.bias
+output head
+";
+
+    struct Frame {
+        color: Vec<[f32; 3]>,
+        depth: Vec<f32>,
+        motion_vectors: Vec<[f32; 2]>,
+    }
+
+    impl Frame {
+        /// A `w × h` frame of `w0 × h0` content, edge pixels repeated past it.
+        fn new(w: u32, h: u32, w0: u32, h0: u32) -> Self {
+            let mut frame = Frame {
+                color: Vec::new(),
+                depth: Vec::new(),
+                motion_vectors: Vec::new(),
+            };
+            for y in 0..h {
+                for x in 0..w {
+                    let (x, y) = (x.min(w0 - 1), y.min(h0 - 1));
+                    frame.color.push([
+                        x as f32 / w0 as f32,
+                        y as f32 / h0 as f32,
+                        ((x * 7 + y * 3) % 13) as f32 / 13.0,
+                    ]);
+                    frame.depth.push(((x ^ y) % 17) as f32 / 17.0);
+                    // Zero: `input_channels` scales them by the render size
+                    frame.motion_vectors.push([0.0; 2]);
+                }
+            }
+            frame
+        }
+
+        fn inputs(&self) -> AAFrameInputs<'_> {
+            AAFrameInputs {
+                color: &self.color,
+                depth: &self.depth,
+                motion_vectors: &self.motion_vectors,
+                jitter: [0.0; 2],
+                history_mask: None,
+            }
+        }
+    }
+
+    fn reference(graph: &Graph, w: u32, h: u32) -> AAReference {
+        let weights = (0..graph.weight_count())
+            .map(|i| ((i * 37 % 101) as f32 / 101.0 - 0.5) * 0.5)
+            .collect();
+        AAReference::new(w, h, graph, weights).unwrap()
+    }
+
+    fn run(graph: &Graph, frame: &Frame, w: u32, h: u32) -> Vec<[f32; 4]> {
+        let inputs = frame.inputs();
+        reference(graph, w, h)
+            .run(&inputs, &inputs)
+            .unwrap()
+            .to_vec()
+    }
+
+    /// Where the mask is fully set the previous frame has no effect; where it
+    /// is clear history still counts.
+    #[test]
+    fn history_mask_rejects_the_warped_history() {
+        let graph = Graph::parse(TEMPORAL_GRAPH).unwrap();
+        let dispatches = graph.dispatch_table(8, 8, 8, 8).dispatches;
+        let mask_pass = dispatches
+            .iter()
+            .position(|c| c.pass_type == PassType::HistoryMask as u32)
+            .unwrap();
+        assert_eq!(
+            dispatches[mask_pass + 1].pass_type,
+            PassType::Attention as u32
+        );
+        assert_eq!(dispatches[mask_pass].out_buf, dispatches[mask_pass].aux_buf);
+
+        let curr = Frame::new(8, 8, 8, 8);
+        let mut prev = Frame::new(8, 8, 8, 8);
+        prev.color
+            .iter_mut()
+            .for_each(|c| *c = [1.0 - c[2], c[0], 0.25]);
+        // Left half rejects history
+        let mask: Vec<f32> = (0..64).map(|i| if i % 8 < 4 { 1.0 } else { 0.0 }).collect();
+
+        let output = |prev: &Frame, mask: Option<&[f32]>| {
+            let curr = AAFrameInputs {
+                history_mask: mask,
+                ..curr.inputs()
+            };
+            let output = reference(&graph, 8, 8)
+                .run(&curr, &prev.inputs())
+                .unwrap()
+                .to_vec();
+            output
+        };
+        let still = output(&curr, Some(&mask));
+        let moved = output(&prev, Some(&mask));
+        for i in 0..64 {
+            if i % 8 < 4 {
+                assert_eq!(still[i], moved[i], "pixel {}", i);
+            } else {
+                assert_ne!(still[i], moved[i], "pixel {}", i);
+            }
+        }
+        assert_ne!(output(&curr, None), output(&prev, None));
+
+        let short = [0.0; 3];
+        let inputs = AAFrameInputs {
+            history_mask: Some(&short),
+            ..curr.inputs()
+        };
+        assert!(reference(&graph, 8, 8).run(&inputs, &inputs).is_err());
+    }
+
+    /// A render size the network does not divide gives the aligned size's
+    /// output for the edge pixels repeated, cropped; the residual reaches the
+    /// last row and column.
+    #[test]
+    fn padded_frame_matches_edge_repeated_input() {
+        let graph = Graph::parse(PAD_GRAPH).unwrap();
+        assert_eq!(graph.align(), 4);
+        for (w, h) in [(1706u32, 960u32), (2293, 960), (37, 29)] {
+            let (pw, ph) = (w.next_multiple_of(4), h.next_multiple_of(4));
+            assert_ne!((pw, ph), (w, h));
+
+            let frame = Frame::new(w, h, w, h);
+            let output = run(&graph, &frame, w, h);
+            let aligned = run(&graph, &Frame::new(pw, ph, w, h), pw, ph);
+            assert_eq!(output.len(), (w * h) as usize);
+            for y in 0..h as usize {
+                for x in 0..w as usize {
+                    assert_eq!(
+                        output[y * w as usize + x],
+                        aligned[y * pw as usize + x],
+                        "{}x{} at ({}, {})",
+                        w,
+                        h,
+                        x,
+                        y
+                    );
+                }
+            }
+
+            let changed = |i: usize| (0..3).any(|ch| output[i][ch] != frame.color[i][ch]);
+            let (w, h) = (w as usize, h as usize);
+            assert!((0..h).all(|y| changed(y * w + w - 1)));
+            assert!((0..w).all(|x| changed((h - 1) * w + x)));
+        }
+    }
+}
@@ -0,0 +1,1118 @@
+//! IMBA network description and its compilation to a dispatch table.
+//!
+//! A model is a text file, one statement per line, `#` starting a comment:
+//!
+//! ```text
+//! model <name>                      # weight files must declare this name
+//! <tensor> = <op> <inputs...> <key>=<value>...
+//! temporal <current> <previous>     # <current> is <previous> next frame
+//! output <tensor>                   # PixelShuffleOut, ends the frame
+//! ```
+//!
+//! The network runs at render resolution. When the output is larger, the
+//! `output` node becomes `PixelShuffleUpscale`: the frame is reconstructed at
+//! render resolution as usual and resampled to the output size in the same
+//! pass. A render size that some tensor's downscale does not divide is padded
+//! (`Graph::align`): the unshuffle and `scale_mv` read the edge pixels again
+//! past it, and the output is cropped back.
+//!
+//! Tensors are named once. Ops and their options (`act` is `none`, `silu` or
+//! `tanh`; bracketed options may be left out):
+//!
+//! - `unshuffle [frame=current|previous]`: the frame's inputs, 32 channels at
+//!   half resolution;
+//! - `conv x k= out= weight= [stride=1] [bias=] [act=none]`;
+//! - `groupnorm x groups= gamma= beta= [act=none] [skip=]`: GNStats,
+//!   GNStatsReduce and GNApply; `skip` is added before the activation;
+//! - `scale_mv div=`: motion vectors at `1/div` resolution;
+//! - `warp x mv`: `x` backward-warped along `mv`;
+//! - `attention current warped weight= bias=`: preceded by a `HistoryMask`
+//!   pass that replaces `warped` by `current` where the game's masks reject
+//!   history;
+//! - `upsample x`: 2× nearest;
+//! - `skip_concat a b out= weight= [bias=]`: 1×1 conv over both;
+//! - `history x frames=`: `x` as it was `frames` frames ago, 2 up to
+//!   `AA_MAX_HISTORY`; `x` must be the `temporal` current. Until that many
+//!   frames are cached the oldest one stands in.
+//!
+//! The weight layout is taken from the ops, in first-use order, with shapes
+//! `[out, in, k, k]` for convolutions and `[channels]` for biases and
+//! GroupNorm parameters. Feature slots are assigned by liveness: a tensor
+//! holds its slot from its producer to its last reader, `groupnorm` works in
+//! place when its input dies there, and the temporal pair stays live across
+//! the whole frame. The nodes that only feed `<previous>` form the
+//! previous-frame encoder, skipped once `<current>` has been cached.
+//!
+//! The runtime keeps the last `Graph::history_depth` frames of `<current>`.
+//! `history` tensors are filled from them before the first dispatch, or with
+//! nothing cached, from `<previous>` once the encoder has run; they may only
+//! be read after it.
+
+use super::*;
+use crate::weights::TensorInfo;
+use std::ops::Range;
+
+/// Most GroupNorm groups a node may use; `gn_stats` holds a mean and an
+/// inverse deviation per group.
+pub const AA_GN_MAX_GROUPS: u32 = 32;
+/// Most frames a `history` tensor may look back.
+pub const AA_MAX_HISTORY: u32 = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    PixelUnshuffle {
+        previous: bool,
+    },
+    Conv {
+        kernel: u32,
+        stride: u32,
+        weight_off: u32,
+        bias_off: u32,
+        activation: u32,
+    },
+    GroupNorm {
+        groups: u32,
+        gamma_off: u32,
+        beta_off: u32,
+        activation: u32,
+        skip: Option<usize>,
+    },
+    ScaleMV,
+    BackwardWarp,
+    Attention {
+        weight_off: u32,
+        bias_off: u32,
+    },
+    NearestUpsample,
+    SkipConcatConv {
+        weight_off: u32,
+        bias_off: u32,
+    },
+    PixelShuffleOut,
+}
+
+/// A named feature tensor at `1/div` of the render resolution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphTensor {
+    pub name: String,
+    pub channels: u32,
+    pub div: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphNode {
+    pub op: Op,
+    pub inputs: Vec<usize>,
+    /// `None` for the output node.
+    pub output: Option<usize>,
+    /// Source line, for messages.
+    pub line: usize,
+}
+
+/// A parsed and checked model, slots assigned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Graph {
+    pub model_name: String,
+    pub tensors: Vec<GraphTensor>,
+    pub nodes: Vec<GraphNode>,
+    /// Weight tensors in buffer order, offsets packed.
+    pub weights: Vec<TensorInfo>,
+    /// `(current, previous)` temporal tensors.
+    pub temporal: Option<(usize, usize)>,
+    /// `(tensor, frames)` of each `history` tensor.
+    pub history: Vec<(usize, u32)>,
+    /// Feature slot of each tensor.
+    pub slots: Vec<u32>,
+    pub slot_count: u32,
+    /// Nodes of the previous-frame encoder; empty without `temporal`.
+    pub prev_encoder: Range<usize>,
+}
+
+/// The cached temporal tensor of a compiled table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemporalCache {
+    /// Slot copied out after the frame.
+    pub slot: u32,
+    /// Slot the cache is copied into before the next one.
+    pub prev_slot: u32,
+    /// Floats copied.
+    pub len: u32,
+    /// Frames kept, `Graph::history_depth`.
+    pub depth: u32,
+}
+
+/// A `history` tensor of a compiled table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryRead {
+    pub slot: u32,
+    /// How many frames back; the oldest cached frame when fewer are.
+    pub frames: u32,
+}
+
+/// A named tensor of a compiled table, in its slot right after `dispatch`
+/// (later dispatches may reuse the slot).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Activation {
+    pub name: String,
+    /// Index of the dispatch that completes it.
+    pub dispatch: u32,
+    pub slot: u32,
+    pub channels: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Activation {
+    /// Floats in the tensor.
+    pub fn len(&self) -> u32 {
+        self.channels * self.width * self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// A `DebugView` dispatch drawing `channel` over an `output_w × output_h`
+    /// output, nearest-sampled: positive values red, negative blue, `range`
+    /// at full intensity.
+    pub fn view(
+        &self,
+        channel: u32,
+        range: f32,
+        output_w: u32,
+        output_h: u32,
+        buf_stride: u32,
+    ) -> AAConstants {
+        AAConstants {
+            pass_type: PassType::DebugView as u32,
+            in_buf: self.slot,
+            in_channels: channel.min(self.channels.saturating_sub(1)),
+            in_width: self.width,
+            in_height: self.height,
+            width: output_w,
+            height: output_h,
+            buf_stride,
+            debug_mode: range.to_bits(),
+            ..Default::default()
+        }
+    }
+}
+
+/// A graph compiled for one render size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DispatchTable {
+    pub dispatches: Vec<AAConstants>,
+    /// Floats per feature slot.
+    pub buf_stride: u32,
+    pub slots: u32,
+    /// Dispatches skipped while the temporal cache is valid.
+    pub prev_encoder: Range<u32>,
+    pub temporal: Option<TemporalCache>,
+    /// Filled before the first dispatch, or with nothing cached right after
+    /// `prev_encoder`.
+    pub history: Vec<HistoryRead>,
+    /// Largest GroupNorm group count, sizing `gn_partials`.
+    pub gn_max_groups: u32,
+    /// Every tensor a dispatch produces, in order (not `history` tensors).
+    pub activations: Vec<Activation>,
+}
+
+fn activation(name: &str) -> Option<u32> {
+    match name {
+        "none" => Some(ACT_NONE),
+        "silu" => Some(ACT_SILU),
+        "tanh" => Some(ACT_TANH),
+        _ => None,
+    }
+}
+
+/// One statement's positional inputs and `key=value` options.
+struct Args<'a> {
+    inputs: Vec<&'a str>,
+    options: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Args<'a> {
+    fn parse(tokens: &[&'a str]) -> Self {
+        let mut args = Args {
+            inputs: Vec::new(),
+            options: Vec::new(),
+        };
+        for &t in tokens {
+            match t.split_once('=') {
+                Some((k, v)) => args.options.push((k, v)),
+                None => args.inputs.push(t),
+            }
+        }
+        args
+    }
+
+    fn take(&mut self, key: &str) -> Option<&'a str> {
+        let i = self.options.iter().position(|&(k, _)| k == key)?;
+        Some(self.options.remove(i).1)
+    }
+
+    fn required(&mut self, key: &str) -> Result<&'a str, String> {
+        self.take(key).ok_or(format!("missing {}=", key))
+    }
+
+    fn number(&mut self, key: &str, default: Option<u32>) -> Result<u32, String> {
+        match self.take(key) {
+            Some(v) => v
+                .parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or(format!("{}={} is not a positive number", key, v)),
+            None => default.ok_or(format!("missing {}=", key)),
+        }
+    }
+
+    fn activation(&mut self) -> Result<u32, String> {
+        let name = self.take("act").unwrap_or("none");
+        activation(name).ok_or(format!("unknown activation '{}'", name))
+    }
+
+    /// Check the input count before reading options that depend on inputs.
+    fn finish_inputs(&self, inputs: usize) -> Result<(), String> {
+        if self.inputs.len() != inputs {
+            return Err(format!(
+                "takes {} input(s), got {}",
+                inputs,
+                self.inputs.len()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check the input count and that every option was used.
+    fn finish(&self, inputs: usize) -> Result<(), String> {
+        self.finish_inputs(inputs)?;
+        match self.options.first() {
+            Some((k, _)) => Err(format!("unknown option {}=", k)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Builder {
+    model_name: Option<String>,
+    tensors: Vec<GraphTensor>,
+    nodes: Vec<GraphNode>,
+    weights: Vec<TensorInfo>,
+    temporal: Option<(usize, usize)>,
+    /// `(tensor, source, frames)` of each `history` tensor.
+    history: Vec<(usize, usize, u32)>,
+    has_output: bool,
+}
+
+impl Builder {
+    fn tensor(&self, name: &str) -> Result<usize, String> {
+        self.tensors
+            .iter()
+            .position(|t| t.name == name)
+            .ok_or(format!("unknown tensor '{}'", name))
+    }
+
+    /// Offset of weight tensor `name`, added on first use.
+    fn weight(&mut self, name: &str, shape: Vec<u32>) -> Result<u32, String> {
+        if let Some(t) = self.weights.iter().find(|t| t.name == name) {
+            if t.shape != shape {
+                return Err(format!(
+                    "weight '{}' used as {:?} and {:?}",
+                    name, t.shape, shape
+                ));
+            }
+            return Ok(t.offset);
+        }
+        let offset = crate::weights::layout_len(&self.weights) as u32;
+        self.weights.push(TensorInfo {
+            name: name.to_string(),
+            shape,
+            offset,
+        });
+        Ok(offset)
+    }
+
+    fn bias(&mut self, name: Option<&str>, channels: u32) -> Result<u32, String> {
+        match name {
+            Some(name) => self.weight(name, vec![channels]),
+            None => Ok(AA_NO_OFFSET),
+        }
+    }
+
+    fn statement(&mut self, tokens: &[&str], line: usize) -> Result<(), String> {
+        if self.has_output {
+            return Err("statement after output".into());
+        }
+        match tokens {
+            ["model", name] => {
+                self.model_name = Some(name.to_string());
+                Ok(())
+            }
+            ["temporal", current, previous] => {
+                self.temporal = Some((self.tensor(current)?, self.tensor(previous)?));
+                Ok(())
+            }
+            ["output", x] => {
+                let x = self.tensor(x)?;
+                let t = &self.tensors[x];
+                if t.channels != 12 || t.div != 2 {
+                    return Err(format!(
+                        "output takes 12 channels at 1/2, got {} at 1/{}",
+                        t.channels, t.div
+                    ));
+                }
+                self.nodes.push(GraphNode {
+                    op: Op::PixelShuffleOut,
+                    inputs: vec![x],
+                    output: None,
+                    line,
+                });
+                self.has_output = true;
+                Ok(())
+            }
+            [name, "=", op, rest @ ..] => {
+                if self.tensors.iter().any(|t| t.name == *name) {
+                    return Err(format!("tensor '{}' already defined", name));
+                }
+                let mut args = Args::parse(rest);
+                if *op == "history" {
+                    let (x, frames) = self.history_op(&mut args)?;
+                    let x_tensor = &self.tensors[x];
+                    self.tensors.push(GraphTensor {
+                        name: name.to_string(),
+                        ..x_tensor.clone()
+                    });
+                    self.history.push((self.tensors.len() - 1, x, frames));
+                    return Ok(());
+                }
+                let (op, inputs, channels, div) = self.op(op, &mut args)?;
+                self.tensors.push(GraphTensor {
+                    name: name.to_string(),
+                    channels,
+                    div,
+                });
+                self.nodes.push(GraphNode {
+                    op,
+                    inputs,
+                    output: Some(self.tensors.len() - 1),
+                    line,
+                });
+                Ok(())
+            }
+            _ => Err("expected `model`, `temporal`, `output` or `<tensor> = <op> ...`".into()),
+        }
+    }
+
+    /// Parse `history x frames=`: returns `x` and how many frames back.
+    fn history_op(&self, args: &mut Args) -> Result<(usize, u32), String> {
+        args.finish_inputs(1)?;
+        let x = self.tensor(args.inputs[0])?;
+        let frames = args.number("frames", None)?;
+        args.finish(1)?;
+        if !(2..=AA_MAX_HISTORY).contains(&frames) {
+            return Err(format!(
+                "frames={} (2 to {}; 1 frame back is the temporal previous)",
+                frames, AA_MAX_HISTORY
+            ));
+        }
+        Ok((x, frames))
+    }
+
+    /// Parse an op: returns it with its input tensors and the output's
+    /// channels and divisor.
+    fn op(&mut self, op: &str, args: &mut Args) -> Result<(Op, Vec<usize>, u32, u32), String> {
+        let inputs = args
+            .inputs
+            .iter()
+            .map(|name| self.tensor(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ins: Vec<GraphTensor> = inputs.iter().map(|&t| self.tensors[t].clone()).collect();
+        let input = |i: usize| ins[i].clone();
+        let result = match op {
+            "unshuffle" => {
+                let previous = match args.take("frame").unwrap_or("current") {
+                    "current" => false,
+                    "previous" => true,
+                    other => return Err(format!("unknown frame '{}'", other)),
+                };
+                args.finish(0)?;
+                let channels = 4 * cpu::AA_INPUT_CHANNELS as u32;
+                (Op::PixelUnshuffle { previous }, channels, 2)
+            }
+            "conv" => {
+                args.finish_inputs(1)?;
+                let x = input(0);
+                let kernel = args.number("k", None)?;
+                let stride = args.number("stride", Some(1))?;
+                let out = args.number("out", None)?;
+                let weight = args.required("weight")?;
+                let bias = args.take("bias");
+                let activation = args.activation()?;
+                args.finish(1)?;
+                if stride > 2 {
+                    return Err(format!("stride {} (1 or 2)", stride));
+                }
+                let weight_off = self.weight(weight, vec![out, x.channels, kernel, kernel])?;
+                let bias_off = self.bias(bias, out)?;
+                let op = Op::Conv {
+                    kernel,
+                    stride,
+                    weight_off,
+                    bias_off,
+                    activation,
+                };
+                (op, out, x.div * stride)
+            }
+            "groupnorm" => {
+                args.finish_inputs(1)?;
+                let x = input(0);
+                let groups = args.number("groups", None)?;
+                let gamma = args.required("gamma")?;
+                let beta = args.required("beta")?;
+                let activation = args.activation()?;
+                let skip = args.take("skip").map(|s| self.tensor(s)).transpose()?;
+                args.finish(1)?;
+                if groups > AA_GN_MAX_GROUPS || x.channels % groups != 0 {
+                    return Err(format!(
+                        "{} groups for {} channels (at most {}, dividing the channels)",
+                        groups, x.channels, AA_GN_MAX_GROUPS
+                    ));
+                }
+                if let Some(s) = skip {
+                    let s = &self.tensors[s];
+                    if (s.channels, s.div) != (x.channels, x.div) {
+                        return Err(format!("skip '{}' does not match the input", s.name));
+                    }
+                }
+                let gamma_off = self.weight(gamma, vec![x.channels])?;
+                let beta_off = self.weight(beta, vec![x.channels])?;
+                let op = Op::GroupNorm {
+                    groups,
+                    gamma_off,
+                    beta_off,
+                    activation,
+                    skip,
+                };
+                let mut inputs = inputs.clone();
+                inputs.extend(skip);
+                return Ok((op, inputs, x.channels, x.div));
+            }
+            "scale_mv" => {
+                let div = args.number("div", None)?;
+                args.finish(0)?;
+                (Op::ScaleMV, 2, div)
+            }
+            "warp" => {
+                args.finish(2)?;
+                let (x, mv) = (input(0), input(1));
+                if mv.channels != 2 || mv.div != x.div {
+                    return Err(format!(
+                        "motion vectors '{}' must be 2 channels at 1/{}",
+                        mv.name, x.div
+                    ));
+                }
+                (Op::BackwardWarp, x.channels, x.div)
+            }
+            "attention" => {
+                args.finish_inputs(2)?;
+                let (a, b) = (input(0), input(1));
+                let weight = args.required("weight")?;
+                let bias = args.required("bias")?;
+                args.finish(2)?;
+                if (a.channels, a.div) != (b.channels, b.div) {
+                    return Err(format!("'{}' and '{}' differ in shape", a.name, b.name));
+                }
+                let weight_off = self.weight(weight, vec![a.channels, 2 * a.channels, 1, 1])?;
+                let bias_off = self.weight(bias, vec![a.channels])?;
+                let op = Op::Attention {
+                    weight_off,
+                    bias_off,
+                };
+                (op, a.channels, a.div)
+            }
+            "upsample" => {
+                args.finish(1)?;
+                let x = input(0);
+                if x.div < 2 || x.div % 2 != 0 {
+                    return Err(format!("cannot upsample 1/{} resolution", x.div));
+                }
+                (Op::NearestUpsample, x.channels, x.div / 2)
+            }
+            "skip_concat" => {
+                args.finish_inputs(2)?;
+                let (a, b) = (input(0), input(1));
+                let out = args.number("out", None)?;
+                let weight = args.required("weight")?;
+                let bias = args.take("bias");
+                args.finish(2)?;
+                if (a.channels, a.div) != (b.channels, b.div) {
+                    return Err(format!("'{}' and '{}' differ in shape", a.name, b.name));
+                }
+                let weight_off = self.weight(weight, vec![out, 2 * a.channels, 1, 1])?;
+                let bias_off = self.bias(bias, out)?;
+                let op = Op::SkipConcatConv {
+                    weight_off,
+                    bias_off,
+                };
+                (op, out, a.div)
+            }
+            other => return Err(format!("unknown op '{}'", other)),
+        };
+        let (op, channels, div) = result;
+        Ok((op, inputs, channels, div))
+    }
+}
+
+impl Graph {
+    /// The IMBA v0 network the legacy proxy ships with.
+    pub fn builtin() -> Self {
+        Self::parse(IMBA_V0_GRAPH).expect("built-in IMBA graph")
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut b = Builder::default();
+        for (i, line) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let code = line.split('#').next().unwrap_or("");
+            let tokens: Vec<&str> = code.split_whitespace().collect();
+            if tokens.is_empty() {
+                continue;
+            }
+            b.statement(&tokens, line_no)
+                .map_err(|e| format!("line {}: {}", line_no, e))?;
+        }
+        let model_name = b.model_name.ok_or("missing `model`")?;
+        if !b.has_output {
+            return Err("missing `output`".into());
+        }
+        for t in &b.tensors {
+            if t.channels > 32 || t.div < 2 {
+                return Err(format!(
+                    "tensor '{}' ({} channels at 1/{}) is larger than a feature slot \
+                     (32 channels at 1/2)",
+                    t.name, t.channels, t.div
+                ));
+            }
+        }
+
+        let prev_encoder = previous_encoder(&b.tensors, &b.nodes, b.temporal)?;
+        let mut history = Vec::with_capacity(b.history.len());
+        for &(t, source, frames) in &b.history {
+            let name = &b.tensors[t].name;
+            if b.temporal.is_none_or(|(current, _)| current != source) {
+                return Err(format!(
+                    "history '{}': '{}' is not the temporal current",
+                    name, b.tensors[source].name
+                ));
+            }
+            if let Some(node) = b.nodes[..prev_encoder.end]
+                .iter()
+                .find(|n| n.inputs.contains(&t))
+            {
+                return Err(format!(
+                    "line {}: reads history '{}' before the previous-frame encoder ends",
+                    node.line, name
+                ));
+            }
+            history.push((t, frames));
+        }
+        let (slots, slot_count) =
+            allocate_slots(&b.tensors, &b.nodes, b.temporal, &history, prev_encoder.end);
+        Ok(Self {
+            model_name,
+            tensors: b.tensors,
+            nodes: b.nodes,
+            weights: b.weights,
+            temporal: b.temporal,
+            history,
+            slots,
+            slot_count,
+            prev_encoder,
+        })
+    }
+
+    /// Frames of `<current>` the runtime keeps: the furthest `history` looks
+    /// back, 1 with only `temporal`, 0 without.
+    pub fn history_depth(&self) -> u32 {
+        match self.temporal {
+            Some(_) => self.history.iter().map(|&(_, k)| k).max().unwrap_or(1),
+            None => 0,
+        }
+    }
+
+    /// Weight floats the graph reads.
+    pub fn weight_count(&self) -> u32 {
+        crate::weights::layout_len(&self.weights) as u32
+    }
+
+    /// What the render size is padded to a multiple of: every tensor's
+    /// downscale divides it.
+    pub fn align(&self) -> u32 {
+        fn gcd(a: u32, b: u32) -> u32 {
+            if b == 0 {
+                a
+            } else {
+                gcd(b, a % b)
+            }
+        }
+        self.tensors
+            .iter()
+            .fold(1, |align, t| align / gcd(align, t.div) * t.div)
+    }
+
+    /// Every dispatch of one frame at `render_w × render_h` written to an
+    /// `output_w × output_h` output, in order. The scale factor is the output size over the render
+    /// size; at 1 the output is `PixelShuffleOut`, above it
+    /// `PixelShuffleUpscale`.
+    ///
+    /// Tensors cover the render size rounded up to `align`. When that pads,
+    /// the passes reading the frame inputs are their `Padded` variants, which
+    /// repeat the edge pixels, and the output passes crop to the render size.
+    pub fn dispatch_table(
+        &self,
+        render_w: u32,
+        render_h: u32,
+        output_w: u32,
+        output_h: u32,
+    ) -> DispatchTable {
+        let align = self.align();
+        let (padded_w, padded_h) = (
+            render_w.next_multiple_of(align),
+            render_h.next_multiple_of(align),
+        );
+        let padded = (padded_w, padded_h) != (render_w, render_h);
+        let size = |div: u32| (padded_w / div, padded_h / div);
+        let buf_stride = self
+            .tensors
+            .iter()
+            .map(|t| {
+                let (w, h) = size(t.div);
+                t.channels * w * h
+            })
+            .max()
+            .unwrap_or(0);
+
+        let mut dispatches = Vec::with_capacity(self.nodes.len() * 2);
+        let mut prev_encoder = 0..0;
+        let mut gn_max_groups = 0;
+        let mut activations = Vec::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            if i == self.prev_encoder.start {
+                prev_encoder.start = dispatches.len() as u32;
+            }
+            let out = node.output.map(|t| &self.tensors[t]);
+            let in0 = node.inputs.first().map(|&t| &self.tensors[t]);
+            let mut c = AAConstants {
+                buf_stride,
+                out_buf: node.output.map_or(0, |t| self.slots[t]),
+                in_buf: node.inputs.first().map_or(0, |&t| self.slots[t]),
+                aux_buf: node.inputs.get(1).map_or(0, |&t| self.slots[t]),
+                render_width: render_w,
+                render_height: render_h,
+                ..Default::default()
+            };
+            if let Some(out) = out {
+                (c.width, c.height) = size(out.div);
+                c.out_channels = out.channels;
+            }
+            if let Some(in0) = in0 {
+                (c.in_width, c.in_height) = size(in0.div);
+                c.in_channels = in0.channels;
+            }
+            match node.op {
+                Op::PixelUnshuffle { previous } => {
+                    c.pass_type = if padded {
+                        PassType::PixelUnshufflePadded as u32
+                    } else {
+                        PassType::PixelUnshuffle as u32
+                    };
+                    (c.in_width, c.in_height) = (render_w, render_h);
+                    c.out_channels = 0;
+                    c.flags = if previous { FLAG_IS_PREV } else { 0 };
+                }
+                Op::Conv {
+                    kernel,
+                    stride,
+                    weight_off,
+                    bias_off,
+                    activation,
+                } => {
+                    c.pass_type = select_conv_pass(kernel, stride, c.in_channels, c.out_channels);
+                    c.kernel_size = kernel;
+                    c.stride = stride;
+                    c.weight_off = weight_off;
+                    c.bias_off = bias_off;
+                    c.activation = activation;
+                }
+                Op::GroupNorm {
+                    groups,
+                    gamma_off,
+                    beta_off,
+                    activation,
+                    skip,
+                } => {
+                    gn_max_groups = gn_max_groups.max(groups);
+                    let stats = AAConstants {
+                        pass_type: PassType::GNStats as u32,
+                        in_buf: c.in_buf,
+                        out_channels: c.out_channels,
+                        num_groups: groups,
+                        width: c.width,
+                        height: c.height,
+                        buf_stride,
+                        ..Default::default()
+                    };
+                    dispatches.push(stats);
+                    dispatches.push(AAConstants {
+                        pass_type: PassType::GNStatsReduce as u32,
+                        ..stats
+                    });
+                    c = AAConstants {
+                        pass_type: PassType::GNApply as u32,
+                        out_buf: c.out_buf,
+                        gamma_off,
+                        beta_off,
+                        activation,
+                        flags: if skip.is_some() { FLAG_HAS_SKIP } else { 0 },
+                        aux_buf: skip.map_or(0, |t| self.slots[t]),
+                        ..stats
+                    };
+                }
+                Op::ScaleMV => {
+                    (c.in_width, c.in_height) = (render_w, render_h);
+                    c.out_channels = 0;
+                    if padded {
+                        c.pass_type = PassType::ScaleMVPadded as u32;
+                        c.stride = out.map_or(1, |t| t.div);
+                    } else {
+                        c.pass_type = PassType::ScaleMV as u32;
+                    }
+                }
+                Op::BackwardWarp => {
+                    c.pass_type = PassType::BackwardWarp as u32;
+                    (c.in_width, c.in_height, c.out_channels) = (0, 0, 0);
+                }
+                Op::Attention {
+                    weight_off,
+                    bias_off,
+                } => {
+                    // In place: later readers of `warped` see the masked history too
+                    dispatches.push(AAConstants {
+                        pass_type: PassType::HistoryMask as u32,
+                        out_buf: c.aux_buf,
+                        stride: out.map_or(1, |t| t.div),
+                        in_width: render_w,
+                        in_height: render_h,
+                        out_channels: 0,
+                        ..c
+                    });
+                    c.pass_type = PassType::Attention as u32;
+                    c.weight_off = weight_off;
+                    c.bias_off = bias_off;
+                    (c.in_width, c.in_height, c.out_channels) = (0, 0, 0);
+                }
+                Op::NearestUpsample => {
+                    c.pass_type = PassType::NearestUpsample as u32;
+                    c.out_channels = 0;
+                }
+                Op::SkipConcatConv {
+                    weight_off,
+                    bias_off,
+                } => {
+                    c.pass_type = PassType::SkipConcatConv as u32;
+                    c.in_channels *= 2;
+                    c.weight_off = weight_off;
+                    c.bias_off = bias_off;
+                    (c.in_width, c.in_height) = (0, 0);
+                }
+                Op::PixelShuffleOut if (output_w, output_h) == (render_w, render_h) => {
+                    c.pass_type = PassType::PixelShuffleOut as u32;
+                    (c.width, c.height) = (render_w, render_h);
+                    c.in_channels = 0;
+                }
+                Op::PixelShuffleOut => {
+                    c.pass_type = PassType::PixelShuffleUpscale as u32;
+                    (c.width, c.height) = (output_w, output_h);
+                    c.stride = in0.map_or(2, |t| t.div);
+                    c.in_channels = 0;
+                }
+            }
+            dispatches.push(c);
+            if let Some(t) = node.output {
+                let tensor = &self.tensors[t];
+                let (width, height) = size(tensor.div);
+                activations.push(Activation {
+                    name: tensor.name.clone(),
+                    dispatch: dispatches.len() as u32 - 1,
+                    slot: self.slots[t],
+                    channels: tensor.channels,
+                    width,
+                    height,
+                });
+            }
+            if i + 1 == self.prev_encoder.end {
+                prev_encoder.end = dispatches.len() as u32;
+            }
+        }
+
+        let temporal = self.temporal.map(|(current, previous)| {
+            let t = &self.tensors[current];
+            let (w, h) = size(t.div);
+            TemporalCache {
+                slot: self.slots[current],
+                prev_slot: self.slots[previous],
+                len: t.channels * w * h,
+                depth: self.history_depth(),
+            }
+        });
+        let history = self
+            .history
+            .iter()
+            .map(|&(t, frames)| HistoryRead {
+                slot: self.slots[t],
+                frames,
+            })
+            .collect();
+        DispatchTable {
+            dispatches,
+            buf_stride,
+            slots: self.slot_count,
+            prev_encoder,
+            temporal,
+            history,
+            gn_max_groups,
+            activations,
+        }
+    }
+}
+
+fn select_conv_pass(ks: u32, stride: u32, in_ch: u32, out_ch: u32) -> u32 {
+    match (ks, stride, in_ch, out_ch) {
+        (3, 1, 16, 16) => PassType::Conv3x3_16x16 as u32,
+        (3, 1, 32, 32) => PassType::Conv3x3_32x32 as u32,
+        (3, 1, 32, 16) => PassType::Conv3x3_32x16 as u32,
+        (3, 1, 16, 12) => PassType::Conv3x3_16x12 as u32,
+        (3, 1, 12, 12) => PassType::Conv3x3_12x12 as u32,
+        (3, 2, 16, 32) => PassType::Conv3x3S2_16x32 as u32,
+        _ => PassType::Conv as u32,
+    }
+}
+
+/// The nodes that compute `previous` from the previous frame's inputs. They
+/// must be contiguous and feed nothing but `previous` to the rest.
+fn previous_encoder(
+    tensors: &[GraphTensor],
+    nodes: &[GraphNode],
+    temporal: Option<(usize, usize)>,
+) -> Result<Range<usize>, String> {
+    let Some((current, previous)) = temporal else {
+        return Ok(0..0);
+    };
+    let mut from_prev = vec![false; tensors.len()];
+    let mut members = Vec::new();
+    for (i, node) in nodes.iter().enumerate() {
+        // Readers of `previous` itself get it from the cache
+        let reads_prev = node.inputs.iter().any(|&t| from_prev[t] && t != previous);
+        let is_prev = matches!(node.op, Op::PixelUnshuffle { previous: true }) || reads_prev;
+        if is_prev {
+            if let Some(out) = node.output {
+                from_prev[out] = true;
+            }
+            members.push(i);
+        }
+    }
+
+    let name = |t: usize| &tensors[t].name;
+    if !from_prev[previous] {
+        return Err(format!(
+            "temporal: '{}' does not come from the previous frame",
+            name(previous)
+        ));
+    }
+    if from_prev[current] {
+        return Err(format!(
+            "temporal: '{}' depends on the previous frame",
+            name(current)
+        ));
+    }
+    let range = members
+        .first()
+        .map_or(0..0, |&s| s..members[members.len() - 1] + 1);
+    if range.len() != members.len() {
+        return Err("temporal: the previous-frame nodes must be contiguous".into());
+    }
+    for node in &nodes[range.end..] {
+        if let Some(&t) = node.inputs.iter().find(|&&t| from_prev[t] && t != previous) {
+            return Err(format!(
+                "line {}: reads '{}' of the previous-frame encoder; only '{}' is cached",
+                node.line,
+                name(t),
+                name(previous)
+            ));
+        }
+    }
+    Ok(range)
+}
+
+/// Linear-scan slot assignment over node order. Returns each tensor's slot
+/// and the number of slots used.
+fn allocate_slots(
+    tensors: &[GraphTensor],
+    nodes: &[GraphNode],
+    temporal: Option<(usize, usize)>,
+    history: &[(usize, u32)],
+    prev_encoder_end: usize,
+) -> (Vec<u32>, u32) {
+    let mut defined = vec![0; tensors.len()];
+    let mut last_use: Vec<Option<usize>> = vec![None; tensors.len()];
+    for (i, node) in nodes.iter().enumerate() {
+        for &t in &node.inputs {
+            last_use[t] = Some(i);
+        }
+        if let Some(t) = node.output {
+            defined[t] = i;
+        }
+    }
+
+    let mut slots: Vec<Option<u32>> = vec![None; tensors.len()];
+    let mut owner: Vec<Option<usize>> = Vec::new();
+    let take_free = |owner: &mut Vec<Option<usize>>, t: usize| -> u32 {
+        let slot = match owner.iter().position(Option::is_none) {
+            Some(s) => s,
+            None => {
+                owner.push(None);
+                owner.len() - 1
+            }
+        };
+        owner[slot] = Some(t);
+        slot as u32
+    };
+
+    // The cached tensor is copied in before the first dispatch, and the
+    // current one copied out after the last
+    if let Some((current, previous)) = temporal {
+        last_use[current] = Some(nodes.len());
+        slots[previous] = Some(take_free(&mut owner, previous));
+    }
+    // History tensors may be filled once the previous-frame encoder is done
+    for &(t, _) in history {
+        last_use[t] = Some(last_use[t].unwrap_or(0).max(prev_encoder_end));
+        slots[t] = Some(take_free(&mut owner, t));
+    }
+
+    for (i, node) in nodes.iter().enumerate() {
+        for o in owner.iter_mut() {
+            if o.is_some_and(|t| defined[t] < i && last_use[t].is_none_or(|l| l < i)) {
+                *o = None;
+            }
+        }
+        let Some(out) = node.output else { continue };
+        if slots[out].is_some() {
+            continue;
+        }
+        let in_place = match node.op {
+            Op::GroupNorm { skip, .. } => {
+                let x = node.inputs[0];
+                (last_use[x] == Some(i) && skip != Some(x)).then_some(x)
+            }
+            _ => None,
+        };
+        slots[out] = Some(match in_place {
+            Some(x) => {
+                let slot = slots[x].unwrap();
+                owner[slot as usize] = Some(out);
+                slot
+            }
+            None => take_free(&mut owner, out),
+        });
+    }
+    let count = owner.len() as u32;
+    (slots.into_iter().map(|s| s.unwrap_or(0)).collect(), count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASS_UNSHUFFLE: [u32; 2] = [
+        PassType::PixelUnshuffle as u32,
+        PassType::PixelUnshufflePadded as u32,
+    ];
+    const PASS_SCALE_MV: [u32; 2] = [PassType::ScaleMV as u32, PassType::ScaleMVPadded as u32];
+
+    /// Every tensor of `table` is `padded / div`, the frame inputs are read
+    /// with the `Padded` passes exactly when the render size is not aligned,
+    /// and the output covers the unpadded size.
+    fn check_table(graph: &Graph, render: (u32, u32), output: (u32, u32)) {
+        let align = graph.align();
+        let padded = (
+            render.0.next_multiple_of(align),
+            render.1.next_multiple_of(align),
+        );
+        let pads = padded != render;
+        let table = graph.dispatch_table(render.0, render.1, output.0, output.1);
+
+        for a in &table.activations {
+            let t = graph.tensors.iter().find(|t| t.name == a.name).unwrap();
+            assert_eq!(
+                (a.width, a.height),
+                (padded.0 / t.div, padded.1 / t.div),
+                "{} at {:?}",
+                a.name,
+                render
+            );
+            assert!(a.len() <= table.buf_stride);
+        }
+
+        let mut outputs = 0;
+        for c in &table.dispatches {
+            if PASS_UNSHUFFLE.contains(&c.pass_type) {
+                assert_eq!(c.pass_type == PASS_UNSHUFFLE[1], pads, "{:?}", render);
+                assert_eq!((c.in_width, c.in_height), render);
+                assert_eq!((c.width, c.height), (padded.0 / 2, padded.1 / 2));
+            }
+            if PASS_SCALE_MV.contains(&c.pass_type) {
+                assert_eq!(c.pass_type == PASS_SCALE_MV[1], pads, "{:?}", render);
+                assert_eq!((c.in_width, c.in_height), render);
+                if pads {
+                    assert_eq!((c.width * c.stride, c.height * c.stride), padded);
+                }
+            }
+            if c.pass_type == PassType::PixelShuffleOut as u32
+                || c.pass_type == PassType::PixelShuffleUpscale as u32
+            {
+                outputs += 1;
+                assert_eq!((c.render_width, c.render_height), render);
+                assert_eq!((c.width, c.height), output);
+                assert_eq!((c.in_width * 2, c.in_height * 2), padded);
+            }
+        }
+        assert_eq!(outputs, 1);
+    }
+
+    #[test]
+    fn builtin_aligns_to_the_quarter_resolution() {
+        assert_eq!(Graph::builtin().align(), 4);
+    }
+
+    #[test]
+    fn odd_render_sizes_are_padded() {
+        let graph = Graph::builtin();
+        for render in [(1706, 960), (2293, 960), (1707, 961)] {
+            check_table(&graph, render, render);
+            check_table(&graph, render, (2560, 1440));
+            check_table(&graph, render, (3840, 2160));
+        }
+    }
+
+    #[test]
+    fn aligned_render_sizes_are_not_padded() {
+        let graph = Graph::builtin();
+        for render in [(1920, 1080), (1704, 960), (2296, 960)] {
+            check_table(&graph, render, render);
+            check_table(&graph, render, (3840, 2160));
+        }
+    }
+}
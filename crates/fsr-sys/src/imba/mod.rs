@@ -0,0 +1,205 @@
+//! IMBA anti-aliasing networks: per-dispatch constants shared with the
+//! compute shaders, the network description (`graph`) the legacy proxy's
+//! `aa_pass` compiles to a dispatch table, and a CPU reference (`cpu`).
+//!
+//! The v0 network works at render resolution on the current and previous
+//! frame: each is pixel-unshuffled to half resolution and encoded, the
+//! previous frame's quarter-resolution features are warped along the motion
+//! vectors and fused with the current ones, and the decoder predicts a
+//! residual that is pixel-shuffled back and added to the current colour.
+//! Upscaling runs the same network at render resolution and resamples the
+//! reconstructed frame to the output size in the last pass. Render sizes that
+//! are not a multiple of the deepest downscale (`Graph::align`, 4 for v0) are
+//! padded to one, the inputs' edge pixels repeated, and the output cropped.
+//!
+//! Features live in one buffer of slots of `buf_stride` floats, each a CHW
+//! tensor; dispatches name slots by index.
+
+pub mod cpu;
+pub mod graph;
+
+use crate::weights::WeightFile;
+use graph::Graph;
+
+/// The IMBA v0 network, see `graph` for the format.
+pub const IMBA_V0_GRAPH: &str = include_str!("v0.graph");
+
+// ── Constants ────────────────────────────────────────────────────────────────
+
+pub const AA_NO_OFFSET: u32 = 0xFFFF_FFFF;
+
+// Pass type indices — must match shader file order in gpu_pipeline AA_PSO array
+// (and the legacy proxy's build.rs)
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassType {
+    PixelUnshuffle = 0,
+    Conv = 1,
+    GNStats = 2,
+    GNApply = 3,
+    BackwardWarp = 4,
+    Attention = 5,
+    NearestUpsample = 6,
+    SkipConcatConv = 7,
+    PixelShuffleOut = 8,
+    ScaleMV = 9,
+    GNStatsReduce = 10,
+    Conv3x3_16x16 = 11,
+    Conv3x3_32x32 = 12,
+    Conv3x3_32x16 = 13,
+    Conv3x3_16x12 = 14,
+    Conv3x3_12x12 = 15,
+    Conv3x3S2_16x32 = 16,
+    PixelShuffleUpscale = 17,
+    /// Not part of a network: draws one channel of a feature slot over the
+    /// output, see `graph::Activation::view`.
+    DebugView = 18,
+    /// `PixelUnshuffle` of a padded table: reads clamped to the render size.
+    PixelUnshufflePadded = 19,
+    /// `ScaleMV` of a padded table: samples the centre of each `stride`-pixel
+    /// cell, clamped, and scales to render pixels over `stride`.
+    ScaleMVPadded = 20,
+    /// Before each `Attention`: the warped history (`aux_buf`, rewritten in
+    /// place) pulled toward the current features (`in_buf`) by the game's
+    /// reactive and composition masks at the centre of each `stride`-pixel
+    /// cell, see `reactive::history_rejection`.
+    HistoryMask = 21,
+}
+
+pub const PASS_COUNT: usize = 22;
+
+/// GroupNorm partial sums per group, one thread group each (`GNStats`).
+pub const AA_GN_TILES_PER_GROUP: u32 = 64;
+/// GroupNorm epsilon (PyTorch's default).
+pub const AA_GN_EPSILON: f32 = 1e-5;
+
+pub const ACT_NONE: u32 = 0;
+pub const ACT_SILU: u32 = 1;
+pub const ACT_TANH: u32 = 3;
+
+pub const FLAG_HAS_SKIP: u32 = 1;
+pub const FLAG_IS_PREV: u32 = 2;
+
+// ── Weights ─────────────────────────────────────────────────────────────────
+
+/// Read `aa_weights.bin` for `graph`: a weight container, or a legacy raw blob
+/// laid out as the graph's weights when its size matches.
+pub fn parse_weights(bytes: &[u8], graph: &Graph) -> Result<WeightFile, String> {
+    if WeightFile::is_container(bytes) {
+        WeightFile::parse(bytes)
+    } else {
+        WeightFile::from_legacy(bytes, &graph.weights, &graph.model_name, "legacy")
+    }
+}
+
+/// Weight values (decoded) for the CPU reference, laid out as the graph
+/// expects.
+pub fn pack_weights(file: &WeightFile, graph: &Graph) -> Result<Vec<f32>, String> {
+    file.pack(&graph.model_name, &graph.weights)
+}
+
+/// The GPU weight buffer: the same values as f32 words. The shaders read t0
+/// as `StructuredBuffer<float>`, so F16 / I8 kernels are decoded here.
+pub fn pack_weight_words(file: &WeightFile, graph: &Graph) -> Result<Vec<u32>, String> {
+    Ok(pack_weights(file, graph)?
+        .into_iter()
+        .map(f32::to_bits)
+        .collect())
+}
+
+// ── AAConstants (root constants, 28 DWORDs = 112 bytes) ─────────────────────
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AAConstants {
+    pub pass_type: u32,
+    pub in_buf: u32,
+    pub out_buf: u32,
+    pub aux_buf: u32,
+    pub width: u32,
+    pub height: u32,
+    pub in_channels: u32,
+    pub out_channels: u32,
+    pub kernel_size: u32,
+    pub stride: u32,
+    pub weight_off: u32,
+    pub bias_off: u32,
+    pub gamma_off: u32,
+    pub beta_off: u32,
+    pub num_groups: u32,
+    pub activation: u32,
+    pub flags: u32,
+    pub in_width: u32,
+    pub in_height: u32,
+    pub buf_stride: u32,
+    pub jitter_x: f32,
+    pub jitter_y: f32,
+    pub prev_jitter_x: f32,
+    pub prev_jitter_y: f32,
+    /// `DebugView`: the magnitude drawn at full intensity, as f32 bits. Zero
+    /// for the network's passes.
+    pub debug_mode: u32,
+    /// Unused; keeps the 28-DWORD layout the shaders declare.
+    pub _pad: u32,
+    /// The frame's render size; the tensors cover it rounded up to
+    /// `Graph::align`.
+    pub render_width: u32,
+    pub render_height: u32,
+}
+
+const _: () = assert!(std::mem::size_of::<AAConstants>() == 112);
+
+impl Default for AAConstants {
+    fn default() -> Self {
+        unsafe { std::mem::zeroed() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weights::{layout_len, WeightDtype};
+
+    /// The builtin graph's weights as an F32 file of varied values.
+    fn f32_file(graph: &Graph) -> WeightFile {
+        let data = (0..layout_len(&graph.weights))
+            .map(|i| ((i * 7919 % 2003) as f32 - 1001.0) / 4000.0)
+            .collect();
+        WeightFile {
+            model_name: graph.model_name.clone(),
+            model_version: "test".to_string(),
+            dtype: WeightDtype::F32,
+            tensors: graph.weights.clone(),
+            data,
+            scales: vec![Vec::new(); graph.weights.len()],
+        }
+    }
+
+    #[test]
+    fn quantized_files_load_as_f32_words() {
+        let graph = Graph::builtin();
+        let original = f32_file(&graph);
+        let reference = pack_weights(&original, &graph).unwrap();
+        for dtype in [WeightDtype::F16, WeightDtype::I8] {
+            let quantized = original.quantize(dtype, |_, max| max).unwrap();
+            let loaded = parse_weights(&quantized.to_bytes(), &graph).unwrap();
+            assert_eq!(loaded.dtype, dtype);
+
+            // The upload is the decoded values, bit for bit, as f32 words
+            let words = pack_weight_words(&loaded, &graph).unwrap();
+            let values: Vec<f32> = words.iter().map(|&w| f32::from_bits(w)).collect();
+            assert_eq!(values, pack_weights(&quantized, &graph).unwrap());
+
+            // ... and within the dtype's rounding of the originals (I8 rows
+            // span at most +-0.25, so half a step is 0.25 / 254)
+            let tolerance = match dtype {
+                WeightDtype::I8 => 0.25 / 254.0 + 1e-6,
+                _ => 0.25 / 2048.0,
+            };
+            for (i, (v, r)) in values.iter().zip(&reference).enumerate() {
+                assert!((v - r).abs() <= tolerance, "{dtype:?} {i}: {v} vs {r}");
+            }
+            assert_ne!(values, reference, "{dtype:?} rounded nothing");
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use crate::reactive::{inverse_tonemap, tonemap};
+use crate::upscale::*;
+
+// ---- Input conventions from the context flags (CPU reference) ----
+//
+// The upscalers are written against one convention: reverse-Z depth (near = 1,
+// far = 0) and render-resolution motion vectors in UV units, pointing from the
+// current pixel to its previous position, with no jitter baked in. Games say
+// how their inputs differ through the creation flags; `InputNormalization`
+// converts one pixel at a time, and `input_normalize_cs.hlsl` is its GPU twin.
+//
+// Motion vectors are scaled on every frame whose `motion_vector_scale` does
+// not already yield UV units, whatever the flags say. Jitter is in render
+// pixels, as the FSR API defines `jitterOffset`.
+//
+// `FFX_UPSCALE_ENABLE_DEPTH_INFINITE` is not decoded: it only matters when
+// depth is linearized, and raw device depth already ends at 0 (reversed) or 1.
+//
+// HDR colour is squashed with FSR's reversible tonemap before the spatial
+// filters (Lanczos, RCAS) weigh it, and expanded again afterwards, so a few
+// very bright pixels do not dominate the kernel or the sharpening limiter.
+
+/// Creation flags that change how dispatch inputs are read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InputFlags {
+    pub hdr: bool,
+    pub display_res_mvs: bool,
+    pub jitter_cancellation: bool,
+    pub depth_inverted: bool,
+    pub auto_exposure: bool,
+}
+
+impl InputFlags {
+    /// Decode `FFX_UPSCALE_ENABLE_*` bits; the legacy
+    /// `FfxFsr3UpscalerInitializationFlagBits` share the same low bits.
+    pub fn from_bits(flags: u32) -> Self {
+        let has = |bit: u32| flags & bit != 0;
+        Self {
+            hdr: has(FFX_UPSCALE_ENABLE_HIGH_DYNAMIC_RANGE),
+            display_res_mvs: has(FFX_UPSCALE_ENABLE_DISPLAY_RESOLUTION_MOTION_VECTORS),
+            jitter_cancellation: has(FFX_UPSCALE_ENABLE_MOTION_VECTORS_JITTER_CANCELLATION),
+            depth_inverted: has(FFX_UPSCALE_ENABLE_DEPTH_INVERTED),
+            auto_exposure: has(FFX_UPSCALE_ENABLE_AUTO_EXPOSURE),
+        }
+    }
+
+    /// Whether depth or motion vectors of a frame with these sizes and
+    /// `motion_vector_scale` differ from the upscalers' convention.
+    pub fn needs_normalization(
+        &self,
+        render_size: (u32, u32),
+        upscale_size: (u32, u32),
+        motion_vector_scale: [f32; 2],
+    ) -> bool {
+        let still = [0.0; 2];
+        self.jitter_cancellation
+            || !InputNormalization::new(
+                *self,
+                render_size,
+                upscale_size,
+                motion_vector_scale,
+                still,
+                still,
+            )
+            .is_identity()
+    }
+}
+
+/// Per-frame conversion of depth and motion vectors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputNormalization {
+    /// Store `1 - z` (the game renders with standard Z).
+    pub flip_depth: bool,
+    /// Motion-vector texels per render pixel; above 1 for display-res MVs.
+    pub mv_sample_scale: [f32; 2],
+    /// Multiplier taking a raw motion vector to UV units.
+    pub mv_uv_scale: [f32; 2],
+    /// UV offset subtracted from every motion vector.
+    pub jitter_cancellation: [f32; 2],
+}
+
+impl InputNormalization {
+    /// Follows FSR: vectors are `raw * motion_vector_scale / size` where size is
+    /// the render size, or the upscale size for display-res vectors. Jitter is
+    /// in render pixels; cancellation removes `(prev - jitter) / render_size`.
+    ///
+    /// A zero `motion_vector_scale` component means the game already writes UV
+    /// units, which is what the proxy assumed before honouring the flags.
+    pub fn new(
+        flags: InputFlags,
+        render_size: (u32, u32),
+        upscale_size: (u32, u32),
+        motion_vector_scale: [f32; 2],
+        jitter: [f32; 2],
+        prev_jitter: [f32; 2],
+    ) -> Self {
+        let render = [render_size.0.max(1) as f32, render_size.1.max(1) as f32];
+        let mv_size = if flags.display_res_mvs {
+            [upscale_size.0.max(1) as f32, upscale_size.1.max(1) as f32]
+        } else {
+            render
+        };
+        let uv_scale = |i: usize| {
+            if motion_vector_scale[i] == 0.0 {
+                1.0
+            } else {
+                motion_vector_scale[i] / mv_size[i]
+            }
+        };
+        let cancel = |i: usize| {
+            if flags.jitter_cancellation {
+                (prev_jitter[i] - jitter[i]) / render[i]
+            } else {
+                0.0
+            }
+        };
+        Self {
+            flip_depth: !flags.depth_inverted,
+            mv_sample_scale: [mv_size[0] / render[0], mv_size[1] / render[1]],
+            mv_uv_scale: [uv_scale(0), uv_scale(1)],
+            jitter_cancellation: [cancel(0), cancel(1)],
+        }
+    }
+
+    /// Whether the conversion leaves every input as it is.
+    pub fn is_identity(&self) -> bool {
+        !self.flip_depth
+            && self.mv_sample_scale == [1.0; 2]
+            && self.mv_uv_scale == [1.0; 2]
+            && self.jitter_cancellation == [0.0; 2]
+    }
+
+    /// Device depth in the upscalers' reverse-Z convention.
+    pub fn depth(&self, z: f32) -> f32 {
+        if self.flip_depth {
+            1.0 - z
+        } else {
+            z
+        }
+    }
+
+    /// Motion-vector texel to read for render pixel `(x, y)` (pixel centres
+    /// mapped, then truncated like `Load`).
+    pub fn mv_load_pos(&self, x: u32, y: u32) -> (u32, u32) {
+        let map = |p: u32, s: f32| ((p as f32 + 0.5) * s) as u32;
+        (
+            map(x, self.mv_sample_scale[0]),
+            map(y, self.mv_sample_scale[1]),
+        )
+    }
+
+    /// Raw motion vector to UV units with jitter removed.
+    pub fn motion_vector(&self, raw: [f32; 2]) -> [f32; 2] {
+        [
+            raw[0] * self.mv_uv_scale[0] - self.jitter_cancellation[0],
+            raw[1] * self.mv_uv_scale[1] - self.jitter_cancellation[1],
+        ]
+    }
+}
+
+/// Colour as the spatial filters should weigh it.
+pub fn to_filter_space(c: [f32; 3], hdr: bool) -> [f32; 3] {
+    if hdr {
+        tonemap(c)
+    } else {
+        c
+    }
+}
+
+/// Inverse of [`to_filter_space`], applied to the filtered result.
+pub fn from_filter_space(c: [f32; 3], hdr: bool) -> [f32; 3] {
+    if hdr {
+        inverse_tonemap(c)
+    } else {
+        c
+    }
+}
+
+/// RCAS luma (times two) in filter space: `0.5 * b + 0.5 * r + g`.
+pub fn rcas_luma(c: [f32; 3], hdr: bool) -> f32 {
+    let [r, g, b] = to_filter_space(c, hdr);
+    b * 0.5 + (r * 0.5 + g)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RENDER: (u32, u32) = (1280, 720);
+    const UPSCALE: (u32, u32) = (2560, 1440);
+    /// Scale that turns UV-unit vectors into UV units.
+    const UV: [f32; 2] = [1280.0, 720.0];
+
+    fn inverted() -> InputFlags {
+        InputFlags::from_bits(FFX_UPSCALE_ENABLE_DEPTH_INVERTED)
+    }
+
+    fn close(a: [f32; 2], b: [f32; 2]) -> bool {
+        (a[0] - b[0]).abs() < 1e-6 && (a[1] - b[1]).abs() < 1e-6
+    }
+
+    #[test]
+    fn decodes_creation_flags() {
+        let flags = InputFlags::from_bits(
+            FFX_UPSCALE_ENABLE_HIGH_DYNAMIC_RANGE
+                | FFX_UPSCALE_ENABLE_MOTION_VECTORS_JITTER_CANCELLATION
+                | FFX_UPSCALE_ENABLE_DEPTH_INFINITE
+                | FFX_UPSCALE_ENABLE_AUTO_EXPOSURE,
+        );
+        assert_eq!(
+            flags,
+            InputFlags {
+                hdr: true,
+                display_res_mvs: false,
+                jitter_cancellation: true,
+                depth_inverted: false,
+                auto_exposure: true,
+            }
+        );
+    }
+
+    #[test]
+    fn upscaler_convention_passes_through() {
+        assert!(!inverted().needs_normalization(RENDER, UPSCALE, UV));
+        // Zero scale: the game already writes UV units
+        assert!(!inverted().needs_normalization(RENDER, UPSCALE, [0.0; 2]));
+        let n = InputNormalization::new(inverted(), RENDER, UPSCALE, UV, [0.3; 2], [0.1; 2]);
+        assert!(n.is_identity());
+        assert_eq!(n.depth(0.25), 0.25);
+        assert_eq!(n.motion_vector([0.01, -0.02]), [0.01, -0.02]);
+        assert_eq!(n.mv_load_pos(17, 9), (17, 9));
+    }
+
+    #[test]
+    fn motion_vector_scale_applies_without_flags() {
+        // Pixel vectors with FSR's default scale of 1
+        assert!(inverted().needs_normalization(RENDER, UPSCALE, [1.0; 2]));
+        let n = InputNormalization::new(inverted(), RENDER, UPSCALE, [1.0; 2], [0.0; 2], [0.0; 2]);
+        assert!(close(n.motion_vector([64.0, -36.0]), [0.05, -0.05]));
+
+        // A flipped convention is a negative scale
+        let n = InputNormalization::new(inverted(), RENDER, UPSCALE, [-1.0; 2], [0.0; 2], [0.0; 2]);
+        assert!(close(n.motion_vector([64.0, -36.0]), [-0.05, 0.05]));
+    }
+
+    #[test]
+    fn standard_depth_is_flipped() {
+        let flags = InputFlags::default();
+        assert!(flags.needs_normalization(RENDER, UPSCALE, UV));
+        let n = InputNormalization::new(flags, RENDER, UPSCALE, UV, [0.0; 2], [0.0; 2]);
+        assert_eq!(n.depth(0.0), 1.0);
+        assert_eq!(n.depth(1.0), 0.0);
+        assert_eq!(n.depth(0.25), 0.75);
+    }
+
+    #[test]
+    fn display_resolution_vectors() {
+        let flags = InputFlags::from_bits(
+            FFX_UPSCALE_ENABLE_DEPTH_INVERTED
+                | FFX_UPSCALE_ENABLE_DISPLAY_RESOLUTION_MOTION_VECTORS,
+        );
+        assert!(flags.needs_normalization(RENDER, UPSCALE, [1.0; 2]));
+        let n = InputNormalization::new(flags, RENDER, UPSCALE, [1.0; 2], [0.0; 2], [0.0; 2]);
+        assert_eq!(n.mv_sample_scale, [2.0, 2.0]);
+        // Render pixel centre 10.5 lands on display texel 21
+        assert_eq!(n.mv_load_pos(10, 0), (21, 1));
+        assert_eq!(n.mv_load_pos(1279, 719), (2559, 1439));
+        // Display pixels are divided by the display size
+        assert!(close(n.motion_vector([128.0, 72.0]), [0.05, 0.05]));
+
+        // Native resolution display-res vectors need nothing
+        assert!(!flags.needs_normalization(RENDER, RENDER, UV));
+    }
+
+    #[test]
+    fn jitter_cancellation_in_render_pixels() {
+        let flags = InputFlags::from_bits(
+            FFX_UPSCALE_ENABLE_DEPTH_INVERTED
+                | FFX_UPSCALE_ENABLE_MOTION_VECTORS_JITTER_CANCELLATION,
+        );
+        assert!(flags.needs_normalization(RENDER, UPSCALE, UV));
+
+        // A static scene whose vectors carry the jitter change from the
+        // previous frame: (prev - jitter) pixels, in UV
+        let (jitter, prev) = ([0.25, -0.125], [-0.25, 0.375]);
+        let baked = [(-0.25 - 0.25) / 1280.0, (0.375 + 0.125) / 720.0];
+        let n = InputNormalization::new(flags, RENDER, UPSCALE, UV, jitter, prev);
+        assert!(close(n.jitter_cancellation, baked));
+        assert!(close(n.motion_vector(baked), [0.0, 0.0]));
+
+        // Without the flag the same jitter is left in
+        let n = InputNormalization::new(inverted(), RENDER, UPSCALE, UV, jitter, prev);
+        assert_eq!(n.motion_vector(baked), baked);
+    }
+
+    #[test]
+    fn zero_sizes_do_not_divide_by_zero() {
+        let n = InputNormalization::new(
+            InputFlags::default(),
+            (0, 0),
+            (0, 0),
+            [1.0; 2],
+            [0.5; 2],
+            [0.0; 2],
+        );
+        assert!(n.motion_vector([1.0, 1.0]).iter().all(|v| v.is_finite()));
+    }
+
+    #[test]
+    fn filter_space_round_trip() {
+        let c = [4.0, 1.0, 0.25];
+        assert_eq!(to_filter_space(c, false), c);
+        let squashed = to_filter_space(c, true);
+        assert!(squashed.iter().all(|&v| v < 1.0));
+        let back = from_filter_space(squashed, true);
+        assert!(back.iter().zip(c).all(|(a, b)| (a - b).abs() < 1e-5));
+        assert_eq!(rcas_luma([0.2, 0.4, 0.6], false), 0.3 + 0.1 + 0.4);
+    }
+}
@@ -0,0 +1,84 @@
+// ---- Camera jitter sequence ----
+//
+// Both proxies hand out FSR's jitter: a Halton (2, 3) sequence, centered on
+// zero, in render pixels. The phase count grows with the square of the
+// upscale ratio so every output pixel is covered by some render sample.
+
+/// Jitter phases for a `render_width` -> `display_width` upscale:
+/// `8 * ratio^2`, truncated like the SDK's `ffxFsr3UpscalerGetJitterPhaseCount`.
+pub fn phase_count(render_width: i32, display_width: i32) -> i32 {
+    let ratio = if render_width > 0 {
+        display_width as f32 / render_width as f32
+    } else {
+        1.0
+    };
+    (8.0 * ratio * ratio) as i32
+}
+
+/// Jitter offset of frame `index` in `[-0.5, 0.5)` render pixels; `(0, 0)`
+/// for a non-positive `phase_count`.
+pub fn offset(index: i32, phase_count: i32) -> (f32, f32) {
+    if phase_count <= 0 {
+        return (0.0, 0.0);
+    }
+    let i = index.rem_euclid(phase_count) as u32;
+    let x = halton(i + 1, 2) - 0.5;
+    let y = halton(i + 1, 3) - 0.5;
+    (x, y)
+}
+
+/// Halton sequence value for a given index and base.
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut f = 1.0f32;
+    let mut r = 0.0f32;
+    let inv_base = 1.0 / base as f32;
+
+    while index > 0 {
+        f *= inv_base;
+        r += f * (index % base) as f32;
+        index /= base;
+    }
+
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phase_count_follows_the_ratio() {
+        assert_eq!(phase_count(1920, 1920), 8);
+        assert_eq!(phase_count(1280, 1920), 18);
+        assert_eq!(phase_count(960, 1920), 32);
+        assert_eq!(phase_count(0, 1920), 8);
+    }
+
+    #[test]
+    fn phase_count_truncates_like_the_sdk() {
+        // 8 * (2560 / 1706)^2 = 18.01
+        assert_eq!(phase_count(1706, 2560), 18);
+        // 8 * (3840 / 2259)^2 = 23.12
+        assert_eq!(phase_count(2259, 3840), 23);
+        // 8 * (1920 / 1129)^2 = 23.14
+        assert_eq!(phase_count(1129, 1920), 23);
+    }
+
+    #[test]
+    fn offsets_are_halton_2_3() {
+        assert_eq!(offset(0, 8), (0.0, 1.0 / 3.0 - 0.5));
+        assert_eq!(offset(1, 8), (-0.25, 2.0 / 3.0 - 0.5));
+        assert_eq!(offset(2, 8), (0.25, 1.0 / 9.0 - 0.5));
+    }
+
+    #[test]
+    fn offsets_wrap_at_the_phase_count() {
+        assert_eq!(offset(8, 8), offset(0, 8));
+        assert_eq!(offset(-1, 8), offset(7, 8));
+        assert_eq!(offset(5, 0), (0.0, 0.0));
+        for i in 0..32 {
+            let (x, y) = offset(i, 32);
+            assert!((-0.5..0.5).contains(&x) && (-0.5..0.5).contains(&y));
+        }
+    }
+}
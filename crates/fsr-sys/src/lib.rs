@@ -1,9 +1,22 @@
 #![allow(non_camel_case_types, non_snake_case)]
 
 pub mod api;
+pub mod dynres;
+pub mod exposure;
+pub mod frame;
+pub mod imba;
+pub mod inputs;
+pub mod jitter;
+pub mod message;
+pub mod quality;
+pub mod reactive;
+pub mod scene_cut;
 pub mod types;
 pub mod upscale;
+pub mod validate;
+pub mod weights;
 
 pub use api::*;
+pub use frame::*;
 pub use types::*;
 pub use upscale::*;
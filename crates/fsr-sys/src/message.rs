@@ -0,0 +1,207 @@
+use crate::api::*;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+// ---- Game message callback forwarding ----
+//
+// Both context descriptors carry an `fpMessage` callback the game uses to
+// surface upscaler problems in its own debug console. The proxies register
+// it here per context at creation, drop it when that context is destroyed,
+// and mirror their warnings/errors through `GameMessageLayer`. Log events
+// are not tied to a context, so each one goes to every distinct callback of
+// the live contexts. Each message key (the logging call site) is forwarded
+// at most once per `RATE_LIMIT_WINDOW`; repeats inside the window are
+// counted and reported with the next forwarded occurrence.
+
+/// Minimum interval between two forwarded messages with the same key.
+pub const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(5);
+
+struct KeyState {
+    last_sent: Instant,
+    suppressed: u32,
+}
+
+type Callback = unsafe extern "C" fn(u32, *const u16);
+
+struct Forwarder {
+    /// `(context, callback)` in creation order.
+    callbacks: Vec<(u64, Callback)>,
+    keys: HashMap<u64, KeyState>,
+}
+
+static FORWARDER: Mutex<Option<Forwarder>> = Mutex::new(None);
+
+/// Register `context`'s message callback, replacing an earlier one for the
+/// same context. `None` registers nothing. Rate-limit history is kept.
+pub fn set_callback(context: u64, callback: FfxApiMessage) {
+    let mut guard = FORWARDER.lock().unwrap_or_else(|e| e.into_inner());
+    let f = guard.get_or_insert_with(|| Forwarder {
+        callbacks: Vec::new(),
+        keys: HashMap::new(),
+    });
+    f.callbacks.retain(|&(c, _)| c != context);
+    if let Some(callback) = callback {
+        f.callbacks.push((context, callback));
+    }
+}
+
+/// Drop the callback of a destroyed `context`; other contexts keep theirs.
+pub fn clear_callback(context: u64) {
+    let mut guard = FORWARDER.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(f) = guard.as_mut() {
+        f.callbacks.retain(|&(c, _)| c != context);
+    }
+}
+
+/// Whether a callback is registered (lets callers skip formatting otherwise).
+pub fn has_callback() -> bool {
+    let guard = FORWARDER.lock().unwrap_or_else(|e| e.into_inner());
+    guard.as_ref().is_some_and(|f| !f.callbacks.is_empty())
+}
+
+/// Forward `text` to the registered callbacks as `type_`
+/// (`FFX_API_MESSAGE_TYPE_ERROR` / `FFX_API_MESSAGE_TYPE_WARNING`),
+/// subject to per-`key` rate limiting. Returns whether a callback was invoked.
+pub fn forward(key: u64, type_: u32, text: &str) -> bool {
+    let now = Instant::now();
+    let (callbacks, suppressed) = {
+        let mut guard = FORWARDER.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(f) = guard.as_mut() else {
+            return false;
+        };
+        // Contexts created with the same callback hear each message once
+        let mut callbacks: Vec<Callback> = Vec::new();
+        for &(_, cb) in &f.callbacks {
+            if !callbacks.iter().any(|&c| c as usize == cb as usize) {
+                callbacks.push(cb);
+            }
+        }
+        if callbacks.is_empty() {
+            return false;
+        }
+        match f.keys.get_mut(&key) {
+            Some(state) if now.duration_since(state.last_sent) < RATE_LIMIT_WINDOW => {
+                state.suppressed = state.suppressed.saturating_add(1);
+                return false;
+            }
+            Some(state) => {
+                let suppressed = state.suppressed;
+                state.last_sent = now;
+                state.suppressed = 0;
+                (callbacks, suppressed)
+            }
+            None => {
+                f.keys.insert(
+                    key,
+                    KeyState {
+                        last_sent: now,
+                        suppressed: 0,
+                    },
+                );
+                (callbacks, 0)
+            }
+        }
+    };
+
+    let mut wide: Vec<u16> = if suppressed > 0 {
+        format!("[OXR] {text} ({suppressed} similar suppressed)")
+            .encode_utf16()
+            .collect()
+    } else {
+        format!("[OXR] {text}").encode_utf16().collect()
+    };
+    wide.push(0);
+
+    // Called outside the lock: the game may log back into us from the callback.
+    for callback in callbacks {
+        unsafe { callback(type_, wide.as_ptr()) };
+    }
+    true
+}
+
+/// Mirrors WARN/ERROR events to the games' `fpMessage` callbacks; both
+/// proxies add it to their subscriber.
+pub struct GameMessageLayer;
+
+impl<S: Subscriber> Layer<S> for GameMessageLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let type_ = match *event.metadata().level() {
+            Level::ERROR => FFX_API_MESSAGE_TYPE_ERROR,
+            Level::WARN => FFX_API_MESSAGE_TYPE_WARNING,
+            _ => return,
+        };
+        if !has_callback() {
+            return;
+        }
+        let mut visitor = MessageVisitor(String::new());
+        event.record(&mut visitor);
+        // Metadata is 'static per call site, so its address identifies the message.
+        let key = event.metadata() as *const _ as u64;
+        forward(key, type_, &visitor.0);
+    }
+}
+
+/// Flattens an event into `message key=value ...`.
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        use std::fmt::Write;
+        if field.name() == "message" {
+            let _ = write!(self.0, "{value:?}");
+        } else {
+            let _ = write!(self.0, " {}={value:?}", field.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    static FIRST: AtomicU32 = AtomicU32::new(0);
+    static SECOND: AtomicU32 = AtomicU32::new(0);
+
+    unsafe extern "C" fn first(_type: u32, _text: *const u16) {
+        FIRST.fetch_add(1, Ordering::SeqCst);
+    }
+
+    unsafe extern "C" fn second(_type: u32, _text: *const u16) {
+        SECOND.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn counts() -> (u32, u32) {
+        (FIRST.load(Ordering::SeqCst), SECOND.load(Ordering::SeqCst))
+    }
+
+    // One test: the forwarder is process-global.
+    #[test]
+    fn callbacks_live_as_long_as_their_context() {
+        set_callback(1, Some(first));
+        set_callback(2, Some(first));
+        set_callback(3, Some(second));
+        assert!(forward(10, FFX_API_MESSAGE_TYPE_WARNING, "a"));
+        assert_eq!(counts(), (1, 1), "shared callback hears a message once");
+
+        // Destroying one context leaves the others registered
+        clear_callback(3);
+        clear_callback(1);
+        assert!(has_callback());
+        assert!(forward(11, FFX_API_MESSAGE_TYPE_ERROR, "b"));
+        assert_eq!(counts(), (2, 1));
+
+        // Repeats inside the window are held back
+        assert!(!forward(11, FFX_API_MESSAGE_TYPE_ERROR, "b"));
+        assert_eq!(counts(), (2, 1));
+
+        clear_callback(2);
+        assert!(!has_callback());
+        assert!(!forward(12, FFX_API_MESSAGE_TYPE_WARNING, "c"));
+        assert_eq!(counts(), (2, 1));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::upscale::*;
+
+// ---- Quality-mode table ----
+//
+// Both proxies answer "upscale ratio for quality mode" and "render
+// resolution for quality mode" queries. They share this table so the two
+// DLLs hand out the same render sizes. The table can be overridden per mode
+// from the `[quality]` section of `oxr.ini`:
+//
+//   [quality]
+//   native_aa = 1.0
+//   quality = 1.5
+//   balanced = 1.7
+//   performance = 2.0
+//   ultra_performance = 3.0
+//   custom_scale = 1.25    ; every mode renders at display / 1.25
+//   force_native = true    ; DLAA-style: every mode renders at display size
+//
+// Precedence: `force_native`, then `custom_scale`, then per-mode values.
+
+/// Stock FSR upscale ratios, indexed by `FfxApiUpscaleQualityMode`.
+pub const DEFAULT_UPSCALE_RATIOS: [f32; 5] = [1.0, 1.5, 1.7, 2.0, 3.0];
+
+/// `[quality]` keys for the per-mode overrides, indexed like `DEFAULT_UPSCALE_RATIOS`.
+pub const QUALITY_MODE_KEYS: [&str; 5] = [
+    "native_aa",
+    "quality",
+    "balanced",
+    "performance",
+    "ultra_performance",
+];
+
+/// Largest accepted ratio; FSR's ultra performance is 3.0.
+pub const MAX_UPSCALE_RATIO: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityTable {
+    pub ratios: [f32; 5],
+    /// Ratio applied to every mode when set.
+    pub custom_scale: Option<f32>,
+    /// Render at display resolution for every mode (AA only).
+    pub force_native: bool,
+}
+
+impl Default for QualityTable {
+    fn default() -> Self {
+        Self {
+            ratios: DEFAULT_UPSCALE_RATIOS,
+            custom_scale: None,
+            force_native: false,
+        }
+    }
+}
+
+fn parse_ratio(v: &str) -> Option<f32> {
+    v.trim()
+        .trim_end_matches(['x', 'X'])
+        .parse::<f32>()
+        .ok()
+        .filter(|r| r.is_finite() && (1.0..=MAX_UPSCALE_RATIO).contains(r))
+}
+
+impl QualityTable {
+    /// Build the table from `[quality]` values. `get(key)` returns the raw ini
+    /// value; unparsable or out-of-range ratios keep the default and are
+    /// returned as `(key, value)` so the caller can log them.
+    pub fn parse<'a>(get: impl Fn(&str) -> Option<&'a str>) -> (Self, Vec<(String, String)>) {
+        let mut table = Self::default();
+        let mut rejected = Vec::new();
+        let mut ratio = |key: &str| {
+            let v = get(key)?;
+            let r = parse_ratio(v);
+            if r.is_none() {
+                rejected.push((key.to_string(), v.to_string()));
+            }
+            r
+        };
+
+        for (slot, key) in table.ratios.iter_mut().zip(QUALITY_MODE_KEYS) {
+            if let Some(r) = ratio(key) {
+                *slot = r;
+            }
+        }
+        table.custom_scale = ratio("custom_scale");
+        table.force_native = get("force_native").is_some_and(|v| {
+            matches!(
+                v.trim().to_ascii_lowercase().as_str(),
+                "1" | "true" | "yes" | "on"
+            )
+        });
+        (table, rejected)
+    }
+
+    /// Upscale ratio for `mode`, or `None` for an unknown quality mode.
+    pub fn ratio(&self, mode: FfxApiUpscaleQualityMode) -> Option<f32> {
+        let base = *self.ratios.get(mode as usize)?;
+        Some(if self.force_native {
+            1.0
+        } else {
+            self.custom_scale.unwrap_or(base)
+        })
+    }
+
+    /// Whether `mode` renders at display size: `force_native`, native AA or a
+    /// 1.0 ratio. Dynamic resolution leaves such modes alone.
+    pub fn is_native(&self, mode: FfxApiUpscaleQualityMode) -> bool {
+        self.ratio(mode) == Some(1.0)
+    }
+
+    /// Render resolution for `mode` at the given display size.
+    ///
+    /// Truncates like the FSR SDK (`(uint32_t)(display / ratio)`), never below 1.
+    pub fn render_resolution(
+        &self,
+        display_width: u32,
+        display_height: u32,
+        mode: FfxApiUpscaleQualityMode,
+    ) -> Option<(u32, u32)> {
+        let ratio = self.ratio(mode)?;
+        let scale = |d: u32| ((d as f32 / ratio) as u32).max(1);
+        Some((scale(display_width), scale(display_height)))
+    }
+}
+
+// Keep the index mapping above in step with the mode constants.
+const _: () = {
+    assert!(FFX_UPSCALE_QUALITY_MODE_NATIVEAA == 0);
+    assert!(FFX_UPSCALE_QUALITY_MODE_ULTRA_PERFORMANCE == 4);
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn native_modes() {
+        let mut table = QualityTable::default();
+        assert!(table.is_native(FFX_UPSCALE_QUALITY_MODE_NATIVEAA));
+        assert!(!table.is_native(FFX_UPSCALE_QUALITY_MODE_QUALITY));
+        assert!(!table.is_native(5));
+
+        table.force_native = true;
+        assert!(table.is_native(FFX_UPSCALE_QUALITY_MODE_ULTRA_PERFORMANCE));
+
+        table.force_native = false;
+        table.custom_scale = Some(1.0);
+        assert!(table.is_native(FFX_UPSCALE_QUALITY_MODE_PERFORMANCE));
+    }
+}
@@ -0,0 +1,300 @@
+use crate::types::*;
+use crate::upscale::*;
+use core::ffi::c_void;
+
+// ---- Reactive mask generation (CPU reference) ----
+//
+// `ffxDispatchDescUpscaleGenerateReactiveMask` asks the upscaler to derive a
+// reactive mask from two render-resolution images: the scene with only opaque
+// geometry, and the final pre-upscale color with transparencies composited.
+// Pixels that changed a lot are "reactive" and should lean on the current
+// frame instead of history.
+//
+// This is the reference both proxies' `reactive_mask_cs.hlsl` follow
+// step for step; keep them in sync.
+
+/// Parameters shared by the legacy and ffx API reactive-mask descriptors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReactiveMaskParams {
+    pub scale: f32,
+    pub cutoff_threshold: f32,
+    pub binary_value: f32,
+    pub flags: u32,
+}
+
+impl From<&ffxDispatchDescUpscaleGenerateReactiveMask> for ReactiveMaskParams {
+    fn from(d: &ffxDispatchDescUpscaleGenerateReactiveMask) -> Self {
+        Self {
+            scale: d.scale,
+            cutoff_threshold: d.cutoff_threshold,
+            binary_value: d.binary_value,
+            flags: d.flags,
+        }
+    }
+}
+
+/// Backend-agnostic reactive-mask dispatch, converted from either ABI the
+/// same way `UpscaleFrame` is.
+#[derive(Debug, Clone, Copy)]
+pub struct ReactiveMaskFrame {
+    pub command_list: *mut c_void,
+    pub color_opaque_only: FfxApiResource,
+    pub color_pre_upscale: FfxApiResource,
+    pub out_reactive: FfxApiResource,
+    pub render_size: FfxApiDimensions2D,
+    pub params: ReactiveMaskParams,
+}
+
+impl ReactiveMaskFrame {
+    /// Render size, falling back to the pre-upscale color dimensions.
+    pub fn effective_render_size(&self) -> (u32, u32) {
+        if self.render_size.width > 0 && self.render_size.height > 0 {
+            (self.render_size.width, self.render_size.height)
+        } else {
+            (
+                self.color_pre_upscale.description.width,
+                self.color_pre_upscale.description.height,
+            )
+        }
+    }
+}
+
+impl From<&ffxDispatchDescUpscaleGenerateReactiveMask> for ReactiveMaskFrame {
+    fn from(d: &ffxDispatchDescUpscaleGenerateReactiveMask) -> Self {
+        Self {
+            command_list: d.command_list,
+            color_opaque_only: d.color_opaque_only,
+            color_pre_upscale: d.color_pre_upscale,
+            out_reactive: d.out_reactive,
+            render_size: d.render_size,
+            params: d.into(),
+        }
+    }
+}
+
+/// Denominator floor used by FSR (about 1 / fp16 max); keeps the inverse
+/// tonemap finite when `max3(c) >= 1`.
+const INVERSE_TONEMAP_EPSILON: f32 = 1.0 / 65503.0;
+
+fn max3(c: [f32; 3]) -> f32 {
+    c[0].max(c[1]).max(c[2])
+}
+
+/// FSR's reversible tonemap, `c / (max3(c) + 1)`.
+pub fn tonemap(c: [f32; 3]) -> [f32; 3] {
+    let rcp = 1.0 / (max3(c).max(0.0) + 1.0);
+    c.map(|v| v * rcp)
+}
+
+/// Inverse of [`tonemap`].
+pub fn inverse_tonemap(c: [f32; 3]) -> [f32; 3] {
+    let rcp = 1.0 / (1.0 - max3(c)).max(INVERSE_TONEMAP_EPSILON);
+    c.map(|v| v * rcp)
+}
+
+/// Reactive value for one pixel, in the same units the shader writes
+/// (usually `[0, 1]`, but `scale` and `binary_value` are not clamped).
+pub fn reactive_value(opaque: [f32; 3], color: [f32; 3], params: &ReactiveMaskParams) -> f32 {
+    let (mut opaque, mut color) = (opaque, color);
+    if params.flags & FFX_UPSCALE_AUTOREACTIVEFLAGS_APPLY_TONEMAP != 0 {
+        opaque = tonemap(opaque);
+        color = tonemap(color);
+    }
+    if params.flags & FFX_UPSCALE_AUTOREACTIVEFLAGS_APPLY_INVERSETONEMAP != 0 {
+        opaque = inverse_tonemap(opaque);
+        color = inverse_tonemap(color);
+    }
+
+    let delta = [
+        (color[0] - opaque[0]).abs(),
+        (color[1] - opaque[1]).abs(),
+        (color[2] - opaque[2]).abs(),
+    ];
+    let mut out = if params.flags & FFX_UPSCALE_AUTOREACTIVEFLAGS_USE_COMPONENTS_MAX != 0 {
+        max3(delta)
+    } else {
+        (delta[0] * delta[0] + delta[1] * delta[1] + delta[2] * delta[2]).sqrt()
+    };
+    out *= params.scale;
+
+    if params.flags & FFX_UPSCALE_AUTOREACTIVEFLAGS_APPLY_THRESHOLD != 0 {
+        out = if out < params.cutoff_threshold {
+            0.0
+        } else {
+            params.binary_value
+        };
+    }
+    out
+}
+
+/// Generate a whole mask from row-major RGB images of equal size.
+///
+/// Returns `None` when the two images differ in length.
+pub fn generate_reactive_mask(
+    opaque: &[[f32; 3]],
+    color: &[[f32; 3]],
+    params: &ReactiveMaskParams,
+) -> Option<Vec<f32>> {
+    if opaque.len() != color.len() {
+        return None;
+    }
+    Some(
+        opaque
+            .iter()
+            .zip(color)
+            .map(|(&o, &c)| reactive_value(o, c, params))
+            .collect(),
+    )
+}
+
+// ---- History rejection from the game's masks (CPU reference) ----
+//
+// Temporal upscalers and IMBA AA pull their result toward the current frame
+// where the game flags pixels history cannot represent:
+// - `reactive`: particles and transparencies, fully rejected at 1;
+// - `transparency_and_composition`: UI composited into the scene and
+//   alpha-blended surfaces, partly rejected so they stay anti-aliased.
+//
+// SGSRv2's upscale shaders (`max` with their own blend factor),
+// `reactive_blend_cs.hlsl` (one pass per mask) and `imba_history_mask_cs.hlsl`
+// (IMBA's warped history, before each attention) follow these; keep them in
+// sync.
+
+/// Current-frame weight of a fully set transparency & composition mask.
+pub const COMPOSITION_HISTORY_REJECTION: f32 = 0.5;
+
+/// Current-frame weight from the two masks, each clamped to `[0, 1]`. A pixel
+/// is kept from history only as far as neither mask rejects it.
+pub fn history_rejection(reactive: f32, composition: f32) -> f32 {
+    let reactive = reactive.clamp(0.0, 1.0);
+    let composition = composition.clamp(0.0, 1.0) * COMPOSITION_HISTORY_REJECTION;
+    1.0 - (1.0 - reactive) * (1.0 - composition)
+}
+
+/// SGSRv2's final history/current blend factor: its own `alpha`, raised to
+/// the masks' rejection.
+pub fn temporal_blend_alpha(alpha: f32, reactive: f32, composition: f32) -> f32 {
+    alpha.max(history_rejection(reactive, composition))
+}
+
+/// IMBA AA's post-blend: `result` pulled toward `current` by the reactive mask,
+/// then by the composition mask at [`COMPOSITION_HISTORY_REJECTION`]. The two
+/// passes compose to a single lerp by [`history_rejection`].
+pub fn blend_toward_current(
+    result: [f32; 3],
+    current: [f32; 3],
+    reactive: f32,
+    composition: f32,
+) -> [f32; 3] {
+    let t = history_rejection(reactive, composition);
+    [0, 1, 2].map(|i| result[i] + (current[i] - result[i]) * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(flags: u32) -> ReactiveMaskParams {
+        ReactiveMaskParams {
+            scale: 1.0,
+            cutoff_threshold: 0.2,
+            binary_value: 0.9,
+            flags,
+        }
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn tonemap_round_trips() {
+        for c in [
+            [0.0, 0.0, 0.0],
+            [0.25, 0.5, 1.0],
+            [4.0, 2.0, 0.5],
+            [100.0, 0.0, 3.0],
+        ] {
+            let back = inverse_tonemap(tonemap(c));
+            for i in 0..3 {
+                assert!(
+                    (back[i] - c[i]).abs() <= c[i].abs() * 1e-4 + 1e-6,
+                    "{c:?} -> {back:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn distance_is_euclidean_or_component_max() {
+        let (opaque, color) = ([0.0, 0.0, 0.0], [0.3, 0.4, 0.0]);
+        assert!(close(reactive_value(opaque, color, &params(0)), 0.5));
+        let max = params(FFX_UPSCALE_AUTOREACTIVEFLAGS_USE_COMPONENTS_MAX);
+        assert!(close(reactive_value(opaque, color, &max), 0.4));
+        let scaled = ReactiveMaskParams {
+            scale: 2.0,
+            ..params(0)
+        };
+        assert!(close(reactive_value(opaque, color, &scaled), 1.0));
+    }
+
+    #[test]
+    fn threshold_writes_binary_value() {
+        let p = params(FFX_UPSCALE_AUTOREACTIVEFLAGS_APPLY_THRESHOLD);
+        assert_eq!(reactive_value([0.0; 3], [0.1, 0.0, 0.0], &p), 0.0);
+        assert_eq!(reactive_value([0.0; 3], [0.2, 0.0, 0.0], &p), 0.9);
+        assert_eq!(reactive_value([0.0; 3], [5.0, 0.0, 0.0], &p), 0.9);
+    }
+
+    #[test]
+    fn tonemap_compresses_hdr_differences() {
+        let (opaque, color) = ([8.0, 8.0, 8.0], [9.0, 8.0, 8.0]);
+        let max = FFX_UPSCALE_AUTOREACTIVEFLAGS_USE_COMPONENTS_MAX;
+        let linear = reactive_value(opaque, color, &params(max));
+        let mapped = reactive_value(
+            opaque,
+            color,
+            &params(max | FFX_UPSCALE_AUTOREACTIVEFLAGS_APPLY_TONEMAP),
+        );
+        assert!(close(linear, 1.0));
+        // Each pixel is divided by its own max + 1: the unchanged channels move most
+        assert!(close(mapped, 8.0 / 9.0 - 8.0 / 10.0));
+    }
+
+    #[test]
+    fn mask_is_per_pixel() {
+        let opaque = [[0.0; 3], [1.0, 1.0, 1.0], [0.5, 0.5, 0.5]];
+        let color = [[0.0; 3], [1.0, 1.0, 0.0], [0.5, 0.5, 0.5]];
+        let mask = generate_reactive_mask(&opaque, &color, &params(0)).unwrap();
+        assert_eq!(mask, vec![0.0, 1.0, 0.0]);
+        assert!(generate_reactive_mask(&opaque, &color[..2], &params(0)).is_none());
+    }
+
+    #[test]
+    fn composition_rejects_half_as_much() {
+        assert_eq!(history_rejection(0.0, 0.0), 0.0);
+        assert_eq!(history_rejection(1.0, 0.0), 1.0);
+        assert_eq!(history_rejection(0.0, 1.0), COMPOSITION_HISTORY_REJECTION);
+        assert_eq!(history_rejection(1.0, 1.0), 1.0);
+        assert_eq!(history_rejection(-3.0, 7.0), COMPOSITION_HISTORY_REJECTION);
+        assert_eq!(temporal_blend_alpha(0.8, 0.0, 1.0), 0.8);
+        assert_eq!(temporal_blend_alpha(0.1, 0.0, 1.0), 0.5);
+    }
+
+    #[test]
+    fn blend_matches_one_pass_per_mask() {
+        let lerp = |a: [f32; 3], b: [f32; 3], t: f32| [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t);
+        let (result, current) = ([0.2, 0.4, 0.6], [1.0, 0.0, 0.5]);
+        for (reactive, composition) in [(0.0, 0.0), (0.3, 0.0), (0.0, 0.6), (0.5, 1.0)] {
+            let two_pass = lerp(
+                lerp(result, current, reactive),
+                current,
+                composition * COMPOSITION_HISTORY_REJECTION,
+            );
+            let blended = blend_toward_current(result, current, reactive, composition);
+            for i in 0..3 {
+                assert!(close(blended[i], two_pass[i]), "{reactive} {composition}");
+            }
+        }
+    }
+}
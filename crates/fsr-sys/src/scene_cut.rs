@@ -0,0 +1,500 @@
+// ---- Camera-cut / scene-change detection ----
+//
+// Temporal upscalers trust the game's `reset` flag to drop history, and some
+// games set it a frame late or never. This detector compares consecutive
+// frames through a few cheap statistics and asks for a reset when they jump:
+//
+// - luma: a log2-luminance histogram (the auto-exposure one);
+// - depth: a log2 histogram of reverse-Z depth, i.e. of distance;
+// - motion: how much of the screen moves by more than `motion_threshold`
+//   (UV units per frame), and the mean magnitude.
+//
+// A flash changes luma but not depth, a door opening changes depth but not
+// luma; a cut changes both. Motion vectors that throw most of the screen far
+// away mean history is unusable whatever the cause.
+//
+// `scene_stats_cs.hlsl` gathers `FrameStats` on the GPU; `frame_stats` is its
+// CPU twin. Keep the two in sync.
+//
+// Configured from the `[scene_cut]` section of `oxr.ini`:
+//
+//   [scene_cut]
+//   enabled = true           ; on by default
+//   luma_distance = 0.5      ; histogram distance (0..1) counted as a jump
+//   depth_distance = 0.5
+//   motion_threshold = 0.25  ; UV length of a "far" motion vector
+//   motion_fraction = 0.6    ; fraction of far vectors counted as a jump
+
+use crate::exposure::{histogram_bin, luminance, sanitize_pre_exposure, EXPOSURE_HISTOGRAM_BINS};
+
+pub const SCENE_CUT_LUMA_BINS: usize = EXPOSURE_HISTOGRAM_BINS;
+pub const SCENE_CUT_DEPTH_BINS: usize = 32;
+
+/// log2 of reverse-Z depth covered by depth bins 1..BINS; bin 0 holds depth
+/// at or below `2^MIN` (sky, far plane).
+pub const SCENE_CUT_MIN_LOG2_DEPTH: f32 = -16.0;
+
+/// Fixed-point scale of the per-pixel motion magnitude (clamped to 1) summed
+/// on the GPU. 256 keeps a 4K frame within a u32.
+pub const SCENE_CUT_MOTION_SCALE: f32 = 256.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SceneCutConfig {
+    pub luma_distance: f32,
+    pub depth_distance: f32,
+    pub motion_threshold: f32,
+    pub motion_fraction: f32,
+}
+
+impl Default for SceneCutConfig {
+    fn default() -> Self {
+        Self {
+            luma_distance: 0.5,
+            depth_distance: 0.5,
+            motion_threshold: 0.25,
+            motion_fraction: 0.6,
+        }
+    }
+}
+
+impl SceneCutConfig {
+    /// Build the config from `[scene_cut]` values. Returns `None` when the
+    /// section disables detection. Invalid values keep their defaults and are
+    /// returned as `(key, value)` so the caller can log them.
+    pub fn parse<'a>(
+        get: impl Fn(&str) -> Option<&'a str>,
+    ) -> (Option<Self>, Vec<(String, String)>) {
+        let disabled = get("enabled").is_some_and(|v| {
+            matches!(
+                v.trim().to_ascii_lowercase().as_str(),
+                "0" | "false" | "no" | "off"
+            )
+        });
+        if disabled {
+            return (None, Vec::new());
+        }
+
+        let mut config = Self::default();
+        let mut rejected = Vec::new();
+        let mut field = |key: &str, slot: &mut f32| {
+            let Some(v) = get(key) else { return };
+            match v.trim().parse::<f32>() {
+                Ok(x) if x.is_finite() && x > 0.0 => *slot = x,
+                _ => rejected.push((key.to_string(), v.to_string())),
+            }
+        };
+        field("luma_distance", &mut config.luma_distance);
+        field("depth_distance", &mut config.depth_distance);
+        field("motion_threshold", &mut config.motion_threshold);
+        field("motion_fraction", &mut config.motion_fraction);
+        (Some(config), rejected)
+    }
+}
+
+/// Per-frame statistics, laid out as the GPU's `Stats` buffer (u32 words).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct FrameStats {
+    pub luma_histogram: [u32; SCENE_CUT_LUMA_BINS],
+    pub depth_histogram: [u32; SCENE_CUT_DEPTH_BINS],
+    /// Pixels whose motion vector is longer than `motion_threshold`.
+    pub far_motion_count: u32,
+    /// Sum of `min(|mv|, 1) * SCENE_CUT_MOTION_SCALE`.
+    pub motion_sum: u32,
+    pub pixel_count: u32,
+    /// Frame the GPU gathered these for; tells a finished readback apart from
+    /// a stale one.
+    pub frame_index: u32,
+}
+
+/// Size of [`FrameStats`] in u32 words.
+pub const SCENE_CUT_STATS_WORDS: usize = size_of::<FrameStats>() / 4;
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self {
+            luma_histogram: [0; SCENE_CUT_LUMA_BINS],
+            depth_histogram: [0; SCENE_CUT_DEPTH_BINS],
+            far_motion_count: 0,
+            motion_sum: 0,
+            pixel_count: 0,
+            frame_index: 0,
+        }
+    }
+}
+
+impl FrameStats {
+    /// Decode the GPU buffer's words. `None` when `words` is too short.
+    pub fn from_words(words: &[u32]) -> Option<Self> {
+        let words = words.get(..SCENE_CUT_STATS_WORDS)?;
+        let (luma, rest) = words.split_at(SCENE_CUT_LUMA_BINS);
+        let (depth, rest) = rest.split_at(SCENE_CUT_DEPTH_BINS);
+        let mut stats = Self::default();
+        stats.luma_histogram.copy_from_slice(luma);
+        stats.depth_histogram.copy_from_slice(depth);
+        stats.far_motion_count = rest[0];
+        stats.motion_sum = rest[1];
+        stats.pixel_count = rest[2];
+        stats.frame_index = rest[3];
+        Some(stats)
+    }
+
+    /// Fraction of pixels with far motion vectors.
+    pub fn far_motion_fraction(&self) -> f32 {
+        if self.pixel_count == 0 {
+            return 0.0;
+        }
+        self.far_motion_count as f32 / self.pixel_count as f32
+    }
+
+    /// Mean motion vector length in UV units (each clamped to 1).
+    pub fn mean_motion(&self) -> f32 {
+        if self.pixel_count == 0 {
+            return 0.0;
+        }
+        self.motion_sum as f32 / SCENE_CUT_MOTION_SCALE / self.pixel_count as f32
+    }
+}
+
+/// Depth histogram bin for a reverse-Z depth value.
+pub fn depth_bin(depth: f32) -> usize {
+    if depth.is_nan() || depth <= SCENE_CUT_MIN_LOG2_DEPTH.exp2() {
+        return 0;
+    }
+    let t = (1.0 - depth.min(1.0).log2() / SCENE_CUT_MIN_LOG2_DEPTH).clamp(0.0, 1.0);
+    (1 + (t * (SCENE_CUT_DEPTH_BINS - 1) as f32) as usize).min(SCENE_CUT_DEPTH_BINS - 1)
+}
+
+/// Statistics of one frame from row-major render-resolution images: colour
+/// (pre-exposed), reverse-Z depth and UV motion vectors.
+pub fn frame_stats(
+    color: &[[f32; 3]],
+    depth: &[f32],
+    motion_vectors: &[[f32; 2]],
+    pre_exposure: f32,
+    motion_threshold: f32,
+    frame_index: u32,
+) -> FrameStats {
+    let rcp = 1.0 / sanitize_pre_exposure(pre_exposure);
+    let mut stats = FrameStats {
+        frame_index,
+        pixel_count: color.len() as u32,
+        ..Default::default()
+    };
+    for &c in color {
+        stats.luma_histogram[histogram_bin(luminance(c) * rcp)] += 1;
+    }
+    for &z in depth {
+        stats.depth_histogram[depth_bin(z)] += 1;
+    }
+    for &[x, y] in motion_vectors {
+        let len = (x * x + y * y).sqrt();
+        if len > motion_threshold {
+            stats.far_motion_count += 1;
+        }
+        if !len.is_nan() {
+            stats.motion_sum += (len.min(1.0) * SCENE_CUT_MOTION_SCALE) as u32;
+        }
+    }
+    stats
+}
+
+/// Distance between two histograms in `[0, 1]`: half the L1 distance of the
+/// normalized bins, i.e. the fraction of pixels that would have to move bin.
+/// 0 when either is empty.
+pub fn histogram_distance(a: &[u32], b: &[u32]) -> f32 {
+    let (total_a, total_b) = (
+        a.iter().map(|&c| c as f64).sum::<f64>(),
+        b.iter().map(|&c| c as f64).sum::<f64>(),
+    );
+    if total_a == 0.0 || total_b == 0.0 {
+        return 0.0;
+    }
+    let l1: f64 = a
+        .iter()
+        .zip(b)
+        .map(|(&x, &y)| (x as f64 / total_a - y as f64 / total_b).abs())
+        .sum();
+    (l1 * 0.5) as f32
+}
+
+/// Why a cut was detected, with the values that crossed the thresholds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CutReason {
+    /// Both the luma and the depth histograms jumped.
+    Scene {
+        luma_distance: f32,
+        depth_distance: f32,
+    },
+    /// Most motion vectors point far away.
+    Motion { far_fraction: f32, mean: f32 },
+}
+
+impl std::fmt::Display for CutReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CutReason::Scene {
+                luma_distance,
+                depth_distance,
+            } => write!(
+                f,
+                "scene change (luma distance {:.2}, depth distance {:.2})",
+                luma_distance, depth_distance
+            ),
+            CutReason::Motion { far_fraction, mean } => write!(
+                f,
+                "motion ({:.0}% far vectors, mean {:.3})",
+                far_fraction * 100.0,
+                mean
+            ),
+        }
+    }
+}
+
+/// Compares each frame's statistics with the previous frame's.
+#[derive(Debug, Clone)]
+pub struct SceneCutDetector {
+    pub config: SceneCutConfig,
+    prev: Option<FrameStats>,
+}
+
+impl SceneCutDetector {
+    pub fn new(config: SceneCutConfig) -> Self {
+        Self { config, prev: None }
+    }
+
+    /// Feed the next frame. `game_reset` frames are remembered but never
+    /// reported (the game already dropped history). Frames that do not follow
+    /// the previous one (`frame_index` gap) are not compared across the gap.
+    pub fn update(&mut self, stats: FrameStats, game_reset: bool) -> Option<CutReason> {
+        let prev = self.prev.replace(stats)?;
+        if game_reset || stats.frame_index != prev.frame_index.wrapping_add(1) {
+            return None;
+        }
+
+        let c = &self.config;
+        let far_fraction = stats.far_motion_fraction();
+        if far_fraction > c.motion_fraction {
+            return Some(CutReason::Motion {
+                far_fraction,
+                mean: stats.mean_motion(),
+            });
+        }
+        let luma_distance = histogram_distance(&prev.luma_histogram, &stats.luma_histogram);
+        let depth_distance = histogram_distance(&prev.depth_histogram, &stats.depth_histogram);
+        (luma_distance > c.luma_distance && depth_distance > c.depth_distance).then_some(
+            CutReason::Scene {
+                luma_distance,
+                depth_distance,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: usize = 64;
+    const H: usize = 36;
+
+    /// Synthetic render-resolution frame.
+    struct Frame {
+        color: Vec<[f32; 3]>,
+        depth: Vec<f32>,
+        motion_vectors: Vec<[f32; 2]>,
+    }
+
+    /// A room (`indoor`) or a landscape, panned by `pan` pixels. The room is
+    /// dim with walls a few metres away, the landscape bright with sky.
+    fn scene(indoor: bool, pan: usize) -> Frame {
+        let mut frame = Frame {
+            color: Vec::with_capacity(W * H),
+            depth: Vec::with_capacity(W * H),
+            motion_vectors: vec![[pan as f32 / W as f32, 0.0]; W * H],
+        };
+        for y in 0..H {
+            for x in 0..W {
+                let u = ((x + pan) % W) as f32 / W as f32;
+                let v = y as f32 / H as f32;
+                let (luma, depth) = if indoor {
+                    (0.02 + 0.08 * u, 0.05 + 0.1 * v)
+                } else if v < 0.5 {
+                    (4.0 + 4.0 * u, 0.0)
+                } else {
+                    (1.0 + u, 1e-3 * v)
+                };
+                frame.color.push([luma; 3]);
+                frame.depth.push(depth);
+            }
+        }
+        frame
+    }
+
+    fn stats(frame: &Frame, index: u32) -> FrameStats {
+        frame_stats(
+            &frame.color,
+            &frame.depth,
+            &frame.motion_vectors,
+            1.0,
+            SceneCutConfig::default().motion_threshold,
+            index,
+        )
+    }
+
+    /// Detector results for a sequence of frames; `resets` lists frames the
+    /// game flagged.
+    fn run(frames: &[Frame], resets: &[usize]) -> Vec<Option<CutReason>> {
+        let mut detector = SceneCutDetector::new(SceneCutConfig::default());
+        frames
+            .iter()
+            .enumerate()
+            .map(|(i, f)| detector.update(stats(f, i as u32), resets.contains(&i)))
+            .collect()
+    }
+
+    fn cut_frames(results: &[Option<CutReason>]) -> Vec<usize> {
+        results
+            .iter()
+            .enumerate()
+            .filter_map(|(i, r)| r.map(|_| i))
+            .collect()
+    }
+
+    #[test]
+    fn parse_defaults_and_rejects() {
+        let (config, rejected) = SceneCutConfig::parse(|_| None);
+        assert_eq!(config, Some(SceneCutConfig::default()));
+        assert!(rejected.is_empty());
+
+        let (config, _) = SceneCutConfig::parse(|k| (k == "enabled").then_some("off"));
+        assert!(config.is_none());
+
+        let keys = [("luma_distance", "0.3"), ("motion_fraction", "-1")];
+        let get = |k: &str| keys.iter().find(|(key, _)| *key == k).map(|(_, v)| *v);
+        let (config, rejected) = SceneCutConfig::parse(get);
+        let config = config.unwrap();
+        assert_eq!(config.luma_distance, 0.3);
+        assert_eq!(config.motion_fraction, 0.6);
+        assert_eq!(
+            rejected,
+            [("motion_fraction".to_string(), "-1".to_string())]
+        );
+    }
+
+    #[test]
+    fn stats_round_trip_through_gpu_words() {
+        let s = stats(&scene(true, 1), 7);
+        let words: Vec<u32> = s
+            .luma_histogram
+            .iter()
+            .chain(&s.depth_histogram)
+            .copied()
+            .chain([
+                s.far_motion_count,
+                s.motion_sum,
+                s.pixel_count,
+                s.frame_index,
+            ])
+            .collect();
+        assert_eq!(words.len(), SCENE_CUT_STATS_WORDS);
+        assert_eq!(FrameStats::from_words(&words), Some(s));
+        assert_eq!(FrameStats::from_words(&words[1..]), None);
+    }
+
+    #[test]
+    fn depth_bins_cover_reverse_z() {
+        assert_eq!(depth_bin(0.0), 0);
+        assert_eq!(depth_bin(f32::NAN), 0);
+        assert_eq!(depth_bin(1.0), SCENE_CUT_DEPTH_BINS - 1);
+        assert!(depth_bin(1e-3) < depth_bin(0.1));
+    }
+
+    #[test]
+    fn slow_pan_is_not_a_cut() {
+        let frames: Vec<_> = (0..10).map(|i| scene(true, i)).collect();
+        assert!(cut_frames(&run(&frames, &[])).is_empty());
+        let frames: Vec<_> = (0..10).map(|i| scene(false, i)).collect();
+        assert!(cut_frames(&run(&frames, &[])).is_empty());
+    }
+
+    #[test]
+    fn hard_cut_is_detected_once() {
+        let mut frames: Vec<_> = (0..5).map(|i| scene(true, i)).collect();
+        frames.extend((0..5).map(|i| scene(false, i)));
+        let results = run(&frames, &[]);
+        assert_eq!(cut_frames(&results), [5]);
+        let Some(CutReason::Scene {
+            luma_distance,
+            depth_distance,
+        }) = results[5]
+        else {
+            panic!("{:?}", results[5]);
+        };
+        assert!(luma_distance > 0.9 && depth_distance > 0.9);
+    }
+
+    #[test]
+    fn game_reset_on_the_cut_is_not_reported() {
+        let mut frames: Vec<_> = (0..5).map(|i| scene(true, i)).collect();
+        frames.extend((0..5).map(|i| scene(false, i)));
+        assert!(cut_frames(&run(&frames, &[5])).is_empty());
+        // A reset a frame early still leaves the cut to report
+        assert_eq!(cut_frames(&run(&frames, &[4])), [5]);
+    }
+
+    #[test]
+    fn flash_or_door_alone_is_not_a_cut() {
+        let room = scene(true, 0);
+        let flash = Frame {
+            color: room.color.iter().map(|c| c.map(|v| v * 100.0)).collect(),
+            depth: room.depth.clone(),
+            motion_vectors: room.motion_vectors.clone(),
+        };
+        let door = Frame {
+            color: room.color.clone(),
+            depth: vec![1e-4; W * H],
+            motion_vectors: room.motion_vectors.clone(),
+        };
+        for event in [flash, door] {
+            let frames = [scene(true, 0), event, scene(true, 0)];
+            assert!(cut_frames(&run(&frames, &[])).is_empty());
+        }
+    }
+
+    #[test]
+    fn whip_pan_is_a_motion_cut() {
+        let mut whip = scene(true, 0);
+        for (i, mv) in whip.motion_vectors.iter_mut().enumerate() {
+            // Nine in ten pixels fly half the screen
+            *mv = if i % 10 == 0 { [0.0, 0.0] } else { [0.5, 0.1] };
+        }
+        let results = run(&[scene(true, 0), whip], &[]);
+        let Some(CutReason::Motion { far_fraction, mean }) = results[1] else {
+            panic!("{:?}", results[1]);
+        };
+        assert!((far_fraction - 0.9).abs() < 0.01);
+        assert!((mean - 0.9 * 0.26f32.sqrt()).abs() < 0.01);
+    }
+
+    #[test]
+    fn frame_gap_is_not_compared() {
+        let mut detector = SceneCutDetector::new(SceneCutConfig::default());
+        assert_eq!(detector.update(stats(&scene(true, 0), 10), false), None);
+        // The landscape arrives three frames later: no comparison
+        assert_eq!(detector.update(stats(&scene(false, 0), 13), false), None);
+        assert!(detector.update(stats(&scene(true, 0), 14), false).is_some());
+    }
+
+    #[test]
+    fn empty_stats_never_cut() {
+        assert_eq!(histogram_distance(&[0; 4], &[1, 2, 3, 4]), 0.0);
+        let mut detector = SceneCutDetector::new(SceneCutConfig::default());
+        detector.update(FrameStats::default(), false);
+        let next = FrameStats {
+            frame_index: 1,
+            ..Default::default()
+        };
+        assert_eq!(detector.update(next, false), None);
+    }
+}
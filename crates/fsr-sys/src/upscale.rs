@@ -42,6 +42,13 @@ pub const FFX_UPSCALE_ENABLE_DEBUG_CHECKING: u32 = 1 << 7;
 
 pub const FFX_UPSCALE_FLAG_DRAW_DEBUG_VIEW: u32 = 1 << 0;
 
+// ---- Generate reactive mask flags ----
+
+pub const FFX_UPSCALE_AUTOREACTIVEFLAGS_APPLY_TONEMAP: u32 = 1 << 0;
+pub const FFX_UPSCALE_AUTOREACTIVEFLAGS_APPLY_INVERSETONEMAP: u32 = 1 << 1;
+pub const FFX_UPSCALE_AUTOREACTIVEFLAGS_APPLY_THRESHOLD: u32 = 1 << 2;
+pub const FFX_UPSCALE_AUTOREACTIVEFLAGS_USE_COMPONENTS_MAX: u32 = 1 << 3;
+
 // ---- Create context descriptor ----
 
 #[repr(C)]
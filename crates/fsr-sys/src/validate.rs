@@ -0,0 +1,284 @@
+use crate::frame::UpscaleFrame;
+use crate::types::*;
+
+// ---- Dispatch validation (FFX_UPSCALE_ENABLE_DEBUG_CHECKING) ----
+//
+// Checks an `UpscaleFrame` against the limits given at context creation.
+// Each violation carries a stable code so reports can be grepped across
+// logs and game consoles; `Validator` reports every code once per context.
+// Errors are frames the upscaler cannot process correctly; warnings are
+// legal inputs that usually point at a convention mix-up.
+
+/// Stable validation codes. Values are part of the log format; never renumber.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationCode {
+    MissingCommandList = 1,
+    MissingColor = 2,
+    MissingDepth = 3,
+    MissingMotionVectors = 4,
+    MissingOutput = 5,
+    RenderSizeExceedsMax = 6,
+    UpscaleSizeExceedsMax = 7,
+    RenderSizeExceedsUpscale = 8,
+    UnsupportedColorFormat = 9,
+    UnsupportedDepthFormat = 10,
+    UnsupportedMotionVectorFormat = 11,
+    UnsupportedOutputFormat = 12,
+    JitterOutOfRange = 13,
+    MotionVectorScaleSign = 14,
+    CameraNearFarOrder = 15,
+}
+
+impl ValidationCode {
+    /// `OXR-Vnnn` identifier used in log lines.
+    pub fn id(self) -> String {
+        format!("OXR-V{:03}", self as u32)
+    }
+
+    /// Whether the code only flags a suspicious, but legal, input.
+    pub fn is_warning(self) -> bool {
+        matches!(
+            self,
+            ValidationCode::JitterOutOfRange
+                | ValidationCode::MotionVectorScaleSign
+                | ValidationCode::CameraNearFarOrder
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Violation {
+    pub code: ValidationCode,
+    pub detail: String,
+}
+
+/// Context-creation limits the frame is checked against.
+#[derive(Debug, Clone, Copy)]
+pub struct ValidationLimits {
+    pub max_render_size: FfxApiDimensions2D,
+    pub max_upscale_size: FfxApiDimensions2D,
+    pub depth_inverted: bool,
+}
+
+/// Largest expected jitter component, in render pixels: the unit of
+/// `jitterOffset` in both dispatch descriptions, where FSR's own jitter
+/// sequence stays within [-0.5, 0.5]. Past a full pixel the game is most
+/// likely passing a scaled or accumulated offset. Jitter given in NDC
+/// (2 / render size per pixel) is smaller still and is not detected.
+pub const MAX_JITTER: f32 = 1.0;
+
+fn is_color_format(f: u32) -> bool {
+    matches!(
+        f,
+        FFX_API_SURFACE_FORMAT_R32G32B32A32_TYPELESS
+            | FFX_API_SURFACE_FORMAT_R32G32B32A32_FLOAT
+            | FFX_API_SURFACE_FORMAT_R16G16B16A16_FLOAT
+            | FFX_API_SURFACE_FORMAT_R16G16B16A16_TYPELESS
+            | FFX_API_SURFACE_FORMAT_R32G32B32_FLOAT
+            | FFX_API_SURFACE_FORMAT_R8G8B8A8_TYPELESS
+            | FFX_API_SURFACE_FORMAT_R8G8B8A8_UNORM
+            | FFX_API_SURFACE_FORMAT_R8G8B8A8_SNORM
+            | FFX_API_SURFACE_FORMAT_R8G8B8A8_SRGB
+            | FFX_API_SURFACE_FORMAT_B8G8R8A8_TYPELESS
+            | FFX_API_SURFACE_FORMAT_B8G8R8A8_UNORM
+            | FFX_API_SURFACE_FORMAT_B8G8R8A8_SRGB
+            | FFX_API_SURFACE_FORMAT_R11G11B10_FLOAT
+            | FFX_API_SURFACE_FORMAT_R10G10B10A2_UNORM
+            | FFX_API_SURFACE_FORMAT_R10G10B10A2_TYPELESS
+            | FFX_API_SURFACE_FORMAT_R9G9B9E5_SHAREDEXP
+    )
+}
+
+fn is_depth_format(f: u32) -> bool {
+    matches!(
+        f,
+        FFX_API_SURFACE_FORMAT_R32_FLOAT
+            | FFX_API_SURFACE_FORMAT_R32_TYPELESS
+            | FFX_API_SURFACE_FORMAT_R16_UNORM
+            | FFX_API_SURFACE_FORMAT_R16_TYPELESS
+            | FFX_API_SURFACE_FORMAT_R16_FLOAT
+            // D32_S8X24 is exposed as R32G32_TYPELESS.
+            | FFX_API_SURFACE_FORMAT_R32G32_TYPELESS
+            // D24_S8 is exposed as R32_UINT by some backends.
+            | FFX_API_SURFACE_FORMAT_R32_UINT
+    )
+}
+
+fn is_motion_vector_format(f: u32) -> bool {
+    matches!(
+        f,
+        FFX_API_SURFACE_FORMAT_R16G16_FLOAT
+            | FFX_API_SURFACE_FORMAT_R16G16_TYPELESS
+            | FFX_API_SURFACE_FORMAT_R16G16_SINT
+            | FFX_API_SURFACE_FORMAT_R32G32_FLOAT
+            | FFX_API_SURFACE_FORMAT_R32G32_TYPELESS
+            | FFX_API_SURFACE_FORMAT_R16G16B16A16_FLOAT
+            | FFX_API_SURFACE_FORMAT_R32G32B32A32_FLOAT
+    )
+}
+
+/// Check `frame` against `limits`, returning every violation found.
+pub fn validate(frame: &UpscaleFrame, limits: &ValidationLimits) -> Vec<Violation> {
+    let mut out = Vec::new();
+    let mut report = |code, detail: String| out.push(Violation { code, detail });
+
+    if frame.command_list.is_null() {
+        report(
+            ValidationCode::MissingCommandList,
+            "command list is null".into(),
+        );
+    }
+    let required = [
+        (ValidationCode::MissingColor, "color", &frame.color),
+        (ValidationCode::MissingDepth, "depth", &frame.depth),
+        (
+            ValidationCode::MissingMotionVectors,
+            "motion vectors",
+            &frame.motion_vectors,
+        ),
+        (ValidationCode::MissingOutput, "output", &frame.output),
+    ];
+    for (code, name, res) in required {
+        if res.resource.is_null() {
+            report(code, format!("{name} resource is null"));
+        }
+    }
+
+    let (rw, rh) = frame.effective_render_size();
+    let (uw, uh) = frame.effective_upscale_size();
+    let max_r = limits.max_render_size;
+    let max_u = limits.max_upscale_size;
+    if max_r.width > 0 && max_r.height > 0 && (rw > max_r.width || rh > max_r.height) {
+        report(
+            ValidationCode::RenderSizeExceedsMax,
+            format!(
+                "render size {rw}x{rh} exceeds max render size {}x{}",
+                max_r.width, max_r.height
+            ),
+        );
+    }
+    if max_u.width > 0 && max_u.height > 0 && (uw > max_u.width || uh > max_u.height) {
+        report(
+            ValidationCode::UpscaleSizeExceedsMax,
+            format!(
+                "output size {uw}x{uh} exceeds max upscale size {}x{}",
+                max_u.width, max_u.height
+            ),
+        );
+    }
+    if rw > uw || rh > uh {
+        report(
+            ValidationCode::RenderSizeExceedsUpscale,
+            format!("render size {rw}x{rh} larger than output size {uw}x{uh}"),
+        );
+    }
+
+    let formats = [
+        (
+            ValidationCode::UnsupportedColorFormat,
+            "color",
+            &frame.color,
+            is_color_format as fn(u32) -> bool,
+        ),
+        (
+            ValidationCode::UnsupportedDepthFormat,
+            "depth",
+            &frame.depth,
+            is_depth_format,
+        ),
+        (
+            ValidationCode::UnsupportedMotionVectorFormat,
+            "motion vectors",
+            &frame.motion_vectors,
+            is_motion_vector_format,
+        ),
+        (
+            ValidationCode::UnsupportedOutputFormat,
+            "output",
+            &frame.output,
+            is_color_format,
+        ),
+    ];
+    for (code, name, res, supported) in formats {
+        if !res.resource.is_null() && !supported(res.description.format) {
+            report(
+                code,
+                format!("{name} format {} is not supported", res.description.format),
+            );
+        }
+    }
+
+    let j = frame.jitter_offset;
+    if !j.x.is_finite() || !j.y.is_finite() || j.x.abs() > MAX_JITTER || j.y.abs() > MAX_JITTER {
+        report(
+            ValidationCode::JitterOutOfRange,
+            format!(
+                "jitter ({}, {}) outside [-{MAX_JITTER}, {MAX_JITTER}]",
+                j.x, j.y
+            ),
+        );
+    }
+
+    // A negative scale is how some engines flip their vectors, so only say so.
+    let mv = frame.motion_vector_scale;
+    if !(mv.x > 0.0 && mv.y > 0.0) {
+        report(
+            ValidationCode::MotionVectorScaleSign,
+            format!(
+                "motion vector scale ({}, {}) is not positive; check the vector convention",
+                mv.x, mv.y
+            ),
+        );
+    }
+
+    let (near, far) = (frame.camera_near, frame.camera_far);
+    let ordered = if limits.depth_inverted {
+        near > far
+    } else {
+        near < far
+    };
+    if !ordered {
+        report(
+            ValidationCode::CameraNearFarOrder,
+            format!(
+                "camera near {near} / far {far} in wrong order for {} depth",
+                if limits.depth_inverted {
+                    "inverted"
+                } else {
+                    "non-inverted"
+                }
+            ),
+        );
+    }
+
+    out
+}
+
+/// Per-context validation state: reports each code at most once.
+#[derive(Debug, Clone)]
+pub struct Validator {
+    pub limits: ValidationLimits,
+    reported: u64,
+}
+
+impl Validator {
+    pub fn new(limits: ValidationLimits) -> Self {
+        Self {
+            limits,
+            reported: 0,
+        }
+    }
+
+    /// Validate `frame`, returning only violations whose code has not been reported yet.
+    pub fn check(&mut self, frame: &UpscaleFrame) -> Vec<Violation> {
+        let mut fresh = validate(frame, &self.limits);
+        fresh.retain(|v| {
+            let bit = 1u64 << (v.code as u32);
+            let new = self.reported & bit == 0;
+            self.reported |= bit;
+            new
+        });
+        fresh
+    }
+}
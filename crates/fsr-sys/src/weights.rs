@@ -0,0 +1,784 @@
+// ---- Model weight container ----
+//
+// Neural passes (IMBA AA) used to load a raw f32 blob and trust a hard-coded
+// offset table; a file for another model revision loaded fine and produced
+// garbage. Weight files are now self-describing so the loader can check them
+// against the architecture the dispatch code expects.
+//
+// The payload is a buffer of 32-bit words: each tensor owns the words
+// `[offset, offset + len)`. In an F32 file every word is a value.
+// F16 and I8 files store their convolution kernels (rank >= 2, see
+// `WeightDtype::storage`) packed at the start of the tensor's words; vectors
+// (biases, GroupNorm parameters) stay f32 in every file. For a tensor of n
+// values at word o, value i is
+//
+//   F16: half (i & 1) of word o + i / 2
+//   I8:  signed byte (i & 3) of word o + i / 4, times the f32 scale of its
+//        output channel; the shape[0] scales follow the codes at word
+//        o + ceil(n / 4)
+//
+// so offsets do not depend on the dtype. Loaders decode every value back to
+// f32 (`WeightFile::pack`), which is all the shaders read.
+//
+// Layout, little-endian, strings as a u16 byte length followed by UTF-8:
+//
+//   magic           "OXRW"
+//   format_version  u32
+//   dtype           u32     (WeightDtype)
+//   model_name      str
+//   model_version   str
+//   tensor_count    u32
+//   tensor_count x {
+//     name          str
+//     rank          u32     (<= WEIGHTS_MAX_RANK)
+//     dims          u32 x rank
+//     offset        u32     (elements into the payload)
+//   }
+//   payload_len     u32     (words)
+//   payload         payload_len x u32
+//   checksum        u32     (CRC-32 of every byte before it)
+//
+// Files written before the container existed are a bare f32 payload; see
+// `WeightFile::from_legacy`.
+
+pub const WEIGHTS_MAGIC: [u8; 4] = *b"OXRW";
+pub const WEIGHTS_FORMAT_VERSION: u32 = 1;
+
+/// Highest tensor rank accepted by the parser.
+pub const WEIGHTS_MAX_RANK: usize = 8;
+
+/// How a file stores its convolution kernels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum WeightDtype {
+    F32 = 0,
+    F16 = 1,
+    /// Symmetric int8 with one f32 scale per output channel.
+    I8 = 2,
+}
+
+impl WeightDtype {
+    pub fn from_u32(v: u32) -> Option<Self> {
+        match v {
+            0 => Some(Self::F32),
+            1 => Some(Self::F16),
+            2 => Some(Self::I8),
+            _ => None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "f32" | "fp32" => Some(Self::F32),
+            "f16" | "fp16" => Some(Self::F16),
+            "i8" | "int8" => Some(Self::I8),
+            _ => None,
+        }
+    }
+
+    /// Bytes per value, scales not counted.
+    pub fn size(self) -> usize {
+        match self {
+            Self::F32 => 4,
+            Self::F16 => 2,
+            Self::I8 => 1,
+        }
+    }
+
+    /// How a tensor of `shape` is stored in a file of this dtype: vectors stay
+    /// f32, and so does an I8 tensor too small to hold its scales.
+    pub fn storage(self, shape: &[u32]) -> WeightDtype {
+        let n: usize = shape.iter().map(|&d| d as usize).product();
+        match self {
+            _ if shape.len() < 2 || n == 0 => Self::F32,
+            Self::I8 if n.div_ceil(4) + shape[0] as usize > n => Self::F32,
+            dtype => dtype,
+        }
+    }
+}
+
+/// A tensor's entry in a weight file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TensorInfo {
+    pub name: String,
+    pub shape: Vec<u32>,
+    /// Elements into the payload.
+    pub offset: u32,
+}
+
+impl TensorInfo {
+    /// Element count, `None` if it overflows `usize`.
+    pub fn checked_len(&self) -> Option<usize> {
+        self.shape
+            .iter()
+            .try_fold(1usize, |n, &d| n.checked_mul(d as usize))
+    }
+
+    /// Payload elements `[offset, offset + len)`, `None` on overflow.
+    pub fn range(&self) -> Option<std::ops::Range<usize>> {
+        let start = self.offset as usize;
+        Some(start..start.checked_add(self.checked_len()?)?)
+    }
+
+    /// Element count. Saturates on overflow; `WeightFile::parse` and
+    /// `from_legacy` reject such tensors, so it is exact past them.
+    pub fn len(&self) -> usize {
+        self.checked_len().unwrap_or(usize::MAX)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A parsed weight file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightFile {
+    pub model_name: String,
+    pub model_version: String,
+    pub dtype: WeightDtype,
+    pub tensors: Vec<TensorInfo>,
+    /// Values at the tensors' offsets, F16 / I8 tensors decoded.
+    pub data: Vec<f32>,
+    /// Per-output-channel scales of each I8 tensor, indexed like `tensors`
+    /// (empty for tensors stored otherwise). Missing scales are derived from
+    /// the values when writing.
+    pub scales: Vec<Vec<f32>>,
+}
+
+impl WeightFile {
+    /// Whether `bytes` starts like a container (as opposed to a legacy blob).
+    pub fn is_container(bytes: &[u8]) -> bool {
+        bytes.starts_with(&WEIGHTS_MAGIC)
+    }
+
+    /// Parse and check a container: magic, version, checksum, and that every
+    /// tensor has a unique name and lies inside the payload.
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        if !Self::is_container(bytes) {
+            return Err("not a weight file (bad magic)".into());
+        }
+        let Some(body_len) = bytes.len().checked_sub(4) else {
+            return Err("truncated".into());
+        };
+        let (body, checksum) = bytes.split_at(body_len);
+        let checksum = u32::from_le_bytes(checksum.try_into().unwrap());
+        let actual = crc32(body);
+        if checksum != actual {
+            return Err(format!(
+                "checksum mismatch (stored {:08x}, computed {:08x})",
+                checksum, actual
+            ));
+        }
+
+        let mut r = Reader {
+            bytes: body,
+            pos: WEIGHTS_MAGIC.len(),
+        };
+        let version = r.u32()?;
+        if version != WEIGHTS_FORMAT_VERSION {
+            return Err(format!(
+                "unsupported format version {} (expected {})",
+                version, WEIGHTS_FORMAT_VERSION
+            ));
+        }
+        let dtype = r.u32()?;
+        let dtype = WeightDtype::from_u32(dtype).ok_or(format!("unknown dtype {}", dtype))?;
+        let model_name = r.str()?;
+        let model_version = r.str()?;
+
+        let count = r.u32()? as usize;
+        let mut tensors: Vec<TensorInfo> = Vec::with_capacity(count.min(1024));
+        for _ in 0..count {
+            let name = r.str()?;
+            let rank = r.u32()? as usize;
+            if rank > WEIGHTS_MAX_RANK {
+                return Err(format!("tensor '{}': rank {} too high", name, rank));
+            }
+            let shape = (0..rank).map(|_| r.u32()).collect::<Result<Vec<_>, _>>()?;
+            let offset = r.u32()?;
+            if tensors.iter().any(|t| t.name == name) {
+                return Err(format!("duplicate tensor '{}'", name));
+            }
+            tensors.push(TensorInfo {
+                name,
+                shape,
+                offset,
+            });
+        }
+
+        let payload_len = r.u32()? as usize;
+        let payload = r.take(payload_len.saturating_mul(4))?;
+        if r.pos != body.len() {
+            return Err(format!(
+                "{} trailing bytes after the payload",
+                body.len() - r.pos
+            ));
+        }
+        for t in &tensors {
+            let range = t.range().ok_or(format!(
+                "tensor '{}': shape {:?} at offset {} overflows",
+                t.name, t.shape, t.offset
+            ))?;
+            if range.end > payload_len {
+                return Err(format!(
+                    "tensor '{}' [{}..{}) overruns the payload ({} elements)",
+                    t.name, range.start, range.end, payload_len
+                ));
+            }
+        }
+
+        let words: Vec<u32> = payload
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        let mut data: Vec<f32> = words.iter().map(|&w| f32::from_bits(w)).collect();
+        let mut scales = vec![Vec::new(); tensors.len()];
+        for (t, scales) in tensors.iter().zip(&mut scales) {
+            let range = t.offset as usize..t.offset as usize + t.len();
+            let stored = dtype.storage(&t.shape);
+            *scales = decode_tensor(stored, &t.shape, &words[range.clone()], &mut data[range]);
+            if scales.iter().any(|s| !s.is_finite()) {
+                return Err(format!("tensor '{}' has non-finite scales", t.name));
+            }
+        }
+        Ok(Self {
+            model_name,
+            model_version,
+            dtype,
+            tensors,
+            data,
+            scales,
+        })
+    }
+
+    /// Serialize to the container layout, checksum included.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(64 + self.data.len() * self.dtype.size());
+        out.extend_from_slice(&WEIGHTS_MAGIC);
+        out.extend_from_slice(&WEIGHTS_FORMAT_VERSION.to_le_bytes());
+        out.extend_from_slice(&(self.dtype as u32).to_le_bytes());
+        put_str(&mut out, &self.model_name);
+        put_str(&mut out, &self.model_version);
+        out.extend_from_slice(&(self.tensors.len() as u32).to_le_bytes());
+        for t in &self.tensors {
+            put_str(&mut out, &t.name);
+            out.extend_from_slice(&(t.shape.len() as u32).to_le_bytes());
+            for &d in &t.shape {
+                out.extend_from_slice(&d.to_le_bytes());
+            }
+            out.extend_from_slice(&t.offset.to_le_bytes());
+        }
+        let words = self.encode();
+        out.extend_from_slice(&(words.len() as u32).to_le_bytes());
+        for w in &words {
+            out.extend_from_slice(&w.to_le_bytes());
+        }
+        let checksum = crc32(&out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+        out
+    }
+
+    /// Wrap a legacy raw blob: f32 values laid out as `expected` (the
+    /// consuming architecture's tensors, packed in order), with no header.
+    /// Without names or shapes in the file, the checks are that the tensors'
+    /// element counts add up to the blob exactly and that every tensor's
+    /// values are finite.
+    pub fn from_legacy(
+        raw: &[u8],
+        expected: &[TensorInfo],
+        model_name: &str,
+        model_version: &str,
+    ) -> Result<Self, String> {
+        if !raw.len().is_multiple_of(4) {
+            return Err(format!(
+                "legacy weights are {} bytes, not whole f32 values",
+                raw.len()
+            ));
+        }
+        let values = raw.len() / 4;
+        let mut next = 0;
+        for t in expected {
+            let range = t.range().ok_or(format!(
+                "tensor '{}': shape {:?} at offset {} overflows",
+                t.name, t.shape, t.offset
+            ))?;
+            if range.start != next {
+                return Err(format!(
+                    "tensor '{}' starts at {}, expected {}: '{}' is not packed",
+                    t.name, range.start, next, model_name
+                ));
+            }
+            if range.end > values {
+                return Err(format!(
+                    "legacy weights hold {} values, '{}' needs {} for tensor '{}' at {}",
+                    values,
+                    model_name,
+                    t.len(),
+                    t.name,
+                    range.start
+                ));
+            }
+            next = range.end;
+        }
+        if next != values {
+            return Err(format!(
+                "legacy weights hold {} values, '{}' has {} in {} tensors",
+                values,
+                model_name,
+                next,
+                expected.len()
+            ));
+        }
+
+        let data: Vec<f32> = raw
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        if let Some(t) = expected.iter().find(|t| {
+            data[t.offset as usize..][..t.len()]
+                .iter()
+                .any(|v| !v.is_finite())
+        }) {
+            return Err(format!(
+                "tensor '{}' has non-finite values; weights for another model?",
+                t.name
+            ));
+        }
+        Ok(Self {
+            model_name: model_name.to_string(),
+            model_version: model_version.to_string(),
+            dtype: WeightDtype::F32,
+            tensors: expected.to_vec(),
+            data,
+            scales: Vec::new(),
+        })
+    }
+
+    /// Values of the tensor called `name`.
+    pub fn tensor(&self, name: &str) -> Option<&[f32]> {
+        let t = self.tensors.iter().find(|t| t.name == name)?;
+        self.data
+            .get(t.offset as usize..t.offset as usize + t.len())
+    }
+
+    /// Check the file against the architecture `model_name` / `expected` and
+    /// return its values (decoded) laid out at the `expected` offsets. Every
+    /// expected tensor must be present with the same shape, and the file must
+    /// hold nothing else; the file's own offsets may differ.
+    pub fn pack(&self, model_name: &str, expected: &[TensorInfo]) -> Result<Vec<f32>, String> {
+        let found = self.find_expected(model_name, expected)?;
+        Ok(self.relayout(expected, &found))
+    }
+
+    /// Store the kernels as `dtype`. `clip(tensor, max)` picks the I8 range
+    /// of each output channel from its largest magnitude (plain absmax
+    /// quantisation returns `max`); F16 ignores it.
+    pub fn quantize(
+        &self,
+        dtype: WeightDtype,
+        mut clip: impl FnMut(usize, f32) -> f32,
+    ) -> Result<Self, String> {
+        if self.dtype != WeightDtype::F32 {
+            return Err(format!("weights are already {:?}", self.dtype));
+        }
+        let mut out = Self {
+            dtype,
+            scales: vec![Vec::new(); self.tensors.len()],
+            ..self.clone()
+        };
+        for (i, t) in self.tensors.iter().enumerate() {
+            let values = &mut out.data[t.offset as usize..t.offset as usize + t.len()];
+            match dtype.storage(&t.shape) {
+                WeightDtype::F32 => {}
+                WeightDtype::F16 => {
+                    for v in values {
+                        *v = f16_to_f32(f32_to_f16(*v));
+                    }
+                }
+                WeightDtype::I8 => {
+                    let per = t.len() / t.shape[0] as usize;
+                    for row in values.chunks_exact_mut(per) {
+                        let max = row.iter().fold(0.0f32, |m, v| m.max(v.abs()));
+                        let scale = clip(i, max).clamp(0.0, max) / 127.0;
+                        for v in row.iter_mut() {
+                            *v = quantize_i8(*v, scale) as f32 * scale;
+                        }
+                        out.scales[i].push(scale);
+                    }
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// File tensor index for each of `expected`, after the checks `pack`
+    /// documents.
+    fn find_expected(
+        &self,
+        model_name: &str,
+        expected: &[TensorInfo],
+    ) -> Result<Vec<usize>, String> {
+        if self.model_name != model_name {
+            return Err(format!(
+                "model is '{}', expected '{}'",
+                self.model_name, model_name
+            ));
+        }
+        let mut found = Vec::with_capacity(expected.len());
+        for e in expected {
+            let i = self
+                .tensors
+                .iter()
+                .position(|t| t.name == e.name)
+                .ok_or(format!("missing tensor '{}'", e.name))?;
+            let t = &self.tensors[i];
+            if t.shape != e.shape {
+                return Err(format!(
+                    "tensor '{}' has shape {:?}, expected {:?}",
+                    e.name, t.shape, e.shape
+                ));
+            }
+            found.push(i);
+        }
+        if let Some(extra) = self
+            .tensors
+            .iter()
+            .find(|t| !expected.iter().any(|e| e.name == t.name))
+        {
+            return Err(format!("unexpected tensor '{}'", extra.name));
+        }
+        Ok(found)
+    }
+
+    /// Values moved to the `expected` offsets; `found` from `find_expected`.
+    fn relayout(&self, expected: &[TensorInfo], found: &[usize]) -> Vec<f32> {
+        let mut data = vec![0.0; layout_len(expected)];
+        for (e, &i) in expected.iter().zip(found) {
+            let t = &self.tensors[i];
+            data[e.offset as usize..e.offset as usize + e.len()]
+                .copy_from_slice(&self.data[t.offset as usize..t.offset as usize + t.len()]);
+        }
+        data
+    }
+
+    /// The payload words, kernels stored as `dtype`.
+    fn encode(&self) -> Vec<u32> {
+        let mut words: Vec<u32> = self.data.iter().map(|v| v.to_bits()).collect();
+        for (i, t) in self.tensors.iter().enumerate() {
+            let range = t.offset as usize..t.offset as usize + t.len();
+            let scales = self.scales.get(i).filter(|s| !s.is_empty());
+            encode_tensor(
+                self.dtype.storage(&t.shape),
+                &t.shape,
+                &self.data[range.clone()],
+                scales.map(|s| s.as_slice()),
+                &mut words[range],
+            );
+        }
+        words
+    }
+}
+
+/// Pack `values` (one tensor) into its `words` as `stored`, deriving absmax
+/// scales when `scales` is `None`.
+fn encode_tensor(
+    stored: WeightDtype,
+    shape: &[u32],
+    values: &[f32],
+    scales: Option<&[f32]>,
+    words: &mut [u32],
+) {
+    match stored {
+        WeightDtype::F32 => {}
+        WeightDtype::F16 => {
+            words.fill(0);
+            for (i, &v) in values.iter().enumerate() {
+                words[i / 2] |= (f32_to_f16(v) as u32) << (16 * (i & 1));
+            }
+        }
+        WeightDtype::I8 => {
+            words.fill(0);
+            let per = values.len() / shape[0] as usize;
+            let codes = values.len().div_ceil(4);
+            for (c, row) in values.chunks_exact(per).enumerate() {
+                let scale = match scales {
+                    Some(s) => s[c],
+                    None => row.iter().fold(0.0f32, |m, v| m.max(v.abs())) / 127.0,
+                };
+                for (j, &v) in row.iter().enumerate() {
+                    let i = c * per + j;
+                    words[i / 4] |= (quantize_i8(v, scale) as u8 as u32) << (8 * (i & 3));
+                }
+                words[codes + c] = scale.to_bits();
+            }
+        }
+    }
+}
+
+/// Unpack one tensor's `words` stored as `stored` into `values`; returns its
+/// scales (I8 only).
+fn decode_tensor(
+    stored: WeightDtype,
+    shape: &[u32],
+    words: &[u32],
+    values: &mut [f32],
+) -> Vec<f32> {
+    match stored {
+        WeightDtype::F32 => Vec::new(),
+        WeightDtype::F16 => {
+            for (i, v) in values.iter_mut().enumerate() {
+                *v = f16_to_f32((words[i / 2] >> (16 * (i & 1))) as u16);
+            }
+            Vec::new()
+        }
+        WeightDtype::I8 => {
+            let channels = shape[0] as usize;
+            let per = values.len() / channels;
+            let codes = values.len().div_ceil(4);
+            let scales: Vec<f32> = words[codes..codes + channels]
+                .iter()
+                .map(|&w| f32::from_bits(w))
+                .collect();
+            for (i, v) in values.iter_mut().enumerate() {
+                let code = (words[i / 4] >> (8 * (i & 3))) as u8 as i8;
+                *v = code as f32 * scales[i / per];
+            }
+            scales
+        }
+    }
+}
+
+fn quantize_i8(v: f32, scale: f32) -> i8 {
+    if scale > 0.0 {
+        (v / scale).round().clamp(-127.0, 127.0) as i8
+    } else {
+        0
+    }
+}
+
+/// IEEE half to single, subnormals and NaN payloads included.
+pub fn f16_to_f32(h: u16) -> f32 {
+    let sign = ((h & 0x8000) as u32) << 16;
+    let exp = ((h >> 10) & 0x1F) as u32;
+    let mant = (h & 0x3FF) as u32;
+    let bits = match exp {
+        0 if mant == 0 => sign,
+        0 => {
+            // Subnormal: shift the mantissa up until its leading one is implicit.
+            let shift = mant.leading_zeros() - 21;
+            sign | ((113 - shift) << 23) | ((mant << shift) & 0x3FF) << 13
+        }
+        0x1F => sign | 0x7F80_0000 | (mant << 13),
+        _ => sign | ((exp + 112) << 23) | (mant << 13),
+    };
+    f32::from_bits(bits)
+}
+
+/// Single to IEEE half, rounding to nearest even; out-of-range values become
+/// infinities.
+pub fn f32_to_f16(v: f32) -> u16 {
+    let bits = v.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xFF) as i32;
+    let mant = bits & 0x7F_FFFF;
+    if exp == 0xFF {
+        let nan = if mant != 0 {
+            0x200 | (mant >> 13) as u16
+        } else {
+            0
+        };
+        return sign | 0x7C00 | nan;
+    }
+    let e = exp - 127 + 15;
+    if e >= 0x1F {
+        return sign | 0x7C00;
+    }
+    // Value with its implicit one, and how far to shift it down to land on
+    // half's 10 mantissa bits (further for subnormals).
+    let (full, shift) = if e <= 0 {
+        if e < -10 {
+            return sign;
+        }
+        (mant | 0x80_0000, (14 - e) as u32)
+    } else {
+        (mant, 13)
+    };
+    let half = full >> shift;
+    let rest = full & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    let round = (rest > halfway || (rest == halfway && half & 1 == 1)) as u32;
+    let base = if e <= 0 { 0 } else { (e as u32) << 10 };
+    // A mantissa carry rolls into the exponent, up to infinity.
+    sign | (base + half + round) as u16
+}
+
+/// Elements spanned by a tensor layout.
+pub fn layout_len(tensors: &[TensorInfo]) -> usize {
+    tensors
+        .iter()
+        .map(|t| t.offset as usize + t.len())
+        .max()
+        .unwrap_or(0)
+}
+
+/// CRC-32 (IEEE 802.3, as zlib's `crc32`).
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    let mut len = s.len().min(u16::MAX as usize);
+    while !s.is_char_boundary(len) {
+        len -= 1;
+    }
+    let bytes = &s.as_bytes()[..len];
+    out.extend_from_slice(&(bytes.len() as u16).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(n).filter(|&e| e <= self.bytes.len());
+        let end = end.ok_or(format!("truncated at byte {}", self.pos))?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn str(&mut self) -> Result<String, String> {
+        let len = u16::from_le_bytes(self.take(2)?.try_into().unwrap()) as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| format!("invalid UTF-8 string before byte {}", self.pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tensor(name: &str, shape: &[u32], offset: u32) -> TensorInfo {
+        TensorInfo {
+            name: name.to_string(),
+            shape: shape.to_vec(),
+            offset,
+        }
+    }
+
+    /// Container bytes with one tensor, written by hand so the header can
+    /// claim shapes no payload could hold.
+    fn container(t: &TensorInfo, payload_len: u32) -> Vec<u8> {
+        let mut out = WEIGHTS_MAGIC.to_vec();
+        out.extend_from_slice(&WEIGHTS_FORMAT_VERSION.to_le_bytes());
+        out.extend_from_slice(&(WeightDtype::F32 as u32).to_le_bytes());
+        put_str(&mut out, "m");
+        put_str(&mut out, "1");
+        out.extend_from_slice(&1u32.to_le_bytes());
+        put_str(&mut out, &t.name);
+        out.extend_from_slice(&(t.shape.len() as u32).to_le_bytes());
+        for &d in &t.shape {
+            out.extend_from_slice(&d.to_le_bytes());
+        }
+        out.extend_from_slice(&t.offset.to_le_bytes());
+        out.extend_from_slice(&payload_len.to_le_bytes());
+        out.resize(out.len() + payload_len as usize * 4, 0);
+        let checksum = crc32(&out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+        out
+    }
+
+    fn legacy(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn container_round_trip() {
+        let file = WeightFile {
+            model_name: "m".into(),
+            model_version: "1".into(),
+            dtype: WeightDtype::F32,
+            tensors: vec![tensor("w", &[2, 3], 0), tensor("b", &[2], 6)],
+            data: (0..8).map(|i| i as f32).collect(),
+            scales: vec![Vec::new(); 2],
+        };
+        assert_eq!(WeightFile::parse(&file.to_bytes()), Ok(file.clone()));
+        assert!(WeightFile::parse(&container(&tensor("w", &[4], 0), 4)).is_ok());
+        let err = WeightFile::parse(&container(&tensor("w", &[4], 1), 4)).unwrap_err();
+        assert!(err.contains("overruns"), "{err}");
+    }
+
+    #[test]
+    fn overflowing_tensors_are_parse_errors() {
+        // Element count past usize
+        let t = tensor("w", &[u32::MAX, u32::MAX, u32::MAX], 0);
+        assert_eq!(t.checked_len(), None);
+        let err = WeightFile::parse(&container(&t, 4)).unwrap_err();
+        assert!(err.contains("overflows"), "{err}");
+
+        // 65535 * 42009217 * 6700417 = 2^64 - 1 elements: only the offset
+        // pushes the end past usize
+        let t = tensor("w", &[65535, 42_009_217, 6_700_417], 1);
+        assert_eq!(t.checked_len(), Some(usize::MAX));
+        assert_eq!(t.range(), None);
+        let err = WeightFile::parse(&container(&t, 4)).unwrap_err();
+        assert!(err.contains("overflows"), "{err}");
+    }
+
+    #[test]
+    fn legacy_counts_match_every_tensor() {
+        let graph = [tensor("w", &[2, 2], 0), tensor("b", &[2], 4)];
+        let file = WeightFile::from_legacy(&legacy(&[1.0; 6]), &graph, "m", "legacy").unwrap();
+        assert_eq!(file.tensor("b"), Some(&[1.0, 1.0][..]));
+
+        let err = WeightFile::from_legacy(&legacy(&[1.0; 5]), &graph, "m", "").unwrap_err();
+        assert!(err.contains("tensor 'b'"), "{err}");
+        let err = WeightFile::from_legacy(&legacy(&[1.0; 7]), &graph, "m", "").unwrap_err();
+        assert!(err.contains("in 2 tensors"), "{err}");
+        let err = WeightFile::from_legacy(&[0; 9], &graph, "m", "").unwrap_err();
+        assert!(err.contains("whole f32"), "{err}");
+    }
+
+    #[test]
+    fn legacy_layout_must_be_packed() {
+        let gap = [tensor("w", &[2, 2], 0), tensor("b", &[2], 5)];
+        let err = WeightFile::from_legacy(&legacy(&[1.0; 7]), &gap, "m", "").unwrap_err();
+        assert!(err.contains("not packed"), "{err}");
+        let overlap = [tensor("w", &[2, 2], 0), tensor("b", &[2], 3)];
+        let err = WeightFile::from_legacy(&legacy(&[1.0; 5]), &overlap, "m", "").unwrap_err();
+        assert!(err.contains("not packed"), "{err}");
+    }
+
+    #[test]
+    fn builtin_graph_accepts_its_legacy_blob() {
+        let graph = crate::imba::graph::Graph::builtin();
+        let blob = legacy(&vec![0.25; layout_len(&graph.weights)]);
+        let file = WeightFile::from_legacy(&blob, &graph.weights, &graph.model_name, "legacy");
+        assert_eq!(file.unwrap().tensors, graph.weights);
+        assert!(WeightFile::from_legacy(&blob[4..], &graph.weights, "imba", "").is_err());
+    }
+
+    #[test]
+    fn legacy_values_must_be_finite() {
+        let graph = [tensor("w", &[2, 2], 0), tensor("b", &[2], 4)];
+        let mut values = [0.5; 6];
+        values[5] = f32::NAN;
+        let err = WeightFile::from_legacy(&legacy(&values), &graph, "m", "").unwrap_err();
+        assert!(err.contains("tensor 'b'"), "{err}");
+    }
+}
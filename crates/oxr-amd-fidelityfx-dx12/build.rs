@@ -0,0 +1,51 @@
+use hassle_rs::*;
+use std::path::Path;
+
+fn main() {
+    // Shared with the legacy upscaler proxy so both generate identical masks.
+    let shader_dir = Path::new("../oxr-amd-fsr3-upscaler/src/upscalers/shaders");
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+
+    // (path relative to shader_dir, entry, profile)
+    let shaders: &[(&str, &str, &str)] = &[("reactive_mask_cs.hlsl", "main", "cs_6_2")];
+
+    let dxc = Dxc::new(None).expect("Failed to load DXC library");
+    let compiler = dxc
+        .create_compiler()
+        .expect("Failed to create DXC compiler");
+    let library = dxc
+        .create_library()
+        .expect("Failed to create DXC library interface");
+
+    for &(src_path, entry, profile) in shaders {
+        let full_path = shader_dir.join(src_path);
+        println!("cargo:rerun-if-changed={}", full_path.display());
+
+        let source = std::fs::read_to_string(&full_path)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", full_path.display(), e));
+        let blob = library
+            .create_blob_with_encoding_from_str(&source)
+            .unwrap_or_else(|e| panic!("Failed to create blob for {}: {}", src_path, e));
+
+        let dxc_result =
+            match compiler.compile(&blob, src_path, entry, profile, &["-O3"], None, &[]) {
+                Ok(r) => r,
+                Err((dxc_result, _hr)) => {
+                    let err_str = dxc_result
+                        .get_error_buffer()
+                        .ok()
+                        .and_then(|eb| library.get_blob_as_string(&eb.into()).ok())
+                        .unwrap_or_else(|| "unknown error".to_string());
+                    panic!("DXC compilation failed for {}:\n{}", src_path, err_str);
+                }
+            };
+
+        let compiled = dxc_result
+            .get_result()
+            .unwrap_or_else(|e| panic!("Failed to get compiled result for {}: {}", src_path, e));
+
+        let out_path = Path::new(&out_dir).join(src_path.replace(".hlsl", ".dxil"));
+        std::fs::write(&out_path, compiled.to_vec())
+            .unwrap_or_else(|e| panic!("Failed to write {}: {}", out_path.display(), e));
+    }
+}
@@ -1,5 +1,10 @@
+use std::alloc::Layout;
 use std::ffi::c_void;
 
+use crate::settings;
+use fsr_sys::dynres::DynResController;
+use fsr_sys::message;
+use fsr_sys::validate::{ValidationLimits, Validator};
 use fsr_sys::*;
 use tracing::{error, info};
 
@@ -11,18 +16,152 @@ pub struct OxrContext {
     pub flags: u32,
     /// Raw `ID3D12Device*` obtained from the DX12 backend descriptor.
     pub device: *mut c_void,
+    /// Allocator the context block came from. `ffxDestroyContext` must be
+    /// called with matching callbacks.
+    pub allocator: ContextAllocator,
+    /// Present when created with `FFX_UPSCALE_ENABLE_DEBUG_CHECKING`.
+    pub validator: Option<Validator>,
+    /// This context's dynamic resolution controller, started by its first
+    /// dispatch when it opted in; see `dynres`.
+    pub dynres: Option<DynResController>,
+}
+
+// Every piece of per-context state lives inline in the block above, so the
+// game's allocation callbacks see exactly one allocation per context. The
+// validator and dynres controller are fixed-size for that reason; the
+// violation list a debug-checking dispatch builds is transient, global-heap
+// and freed before the dispatch returns.
+const _: () = assert!(!std::mem::needs_drop::<OxrContext>());
+
+type AllocFn = unsafe extern "C" fn(*mut c_void, u64) -> *mut c_void;
+type DeallocFn = unsafe extern "C" fn(*mut c_void, *mut c_void);
+
+/// Memory source for a context, resolved from the caller's `ffxAllocationCallbacks`.
+///
+/// The callbacks take no alignment argument, so allocations are assumed to be
+/// malloc-aligned; a misaligned block is handed back and treated as out-of-memory.
+#[derive(Clone, Copy, Debug)]
+pub enum ContextAllocator {
+    System,
+    Callbacks {
+        user_data: *mut c_void,
+        alloc: AllocFn,
+        dealloc: DeallocFn,
+    },
+}
+
+impl ContextAllocator {
+    /// Resolve the allocator for a `mem_cb` argument. Null (or both callbacks null)
+    /// selects the system allocator; a half-filled struct is rejected.
+    pub unsafe fn from_callbacks(
+        mem_cb: *const ffxAllocationCallbacks,
+    ) -> Result<Self, ffxReturnCode_t> {
+        if mem_cb.is_null() {
+            return Ok(Self::System);
+        }
+        let cb = &*mem_cb;
+        match (cb.alloc, cb.dealloc) {
+            (Some(alloc), Some(dealloc)) => Ok(Self::Callbacks {
+                user_data: cb.p_user_data,
+                alloc,
+                dealloc,
+            }),
+            (None, None) => Ok(Self::System),
+            _ => {
+                error!("ffxAllocationCallbacks: alloc and dealloc must both be set");
+                Err(FFX_API_RETURN_ERROR_PARAMETER)
+            }
+        }
+    }
+
+    /// Whether `other` refers to the same allocator (same functions and user data).
+    pub fn matches(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::System, Self::System) => true,
+            (
+                Self::Callbacks {
+                    user_data: ua,
+                    alloc: aa,
+                    dealloc: da,
+                },
+                Self::Callbacks {
+                    user_data: ub,
+                    alloc: ab,
+                    dealloc: db,
+                },
+            ) => ua == ub && *aa as usize == *ab as usize && *da as usize == *db as usize,
+            _ => false,
+        }
+    }
+
+    /// Allocate `layout`. Returns null on failure.
+    pub unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match *self {
+            Self::System => std::alloc::alloc(layout),
+            Self::Callbacks {
+                user_data,
+                alloc,
+                dealloc,
+            } => {
+                let ptr = alloc(user_data, layout.size() as u64) as *mut u8;
+                if ptr.is_null() {
+                    return ptr;
+                }
+                if !(ptr as usize).is_multiple_of(layout.align()) {
+                    error!(
+                        ptr = ?ptr,
+                        align = layout.align(),
+                        "ffxAllocationCallbacks: alloc returned misaligned memory"
+                    );
+                    dealloc(user_data, ptr as *mut c_void);
+                    return std::ptr::null_mut();
+                }
+                ptr
+            }
+        }
+    }
+
+    /// Free memory previously returned by `alloc` on this allocator with the same layout.
+    pub unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        match *self {
+            Self::System => std::alloc::dealloc(ptr, layout),
+            Self::Callbacks {
+                user_data, dealloc, ..
+            } => dealloc(user_data, ptr as *mut c_void),
+        }
+    }
+
+    /// Move `value` into memory from this allocator. Returns null on failure.
+    pub unsafe fn alloc_value<T>(&self, value: T) -> *mut T {
+        let ptr = self.alloc(Layout::new::<T>()) as *mut T;
+        if !ptr.is_null() {
+            ptr.write(value);
+        }
+        ptr
+    }
+
+    /// Drop and free a value created by `alloc_value` on this allocator.
+    pub unsafe fn free_value<T>(&self, ptr: *mut T) {
+        std::ptr::drop_in_place(ptr);
+        self.dealloc(ptr as *mut u8, Layout::new::<T>());
+    }
 }
 
 pub unsafe fn create_context(
     context: *mut ffxContext,
     desc: *mut ffxCreateContextDescHeader,
-    _mem_cb: *const ffxAllocationCallbacks,
+    mem_cb: *const ffxAllocationCallbacks,
 ) -> ffxReturnCode_t {
     if context.is_null() || desc.is_null() {
         error!("ffxCreateContext: null pointer argument");
         return FFX_API_RETURN_ERROR_PARAMETER;
     }
 
+    let allocator = match ContextAllocator::from_callbacks(mem_cb) {
+        Ok(a) => a,
+        Err(code) => return code,
+    };
+
     // Walk the descriptor chain to find the upscale create descriptor.
     let upscale_desc = find_desc(desc, FFX_API_CREATE_CONTEXT_DESC_TYPE_UPSCALE);
     if upscale_desc.is_null() {
@@ -49,37 +188,262 @@ pub unsafe fn create_context(
         std::ptr::null_mut()
     };
 
-    let ctx = Box::new(OxrContext {
+    let debug_checking = upscale_desc.flags & FFX_UPSCALE_ENABLE_DEBUG_CHECKING != 0;
+    let validator = (debug_checking || settings::get().validation).then(|| {
+        info!(debug_checking, "ffxCreateContext: validation enabled");
+        Validator::new(ValidationLimits {
+            max_render_size: upscale_desc.max_render_size,
+            max_upscale_size: upscale_desc.max_upscale_size,
+            depth_inverted: upscale_desc.flags & FFX_UPSCALE_ENABLE_DEPTH_INVERTED != 0,
+        })
+    });
+
+    let ctx = allocator.alloc_value(OxrContext {
         max_render_size: upscale_desc.max_render_size,
         max_upscale_size: upscale_desc.max_upscale_size,
         flags: upscale_desc.flags,
         device,
+        allocator,
+        validator,
+        dynres: None,
     });
+    if ctx.is_null() {
+        error!(allocator = ?allocator, "ffxCreateContext: context allocation failed");
+        return FFX_API_RETURN_ERROR_MEMORY;
+    }
 
-    // Store the boxed context as the opaque ffxContext handle.
-    *context = Box::into_raw(ctx) as *mut c_void;
+    // Store the context as the opaque ffxContext handle.
+    *context = ctx as *mut c_void;
+    message::set_callback(ctx as u64, upscale_desc.fp_message);
 
     FFX_API_RETURN_OK
 }
 
 pub unsafe fn destroy_context(
     context: *mut ffxContext,
-    _mem_cb: *const ffxAllocationCallbacks,
+    mem_cb: *const ffxAllocationCallbacks,
 ) -> ffxReturnCode_t {
     if context.is_null() || (*context).is_null() {
         error!("ffxDestroyContext: null context");
         return FFX_API_RETURN_ERROR_PARAMETER;
     }
 
-    let ctx = Box::from_raw(*context as *mut OxrContext);
+    let ctx = *context as *mut OxrContext;
     info!(
-        max_render = ?ctx.max_render_size,
-        max_upscale = ?ctx.max_upscale_size,
+        max_render = ?(*ctx).max_render_size,
+        max_upscale = ?(*ctx).max_upscale_size,
         "ffxDestroyContext"
     );
 
-    // ctx is dropped here, freeing the memory.
+    // Always free with the allocator the context was created with; freeing through
+    // mismatched callbacks would hand the block to the wrong heap.
+    let allocator = (*ctx).allocator;
+    let mismatch = match ContextAllocator::from_callbacks(mem_cb) {
+        Ok(requested) => !allocator.matches(&requested),
+        Err(_) => true,
+    };
+    if mismatch {
+        error!(
+            created_with = ?allocator,
+            "ffxDestroyContext: allocation callbacks differ from ffxCreateContext; \
+             freeing with the original allocator"
+        );
+    }
+
+    message::clear_callback(ctx as u64);
+    allocator.free_value(ctx);
     *context = std::ptr::null_mut();
 
-    FFX_API_RETURN_OK
+    if mismatch {
+        FFX_API_RETURN_ERROR_PARAMETER
+    } else {
+        FFX_API_RETURN_OK
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Block header holding the requested size, keeping blocks 16-aligned.
+    const HEADER: usize = 16;
+
+    /// `ffxAllocationCallbacks` user data: counts calls, optionally failing
+    /// or handing out blocks one byte off alignment.
+    #[derive(Default)]
+    struct Counter {
+        allocs: AtomicUsize,
+        frees: AtomicUsize,
+        fail: bool,
+        misalign: bool,
+    }
+
+    unsafe extern "C" fn counting_alloc(user: *mut c_void, size: u64) -> *mut c_void {
+        let c = &*(user as *const Counter);
+        if c.fail {
+            return std::ptr::null_mut();
+        }
+        c.allocs.fetch_add(1, Ordering::SeqCst);
+        let layout = Layout::from_size_align(size as usize + HEADER + 1, HEADER).unwrap();
+        let base = std::alloc::alloc(layout);
+        (base as *mut u64).write(size);
+        base.add(HEADER + c.misalign as usize) as *mut c_void
+    }
+
+    unsafe extern "C" fn counting_dealloc(user: *mut c_void, ptr: *mut c_void) {
+        let c = &*(user as *const Counter);
+        c.frees.fetch_add(1, Ordering::SeqCst);
+        let base = (ptr as *mut u8).sub(HEADER + c.misalign as usize);
+        let size = (base as *const u64).read() as usize;
+        std::alloc::dealloc(
+            base,
+            Layout::from_size_align(size + HEADER + 1, HEADER).unwrap(),
+        );
+    }
+
+    fn callbacks(counter: &Counter) -> ffxAllocationCallbacks {
+        ffxAllocationCallbacks {
+            p_user_data: counter as *const Counter as *mut c_void,
+            alloc: Some(counting_alloc),
+            dealloc: Some(counting_dealloc),
+        }
+    }
+
+    fn upscale_desc() -> ffxCreateContextDescUpscale {
+        ffxCreateContextDescUpscale {
+            header: ffxApiHeader {
+                type_: FFX_API_CREATE_CONTEXT_DESC_TYPE_UPSCALE,
+                p_next: std::ptr::null_mut(),
+            },
+            flags: 0,
+            max_render_size: FfxApiDimensions2D {
+                width: 1920,
+                height: 1080,
+            },
+            max_upscale_size: FfxApiDimensions2D {
+                width: 3840,
+                height: 2160,
+            },
+            fp_message: None,
+        }
+    }
+
+    #[test]
+    fn resolves_callbacks() {
+        unsafe {
+            assert!(matches!(
+                ContextAllocator::from_callbacks(std::ptr::null()),
+                Ok(ContextAllocator::System)
+            ));
+            let mut cb = ffxAllocationCallbacks {
+                p_user_data: std::ptr::null_mut(),
+                alloc: None,
+                dealloc: None,
+            };
+            assert!(matches!(
+                ContextAllocator::from_callbacks(&cb),
+                Ok(ContextAllocator::System)
+            ));
+            cb.alloc = Some(counting_alloc);
+            assert_eq!(
+                ContextAllocator::from_callbacks(&cb).err(),
+                Some(FFX_API_RETURN_ERROR_PARAMETER)
+            );
+        }
+    }
+
+    #[test]
+    fn context_memory_comes_from_callbacks() {
+        let counter = Counter::default();
+        let cb = callbacks(&counter);
+        let mut desc = upscale_desc();
+        let mut context: ffxContext = std::ptr::null_mut();
+        unsafe {
+            assert_eq!(
+                create_context(&mut context, &mut desc.header, &cb),
+                FFX_API_RETURN_OK
+            );
+            assert!(!context.is_null());
+            assert_eq!(counter.allocs.load(Ordering::SeqCst), 1);
+            assert_eq!(counter.frees.load(Ordering::SeqCst), 0);
+
+            assert_eq!(destroy_context(&mut context, &cb), FFX_API_RETURN_OK);
+        }
+        assert!(context.is_null());
+        assert_eq!(counter.allocs.load(Ordering::SeqCst), 1);
+        assert_eq!(counter.frees.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn debug_checking_stays_in_the_context_block() {
+        let counter = Counter::default();
+        let cb = callbacks(&counter);
+        let mut desc = upscale_desc();
+        desc.flags = FFX_UPSCALE_ENABLE_DEBUG_CHECKING;
+        let mut context: ffxContext = std::ptr::null_mut();
+        unsafe {
+            assert_eq!(
+                create_context(&mut context, &mut desc.header, &cb),
+                FFX_API_RETURN_OK
+            );
+            assert!((*(context as *mut OxrContext)).validator.is_some());
+            assert_eq!(counter.allocs.load(Ordering::SeqCst), 1);
+            assert_eq!(destroy_context(&mut context, &cb), FFX_API_RETURN_OK);
+        }
+        assert_eq!(counter.frees.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn destroy_with_other_callbacks_frees_with_original() {
+        let counter = Counter::default();
+        let cb = callbacks(&counter);
+        let mut desc = upscale_desc();
+        let mut context: ffxContext = std::ptr::null_mut();
+        unsafe {
+            assert_eq!(
+                create_context(&mut context, &mut desc.header, &cb),
+                FFX_API_RETURN_OK
+            );
+            assert_eq!(
+                destroy_context(&mut context, std::ptr::null()),
+                FFX_API_RETURN_ERROR_PARAMETER
+            );
+        }
+        assert!(context.is_null());
+        assert_eq!(counter.frees.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn failed_allocation_is_a_memory_error() {
+        let counter = Counter {
+            fail: true,
+            ..Default::default()
+        };
+        let cb = callbacks(&counter);
+        let mut desc = upscale_desc();
+        let mut context: ffxContext = std::ptr::null_mut();
+        unsafe {
+            assert_eq!(
+                create_context(&mut context, &mut desc.header, &cb),
+                FFX_API_RETURN_ERROR_MEMORY
+            );
+        }
+        assert!(context.is_null());
+    }
+
+    #[test]
+    fn misaligned_block_is_handed_back() {
+        let counter = Counter {
+            misalign: true,
+            ..Default::default()
+        };
+        let cb = callbacks(&counter);
+        unsafe {
+            let allocator = ContextAllocator::from_callbacks(&cb).unwrap();
+            assert!(allocator.alloc(Layout::new::<u64>()).is_null());
+        }
+        assert_eq!(counter.allocs.load(Ordering::SeqCst), 1);
+        assert_eq!(counter.frees.load(Ordering::SeqCst), 1);
+    }
 }
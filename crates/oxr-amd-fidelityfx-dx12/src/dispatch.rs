@@ -1,9 +1,12 @@
+use crate::context::OxrContext;
+use crate::dynres;
+use crate::reactive;
 use fsr_sys::*;
-use tracing::{info, warn};
+use tracing::{error, info, warn};
 use windows::Win32::Graphics::Direct3D12::*;
 
 pub unsafe fn handle_dispatch(
-    _context: *mut ffxContext,
+    context: *mut ffxContext,
     desc: *const ffxDispatchDescHeader,
 ) -> ffxReturnCode_t {
     if desc.is_null() {
@@ -13,11 +16,10 @@ pub unsafe fn handle_dispatch(
     let type_ = (*desc).type_;
 
     match type_ {
-        FFX_API_DISPATCH_DESC_TYPE_UPSCALE => dispatch_upscale(desc),
-        FFX_API_DISPATCH_DESC_TYPE_UPSCALE_GENERATEREACTIVEMASK => {
-            info!("ffxDispatch: GenerateReactiveMask (no-op passthrough)");
-            FFX_API_RETURN_OK
-        }
+        FFX_API_DISPATCH_DESC_TYPE_UPSCALE => dispatch_upscale(context, desc),
+        FFX_API_DISPATCH_DESC_TYPE_UPSCALE_GENERATEREACTIVEMASK => reactive::generate(
+            &(&*(desc as *const ffxDispatchDescUpscaleGenerateReactiveMask)).into(),
+        ),
         _ => {
             warn!(type_ = type_, "ffxDispatch: unknown descriptor type");
             FFX_API_RETURN_ERROR_UNKNOWN_DESCTYPE
@@ -25,8 +27,25 @@ pub unsafe fn handle_dispatch(
     }
 }
 
-unsafe fn dispatch_upscale(desc: *const ffxDispatchDescHeader) -> ffxReturnCode_t {
-    let d = &*(desc as *const ffxDispatchDescUpscale);
+unsafe fn dispatch_upscale(
+    context: *mut ffxContext,
+    desc: *const ffxDispatchDescHeader,
+) -> ffxReturnCode_t {
+    let d = &UpscaleFrame::from(&*(desc as *const ffxDispatchDescUpscale));
+
+    if !context.is_null() && !(*context).is_null() {
+        let ctx = &mut *(*context as *mut OxrContext);
+        dynres::update(ctx, d);
+        if let Some(validator) = ctx.validator.as_mut() {
+            for v in validator.check(d) {
+                if v.code.is_warning() {
+                    warn!(code = %v.code.id(), "ffxDispatch: validation: {}", v.detail);
+                } else {
+                    error!(code = %v.code.id(), "ffxDispatch: validation: {}", v.detail);
+                }
+            }
+        }
+    }
 
     info!(
         render_size = format_args!("{}x{}", d.render_size.width, d.render_size.height),
@@ -87,16 +106,7 @@ unsafe fn dispatch_upscale(desc: *const ffxDispatchDescHeader) -> ffxReturnCode_
     // CopyResource requires identical dimensions and silently fails when sizes differ
     // (e.g. 1080p render target into a 4K output buffer).  Use CopyTextureRegion
     // instead so only the rendered region is copied.
-    let src_w = if d.render_size.width > 0 {
-        d.render_size.width
-    } else {
-        d.color.description.width
-    };
-    let src_h = if d.render_size.height > 0 {
-        d.render_size.height
-    } else {
-        d.color.description.height
-    };
+    let (src_w, src_h) = d.effective_render_size();
 
     // Build copy locations without AddRef (transmute_copy borrows the pointer).
     let src_loc = D3D12_TEXTURE_COPY_LOCATION {
@@ -151,7 +161,7 @@ unsafe fn dispatch_upscale(desc: *const ffxDispatchDescHeader) -> ffxReturnCode_
 }
 
 /// Map FFX resource state to D3D12 resource state.
-fn ffx_state_to_d3d12(state: u32) -> D3D12_RESOURCE_STATES {
+pub(crate) fn ffx_state_to_d3d12(state: u32) -> D3D12_RESOURCE_STATES {
     let mut d3d_state = D3D12_RESOURCE_STATES(0);
 
     if state & FFX_API_RESOURCE_STATE_UNORDERED_ACCESS != 0 {
@@ -198,7 +208,7 @@ fn resource_barrier_transition(
 }
 
 /// Build a transition barrier between two D3D12 states, returning None if they're equal.
-fn resource_barrier_transition_d3d12(
+pub(crate) fn resource_barrier_transition_d3d12(
     resource: &ID3D12Resource,
     state_before: D3D12_RESOURCE_STATES,
     state_after: D3D12_RESOURCE_STATES,
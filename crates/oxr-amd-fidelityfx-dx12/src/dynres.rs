@@ -0,0 +1,56 @@
+//! Dynamic resolution for contexts created with
+//! `FFX_UPSCALE_ENABLE_DYNAMIC_RESOLUTION`; see `fsr_sys::dynres`.
+//!
+//! Each such context runs its own controller, and only render-resolution
+//! queries made against that context see its ratio.
+
+use fsr_sys::dynres::DynResController;
+use fsr_sys::*;
+use tracing::info;
+
+use crate::context::OxrContext;
+use crate::settings;
+
+/// The `OxrContext` behind `context`, if it opted into dynamic resolution.
+unsafe fn dynamic_context(context: *mut ffxContext) -> Option<&'static OxrContext> {
+    if context.is_null() || (*context).is_null() {
+        return None;
+    }
+    let ctx = &*(*context as *const OxrContext);
+    (ctx.flags & FFX_UPSCALE_ENABLE_DYNAMIC_RESOLUTION != 0).then_some(ctx)
+}
+
+/// Step `ctx`'s controller with one dispatch's frame time. No-op unless
+/// enabled in `oxr.ini` and requested by the context.
+pub fn update(ctx: &mut OxrContext, d: &UpscaleFrame) {
+    let Some(config) = settings::get().dynamic_resolution else {
+        return;
+    };
+    if ctx.flags & FFX_UPSCALE_ENABLE_DYNAMIC_RESOLUTION == 0 {
+        return;
+    }
+    let controller = ctx.dynres.get_or_insert_with(|| {
+        info!(?config, "dynres: controller started");
+        DynResController::new(config)
+    });
+    let decision = controller.update(d.frame_time_delta);
+    if decision.changed {
+        let (w, h) = controller.render_resolution(d.upscale_size.width, d.upscale_size.height);
+        info!(
+            ratio = decision.ratio,
+            smoothed_ms = decision.smoothed_frame_ms,
+            target_ms = config.target_frame_ms,
+            render = format_args!("{}x{}", w, h),
+            "dynres: ratio changed"
+        );
+    }
+}
+
+/// Ratio to propose from render-resolution queries made against `context`.
+pub unsafe fn active_ratio(context: *mut ffxContext) -> Option<f32> {
+    settings::get().dynamic_resolution?;
+    dynamic_context(context)?
+        .dynres
+        .as_ref()
+        .map(DynResController::ratio)
+}
@@ -2,8 +2,11 @@
 
 mod context;
 mod dispatch;
+mod dynres;
 mod logging;
 mod query;
+mod reactive;
+mod settings;
 
 use fsr_sys::*;
 use tracing::info;
@@ -15,6 +18,7 @@ unsafe extern "system" fn DllMain(_hinst: HINSTANCE, call_reason: u32, _reserved
     match call_reason {
         DLL_PROCESS_ATTACH => {
             logging::init();
+            settings::init();
             info!("OXR upscaler proxy loaded (passthrough mode)");
             true
         }
@@ -1,3 +1,4 @@
+use fsr_sys::message::GameMessageLayer;
 use std::path::PathBuf;
 use std::sync::Once;
 use tracing_appender::non_blocking::WorkerGuard;
@@ -28,6 +29,7 @@ pub unsafe fn init() {
                     .with_ansi(false)
                     .with_target(false),
             )
+            .with(GameMessageLayer)
             .init();
 
         // Leak the guard so the writer stays alive for the process lifetime.
@@ -36,7 +38,7 @@ pub unsafe fn init() {
 }
 
 /// Returns the directory containing the loaded DLL.
-fn dll_directory() -> Option<PathBuf> {
+pub(crate) fn dll_directory() -> Option<PathBuf> {
     use windows::Win32::System::LibraryLoader::GetModuleFileNameW;
 
     let mut buf = vec![0u16; 512];
@@ -1,8 +1,12 @@
+use fsr_sys::jitter;
 use fsr_sys::*;
 use tracing::{info, warn};
 
+use crate::dynres;
+use crate::settings;
+
 pub unsafe fn handle_query(
-    _context: *mut ffxContext,
+    context: *mut ffxContext,
     desc: *mut ffxQueryDescHeader,
 ) -> ffxReturnCode_t {
     if desc.is_null() {
@@ -14,7 +18,7 @@ pub unsafe fn handle_query(
     match type_ {
         FFX_API_QUERY_DESC_TYPE_UPSCALE_GETUPSCALERATIOFROMQUALITYMODE => query_upscale_ratio(desc),
         FFX_API_QUERY_DESC_TYPE_UPSCALE_GETRENDERRESOLUTIONFROMQUALITYMODE => {
-            query_render_resolution(desc)
+            query_render_resolution(context, desc)
         }
         FFX_API_QUERY_DESC_TYPE_UPSCALE_GETJITTERPHASECOUNT => query_jitter_phase_count(desc),
         FFX_API_QUERY_DESC_TYPE_UPSCALE_GETJITTEROFFSET => query_jitter_offset(desc),
@@ -58,21 +62,17 @@ unsafe fn query_get_versions(desc: *mut ffxQueryDescHeader) -> ffxReturnCode_t {
     FFX_API_RETURN_OK
 }
 
-/// Standard FSR upscale ratios per quality mode.
-fn upscale_ratio_for_mode(quality_mode: u32) -> f32 {
-    match quality_mode {
-        FFX_UPSCALE_QUALITY_MODE_NATIVEAA => 1.0,
-        FFX_UPSCALE_QUALITY_MODE_QUALITY => 1.5,
-        FFX_UPSCALE_QUALITY_MODE_BALANCED => 1.7,
-        FFX_UPSCALE_QUALITY_MODE_PERFORMANCE => 2.0,
-        FFX_UPSCALE_QUALITY_MODE_ULTRA_PERFORMANCE => 3.0,
-        _ => 1.0,
-    }
-}
-
+/// Per-mode ratio from the quality table; dynamic resolution only changes
+/// the render-resolution answer of the context it controls.
 unsafe fn query_upscale_ratio(desc: *mut ffxQueryDescHeader) -> ffxReturnCode_t {
     let d = &*(desc as *const ffxQueryDescUpscaleGetUpscaleRatioFromQualityMode);
-    let ratio = upscale_ratio_for_mode(d.quality_mode);
+    let Some(ratio) = settings::get().quality.ratio(d.quality_mode) else {
+        warn!(
+            quality_mode = d.quality_mode,
+            "ffxQuery: unknown quality mode"
+        );
+        return FFX_API_RETURN_ERROR_PARAMETER;
+    };
 
     info!(
         quality_mode = d.quality_mode,
@@ -87,12 +87,28 @@ unsafe fn query_upscale_ratio(desc: *mut ffxQueryDescHeader) -> ffxReturnCode_t
     FFX_API_RETURN_OK
 }
 
-unsafe fn query_render_resolution(desc: *mut ffxQueryDescHeader) -> ffxReturnCode_t {
+unsafe fn query_render_resolution(
+    context: *mut ffxContext,
+    desc: *mut ffxQueryDescHeader,
+) -> ffxReturnCode_t {
     let d = &*(desc as *const ffxQueryDescUpscaleGetRenderResolutionFromQualityMode);
-    let ratio = upscale_ratio_for_mode(d.quality_mode);
-
-    let render_w = (d.display_width as f32 / ratio).round() as u32;
-    let render_h = (d.display_height as f32 / ratio).round() as u32;
+    let quality = &settings::get().quality;
+    let Some((mut render_w, mut render_h)) =
+        quality.render_resolution(d.display_width, d.display_height, d.quality_mode)
+    else {
+        warn!(
+            quality_mode = d.quality_mode,
+            "ffxQuery: unknown quality mode"
+        );
+        return FFX_API_RETURN_ERROR_PARAMETER;
+    };
+    // A forced native / AA mode keeps the display size
+    if let Some(ratio) =
+        dynres::active_ratio(context).filter(|_| !quality.is_native(d.quality_mode))
+    {
+        render_w = ((d.display_width as f32 / ratio) as u32).max(1);
+        render_h = ((d.display_height as f32 / ratio) as u32).max(1);
+    }
 
     info!(
         display = format_args!("{}x{}", d.display_width, d.display_height),
@@ -114,13 +130,7 @@ unsafe fn query_render_resolution(desc: *mut ffxQueryDescHeader) -> ffxReturnCod
 unsafe fn query_jitter_phase_count(desc: *mut ffxQueryDescHeader) -> ffxReturnCode_t {
     let d = &*(desc as *const ffxQueryDescUpscaleGetJitterPhaseCount);
 
-    // Standard FSR formula: ceil(8 * ratio^2) where ratio = displayWidth / renderWidth
-    let ratio = if d.render_width > 0 {
-        d.display_width as f32 / d.render_width as f32
-    } else {
-        1.0
-    };
-    let phase_count = (8.0 * ratio * ratio).ceil() as i32;
+    let phase_count = jitter::phase_count(d.render_width as i32, d.display_width as i32);
 
     info!(
         render_width = d.render_width,
@@ -139,7 +149,7 @@ unsafe fn query_jitter_phase_count(desc: *mut ffxQueryDescHeader) -> ffxReturnCo
 unsafe fn query_jitter_offset(desc: *mut ffxQueryDescHeader) -> ffxReturnCode_t {
     let d = &*(desc as *const ffxQueryDescUpscaleGetJitterOffset);
 
-    let (x, y) = halton_jitter(d.index, d.phase_count);
+    let (x, y) = jitter::offset(d.index, d.phase_count);
 
     info!(
         index = d.index,
@@ -158,29 +168,3 @@ unsafe fn query_jitter_offset(desc: *mut ffxQueryDescHeader) -> ffxReturnCode_t
 
     FFX_API_RETURN_OK
 }
-
-/// Halton sequence jitter (bases 2 and 3), centered around 0.
-fn halton_jitter(index: i32, phase_count: i32) -> (f32, f32) {
-    if phase_count <= 0 {
-        return (0.0, 0.0);
-    }
-    let i = ((index % phase_count) + phase_count) as u32;
-    let x = halton(i + 1, 2) - 0.5;
-    let y = halton(i + 1, 3) - 0.5;
-    (x, y)
-}
-
-/// Halton sequence value for a given index and base.
-fn halton(mut index: u32, base: u32) -> f32 {
-    let mut f = 1.0f32;
-    let mut r = 0.0f32;
-    let inv_base = 1.0 / base as f32;
-
-    while index > 0 {
-        f *= inv_base;
-        r += f * (index % base) as f32;
-        index /= base;
-    }
-
-    r
-}
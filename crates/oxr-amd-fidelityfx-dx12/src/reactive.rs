@@ -0,0 +1,325 @@
+//! Native `FFX_API_DISPATCH_DESC_TYPE_UPSCALE_GENERATEREACTIVEMASK`.
+//!
+//! Runs the same `reactive_mask_cs.hlsl` as the legacy proxy (see
+//! `fsr_sys::reactive` for the CPU reference). The proxy has no other GPU
+//! pipeline, so the compute root signature, PSO and a three-slot descriptor
+//! heap are created here on first use.
+
+use crate::dispatch::{ffx_state_to_d3d12, resource_barrier_transition_d3d12};
+use fsr_sys::reactive::ReactiveMaskFrame;
+use fsr_sys::*;
+use std::sync::OnceLock;
+use tracing::{error, info, warn};
+use windows::Win32::Graphics::Direct3D::ID3DBlob;
+use windows::Win32::Graphics::Direct3D12::*;
+use windows::Win32::Graphics::Dxgi::Common::*;
+
+const REACTIVE_MASK_CS_DXIL: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/reactive_mask_cs.dxil"));
+
+// Slots 0-1: SRV table (opaque, color), Slot 2: UAV (out reactive)
+const SLOT_OPAQUE: u32 = 0;
+const SLOT_COLOR: u32 = 1;
+const SLOT_OUT: u32 = 2;
+
+const GROUP_SIZE: u32 = 8;
+
+struct ReactiveGpu {
+    device: ID3D12Device,
+    root_signature: ID3D12RootSignature,
+    pso: ID3D12PipelineState,
+    heap: ID3D12DescriptorHeap,
+    descriptor_size: u32,
+}
+
+// Stores Option so init failure doesn't retry every frame.
+static GPU: OnceLock<Option<ReactiveGpu>> = OnceLock::new();
+
+unsafe fn get_or_init(cmd_list: &ID3D12GraphicsCommandList) -> Option<&'static ReactiveGpu> {
+    GPU.get_or_init(|| match try_init(cmd_list) {
+        Ok(gpu) => Some(gpu),
+        Err(e) => {
+            error!("reactive: init failed: {}", e);
+            None
+        }
+    })
+    .as_ref()
+}
+
+unsafe fn try_init(cmd_list: &ID3D12GraphicsCommandList) -> Result<ReactiveGpu, String> {
+    let mut device: Option<ID3D12Device> = None;
+    cmd_list
+        .GetDevice(&mut device)
+        .map_err(|e| format!("GetDevice failed: {}", e))?;
+    let device = device.ok_or_else(|| "GetDevice returned null".to_string())?;
+
+    let root_signature = create_root_signature(&device)?;
+
+    let pso_desc = D3D12_COMPUTE_PIPELINE_STATE_DESC {
+        pRootSignature: std::mem::transmute_copy(&root_signature),
+        CS: D3D12_SHADER_BYTECODE {
+            pShaderBytecode: REACTIVE_MASK_CS_DXIL.as_ptr() as *const _,
+            BytecodeLength: REACTIVE_MASK_CS_DXIL.len(),
+        },
+        ..Default::default()
+    };
+    let pso = device
+        .CreateComputePipelineState(&pso_desc)
+        .map_err(|e| format!("CreateComputePipelineState failed: {}", e))?;
+
+    let heap_desc = D3D12_DESCRIPTOR_HEAP_DESC {
+        Type: D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
+        NumDescriptors: 3,
+        Flags: D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE,
+        NodeMask: 0,
+    };
+    let heap: ID3D12DescriptorHeap = device
+        .CreateDescriptorHeap(&heap_desc)
+        .map_err(|e| format!("CreateDescriptorHeap failed: {}", e))?;
+    let descriptor_size =
+        device.GetDescriptorHandleIncrementSize(D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV);
+
+    info!("reactive: GPU state created");
+    Ok(ReactiveGpu {
+        device,
+        root_signature,
+        pso,
+        heap,
+        descriptor_size,
+    })
+}
+
+unsafe fn create_root_signature(device: &ID3D12Device) -> Result<ID3D12RootSignature, String> {
+    // [0] 8 root constants (b0), [1] SRV table t0-t1, [2] UAV table u0
+    let srv_range = D3D12_DESCRIPTOR_RANGE {
+        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+        NumDescriptors: 2,
+        BaseShaderRegister: 0,
+        RegisterSpace: 0,
+        OffsetInDescriptorsFromTableStart: 0,
+    };
+    let uav_range = D3D12_DESCRIPTOR_RANGE {
+        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_UAV,
+        NumDescriptors: 1,
+        BaseShaderRegister: 0,
+        RegisterSpace: 0,
+        OffsetInDescriptorsFromTableStart: 0,
+    };
+    let table = |range: &D3D12_DESCRIPTOR_RANGE| D3D12_ROOT_PARAMETER {
+        ParameterType: D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
+        Anonymous: D3D12_ROOT_PARAMETER_0 {
+            DescriptorTable: D3D12_ROOT_DESCRIPTOR_TABLE {
+                NumDescriptorRanges: 1,
+                pDescriptorRanges: range,
+            },
+        },
+        ShaderVisibility: D3D12_SHADER_VISIBILITY_ALL,
+    };
+    let params = [
+        D3D12_ROOT_PARAMETER {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_32BIT_CONSTANTS,
+            Anonymous: D3D12_ROOT_PARAMETER_0 {
+                Constants: D3D12_ROOT_CONSTANTS {
+                    ShaderRegister: 0,
+                    RegisterSpace: 0,
+                    Num32BitValues: 8,
+                },
+            },
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_ALL,
+        },
+        table(&srv_range),
+        table(&uav_range),
+    ];
+
+    let desc = D3D12_ROOT_SIGNATURE_DESC {
+        NumParameters: params.len() as u32,
+        pParameters: params.as_ptr(),
+        NumStaticSamplers: 0,
+        pStaticSamplers: std::ptr::null(),
+        Flags: D3D12_ROOT_SIGNATURE_FLAG_NONE,
+    };
+
+    let mut blob: Option<ID3DBlob> = None;
+    let mut error_blob: Option<ID3DBlob> = None;
+    if let Err(e) = D3D12SerializeRootSignature(
+        &desc,
+        D3D_ROOT_SIGNATURE_VERSION_1,
+        &mut blob,
+        Some(&mut error_blob),
+    ) {
+        if let Some(err_blob) = &error_blob {
+            let err_msg = String::from_utf8_lossy(std::slice::from_raw_parts(
+                err_blob.GetBufferPointer() as *const u8,
+                err_blob.GetBufferSize(),
+            ));
+            error!(
+                "reactive: D3D12SerializeRootSignature error: {}",
+                err_msg.trim_end_matches('\0')
+            );
+        }
+        return Err(format!("D3D12SerializeRootSignature failed: {}", e));
+    }
+    let blob = blob.ok_or_else(|| "D3D12SerializeRootSignature produced no blob".to_string())?;
+
+    device
+        .CreateRootSignature(
+            0,
+            std::slice::from_raw_parts(blob.GetBufferPointer() as *const u8, blob.GetBufferSize()),
+        )
+        .map_err(|e| format!("CreateRootSignature failed: {}", e))
+}
+
+/// Typed view format for a game resource (typeless formats are read as their
+/// float/unorm variant).
+fn view_format(resource: &ID3D12Resource) -> DXGI_FORMAT {
+    match unsafe { resource.GetDesc() }.Format {
+        DXGI_FORMAT_R8G8B8A8_TYPELESS => DXGI_FORMAT_R8G8B8A8_UNORM,
+        DXGI_FORMAT_B8G8R8A8_TYPELESS => DXGI_FORMAT_B8G8R8A8_UNORM,
+        DXGI_FORMAT_R10G10B10A2_TYPELESS => DXGI_FORMAT_R10G10B10A2_UNORM,
+        DXGI_FORMAT_R16G16B16A16_TYPELESS => DXGI_FORMAT_R16G16B16A16_FLOAT,
+        DXGI_FORMAT_R32G32B32A32_TYPELESS => DXGI_FORMAT_R32G32B32A32_FLOAT,
+        DXGI_FORMAT_R8_TYPELESS => DXGI_FORMAT_R8_UNORM,
+        DXGI_FORMAT_R16_TYPELESS => DXGI_FORMAT_R16_FLOAT,
+        DXGI_FORMAT_R32_TYPELESS => DXGI_FORMAT_R32_FLOAT,
+        other => other,
+    }
+}
+
+fn borrow(raw: *mut core::ffi::c_void) -> Option<ID3D12Resource> {
+    if raw.is_null() {
+        return None;
+    }
+    unsafe { windows::core::Interface::from_raw_borrowed(&raw) }.cloned()
+}
+
+/// Record the reactive mask pass into the game's command list.
+pub unsafe fn generate(d: &ReactiveMaskFrame) -> ffxReturnCode_t {
+    let Some(cmd_list) =
+        <ID3D12GraphicsCommandList as windows::core::Interface>::from_raw_borrowed(&d.command_list)
+            .cloned()
+    else {
+        warn!("GenerateReactiveMask: null command list");
+        return FFX_API_RETURN_ERROR_PARAMETER;
+    };
+    let (Some(opaque), Some(color), Some(out)) = (
+        borrow(d.color_opaque_only.resource),
+        borrow(d.color_pre_upscale.resource),
+        borrow(d.out_reactive.resource),
+    ) else {
+        warn!("GenerateReactiveMask: null opaque, color or output resource");
+        return FFX_API_RETURN_ERROR_PARAMETER;
+    };
+    let (w, h) = d.effective_render_size();
+    if w == 0 || h == 0 {
+        warn!("GenerateReactiveMask: zero render size");
+        return FFX_API_RETURN_ERROR_PARAMETER;
+    }
+    let Some(gpu) = get_or_init(&cmd_list) else {
+        return FFX_API_RETURN_ERROR_RUNTIME_ERROR;
+    };
+
+    let states = [
+        (
+            &opaque,
+            ffx_state_to_d3d12(d.color_opaque_only.state),
+            D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE,
+        ),
+        (
+            &color,
+            ffx_state_to_d3d12(d.color_pre_upscale.state),
+            D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE,
+        ),
+        (
+            &out,
+            ffx_state_to_d3d12(d.out_reactive.state),
+            D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+        ),
+    ];
+    let before: Vec<_> = states
+        .iter()
+        .filter_map(|&(res, from, to)| resource_barrier_transition_d3d12(res, from, to))
+        .collect();
+    if !before.is_empty() {
+        cmd_list.ResourceBarrier(&before);
+    }
+
+    let cpu = |slot: u32| D3D12_CPU_DESCRIPTOR_HANDLE {
+        ptr: gpu.heap.GetCPUDescriptorHandleForHeapStart().ptr
+            + (slot * gpu.descriptor_size) as usize,
+    };
+    let gpu_handle = |slot: u32| D3D12_GPU_DESCRIPTOR_HANDLE {
+        ptr: gpu.heap.GetGPUDescriptorHandleForHeapStart().ptr
+            + (slot * gpu.descriptor_size) as u64,
+    };
+    for (res, slot) in [(&opaque, SLOT_OPAQUE), (&color, SLOT_COLOR)] {
+        let srv = D3D12_SHADER_RESOURCE_VIEW_DESC {
+            Format: view_format(res),
+            ViewDimension: D3D12_SRV_DIMENSION_TEXTURE2D,
+            Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+            Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                Texture2D: D3D12_TEX2D_SRV {
+                    MostDetailedMip: 0,
+                    MipLevels: 1,
+                    PlaneSlice: 0,
+                    ResourceMinLODClamp: 0.0,
+                },
+            },
+        };
+        gpu.device
+            .CreateShaderResourceView(res, Some(&srv), cpu(slot));
+    }
+    let uav = D3D12_UNORDERED_ACCESS_VIEW_DESC {
+        Format: view_format(&out),
+        ViewDimension: D3D12_UAV_DIMENSION_TEXTURE2D,
+        Anonymous: D3D12_UNORDERED_ACCESS_VIEW_DESC_0 {
+            Texture2D: D3D12_TEX2D_UAV {
+                MipSlice: 0,
+                PlaneSlice: 0,
+            },
+        },
+    };
+    gpu.device
+        .CreateUnorderedAccessView(&out, None, Some(&uav), cpu(SLOT_OUT));
+
+    let p = &d.params;
+    let constants = [
+        w,
+        h,
+        p.scale.to_bits(),
+        p.cutoff_threshold.to_bits(),
+        p.binary_value.to_bits(),
+        p.flags,
+        0,
+        0,
+    ];
+    cmd_list.SetDescriptorHeaps(&[Some(gpu.heap.clone())]);
+    cmd_list.SetComputeRootSignature(&gpu.root_signature);
+    cmd_list.SetPipelineState(&gpu.pso);
+    cmd_list.SetComputeRoot32BitConstants(
+        0,
+        constants.len() as u32,
+        constants.as_ptr() as *const core::ffi::c_void,
+        0,
+    );
+    cmd_list.SetComputeRootDescriptorTable(1, gpu_handle(SLOT_OPAQUE));
+    cmd_list.SetComputeRootDescriptorTable(2, gpu_handle(SLOT_OUT));
+    cmd_list.Dispatch(w.div_ceil(GROUP_SIZE), h.div_ceil(GROUP_SIZE), 1);
+
+    let after: Vec<_> = states
+        .iter()
+        .filter_map(|&(res, from, to)| resource_barrier_transition_d3d12(res, to, from))
+        .collect();
+    if !after.is_empty() {
+        cmd_list.ResourceBarrier(&after);
+    }
+
+    info!(
+        size = format_args!("{}x{}", w, h),
+        scale = p.scale,
+        cutoff = p.cutoff_threshold,
+        binary = p.binary_value,
+        flags = p.flags,
+        "ffxDispatch: GenerateReactiveMask"
+    );
+    FFX_API_RETURN_OK
+}
@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use fsr_sys::dynres::DynResConfig;
+use fsr_sys::quality::QualityTable;
+use ini::Ini;
+use tracing::{info, warn};
+
+use crate::logging;
+
+/// `oxr.ini` values this proxy honours. Shares the file with
+/// `ffx_fsr3upscaler_x64.dll`, which reads the remaining sections.
+pub struct Settings {
+    /// `[quality]` — per-mode upscale ratios, `custom_scale` and `force_native`.
+    pub quality: QualityTable,
+    /// `[dynamic_resolution]` — controller config, `None` unless `enabled`.
+    pub dynamic_resolution: Option<DynResConfig>,
+    /// `[validation] enabled` — validate dispatches even without
+    /// `FFX_UPSCALE_ENABLE_DEBUG_CHECKING`.
+    pub validation: bool,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+pub fn init() {
+    SETTINGS.get_or_init(load);
+}
+
+pub fn get() -> &'static Settings {
+    SETTINGS.get_or_init(load)
+}
+
+fn load() -> Settings {
+    let dll_dir = logging::dll_directory().unwrap_or_else(|| PathBuf::from("."));
+    let ini = match Ini::load_from_file_opt(
+        dll_dir.join("oxr.ini"),
+        ini::ParseOption {
+            enabled_escape: false,
+            ..Default::default()
+        },
+    ) {
+        Ok(ini) => Some(ini),
+        Err(_) => {
+            info!("settings: oxr.ini not found, using defaults");
+            None
+        }
+    };
+    let get = |section: &str, key: &str| {
+        ini.as_ref()
+            .and_then(|ini| ini.section(Some(section)))
+            .and_then(|s| s.get(key))
+            .filter(|v| !v.is_empty())
+    };
+
+    let validation = get("validation", "enabled").is_some_and(parse_bool);
+    let (quality, rejected) = QualityTable::parse(|key| get("quality", key));
+    for (key, value) in rejected {
+        warn!("settings: ignoring [quality] {} = {:?}", key, value);
+    }
+    let (dynamic_resolution, rejected) = DynResConfig::parse(|key| get("dynamic_resolution", key));
+    for (key, value) in rejected {
+        warn!(
+            "settings: ignoring [dynamic_resolution] {} = {:?}",
+            key, value
+        );
+    }
+
+    info!("settings: quality = {:?}", quality);
+    info!("settings: dynamic_resolution = {:?}", dynamic_resolution);
+    info!("settings: validation = {}", validation);
+    Settings {
+        quality,
+        dynamic_resolution,
+        validation,
+    }
+}
+
+fn parse_bool(v: &str) -> bool {
+    matches!(
+        v.trim().to_ascii_lowercase().as_str(),
+        "1" | "true" | "yes" | "on"
+    )
+}
@@ -23,6 +23,21 @@ fn main() {
         ("SGSRv2/3Pass/sgsr2_3p_upscale_ps.hlsl", "PS", "ps_6_2"),
         ("imgui_vs.hlsl", "VS", "vs_6_2"),
         ("imgui_ps.hlsl", "PS", "ps_6_2"),
+        ("reactive_mask_cs.hlsl", "main", "cs_6_2"),
+        ("reactive_blend_cs.hlsl", "main", "cs_6_2"),
+        ("input_normalize_cs.hlsl", "main", "cs_6_2"),
+        ("exposure_histogram_cs.hlsl", "main", "cs_6_2"),
+        ("exposure_resolve_cs.hlsl", "main", "cs_6_2"),
+        ("scene_stats_cs.hlsl", "main", "cs_6_2"),
+        // IMBA's upscaling output pass; runs on the AA root signature
+        ("imba_upscale_out_cs.hlsl", "main", "cs_6_2"),
+        // Overlay view of one IMBA feature channel; same root signature
+        ("imba_debug_cs.hlsl", "main", "cs_6_2"),
+        // IMBA's input passes for render sizes padded to the network's alignment
+        ("imba_unshuffle_padded_cs.hlsl", "main", "cs_6_2"),
+        ("imba_scale_mv_padded_cs.hlsl", "main", "cs_6_2"),
+        // IMBA's history rejection from the game's masks, before each attention
+        ("imba_history_mask_cs.hlsl", "main", "cs_6_2"),
     ];
 
     // AA compute shaders (filename, entry, profile) — read from aa_shader_dir
@@ -0,0 +1,280 @@
+//! Per-context state for the legacy FSR3 exports.
+//!
+//! Games may create several `FfxFsr3UpscalerContext`s (split-screen, resolution
+//! change, photo mode). Everything derived from a context description lives here,
+//! keyed by the context pointer; GPU history owned by the upscalers is keyed the
+//! same way and released together in [`destroy`].
+//!
+//! Released GPU state is not dropped at once: the game may still be executing
+//! command lists that reference it. See [`retire`].
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use fsr_sys::dynres::DynResController;
+use fsr_sys::validate::{ValidationLimits, Validator};
+use tracing::{info, warn};
+
+use crate::exposure;
+use crate::fsr3_types::*;
+use crate::gpu_pipeline;
+use crate::input_normalize;
+use crate::post_processing;
+use crate::scene_cut;
+use crate::settings;
+use crate::upscalers;
+
+/// Identifies a game context: the address of its `FfxFsr3UpscalerContext`.
+pub type ContextKey = usize;
+
+pub fn key(ctx: *const FfxFsr3UpscalerContext) -> ContextKey {
+    ctx as ContextKey
+}
+
+#[allow(dead_code)]
+pub struct ContextState {
+    pub max_render_size: FfxDimensions2D,
+    pub max_upscale_size: FfxDimensions2D,
+    pub flags: u32,
+    /// Enabled by `EnableDebugChecking` or `[validation] enabled`.
+    pub validator: Option<Validator>,
+    /// Dispatches recorded for this context.
+    pub frames: u64,
+    /// Dynamic resolution controller, started by the first dispatch of a
+    /// context that opted in; see `dynres`.
+    pub dynres: Option<DynResController>,
+    /// Display size the controller was last stepped at; its ratio is only
+    /// proposed to render-resolution queries for this size.
+    pub dynres_display_size: (u32, u32),
+}
+
+static CONTEXTS: Mutex<Option<HashMap<ContextKey, ContextState>>> = Mutex::new(None);
+
+/// Register a context from its creation description. Replaces any stale entry
+/// left at the same address.
+pub fn create(key: ContextKey, desc: &FfxFsr3UpscalerContextDescription) {
+    let flags = desc.flags;
+    let debug_checking =
+        flags & FfxFsr3UpscalerInitializationFlagBits::EnableDebugChecking as u32 != 0;
+    let validator = (debug_checking || settings::get().validation).then(|| {
+        info!(debug_checking, "context 0x{:x}: validation enabled", key);
+        Validator::new(ValidationLimits {
+            max_render_size: fsr_sys::FfxApiDimensions2D {
+                width: desc.max_render_size.width,
+                height: desc.max_render_size.height,
+            },
+            max_upscale_size: fsr_sys::FfxApiDimensions2D {
+                width: desc.max_upscale_size.width,
+                height: desc.max_upscale_size.height,
+            },
+            depth_inverted: flags
+                & FfxFsr3UpscalerInitializationFlagBits::EnableDepthInverted as u32
+                != 0,
+        })
+    });
+
+    let mut guard = CONTEXTS.lock().unwrap_or_else(PoisonError::into_inner);
+    let map = guard.get_or_insert_with(HashMap::new);
+    let stale = map
+        .insert(
+            key,
+            ContextState {
+                max_render_size: desc.max_render_size,
+                max_upscale_size: desc.max_upscale_size,
+                flags,
+                validator,
+                frames: 0,
+                dynres: None,
+                dynres_display_size: (0, 0),
+            },
+        )
+        .is_some();
+    let live = map.len();
+    drop(guard);
+    // The new entry has no GPU state yet; whatever is keyed here is the stale one's
+    if stale {
+        warn!("context 0x{:x}: created twice without destroy", key);
+        release_gpu_state(key);
+    }
+    info!("context 0x{:x}: registered ({} live)", key, live);
+}
+
+/// Drop a context and every upscaler resource keyed to it.
+/// Returns the number of contexts still alive.
+pub fn destroy(key: ContextKey) -> usize {
+    let mut guard = CONTEXTS.lock().unwrap_or_else(PoisonError::into_inner);
+    let map = guard.get_or_insert_with(HashMap::new);
+    let known = map.remove(&key).is_some();
+    let live = map.len();
+    drop(guard);
+    if !known {
+        warn!("context 0x{:x}: destroy for unknown context", key);
+    }
+    release_gpu_state(key);
+    live
+}
+
+/// Run `f` with the state of `key`. Returns `None` for unknown contexts.
+///
+/// `f` runs under the context lock and must not log warnings or errors:
+/// those are forwarded to the game's message callback, which may re-enter
+/// the proxy. Return what needs reporting and log it afterwards.
+pub fn with<R>(key: ContextKey, f: impl FnOnce(&mut ContextState) -> R) -> Option<R> {
+    CONTEXTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_mut()
+        .and_then(|map| map.get_mut(&key))
+        .map(f)
+}
+
+/// `f` applied to every live context, keeping the `Some` results.
+pub fn filter_map<R>(f: impl Fn(&ContextState) -> Option<R>) -> Vec<R> {
+    CONTEXTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .map_or_else(Vec::new, |map| map.values().filter_map(f).collect())
+}
+
+fn release_gpu_state(key: ContextKey) {
+    input_normalize::release(key);
+    exposure::release(key);
+    scene_cut::release(key);
+    upscalers::release(key);
+    post_processing::release(key);
+    gpu_pipeline::release(key);
+}
+
+// ── Deferred release ─────────────────────────────────────────────────────────
+//
+// The proxy records into the game's command lists and never sees its queue or
+// fences, so it cannot wait for the GPU to finish with a resource. Removed
+// state is parked instead, tagged with the frame clock, and dropped once the
+// clock has moved `RETIRE_AFTER_FRAMES` past it. The clock counts dispatches
+// across all contexts, so it never runs slower than game frames and never
+// rewinds when a context is destroyed and recreated.
+
+/// Frames a removed resource outlives its removal. DXGI queues 3 frames by
+/// default; this leaves room for games that raise the frame latency.
+pub const RETIRE_AFTER_FRAMES: u64 = 8;
+
+struct Retired {
+    frame: u64,
+    _value: Box<dyn Any + Send>,
+}
+
+static FRAME_CLOCK: AtomicU64 = AtomicU64::new(0);
+static RETIRED: Mutex<Vec<Retired>> = Mutex::new(Vec::new());
+
+/// Keep `value` alive until the GPU can no longer be using it.
+pub fn retire<T: Send + 'static>(value: T) {
+    let frame = FRAME_CLOCK.load(Ordering::Relaxed);
+    RETIRED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Retired {
+            frame,
+            _value: Box::new(value),
+        });
+}
+
+/// Count a dispatch of `key` and drop retired state the GPU is done with.
+/// Called at the start of every dispatch, before any GPU work is recorded.
+pub fn begin_frame(key: ContextKey) {
+    with(key, |state| state.frames += 1);
+    let now = FRAME_CLOCK.fetch_add(1, Ordering::Relaxed) + 1;
+    // Dropped outside the lock: COM releases can be slow
+    let expired: Vec<Retired> = {
+        let mut retired = RETIRED.lock().unwrap_or_else(|e| e.into_inner());
+        let (expired, kept) = retired
+            .drain(..)
+            .partition(|r| now >= r.frame + RETIRE_AFTER_FRAMES);
+        *retired = kept;
+        expired
+    };
+    if !expired.is_empty() {
+        info!("context: released {} retired GPU objects", expired.len());
+    }
+}
+
+// ── Per-context storage for upscaler / post-fx resources ────────────────────
+
+/// Map of `T` keyed by context, for module-level `static`s that used to hold a
+/// single `Mutex<Option<T>>`. Replaced and removed entries are [`retire`]d.
+pub struct PerContext<T> {
+    map: Mutex<Option<HashMap<ContextKey, T>>>,
+}
+
+/// Locked access to one context's entry; derefs to `T`.
+pub struct PerContextGuard<'a, T> {
+    guard: MutexGuard<'a, Option<HashMap<ContextKey, T>>>,
+    key: ContextKey,
+}
+
+impl<T: Send + 'static> PerContext<T> {
+    pub const fn new() -> Self {
+        Self {
+            map: Mutex::new(None),
+        }
+    }
+
+    /// Lock the entry for `key`, (re)creating it when missing or when `stale`
+    /// returns true. On creation failure the old entry is removed.
+    pub fn get_or_create<E>(
+        &self,
+        key: ContextKey,
+        stale: impl FnOnce(&T) -> bool,
+        create: impl FnOnce() -> Result<T, E>,
+    ) -> Result<PerContextGuard<'_, T>, E> {
+        let mut guard = self.map.lock().unwrap_or_else(|e| e.into_inner());
+        let map = guard.get_or_insert_with(HashMap::new);
+        if map.get(&key).is_none_or(stale) {
+            if let Some(old) = map.remove(&key) {
+                retire(old);
+            }
+            map.insert(key, create()?);
+        }
+        Ok(PerContextGuard { guard, key })
+    }
+
+    /// Lock the entry for `key` if it exists.
+    pub fn get(&self, key: ContextKey) -> Option<PerContextGuard<'_, T>> {
+        let guard = self.map.lock().unwrap_or_else(|e| e.into_inner());
+        guard
+            .as_ref()
+            .is_some_and(|map| map.contains_key(&key))
+            .then_some(PerContextGuard { guard, key })
+    }
+
+    /// Retire the entry for `key`, if any.
+    pub fn remove(&self, key: ContextKey) {
+        let old = self
+            .map
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_mut()
+            .and_then(|map| map.remove(&key));
+        if let Some(old) = old {
+            retire(old);
+        }
+    }
+}
+
+impl<T> Deref for PerContextGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Entry presence is guaranteed by `get_or_create`.
+        &self.guard.as_ref().unwrap()[&self.key]
+    }
+}
+
+impl<T> DerefMut for PerContextGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.guard.as_mut().unwrap().get_mut(&self.key).unwrap()
+    }
+}
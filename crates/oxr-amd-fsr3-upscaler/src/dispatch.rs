@@ -1,15 +1,21 @@
-use crate::fsr3_types::*;
+use crate::context::{self, ContextKey};
+use crate::exposure;
 use crate::gpu_pipeline;
+use crate::input_normalize;
 use crate::overlay;
 use crate::post_processing::{self, PostContext};
+use crate::reactive_mask;
+use crate::scene_cut;
 use crate::upscaler_type;
 use crate::upscalers::{self, DispatchContext};
+use fsr_sys::inputs::InputFlags;
+use fsr_sys::UpscaleFrame;
 use tracing::{error, warn};
 use windows::Win32::Graphics::Direct3D12::*;
 use windows::Win32::Graphics::Dxgi::Common::*;
 
 /// Main upscale dispatch: extract resources, run upscaler, post-fx chain, overlay.
-pub unsafe fn dispatch_upscale(d: &FfxFsr3UpscalerDispatchDescription) -> u32 {
+pub unsafe fn dispatch_upscale(context: ContextKey, d: &UpscaleFrame) -> u32 {
     let cmd_list_raw = d.command_list;
     if cmd_list_raw.is_null() {
         warn!("dispatch_upscale: null command list");
@@ -54,13 +60,17 @@ pub unsafe fn dispatch_upscale(d: &FfxFsr3UpscalerDispatchDescription) -> u32 {
 
     // Determine output format and initialize GPU pipeline.
     let output_format = gpu_pipeline::ffx_format_to_dxgi(d.output.description.format);
-    let gpu = match gpu_pipeline::get_or_init(&cmd_list, output_format) {
+    let shared = match gpu_pipeline::get_or_init(&cmd_list, output_format) {
         Some(g) => g,
         None => {
             error!("dispatch_upscale: GPU pipeline init failed, skipping blit");
             return 1;
         }
     };
+    let Some(gpu) = gpu_pipeline::for_context(context, shared) else {
+        return 1;
+    };
+    let gpu = &*gpu;
 
     // Render dimensions.
     let render_w = if d.render_size.width > 0 {
@@ -91,11 +101,61 @@ pub unsafe fn dispatch_upscale(d: &FfxFsr3UpscalerDispatchDescription) -> u32 {
 
     let current_upscaler = upscaler_type::get();
 
+    // Creation flags: rewrite depth / motion vectors into the upscalers'
+    // convention, and tell the spatial filters whether colour is HDR.
+    let input_flags = context::with(context, |state| InputFlags::from_bits(state.flags));
+    let normalized = input_flags.and_then(|flags| {
+        input_normalize::normalize(context, &cmd_list, gpu, d, flags, render_w, render_h)
+    });
+    let d = normalized.as_ref().unwrap_or(d);
+    let input_flags = input_flags.unwrap_or_default();
+
+    let temporal = matches!(
+        current_upscaler,
+        upscaler_type::UpscalerType::SGSRv2TwoPass | upscaler_type::UpscalerType::SGSRv2
+    );
+
+    // Drop history on camera cuts the game did not flag; IMBA keeps history
+    // too, but works in the game's colour space
+    let history = temporal || current_upscaler == upscaler_type::UpscalerType::Imba;
+    let cut = if history {
+        scene_cut::detect(context, &cmd_list, gpu, d, &color_res, render_w, render_h)
+    } else {
+        None
+    };
+    let d = cut.as_ref().unwrap_or(d);
+
+    // Temporal upscalers accumulate in a stable exposure space
+    let exposure = if temporal {
+        match exposure::update(
+            context,
+            &cmd_list,
+            gpu,
+            d,
+            &color_res,
+            input_flags,
+            render_w,
+            render_h,
+        ) {
+            Ok(address) => address,
+            Err(e) => {
+                error!("dispatch_upscale: exposure failed: {}", e);
+                return 1;
+            }
+        }
+    } else {
+        0
+    };
+
     // Build dispatch context
     let ctx = DispatchContext {
+        context,
         cmd_list: &cmd_list,
         gpu,
         d,
+        scene_cut: cut.is_some(),
+        input_flags,
+        exposure,
         color_res: &color_res,
         output_res: &output_res,
         render_w,
@@ -110,6 +170,7 @@ pub unsafe fn dispatch_upscale(d: &FfxFsr3UpscalerDispatchDescription) -> u32 {
     let result = match current_upscaler {
         upscaler_type::UpscalerType::SGSRv2TwoPass => upscalers::sgsr2_two_pass::dispatch(&ctx),
         upscaler_type::UpscalerType::SGSRv2 => upscalers::sgsr2_three_pass::dispatch(&ctx),
+        upscaler_type::UpscalerType::Imba => upscalers::imba::dispatch(&ctx),
         upscaler_type::UpscalerType::Bilinear
         | upscaler_type::UpscalerType::Lanczos
         | upscaler_type::UpscalerType::SGSR => upscalers::simple::dispatch(&ctx),
@@ -122,9 +183,11 @@ pub unsafe fn dispatch_upscale(d: &FfxFsr3UpscalerDispatchDescription) -> u32 {
     // --- Post-processing chain ---
     // After upscaler dispatch, output is in RENDER_TARGET state with RTV at slot 0.
     let post_ctx = PostContext {
+        context,
         cmd_list: &cmd_list,
         gpu,
         d,
+        input_flags,
         output_res: &output_res,
         output_w,
         output_h,
@@ -155,7 +218,12 @@ pub unsafe fn dispatch_upscale(d: &FfxFsr3UpscalerDispatchDescription) -> u32 {
     };
     cmd_list.RSSetViewports(&[full_viewport]);
     cmd_list.RSSetScissorRects(&[full_scissor]);
-    overlay::render_frame(&cmd_list, gpu, output_w, output_h);
+    overlay::render_frame(
+        &cmd_list,
+        gpu_pipeline::get_rtv_cpu_handle(gpu, 0),
+        output_w,
+        output_h,
+    );
 
     // --- Restore output barrier ---
     // All upscalers leave output in RENDER_TARGET state. Restore to original FFX state.
@@ -170,7 +238,7 @@ pub unsafe fn dispatch_upscale(d: &FfxFsr3UpscalerDispatchDescription) -> u32 {
 }
 
 /// AA mode dispatch: run neural AA model when enabled, else passthrough copy.
-pub unsafe fn dispatch_anti_aliasing(d: &FfxFsr3UpscalerDispatchDescription) -> u32 {
+pub unsafe fn dispatch_anti_aliasing(context: ContextKey, d: &UpscaleFrame) -> u32 {
     let cmd_list_raw = d.command_list;
     if cmd_list_raw.is_null() {
         warn!("dispatch_aa: null command list");
@@ -227,18 +295,37 @@ pub unsafe fn dispatch_anti_aliasing(d: &FfxFsr3UpscalerDispatchDescription) ->
     };
 
     let output_format = gpu_pipeline::ffx_format_to_dxgi(d.output.description.format);
-    let gpu = match gpu_pipeline::get_or_init(&cmd_list, output_format) {
+    let shared = match gpu_pipeline::get_or_init(&cmd_list, output_format) {
         Some(g) => g,
         None => {
             error!("dispatch_aa: GPU pipeline init failed");
             return 1;
         }
     };
+    let Some(gpu) = gpu_pipeline::for_context(context, shared) else {
+        return 1;
+    };
+    let gpu = &*gpu;
 
     let aa_type = upscaler_type::aa_get();
 
     // Try to run the AA model
-    if matches!(aa_type, upscaler_type::AntiAliasingType::ImbaV0) {
+    let model = (aa_type == upscaler_type::AntiAliasingType::Imba)
+        .then(upscalers::aa_models::selected)
+        .flatten();
+    let compare = model.as_ref().and_then(|_| upscalers::aa_models::compare());
+    if compare.is_none() {
+        upscalers::aa_pass::release_compare(context);
+    }
+    if let Some(model) = model {
+        // IMBA reads reverse-Z depth and UV motion vectors like SGSRv2
+        let normalized = context::with(context, |state| InputFlags::from_bits(state.flags))
+            .and_then(|flags| {
+                input_normalize::normalize(context, &cmd_list, gpu, d, flags, render_w, render_h)
+            });
+        let d = normalized.as_ref().unwrap_or(d);
+        let cut = scene_cut::detect(context, &cmd_list, gpu, d, &color_res, render_w, render_h);
+        let d = cut.as_ref().unwrap_or(d);
         let depth_res = upscalers::borrow_resource(d.depth.resource);
         let mv_res = upscalers::borrow_resource(d.motion_vectors.resource);
 
@@ -256,11 +343,24 @@ pub unsafe fn dispatch_anti_aliasing(d: &FfxFsr3UpscalerDispatchDescription) ->
                 color_format
             };
 
-            let mut aa_guard =
-                upscalers::aa_pass::get_or_create(&gpu.device, render_w, render_h, color_format);
-            let aa_state = aa_guard.as_mut();
+            let mut aa_guard = upscalers::aa_pass::get_or_create(
+                context,
+                &gpu.device,
+                render_w,
+                render_h,
+                render_w,
+                render_h,
+                color_format,
+                &model,
+            );
+            let aa_state = aa_guard.as_deref_mut();
 
             if let Some(state) = aa_state {
+                // A reset (from the game or a detected cut) or a long pause starts
+                // over like a first frame
+                if let Some(reason) = upscalers::aa_pass::history_break(d, cut.is_some()) {
+                    state.reset_history(reason);
+                }
                 if state.prev_frame_valid {
                     // ── Run AA inference ──
                     // Transition inputs → NON_PIXEL_SHADER_RESOURCE
@@ -298,9 +398,11 @@ pub unsafe fn dispatch_anti_aliasing(d: &FfxFsr3UpscalerDispatchDescription) ->
                         output_format,
                     );
 
-                    // Convert jitter from NDC to pixel space
-                    let jitter_x = d.jitter_offset.x * render_w as f32 * 0.5;
-                    let jitter_y = d.jitter_offset.y * render_h as f32 * 0.5;
+                    // t7-t8: the masks that reject history inside the network
+                    let mask_restore =
+                        upscalers::bind_compute_mask_srvs(&cmd_list, gpu, d, state.mask_srv_slot());
+
+                    let [jitter_x, jitter_y] = d.jitter_pixels();
                     let prev_jitter_x = state.prev_jitter_x;
                     let prev_jitter_y = state.prev_jitter_y;
 
@@ -314,6 +416,35 @@ pub unsafe fn dispatch_anti_aliasing(d: &FfxFsr3UpscalerDispatchDescription) ->
                         prev_jitter_x,
                         prev_jitter_y,
                     );
+                    apply_barriers(&cmd_list, &mask_restore);
+
+                    // Side-by-side: the comparison model fills the right half
+                    if let Some(compare) = &compare {
+                        run_compare(
+                            context,
+                            &cmd_list,
+                            gpu,
+                            d,
+                            compare,
+                            &state.output_texture,
+                            [&color_res, &depth_res, &mv_res],
+                            color_format,
+                            output_format,
+                            (jitter_x, jitter_y),
+                        );
+                    }
+
+                    // The network only dropped their history; reactive and
+                    // composited pixels also lean on the current frame's colour
+                    reactive_mask::blend(
+                        &cmd_list,
+                        gpu,
+                        d,
+                        &color_res,
+                        &state.output_texture,
+                        render_w,
+                        render_h,
+                    );
 
                     // Copy AA output → game output
                     apply_barriers(
@@ -422,7 +553,15 @@ pub unsafe fn dispatch_anti_aliasing(d: &FfxFsr3UpscalerDispatchDescription) ->
                     // Drop AA guard before post-fx (no longer needed)
                     drop(aa_guard);
 
-                    return finish_aa_postfx(&cmd_list, gpu, d, &output_res, output_w, output_h);
+                    return finish_aa_postfx(
+                        context,
+                        &cmd_list,
+                        gpu,
+                        d,
+                        &output_res,
+                        output_w,
+                        output_h,
+                    );
                 } else {
                     // First frame: copy current → prev, passthrough
                     apply_barriers(
@@ -472,8 +611,8 @@ pub unsafe fn dispatch_anti_aliasing(d: &FfxFsr3UpscalerDispatchDescription) ->
                     cmd_list.CopyResource(&output_res, &color_res);
 
                     state.prev_frame_valid = true;
-                    state.prev_jitter_x = d.jitter_offset.x * render_w as f32 * 0.5;
-                    state.prev_jitter_y = d.jitter_offset.y * render_h as f32 * 0.5;
+                    upscalers::aa_pass::reset_compare(context);
+                    [state.prev_jitter_x, state.prev_jitter_y] = d.jitter_pixels();
 
                     apply_barriers(
                         &cmd_list,
@@ -517,7 +656,15 @@ pub unsafe fn dispatch_anti_aliasing(d: &FfxFsr3UpscalerDispatchDescription) ->
                     );
 
                     drop(aa_guard);
-                    return finish_aa_postfx(&cmd_list, gpu, d, &output_res, output_w, output_h);
+                    return finish_aa_postfx(
+                        context,
+                        &cmd_list,
+                        gpu,
+                        d,
+                        &output_res,
+                        output_w,
+                        output_h,
+                    );
                 }
             }
         }
@@ -525,6 +672,7 @@ pub unsafe fn dispatch_anti_aliasing(d: &FfxFsr3UpscalerDispatchDescription) ->
 
     // Fallback: simple copy passthrough
     dispatch_aa_copy(
+        context,
         &cmd_list,
         d,
         &color_res,
@@ -535,10 +683,156 @@ pub unsafe fn dispatch_anti_aliasing(d: &FfxFsr3UpscalerDispatchDescription) ->
     )
 }
 
+/// Run the comparison model on the same inputs (in NON_PIXEL_SHADER_RESOURCE)
+/// and copy the right half of its output over `primary`, which stays in
+/// UNORDERED_ACCESS. On its first frame the model only records history.
+#[allow(clippy::too_many_arguments)]
+unsafe fn run_compare(
+    context: ContextKey,
+    cmd_list: &ID3D12GraphicsCommandList,
+    gpu: &gpu_pipeline::GpuState,
+    d: &UpscaleFrame,
+    model: &upscalers::aa_models::Model,
+    primary: &ID3D12Resource,
+    [color_res, depth_res, mv_res]: [&ID3D12Resource; 3],
+    color_format: DXGI_FORMAT,
+    output_format: DXGI_FORMAT,
+    (jitter_x, jitter_y): (f32, f32),
+) {
+    let (render_w, render_h) = {
+        let desc = primary.GetDesc();
+        (desc.Width as u32, desc.Height)
+    };
+    let Some(mut state) = upscalers::aa_pass::get_or_create_compare(
+        context,
+        &gpu.device,
+        render_w,
+        render_h,
+        color_format,
+        model,
+    ) else {
+        return;
+    };
+    if let Some(reason) = upscalers::aa_pass::history_break(d, false) {
+        state.reset_history(reason);
+    }
+
+    if state.prev_frame_valid {
+        upscalers::aa_pass::setup_descriptors(
+            gpu,
+            &state,
+            color_res,
+            depth_res,
+            mv_res,
+            output_format,
+        );
+        let mask_restore =
+            upscalers::bind_compute_mask_srvs(cmd_list, gpu, d, state.mask_srv_slot());
+        let (prev_jitter_x, prev_jitter_y) = (state.prev_jitter_x, state.prev_jitter_y);
+        upscalers::aa_pass::execute(
+            cmd_list,
+            gpu,
+            &mut state,
+            jitter_x,
+            jitter_y,
+            prev_jitter_x,
+            prev_jitter_y,
+        );
+        apply_barriers(cmd_list, &mask_restore);
+
+        apply_barriers(
+            cmd_list,
+            &[
+                resource_barrier_transition_d3d12(
+                    &state.output_texture,
+                    D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+                    D3D12_RESOURCE_STATE_COPY_SOURCE,
+                ),
+                resource_barrier_transition_d3d12(
+                    primary,
+                    D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+                    D3D12_RESOURCE_STATE_COPY_DEST,
+                ),
+            ],
+        );
+        let dst_loc = D3D12_TEXTURE_COPY_LOCATION {
+            pResource: std::mem::transmute_copy(primary),
+            Type: D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
+            Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+                SubresourceIndex: 0,
+            },
+        };
+        let src_loc = D3D12_TEXTURE_COPY_LOCATION {
+            pResource: std::mem::transmute_copy(&state.output_texture),
+            Type: D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
+            Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+                SubresourceIndex: 0,
+            },
+        };
+        let half = render_w / 2;
+        let right_half = D3D12_BOX {
+            left: half,
+            top: 0,
+            front: 0,
+            right: render_w,
+            bottom: render_h,
+            back: 1,
+        };
+        cmd_list.CopyTextureRegion(&dst_loc, half, 0, 0, &src_loc, Some(&right_half));
+        apply_barriers(
+            cmd_list,
+            &[
+                resource_barrier_transition_d3d12(
+                    &state.output_texture,
+                    D3D12_RESOURCE_STATE_COPY_SOURCE,
+                    D3D12_RESOURCE_STATE_COMMON,
+                ),
+                resource_barrier_transition_d3d12(
+                    primary,
+                    D3D12_RESOURCE_STATE_COPY_DEST,
+                    D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+                ),
+            ],
+        );
+    }
+
+    // Current frame → the comparison model's prev
+    let prev = [&state.prev_color, &state.prev_depth, &state.prev_motion];
+    apply_barriers(
+        cmd_list,
+        &prev.map(|res| {
+            resource_barrier_transition_d3d12(
+                res,
+                D3D12_RESOURCE_STATE_COMMON,
+                D3D12_RESOURCE_STATE_COPY_DEST,
+            )
+        }),
+    );
+    cmd_list.CopyResource(&state.prev_color, color_res);
+    cmd_list.CopyResource(&state.prev_depth, depth_res);
+    cmd_list.CopyResource(&state.prev_motion, mv_res);
+    apply_barriers(
+        cmd_list,
+        &prev.map(|res| {
+            resource_barrier_transition_d3d12(
+                res,
+                D3D12_RESOURCE_STATE_COPY_DEST,
+                D3D12_RESOURCE_STATE_COMMON,
+            )
+        }),
+    );
+
+    state.prev_frame_valid = true;
+    state.prev_jitter_x = jitter_x;
+    state.prev_jitter_y = jitter_y;
+}
+
 /// Simple copy fallback for AA mode when model is disabled or resources unavailable.
+#[allow(clippy::too_many_arguments)]
 unsafe fn dispatch_aa_copy(
+    context: ContextKey,
     cmd_list: &ID3D12GraphicsCommandList,
-    d: &FfxFsr3UpscalerDispatchDescription,
+    d: &UpscaleFrame,
     color_res: &ID3D12Resource,
     output_res: &ID3D12Resource,
     gpu: &gpu_pipeline::GpuState,
@@ -571,15 +865,16 @@ unsafe fn dispatch_aa_copy(
         ],
     );
 
-    finish_aa_postfx(cmd_list, gpu, d, output_res, output_w, output_h)
+    finish_aa_postfx(context, cmd_list, gpu, d, output_res, output_w, output_h)
 }
 
 /// Post-processing, overlay, and final barrier restore for AA dispatch.
 /// Expects output in RENDER_TARGET state.
 unsafe fn finish_aa_postfx(
+    context: ContextKey,
     cmd_list: &ID3D12GraphicsCommandList,
     gpu: &gpu_pipeline::GpuState,
-    d: &FfxFsr3UpscalerDispatchDescription,
+    d: &UpscaleFrame,
     output_res: &ID3D12Resource,
     output_w: u32,
     output_h: u32,
@@ -607,10 +902,13 @@ unsafe fn finish_aa_postfx(
     let rtv_handle = gpu_pipeline::get_rtv_cpu_handle(gpu, 0);
     cmd_list.OMSetRenderTargets(1, Some(&rtv_handle), false, None);
 
+    let input_flags = context::with(context, |state| InputFlags::from_bits(state.flags));
     let post_ctx = PostContext {
+        context,
         cmd_list,
         gpu,
         d,
+        input_flags: input_flags.unwrap_or_default(),
         output_res,
         output_w,
         output_h,
@@ -624,9 +922,7 @@ unsafe fn finish_aa_postfx(
         post_processing::debug_view::apply(&post_ctx);
     }
 
-    // Set descriptor heap for imgui
-    cmd_list.SetDescriptorHeaps(&[Some(gpu.srv_heap.clone())]);
-    overlay::render_frame(cmd_list, gpu, output_w, output_h);
+    overlay::render_frame(cmd_list, rtv_handle, output_w, output_h);
 
     apply_barriers(
         cmd_list,
@@ -739,3 +1035,18 @@ pub(crate) fn resource_barrier_transition_d3d12(
 
     barrier
 }
+
+/// Transition barriers for `(resource, before, after)` triples, skipping the
+/// ones already in their target state (game-owned inputs often are).
+pub(crate) fn transitions_if_needed(
+    list: &[(
+        &ID3D12Resource,
+        D3D12_RESOURCE_STATES,
+        D3D12_RESOURCE_STATES,
+    )],
+) -> Vec<D3D12_RESOURCE_BARRIER> {
+    list.iter()
+        .filter(|(_, before, after)| before != after)
+        .map(|&(res, before, after)| resource_barrier_transition_d3d12(res, before, after))
+        .collect()
+}
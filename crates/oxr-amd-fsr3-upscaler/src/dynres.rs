@@ -0,0 +1,90 @@
+//! Dynamic resolution for contexts created with `EnableDynamicResolution`.
+//!
+//! Each such context runs its own [`DynResController`] (enabled by
+//! `[dynamic_resolution]` in `oxr.ini`), fed with its `frame_time_delta`.
+//! The legacy render-resolution query carries no context. It proposes a
+//! controller's size only for the display size that controller was last
+//! stepped at, only while exactly one live context matches, and never for a
+//! native mode; otherwise it answers from the quality table like the ratio
+//! query always does.
+
+use std::sync::Mutex;
+
+use fsr_sys::dynres::{DynResController, DynResDecision};
+use tracing::info;
+
+use crate::context::{self, ContextKey};
+use crate::fsr3_types::FfxFsr3UpscalerInitializationFlagBits;
+use crate::settings;
+
+/// Last decision and the display size it applied to, for the overlay.
+#[derive(Debug, Clone, Copy)]
+pub struct DynResStatus {
+    pub decision: DynResDecision,
+    pub target_frame_ms: f32,
+    pub render_size: (u32, u32),
+}
+
+static STATUS: Mutex<Option<DynResStatus>> = Mutex::new(None);
+
+pub fn wants_dynamic_resolution(flags: u32) -> bool {
+    flags & FfxFsr3UpscalerInitializationFlagBits::EnableDynamicResolution as u32 != 0
+}
+
+/// Step `key`'s controller with one dispatch's frame time. No-op unless
+/// enabled in `oxr.ini` and requested by the context.
+pub fn update(key: ContextKey, frame_time_ms: f32, display_width: u32, display_height: u32) {
+    let Some(config) = settings::get().dynamic_resolution else {
+        return;
+    };
+    let status = context::with(key, |state| {
+        if !wants_dynamic_resolution(state.flags) {
+            return None;
+        }
+        state.dynres_display_size = (display_width, display_height);
+        let controller = state.dynres.get_or_insert_with(|| {
+            info!(?config, "dynres: context 0x{:x}: controller started", key);
+            DynResController::new(config)
+        });
+        let decision = controller.update(frame_time_ms);
+        let render_size = controller.render_resolution(display_width, display_height);
+        if decision.changed {
+            info!(
+                ratio = decision.ratio,
+                smoothed_ms = decision.smoothed_frame_ms,
+                target_ms = config.target_frame_ms,
+                render = format_args!("{}x{}", render_size.0, render_size.1),
+                "dynres: context 0x{:x}: ratio changed",
+                key
+            );
+        }
+        Some(DynResStatus {
+            decision,
+            target_frame_ms: config.target_frame_ms,
+            render_size,
+        })
+    });
+    if let Some(status) = status.flatten() {
+        *STATUS.lock().unwrap_or_else(|e| e.into_inner()) = Some(status);
+    }
+}
+
+/// Ratio to propose from a render-resolution query at the given display
+/// size: that of the only live context that opted into dynamic resolution
+/// and last dispatched at this size.
+pub fn active_ratio(display_width: u32, display_height: u32) -> Option<f32> {
+    settings::get().dynamic_resolution?;
+    let opted_in = context::filter_map(|s| {
+        (wants_dynamic_resolution(s.flags)
+            && s.dynres_display_size == (display_width, display_height))
+            .then(|| s.dynres.as_ref().map(DynResController::ratio))
+    });
+    match opted_in[..] {
+        [ratio] => ratio,
+        _ => None,
+    }
+}
+
+pub fn status() -> Option<DynResStatus> {
+    *STATUS.lock().unwrap_or_else(|e| e.into_inner())
+}
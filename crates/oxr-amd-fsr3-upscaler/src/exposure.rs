@@ -0,0 +1,320 @@
+//! Per-context exposure for the temporal upscalers.
+//!
+//! Every SGSRv2 dispatch first resolves an `ExposureState` into a small GPU
+//! buffer, which the shaders read as a root SRV (t4) to move colour into and
+//! out of a stable exposure space. The exposure comes from the histogram
+//! auto-exposure passes when the context was created with
+//! `FFX_UPSCALE_ENABLE_AUTO_EXPOSURE`, else from the game's exposure texture,
+//! else 1.0.
+//!
+//! Follows `fsr_sys::exposure`; `exposure_histogram_cs.hlsl` and
+//! `exposure_resolve_cs.hlsl` are its GPU twins.
+
+use crate::context::{ContextKey, PerContext};
+use crate::dispatch::{
+    apply_barriers, ffx_state_to_d3d12, resource_barrier_transition_d3d12, transitions_if_needed,
+};
+use crate::gpu_pipeline::{self, GpuState};
+use crate::upscalers::{borrow_resource, create_typed_srv};
+use fsr_sys::exposure::{sanitize_pre_exposure, ExposureState, EXPOSURE_HISTOGRAM_BINS};
+use fsr_sys::inputs::InputFlags;
+use fsr_sys::UpscaleFrame;
+use tracing::{info, warn};
+use windows::Win32::Graphics::Direct3D12::*;
+use windows::Win32::Graphics::Dxgi::Common::*;
+
+// --- SRV/UAV slots (each pair is one descriptor table) ---
+const SRV_COLOR: u32 = 50;
+const SRV_GAME_EXPOSURE: u32 = 51;
+const UAV_HISTOGRAM: u32 = 52;
+const UAV_STATE: u32 = 53;
+
+const GROUP_SIZE: u32 = 8;
+
+/// Resolve modes; match `MODE_*` in `exposure_resolve_cs.hlsl`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+enum Mode {
+    Auto = 0,
+    GameTexture = 1,
+    Fixed = 2,
+    Clear = 3,
+}
+
+struct ExposureBuffers {
+    histogram: ID3D12Resource,
+    state: ID3D12Resource,
+    /// Whether the histogram has been cleared since creation.
+    initialized: bool,
+    mode: Option<Mode>,
+}
+
+static EXPOSURE: PerContext<ExposureBuffers> = PerContext::new();
+
+/// Drop the exposure buffers of a destroyed context.
+pub fn release(key: ContextKey) {
+    EXPOSURE.remove(key);
+}
+
+pub(crate) unsafe fn create_buffer(
+    device: &ID3D12Device,
+    size: u64,
+) -> Result<ID3D12Resource, String> {
+    let heap_props = D3D12_HEAP_PROPERTIES {
+        Type: D3D12_HEAP_TYPE_DEFAULT,
+        ..Default::default()
+    };
+    let desc = D3D12_RESOURCE_DESC {
+        Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
+        Width: size,
+        Height: 1,
+        DepthOrArraySize: 1,
+        MipLevels: 1,
+        SampleDesc: DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+        Flags: D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS,
+        ..Default::default()
+    };
+
+    let mut resource: Option<ID3D12Resource> = None;
+    device
+        .CreateCommittedResource(
+            &heap_props,
+            D3D12_HEAP_FLAG_NONE,
+            &desc,
+            D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+            None,
+            &mut resource,
+        )
+        .map_err(|e| format!("CreateCommittedResource for exposure buffer failed: {}", e))?;
+    resource.ok_or_else(|| "CreateCommittedResource returned null".to_string())
+}
+
+pub(crate) unsafe fn create_structured_uav(
+    gpu: &GpuState,
+    resource: &ID3D12Resource,
+    elements: u32,
+    stride: u32,
+    slot: u32,
+) {
+    let uav = D3D12_UNORDERED_ACCESS_VIEW_DESC {
+        Format: DXGI_FORMAT_UNKNOWN,
+        ViewDimension: D3D12_UAV_DIMENSION_BUFFER,
+        Anonymous: D3D12_UNORDERED_ACCESS_VIEW_DESC_0 {
+            Buffer: D3D12_BUFFER_UAV {
+                FirstElement: 0,
+                NumElements: elements,
+                StructureByteStride: stride,
+                CounterOffsetInBytes: 0,
+                Flags: D3D12_BUFFER_UAV_FLAG_NONE,
+            },
+        },
+    };
+    gpu.device.CreateUnorderedAccessView(
+        resource,
+        None,
+        Some(&uav),
+        gpu_pipeline::get_srv_cpu_handle(gpu, slot),
+    );
+}
+
+unsafe fn null_texture_srv(gpu: &GpuState, slot: u32) {
+    let null_desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
+        Format: DXGI_FORMAT_R32_FLOAT,
+        ViewDimension: D3D12_SRV_DIMENSION_TEXTURE2D,
+        Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+        Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+            Texture2D: D3D12_TEX2D_SRV {
+                MostDetailedMip: 0,
+                MipLevels: 1,
+                PlaneSlice: 0,
+                ResourceMinLODClamp: 0.0,
+            },
+        },
+    };
+    gpu.device.CreateShaderResourceView(
+        None,
+        Some(&null_desc),
+        gpu_pipeline::get_srv_cpu_handle(gpu, slot),
+    );
+}
+
+fn uav_barrier(resource: &ID3D12Resource) -> D3D12_RESOURCE_BARRIER {
+    D3D12_RESOURCE_BARRIER {
+        Type: D3D12_RESOURCE_BARRIER_TYPE_UAV,
+        Flags: D3D12_RESOURCE_BARRIER_FLAG_NONE,
+        Anonymous: D3D12_RESOURCE_BARRIER_0 {
+            UAV: std::mem::ManuallyDrop::new(D3D12_RESOURCE_UAV_BARRIER {
+                pResource: unsafe { std::mem::transmute_copy(resource) },
+            }),
+        },
+    }
+}
+
+/// Resolve this frame's exposure for `context` and return the GPU address of
+/// its `ExposureState`, left readable from pixel shaders. `color_res` must be
+/// in the game's state (`d.color.state`); it is restored before returning.
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn update(
+    context: ContextKey,
+    cmd_list: &ID3D12GraphicsCommandList,
+    gpu: &GpuState,
+    d: &UpscaleFrame,
+    color_res: &ID3D12Resource,
+    flags: InputFlags,
+    render_w: u32,
+    render_h: u32,
+) -> Result<u64, String> {
+    let mut buffers = EXPOSURE.get_or_create(
+        context,
+        |_| false,
+        || {
+            let histogram = create_buffer(&gpu.device, (EXPOSURE_HISTOGRAM_BINS * 4) as u64)?;
+            let state = create_buffer(&gpu.device, size_of::<ExposureState>() as u64)?;
+            Ok::<_, String>(ExposureBuffers {
+                histogram,
+                state,
+                initialized: false,
+                mode: None,
+            })
+        },
+    )?;
+
+    let game_exposure = borrow_resource(d.exposure.resource);
+    let mode = if flags.auto_exposure {
+        Mode::Auto
+    } else if game_exposure.is_some() {
+        Mode::GameTexture
+    } else {
+        Mode::Fixed
+    };
+    if buffers.mode != Some(mode) {
+        info!(
+            "exposure: context 0x{:x} using {:?} exposure",
+            context, mode
+        );
+    }
+    let reset = d.reset || !buffers.initialized || buffers.mode != Some(mode);
+    buffers.mode = Some(mode);
+
+    let mut states = vec![(
+        color_res,
+        ffx_state_to_d3d12(d.color.state),
+        D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE,
+    )];
+    if let Some(tex) = &game_exposure {
+        states.push((
+            tex,
+            ffx_state_to_d3d12(d.exposure.state),
+            D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE,
+        ));
+    }
+    let mut entry = transitions_if_needed(&states);
+    if buffers.initialized {
+        entry.push(resource_barrier_transition_d3d12(
+            &buffers.state,
+            D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+            D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+        ));
+    }
+    apply_barriers(cmd_list, &entry);
+
+    let color_ok = create_typed_srv(gpu, color_res, d.color.description.format, SRV_COLOR);
+    let game_ok = match &game_exposure {
+        Some(tex) => create_typed_srv(gpu, tex, d.exposure.description.format, SRV_GAME_EXPOSURE),
+        None => false,
+    };
+    if !game_ok {
+        if mode == Mode::GameTexture {
+            warn!("exposure: unsupported game exposure format, reading 1.0");
+        }
+        null_texture_srv(gpu, SRV_GAME_EXPOSURE);
+    }
+    create_structured_uav(
+        gpu,
+        &buffers.histogram,
+        EXPOSURE_HISTOGRAM_BINS as u32,
+        4,
+        UAV_HISTOGRAM,
+    );
+    create_structured_uav(
+        gpu,
+        &buffers.state,
+        1,
+        size_of::<ExposureState>() as u32,
+        UAV_STATE,
+    );
+
+    cmd_list.SetDescriptorHeaps(&[Some(gpu.srv_heap.clone())]);
+    cmd_list.SetComputeRootSignature(&gpu.compute_2uav_root_signature);
+    cmd_list.SetComputeRootDescriptorTable(1, gpu_pipeline::get_srv_gpu_handle(gpu, SRV_COLOR));
+    cmd_list.SetComputeRootDescriptorTable(2, gpu_pipeline::get_srv_gpu_handle(gpu, UAV_HISTOGRAM));
+
+    let pre_exposure = sanitize_pre_exposure(d.pre_exposure);
+    let set_constants = |mode: Mode| {
+        let constants = [
+            render_w | (render_h << 16),
+            mode as u32,
+            pre_exposure.to_bits(),
+            d.frame_time_delta.to_bits(),
+            reset as u32,
+            0,
+            0,
+            0,
+        ];
+        cmd_list.SetComputeRoot32BitConstants(
+            0,
+            constants.len() as u32,
+            constants.as_ptr() as *const core::ffi::c_void,
+            0,
+        );
+    };
+
+    // Fresh buffers hold garbage; the clear-only resolve zeroes the histogram
+    if !buffers.initialized {
+        cmd_list.SetPipelineState(&gpu.pso_exposure_resolve);
+        set_constants(Mode::Clear);
+        cmd_list.Dispatch(1, 1, 1);
+        apply_barriers(cmd_list, &[uav_barrier(&buffers.histogram)]);
+    }
+
+    let mode = if mode == Mode::Auto && !color_ok {
+        warn!("exposure: unsupported color format, skipping histogram");
+        Mode::Fixed
+    } else {
+        mode
+    };
+    if mode == Mode::Auto {
+        cmd_list.SetPipelineState(&gpu.pso_exposure_histogram);
+        set_constants(mode);
+        cmd_list.Dispatch(
+            render_w.div_ceil(GROUP_SIZE),
+            render_h.div_ceil(GROUP_SIZE),
+            1,
+        );
+        apply_barriers(cmd_list, &[uav_barrier(&buffers.histogram)]);
+    }
+
+    cmd_list.SetPipelineState(&gpu.pso_exposure_resolve);
+    set_constants(mode);
+    cmd_list.Dispatch(1, 1, 1);
+
+    let restore = states
+        .iter()
+        .map(|&(res, before, after)| (res, after, before))
+        .collect::<Vec<_>>();
+    let mut exit = transitions_if_needed(&restore);
+    exit.push(uav_barrier(&buffers.histogram));
+    exit.push(resource_barrier_transition_d3d12(
+        &buffers.state,
+        D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+        D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+    ));
+    apply_barriers(cmd_list, &exit);
+    buffers.initialized = true;
+
+    Ok(buffers.state.GetGPUVirtualAddress())
+}
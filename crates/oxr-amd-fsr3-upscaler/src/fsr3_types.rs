@@ -59,6 +59,58 @@ pub enum FfxSurfaceFormat {
     R32Typeless = 37,
 }
 
+impl FfxSurfaceFormat {
+    /// Every variant, indexed by its header value.
+    pub const ALL: [FfxSurfaceFormat; 38] = [
+        Self::Unknown,
+        Self::R32G32B32A32Typeless,
+        Self::R32G32B32A32Uint,
+        Self::R32G32B32A32Float,
+        Self::R16G16B16A16Float,
+        Self::R32G32B32Float,
+        Self::R32G32Float,
+        Self::R8Uint,
+        Self::R32Uint,
+        Self::R8G8B8A8Typeless,
+        Self::R8G8B8A8Unorm,
+        Self::R8G8B8A8Snorm,
+        Self::R8G8B8A8Srgb,
+        Self::B8G8R8A8Typeless,
+        Self::B8G8R8A8Unorm,
+        Self::B8G8R8A8Srgb,
+        Self::R11G11B10Float,
+        Self::R10G10B10A2Unorm,
+        Self::R16G16Float,
+        Self::R16G16Uint,
+        Self::R16G16Sint,
+        Self::R16Float,
+        Self::R16Uint,
+        Self::R16Unorm,
+        Self::R16Snorm,
+        Self::R8Unorm,
+        Self::R8G8Unorm,
+        Self::R8G8Uint,
+        Self::R32Float,
+        Self::R9G9B9E5Sharedexp,
+        Self::R16G16B16A16Typeless,
+        Self::R32G32Typeless,
+        Self::R10G10B10A2Typeless,
+        Self::R16G16Typeless,
+        Self::R16Typeless,
+        Self::R8Typeless,
+        Self::R8G8Typeless,
+        Self::R32Typeless,
+    ];
+}
+
+const _: () = {
+    let mut i = 0;
+    while i < FfxSurfaceFormat::ALL.len() {
+        assert!(FfxSurfaceFormat::ALL[i] as usize == i);
+        i += 1;
+    }
+};
+
 // vendor/FidelityFX-SDK-v1/sdk/include/FidelityFX/host/ffx_types.h:L330
 // Bitflags — represented as u32 to avoid UB when the game passes combined values.
 pub type FfxResourceUsage = u32;
@@ -138,15 +190,49 @@ pub struct FfxResource {
                          // 4 bytes of trailing padding implicit in repr(C)
 }
 
+// vendor/FidelityFX-SDK-v1/sdk/include/FidelityFX/host/ffx_types.h:L767
+// Index into the backend's resource table; passed by value.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct FfxResourceInternal {
+    pub internal_index: i32,
+}
+
+// vendor/FidelityFX-SDK-v1/sdk/include/FidelityFX/host/ffx_interface.h:L233
+pub type FfxDestroyResourceFunc =
+    unsafe extern "C" fn(*mut FfxInterface, FfxResourceInternal, u32) -> u32;
+
+// vendor/FidelityFX-SDK-v1/sdk/include/FidelityFX/host/ffx_interface.h:L437
+// The pipeline is an `FfxPipelineState*`, opaque to the proxy.
+pub type FfxDestroyPipelineFunc = unsafe extern "C" fn(*mut FfxInterface, *mut c_void, u32) -> u32;
+
 // vendor/FidelityFX-SDK-v1/sdk/include/FidelityFX/host/ffx_interface.h:L622
-// Opaque: 26 fn-ptrs + scratchBuffer (void*) + scratchBufferSize (size_t) + device (void*)
+// 26 fn-ptrs + scratchBuffer (void*) + scratchBufferSize (size_t) + device (void*)
 // = 29 × 8 bytes = 232 bytes, alignment 8.
-// We never inspect the fields — all callers pass/receive a pointer.
-#[repr(C, align(8))]
+// Only the two destroy callbacks are named; the safe-release exports call them.
+//   offset   0: fpGetSDKVersion .. fpCreateResource           (6 fn-ptrs)
+//   offset  48: fpDestroyResource
+//   offset  56: fpMapResource .. fpSwapChainConfigureFrameGeneration (11 fn-ptrs)
+//   offset 144: fpDestroyPipeline
+//   offset 152: fpScheduleGpuJob .. fpRegisterConstantBufferAllocator (7 fn-ptrs)
+//   offset 208: scratchBuffer, scratchBufferSize, device
+#[repr(C)]
 pub struct FfxInterface {
-    _opaque: [u64; 29],
+    _fp_head: [*const c_void; 6],
+    pub fp_destroy_resource: Option<FfxDestroyResourceFunc>,
+    _fp_mid: [*const c_void; 11],
+    pub fp_destroy_pipeline: Option<FfxDestroyPipelineFunc>,
+    _fp_tail: [*const c_void; 7],
+    pub scratch_buffer: *mut c_void,
+    pub scratch_buffer_size: usize,
+    pub device: *mut c_void,
 }
 
+const _: () = assert!(std::mem::size_of::<FfxInterface>() == 232);
+const _: () = assert!(std::mem::offset_of!(FfxInterface, fp_destroy_resource) == 48);
+const _: () = assert!(std::mem::offset_of!(FfxInterface, fp_destroy_pipeline) == 144);
+const _: () = assert!(std::mem::offset_of!(FfxInterface, scratch_buffer) == 208);
+
 // ── From ffx_fsr3upscaler.h ──────────────────────────────────────────────────
 
 // vendor/FidelityFX-SDK-v1/sdk/include/FidelityFX/host/ffx_fsr3upscaler.h:L119
@@ -266,6 +352,44 @@ pub struct FfxFsr3UpscalerDispatchDescription {
     pub flags: u32,
 }
 
+// FfxFsr3UpscalerDispatchDescription as declared by the v1.1.4 SDK header, with
+// `upscale_size` after `render_size`. Selected by `DispatchAbi::Sdk` profiles.
+//   offset 1784: render_size   (FfxDimensions2D, 8)
+//   offset 1792: upscale_size  (FfxDimensions2D, 8)
+//   offset 1800: enable_sharpening ... (as above, shifted by 8)
+//   total: 1840 bytes
+#[repr(C)]
+pub struct FfxFsr3UpscalerDispatchDescriptionSdk {
+    pub command_list: *mut c_void,
+    pub color: FfxResource,
+    pub depth: FfxResource,
+    pub motion_vectors: FfxResource,
+    pub exposure: FfxResource,
+    pub reactive: FfxResource,
+    pub transparency_and_composition: FfxResource,
+    pub dilated_depth: FfxResource,
+    pub dilated_motion_vectors: FfxResource,
+    pub reconstructed_prev_nearest_depth: FfxResource,
+    pub output: FfxResource,
+    pub jitter_offset: FfxFloatCoords2D,
+    pub motion_vector_scale: FfxFloatCoords2D,
+    pub render_size: FfxDimensions2D,
+    pub upscale_size: FfxDimensions2D,
+    pub enable_sharpening: bool,
+    pub sharpness: f32,
+    pub frame_time_delta: f32,
+    pub pre_exposure: f32,
+    pub reset: bool,
+    pub camera_near: f32,
+    pub camera_far: f32,
+    pub camera_fov_angle_vertical: f32,
+    pub view_space_to_meters_factor: f32,
+    pub flags: u32,
+}
+
+const _: () = assert!(std::mem::size_of::<FfxFsr3UpscalerDispatchDescription>() == 1832);
+const _: () = assert!(std::mem::size_of::<FfxFsr3UpscalerDispatchDescriptionSdk>() == 1840);
+
 // vendor/FidelityFX-SDK-v1/sdk/include/FidelityFX/host/ffx_fsr3upscaler.h:L221
 #[repr(C)]
 pub struct FfxFsr3UpscalerGenerateReactiveDescription {
@@ -336,6 +460,39 @@ const _: () = assert!(std::mem::size_of::<FfxResourceInitData>() == 16);
 const _: () = assert!(std::mem::size_of::<FfxCreateResourceDescription>() == 72);
 const _: () = assert!(std::mem::size_of::<FfxFsr3UpscalerSharedResourceDescriptions>() == 216);
 
+// FfxResourceInitData / FfxCreateResourceDescription as declared by the v1.1.4
+// SDK header: `size_t size`, and `name`/`id` before `initData`. Selected by
+// `ResourceAbi::Sdk` profiles.
+//   FfxResourceInitDataSdk:         type_ 0, size 8, buffer 16 → 24 bytes
+//   FfxCreateResourceDescriptionSdk: name 40, id 48, init_data 56 → 80 bytes
+#[repr(C)]
+pub struct FfxResourceInitDataSdk {
+    pub type_: u32,
+    pub size: usize,
+    pub buffer: *const c_void, // union with value: u8
+}
+
+#[repr(C)]
+pub struct FfxCreateResourceDescriptionSdk {
+    pub heap_type: u32,
+    pub resource_description: FfxResourceDescription,
+    pub initial_state: FfxResourceStates,
+    pub name: *const u16,
+    pub id: u32,
+    pub init_data: FfxResourceInitDataSdk,
+}
+
+#[repr(C)]
+pub struct FfxFsr3UpscalerSharedResourceDescriptionsSdk {
+    pub reconstructed_prev_nearest_depth: FfxCreateResourceDescriptionSdk,
+    pub dilated_depth: FfxCreateResourceDescriptionSdk,
+    pub dilated_motion_vectors: FfxCreateResourceDescriptionSdk,
+}
+
+const _: () = assert!(std::mem::size_of::<FfxResourceInitDataSdk>() == 24);
+const _: () = assert!(std::mem::size_of::<FfxCreateResourceDescriptionSdk>() == 80);
+const _: () = assert!(std::mem::size_of::<FfxFsr3UpscalerSharedResourceDescriptionsSdk>() == 240);
+
 // vendor/FidelityFX-SDK-v1/sdk/include/FidelityFX/host/ffx_fsr3upscaler.h:L256
 // FFX_FSR3UPSCALER_CONTEXT_SIZE = FFX_SDK_DEFAULT_CONTEXT_SIZE = 1024 × 128 = 131072
 // 131072 × 4 = 524288 bytes (512 KB). Never stack-allocate this.
@@ -343,3 +500,325 @@ const _: () = assert!(std::mem::size_of::<FfxFsr3UpscalerSharedResourceDescripti
 pub struct FfxFsr3UpscalerContext {
     pub data: [u32; 131072],
 }
+
+// ── Conversion to the shared frame type ──────────────────────────────────────
+
+// FfxResourceStates share their numbering with the ffx API (fsr-sys) and pass
+// through unchanged. Formats are copied as the game numbers them;
+// `game_profile::GameProfile::read_dispatch` translates them to header
+// numbering afterwards. The debug `name` has no counterpart in
+// FfxApiResource and is dropped.
+impl From<&FfxResource> for fsr_sys::FfxApiResource {
+    fn from(r: &FfxResource) -> Self {
+        Self {
+            resource: r.resource,
+            description: fsr_sys::FfxApiResourceDescription {
+                type_: r.description.type_,
+                format: r.description.format,
+                width: r.description.width,
+                height: r.description.height,
+                depth: r.description.depth,
+                mip_count: r.description.mip_count,
+                flags: r.description.flags,
+                usage: r.description.usage,
+            },
+            state: r.state,
+        }
+    }
+}
+
+// The game ABI has no `upscaleSize`; it is taken from the output description.
+impl From<&FfxFsr3UpscalerDispatchDescription> for fsr_sys::UpscaleFrame {
+    fn from(d: &FfxFsr3UpscalerDispatchDescription) -> Self {
+        Self {
+            command_list: d.command_list,
+            color: (&d.color).into(),
+            depth: (&d.depth).into(),
+            motion_vectors: (&d.motion_vectors).into(),
+            exposure: (&d.exposure).into(),
+            reactive: (&d.reactive).into(),
+            transparency_and_composition: (&d.transparency_and_composition).into(),
+            dilated_depth: (&d.dilated_depth).into(),
+            dilated_motion_vectors: (&d.dilated_motion_vectors).into(),
+            reconstructed_prev_nearest_depth: (&d.reconstructed_prev_nearest_depth).into(),
+            output: (&d.output).into(),
+            jitter_offset: fsr_sys::FfxApiFloatCoords2D {
+                x: d.jitter_offset.x,
+                y: d.jitter_offset.y,
+            },
+            motion_vector_scale: fsr_sys::FfxApiFloatCoords2D {
+                x: d.motion_vector_scale.x,
+                y: d.motion_vector_scale.y,
+            },
+            render_size: fsr_sys::FfxApiDimensions2D {
+                width: d.render_size.width,
+                height: d.render_size.height,
+            },
+            upscale_size: fsr_sys::FfxApiDimensions2D {
+                width: d.output.description.width,
+                height: d.output.description.height,
+            },
+            enable_sharpening: d.enable_sharpening,
+            sharpness: d.sharpness,
+            frame_time_delta: d.frame_time_delta,
+            pre_exposure: d.pre_exposure,
+            reset: d.reset,
+            camera_near: d.camera_near,
+            camera_far: d.camera_far,
+            camera_fov_angle_vertical: d.camera_fov_angle_vertical,
+            view_space_to_meters_factor: d.view_space_to_meters_factor,
+            flags: d.flags,
+        }
+    }
+}
+
+impl From<&FfxFsr3UpscalerDispatchDescriptionSdk> for fsr_sys::UpscaleFrame {
+    fn from(d: &FfxFsr3UpscalerDispatchDescriptionSdk) -> Self {
+        Self {
+            command_list: d.command_list,
+            color: (&d.color).into(),
+            depth: (&d.depth).into(),
+            motion_vectors: (&d.motion_vectors).into(),
+            exposure: (&d.exposure).into(),
+            reactive: (&d.reactive).into(),
+            transparency_and_composition: (&d.transparency_and_composition).into(),
+            dilated_depth: (&d.dilated_depth).into(),
+            dilated_motion_vectors: (&d.dilated_motion_vectors).into(),
+            reconstructed_prev_nearest_depth: (&d.reconstructed_prev_nearest_depth).into(),
+            output: (&d.output).into(),
+            jitter_offset: fsr_sys::FfxApiFloatCoords2D {
+                x: d.jitter_offset.x,
+                y: d.jitter_offset.y,
+            },
+            motion_vector_scale: fsr_sys::FfxApiFloatCoords2D {
+                x: d.motion_vector_scale.x,
+                y: d.motion_vector_scale.y,
+            },
+            render_size: fsr_sys::FfxApiDimensions2D {
+                width: d.render_size.width,
+                height: d.render_size.height,
+            },
+            upscale_size: fsr_sys::FfxApiDimensions2D {
+                width: d.upscale_size.width,
+                height: d.upscale_size.height,
+            },
+            enable_sharpening: d.enable_sharpening,
+            sharpness: d.sharpness,
+            frame_time_delta: d.frame_time_delta,
+            pre_exposure: d.pre_exposure,
+            reset: d.reset,
+            camera_near: d.camera_near,
+            camera_far: d.camera_far,
+            camera_fov_angle_vertical: d.camera_fov_angle_vertical,
+            view_space_to_meters_factor: d.view_space_to_meters_factor,
+            flags: d.flags,
+        }
+    }
+}
+
+impl From<&FfxFsr3UpscalerGenerateReactiveDescription> for fsr_sys::reactive::ReactiveMaskFrame {
+    fn from(d: &FfxFsr3UpscalerGenerateReactiveDescription) -> Self {
+        Self {
+            command_list: d.command_list,
+            color_opaque_only: (&d.color_opaque_only).into(),
+            color_pre_upscale: (&d.color_pre_upscale).into(),
+            out_reactive: (&d.out_reactive).into(),
+            render_size: fsr_sys::FfxApiDimensions2D {
+                width: d.render_size.width,
+                height: d.render_size.height,
+            },
+            params: fsr_sys::reactive::ReactiveMaskParams {
+                scale: d.scale,
+                cutoff_threshold: d.cutoff_threshold,
+                binary_value: d.binary_value,
+                flags: d.flags,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A labelled 2D texture with a recognisable handle, size and state.
+    fn texture(handle: usize, width: u32, height: u32, state: u32) -> FfxResource {
+        let mut name = [0u16; 64];
+        for (dst, src) in name.iter_mut().zip("FSR3_Input".encode_utf16()) {
+            *dst = src;
+        }
+        FfxResource {
+            resource: handle as *mut c_void,
+            description: FfxResourceDescription {
+                type_: FfxResourceType::Texture2D as u32,
+                format: FfxSurfaceFormat::R16G16B16A16Float as u32,
+                width,
+                height,
+                depth: 1,
+                mip_count: 1,
+                flags: FfxResourceFlags::None as u32,
+                usage: 1,
+            },
+            state,
+            name,
+        }
+    }
+
+    fn game_desc() -> FfxFsr3UpscalerDispatchDescription {
+        FfxFsr3UpscalerDispatchDescription {
+            command_list: 0x10 as *mut c_void,
+            color: texture(0x20, 1280, 720, 4),
+            depth: texture(0x30, 1280, 720, 4),
+            motion_vectors: texture(0x40, 1280, 720, 4),
+            exposure: texture(0x50, 1, 1, 4),
+            reactive: texture(0x60, 1280, 720, 12),
+            transparency_and_composition: texture(0x70, 1280, 720, 1),
+            dilated_depth: texture(0x90, 1280, 720, 2),
+            dilated_motion_vectors: texture(0xa0, 1280, 720, 2),
+            reconstructed_prev_nearest_depth: texture(0xb0, 1280, 720, 2),
+            output: texture(0x80, 2560, 1440, 2),
+            jitter_offset: FfxFloatCoords2D { x: 0.25, y: -0.125 },
+            motion_vector_scale: FfxFloatCoords2D {
+                x: 1280.0,
+                y: -720.0,
+            },
+            render_size: FfxDimensions2D {
+                width: 1280,
+                height: 720,
+            },
+            enable_sharpening: true,
+            sharpness: 0.4,
+            frame_time_delta: 16.6,
+            pre_exposure: 1.5,
+            reset: true,
+            camera_near: 0.1,
+            camera_far: 1000.0,
+            camera_fov_angle_vertical: 1.0,
+            view_space_to_meters_factor: 0.01,
+            flags: 0x3,
+        }
+    }
+
+    fn sdk_desc(upscale_size: FfxDimensions2D) -> FfxFsr3UpscalerDispatchDescriptionSdk {
+        let d = game_desc();
+        FfxFsr3UpscalerDispatchDescriptionSdk {
+            command_list: d.command_list,
+            color: d.color,
+            depth: d.depth,
+            motion_vectors: d.motion_vectors,
+            exposure: d.exposure,
+            reactive: d.reactive,
+            transparency_and_composition: d.transparency_and_composition,
+            dilated_depth: d.dilated_depth,
+            dilated_motion_vectors: d.dilated_motion_vectors,
+            reconstructed_prev_nearest_depth: d.reconstructed_prev_nearest_depth,
+            output: d.output,
+            jitter_offset: d.jitter_offset,
+            motion_vector_scale: d.motion_vector_scale,
+            render_size: d.render_size,
+            upscale_size,
+            enable_sharpening: d.enable_sharpening,
+            sharpness: d.sharpness,
+            frame_time_delta: d.frame_time_delta,
+            pre_exposure: d.pre_exposure,
+            reset: d.reset,
+            camera_near: d.camera_near,
+            camera_far: d.camera_far,
+            camera_fov_angle_vertical: d.camera_fov_angle_vertical,
+            view_space_to_meters_factor: d.view_space_to_meters_factor,
+            flags: d.flags,
+        }
+    }
+
+    fn same_resource(a: &fsr_sys::FfxApiResource, b: &FfxResource) -> bool {
+        a.resource == b.resource
+            && a.state == b.state
+            && a.description.type_ == b.description.type_
+            && a.description.format == b.description.format
+            && a.description.width == b.description.width
+            && a.description.height == b.description.height
+            && a.description.depth == b.description.depth
+            && a.description.mip_count == b.description.mip_count
+            && a.description.flags == b.description.flags
+            && a.description.usage == b.description.usage
+    }
+
+    /// Resources and scalars shared by both ABIs.
+    fn check_common(f: &fsr_sys::UpscaleFrame, d: &FfxFsr3UpscalerDispatchDescription) {
+        assert_eq!(f.command_list, d.command_list);
+        let pairs = [
+            (&f.color, &d.color),
+            (&f.depth, &d.depth),
+            (&f.motion_vectors, &d.motion_vectors),
+            (&f.exposure, &d.exposure),
+            (&f.reactive, &d.reactive),
+            (
+                &f.transparency_and_composition,
+                &d.transparency_and_composition,
+            ),
+            (&f.dilated_depth, &d.dilated_depth),
+            (&f.dilated_motion_vectors, &d.dilated_motion_vectors),
+            (
+                &f.reconstructed_prev_nearest_depth,
+                &d.reconstructed_prev_nearest_depth,
+            ),
+            (&f.output, &d.output),
+        ];
+        for (i, (a, b)) in pairs.into_iter().enumerate() {
+            assert!(same_resource(a, b), "resource {i}");
+        }
+        assert_eq!(f.jitter_pixels(), [0.25, -0.125]);
+        assert_eq!(
+            (f.motion_vector_scale.x, f.motion_vector_scale.y),
+            (1280.0, -720.0)
+        );
+        assert_eq!(f.effective_render_size(), (1280, 720));
+        assert!(f.enable_sharpening && f.reset);
+        assert_eq!(f.sharpness, 0.4);
+        assert_eq!(f.frame_time_delta, 16.6);
+        assert_eq!(f.pre_exposure, 1.5);
+        assert_eq!((f.camera_near, f.camera_far), (0.1, 1000.0));
+        assert_eq!(f.camera_fov_angle_vertical, 1.0);
+        assert_eq!(f.view_space_to_meters_factor, 0.01);
+        assert_eq!(f.flags, 0x3);
+    }
+
+    #[test]
+    fn game_dispatch_takes_the_upscale_size_from_the_output() {
+        let d = game_desc();
+        let f = fsr_sys::UpscaleFrame::from(&d);
+        check_common(&f, &d);
+        assert_eq!((f.upscale_size.width, f.upscale_size.height), (2560, 1440));
+        assert_eq!(f.effective_upscale_size(), (2560, 1440));
+    }
+
+    #[test]
+    fn sdk_dispatch_copies_the_upscale_size() {
+        let size = FfxDimensions2D {
+            width: 1920,
+            height: 1080,
+        };
+        let f = fsr_sys::UpscaleFrame::from(&sdk_desc(size));
+        check_common(&f, &game_desc());
+        assert_eq!(f.effective_upscale_size(), (1920, 1080));
+
+        // A zeroed upscale size falls back to the output description
+        let size = FfxDimensions2D {
+            width: 0,
+            height: 0,
+        };
+        let f = fsr_sys::UpscaleFrame::from(&sdk_desc(size));
+        assert_eq!(f.effective_upscale_size(), (2560, 1440));
+    }
+
+    #[test]
+    fn null_game_resources_stay_null() {
+        let mut d = game_desc();
+        d.reactive.resource = core::ptr::null_mut();
+        d.dilated_depth.resource = core::ptr::null_mut();
+        let f = fsr_sys::UpscaleFrame::from(&d);
+        assert!(f.reactive.resource.is_null());
+        assert!(f.dilated_depth.resource.is_null());
+        assert!(!f.color.resource.is_null());
+    }
+}
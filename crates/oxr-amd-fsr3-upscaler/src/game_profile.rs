@@ -0,0 +1,719 @@
+//! Per-game ABI profiles for the legacy exports.
+//!
+//! FSR3 titles were built against different SDK drops, and the structs and
+//! enums they pass differ from the v1.1.4 header in `fsr3_types`:
+//!
+//! | Profile               | Dispatch desc          | Shared resource desc        | Surface formats     |
+//! |-----------------------|------------------------|-----------------------------|---------------------|
+//! | Cyberpunk 2077        | no `upscaleSize`       | `u32` init size, init first | no R32G32B32 / B8G8R8A8 |
+//! | FidelityFX SDK v1.1.4 | with `upscaleSize`     | `size_t` init size, name first | header values    |
+//!
+//! A profile is picked from the host executable name, or forced with
+//! `[game] profile = <name>` in `oxr.ini`. New games are added as data, in
+//! `oxr.ini`, without code changes:
+//!
+//! ```ini
+//! [profile.my_game]
+//! executables = MyGame.exe, MyGame_DX12.exe
+//! dispatch_abi = sdk            ; sdk | no_upscale_size
+//! resource_abi = sdk            ; sdk | packed
+//! format.R32Uint = 7            ; game value for an FfxSurfaceFormat variant
+//! format.R8G8B8A8Typeless = 8   ; list every value that differs, or the
+//!                               ; header value it shadows reads as Unknown
+//! resource_id.dilated_depth = 10
+//! ```
+//!
+//! Formats the game passes in are translated to header numbering as each
+//! frame is converted, so `ffx_format_to_dxgi` and everything downstream only
+//! ever see one numbering; formats handed back are translated the other way.
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use fsr_sys::reactive::ReactiveMaskFrame;
+use fsr_sys::{FfxApiResource, UpscaleFrame};
+use tracing::{info, warn};
+
+use crate::fsr3_types::*;
+use crate::settings;
+
+/// Layout of `FfxFsr3UpscalerDispatchDescription`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DispatchAbi {
+    /// `FfxFsr3UpscalerDispatchDescription` — no `upscaleSize`.
+    NoUpscaleSize,
+    /// `FfxFsr3UpscalerDispatchDescriptionSdk`.
+    Sdk,
+}
+
+/// Layout of `FfxFsr3UpscalerSharedResourceDescriptions`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ResourceAbi {
+    /// `FfxCreateResourceDescription` — `u32` init size, init data before name.
+    Packed,
+    /// `FfxCreateResourceDescriptionSdk`.
+    Sdk,
+}
+
+// Enum values that agree between every known game and the SDK header.
+const FFX_HEAP_TYPE_DEFAULT: u32 = 0;
+const FFX_RESOURCE_STATE_UNORDERED_ACCESS: u32 = 2;
+const FFX_RESOURCE_USAGE_RT: u32 = 1;
+const FFX_RESOURCE_USAGE_UAV: u32 = 2;
+// FfxResourceInitDataType: the SDK header starts with INVALID, Cyberpunk's does not.
+const INIT_DATA_UNINITIALIZED_PACKED: u32 = 0;
+const INIT_DATA_UNINITIALIZED_SDK: u32 = 1;
+
+/// Resources described by `ffxFsr3UpscalerGetSharedResourceDescriptions`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SharedResource {
+    ReconstructedPrevNearestDepth,
+    DilatedDepth,
+    DilatedMotionVectors,
+}
+
+impl SharedResource {
+    pub const ALL: [SharedResource; 3] = [
+        SharedResource::ReconstructedPrevNearestDepth,
+        SharedResource::DilatedDepth,
+        SharedResource::DilatedMotionVectors,
+    ];
+
+    /// Debug name the original DLL gives the resource.
+    pub fn name(self) -> &'static str {
+        match self {
+            SharedResource::ReconstructedPrevNearestDepth => {
+                "FSR3UPSCALER_ReconstructedPrevNearestDepth"
+            }
+            SharedResource::DilatedDepth => "FSR3UPSCALER_DilatedDepth",
+            SharedResource::DilatedMotionVectors => "FSR3UPSCALER_DilatedVelocity",
+        }
+    }
+
+    /// `resource_id.<key>` in profile sections.
+    fn key(self) -> &'static str {
+        match self {
+            SharedResource::ReconstructedPrevNearestDepth => "reconstructed_prev_nearest_depth",
+            SharedResource::DilatedDepth => "dilated_depth",
+            SharedResource::DilatedMotionVectors => "dilated_motion_vectors",
+        }
+    }
+
+    pub fn format(self) -> FfxSurfaceFormat {
+        match self {
+            SharedResource::ReconstructedPrevNearestDepth => FfxSurfaceFormat::R32Uint,
+            SharedResource::DilatedDepth => FfxSurfaceFormat::R32Float,
+            SharedResource::DilatedMotionVectors => FfxSurfaceFormat::R16G16Float,
+        }
+    }
+
+    /// `FfxResourceUsage` the original DLL requests.
+    fn usage(self) -> u32 {
+        match self {
+            SharedResource::ReconstructedPrevNearestDepth => FFX_RESOURCE_USAGE_UAV,
+            _ => FFX_RESOURCE_USAGE_RT | FFX_RESOURCE_USAGE_UAV,
+        }
+    }
+
+    /// `FFX_FSR3UPSCALER_RESOURCE_IDENTIFIER_*`.
+    fn sdk_id(self) -> u32 {
+        match self {
+            SharedResource::ReconstructedPrevNearestDepth => 8,
+            SharedResource::DilatedMotionVectors => 9,
+            SharedResource::DilatedDepth => 10,
+        }
+    }
+
+    /// Null-terminated UTF-16 name, alive for the whole process so the game
+    /// can keep the pointer.
+    pub fn wide_name(self) -> &'static [u16] {
+        static NAMES: OnceLock<Vec<Vec<u16>>> = OnceLock::new();
+        let names = NAMES.get_or_init(|| {
+            SharedResource::ALL
+                .iter()
+                .map(|r| r.name().encode_utf16().chain([0]).collect())
+                .collect()
+        });
+        &names[self as usize]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GameProfile {
+    pub name: String,
+    /// Executable file names (case-insensitive) that select this profile.
+    pub executables: Vec<String>,
+    pub dispatch_abi: DispatchAbi,
+    pub resource_abi: ResourceAbi,
+    /// `(header value, game value)` for every format the game numbers
+    /// differently; unlisted formats pass through unchanged unless another
+    /// entry claims their value. Game values are unique.
+    pub formats: Vec<(FfxSurfaceFormat, u32)>,
+    /// Resource identifiers that differ from the header's.
+    pub resource_ids: Vec<(SharedResource, u32)>,
+}
+
+impl GameProfile {
+    /// Game enum value for a header format. `Unknown` if the game's enum
+    /// has no such format, i.e. its header value belongs to another one.
+    pub fn game_format(&self, format: FfxSurfaceFormat) -> u32 {
+        if let Some(&(_, v)) = self.formats.iter().find(|(f, _)| *f == format) {
+            return v;
+        }
+        if self.formats.iter().any(|&(_, v)| v == format as u32) {
+            return FfxSurfaceFormat::Unknown as u32;
+        }
+        format as u32
+    }
+
+    /// Header value for a format the game passed in. `Unknown` if the
+    /// value is unlisted and its header format is listed under another one.
+    pub fn header_format(&self, value: u32) -> u32 {
+        if let Some(&(f, _)) = self.formats.iter().find(|(_, v)| *v == value) {
+            return f as u32;
+        }
+        if self.formats.iter().any(|&(f, _)| f as u32 == value) {
+            return FfxSurfaceFormat::Unknown as u32;
+        }
+        value
+    }
+
+    pub fn resource_id(&self, resource: SharedResource) -> u32 {
+        self.resource_ids
+            .iter()
+            .find(|(r, _)| *r == resource)
+            .map_or(resource.sdk_id(), |&(_, v)| v)
+    }
+
+    /// Texture description of `resource` at `max_render_size`, in this game's
+    /// format numbering.
+    pub fn resource_description(
+        &self,
+        resource: SharedResource,
+        max_render_size: FfxDimensions2D,
+    ) -> FfxResourceDescription {
+        FfxResourceDescription {
+            type_: FfxResourceType::Texture2D as u32,
+            format: self.game_format(resource.format()),
+            width: max_render_size.width,
+            height: max_render_size.height,
+            depth: 1,
+            mip_count: 1,
+            flags: FfxResourceFlags::Aliasable as u32,
+            usage: resource.usage(),
+        }
+    }
+
+    /// [`ResourceAbi::Packed`] creation description of `resource`.
+    pub fn packed_description(
+        &self,
+        resource: SharedResource,
+        max_render_size: FfxDimensions2D,
+    ) -> FfxCreateResourceDescription {
+        FfxCreateResourceDescription {
+            heap_type: FFX_HEAP_TYPE_DEFAULT,
+            resource_description: self.resource_description(resource, max_render_size),
+            initial_state: FFX_RESOURCE_STATE_UNORDERED_ACCESS,
+            init_data: FfxResourceInitData {
+                type_: INIT_DATA_UNINITIALIZED_PACKED,
+                size: 0,
+                buffer: std::ptr::null(),
+            },
+            name: resource.wide_name().as_ptr(),
+            id: self.resource_id(resource),
+            _pad: 0,
+        }
+    }
+
+    /// [`ResourceAbi::Sdk`] creation description of `resource`.
+    pub fn sdk_description(
+        &self,
+        resource: SharedResource,
+        max_render_size: FfxDimensions2D,
+    ) -> FfxCreateResourceDescriptionSdk {
+        FfxCreateResourceDescriptionSdk {
+            heap_type: FFX_HEAP_TYPE_DEFAULT,
+            resource_description: self.resource_description(resource, max_render_size),
+            initial_state: FFX_RESOURCE_STATE_UNORDERED_ACCESS,
+            name: resource.wide_name().as_ptr(),
+            id: self.resource_id(resource),
+            init_data: FfxResourceInitDataSdk {
+                type_: INIT_DATA_UNINITIALIZED_SDK,
+                size: 0,
+                buffer: std::ptr::null(),
+            },
+        }
+    }
+
+    fn normalize(&self, r: &mut FfxApiResource) {
+        r.description.format = self.header_format(r.description.format);
+    }
+
+    /// Rewrite every resource format in `frame` to header numbering.
+    pub fn normalize_frame(&self, frame: &mut UpscaleFrame) {
+        if self.formats.is_empty() {
+            return;
+        }
+        for r in [
+            &mut frame.color,
+            &mut frame.depth,
+            &mut frame.motion_vectors,
+            &mut frame.exposure,
+            &mut frame.reactive,
+            &mut frame.transparency_and_composition,
+            &mut frame.dilated_depth,
+            &mut frame.dilated_motion_vectors,
+            &mut frame.reconstructed_prev_nearest_depth,
+            &mut frame.output,
+        ] {
+            self.normalize(r);
+        }
+    }
+
+    pub fn normalize_reactive(&self, frame: &mut ReactiveMaskFrame) {
+        for r in [
+            &mut frame.color_opaque_only,
+            &mut frame.color_pre_upscale,
+            &mut frame.out_reactive,
+        ] {
+            self.normalize(r);
+        }
+    }
+
+    /// Convert a game dispatch descriptor with this profile's layout.
+    ///
+    /// # Safety
+    /// `desc` must point to a live descriptor of the profile's layout.
+    pub unsafe fn read_dispatch(
+        &self,
+        desc: *const FfxFsr3UpscalerDispatchDescription,
+    ) -> UpscaleFrame {
+        let mut frame = match self.dispatch_abi {
+            DispatchAbi::NoUpscaleSize => UpscaleFrame::from(&*desc),
+            DispatchAbi::Sdk => {
+                UpscaleFrame::from(&*(desc as *const FfxFsr3UpscalerDispatchDescriptionSdk))
+            }
+        };
+        self.normalize_frame(&mut frame);
+        frame
+    }
+
+    /// Build a profile from a `[profile.<name>]` section. `get(key)` returns
+    /// the raw value. Returns the profile and any rejected `(key, value)`s.
+    pub fn parse<'a>(
+        name: &str,
+        keys: &[&'a str],
+        get: impl Fn(&str) -> Option<&'a str>,
+    ) -> (Self, Vec<(String, String)>) {
+        let mut rejected = Vec::new();
+        let mut profile = GameProfile {
+            name: name.to_string(),
+            executables: get("executables")
+                .map(|v| {
+                    v.split(',')
+                        .map(|e| e.trim().to_string())
+                        .filter(|e| !e.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            dispatch_abi: DispatchAbi::Sdk,
+            resource_abi: ResourceAbi::Sdk,
+            formats: Vec::new(),
+            resource_ids: Vec::new(),
+        };
+
+        match get("dispatch_abi").map(|v| v.trim().to_ascii_lowercase()) {
+            None => {}
+            Some(v) if v == "sdk" => profile.dispatch_abi = DispatchAbi::Sdk,
+            Some(v) if v == "no_upscale_size" => profile.dispatch_abi = DispatchAbi::NoUpscaleSize,
+            Some(v) => rejected.push(("dispatch_abi".to_string(), v)),
+        }
+        match get("resource_abi").map(|v| v.trim().to_ascii_lowercase()) {
+            None => {}
+            Some(v) if v == "sdk" => profile.resource_abi = ResourceAbi::Sdk,
+            Some(v) if v == "packed" => profile.resource_abi = ResourceAbi::Packed,
+            Some(v) => rejected.push(("resource_abi".to_string(), v)),
+        }
+
+        for &key in keys {
+            let Some(value) = get(key) else { continue };
+            let parsed = value.trim().parse::<u32>().ok();
+            if let Some(format) = key.strip_prefix("format.") {
+                let duplicate = |v| profile.formats.iter().any(|&(_, u)| u == v);
+                match (surface_format_from_name(format), parsed) {
+                    (Some(f), Some(v)) if !duplicate(v) => profile.formats.push((f, v)),
+                    _ => rejected.push((key.to_string(), value.to_string())),
+                }
+            } else if let Some(resource) = key.strip_prefix("resource_id.") {
+                let resource = SharedResource::ALL
+                    .into_iter()
+                    .find(|r| r.key().eq_ignore_ascii_case(resource));
+                match (resource, parsed) {
+                    (Some(r), Some(v)) => profile.resource_ids.push((r, v)),
+                    _ => rejected.push((key.to_string(), value.to_string())),
+                }
+            }
+        }
+        (profile, rejected)
+    }
+}
+
+/// Cyberpunk 2077's `FfxSurfaceFormat`, in enum order. R32_UINT = 7,
+/// R16G16_FLOAT = 14 and R32_FLOAT = 24 are confirmed from the original
+/// DLL's shared resource descriptions; they fit the header enum without the
+/// formats later SDKs added (R32G32B32_FLOAT and the B8G8R8A8 family), which
+/// is the numbering the rest of the table assumes.
+const CYBERPUNK_2077_FORMATS: [FfxSurfaceFormat; 34] = {
+    use FfxSurfaceFormat::*;
+    [
+        Unknown,
+        R32G32B32A32Typeless,
+        R32G32B32A32Uint,
+        R32G32B32A32Float,
+        R16G16B16A16Float,
+        R32G32Float,
+        R8Uint,
+        R32Uint,
+        R8G8B8A8Typeless,
+        R8G8B8A8Unorm,
+        R8G8B8A8Snorm,
+        R8G8B8A8Srgb,
+        R11G11B10Float,
+        R10G10B10A2Unorm,
+        R16G16Float,
+        R16G16Uint,
+        R16G16Sint,
+        R16Float,
+        R16Uint,
+        R16Unorm,
+        R16Snorm,
+        R8Unorm,
+        R8G8Unorm,
+        R8G8Uint,
+        R32Float,
+        R9G9B9E5Sharedexp,
+        R16G16B16A16Typeless,
+        R32G32Typeless,
+        R10G10B10A2Typeless,
+        R16G16Typeless,
+        R16Typeless,
+        R8Typeless,
+        R8G8Typeless,
+        R32Typeless,
+    ]
+};
+
+/// Cyberpunk 2077. Layouts and format values confirmed by hex-dumping the
+/// original DLL's `ffxFsr3UpscalerGetSharedResourceDescriptions` output and
+/// scanning its dispatch descriptors (see `fsr3_types`).
+pub fn cyberpunk_2077() -> GameProfile {
+    GameProfile {
+        name: "Cyberpunk 2077".to_string(),
+        executables: vec!["Cyberpunk2077.exe".to_string()],
+        dispatch_abi: DispatchAbi::NoUpscaleSize,
+        resource_abi: ResourceAbi::Packed,
+        formats: CYBERPUNK_2077_FORMATS
+            .into_iter()
+            .zip(0..)
+            .filter(|&(f, v)| f as u32 != v)
+            .collect(),
+        resource_ids: Vec::new(),
+    }
+}
+
+/// Titles built against the FidelityFX SDK v1.1.4 headers.
+pub fn sdk_v1() -> GameProfile {
+    GameProfile {
+        name: "FidelityFX SDK v1.1.4".to_string(),
+        executables: Vec::new(),
+        dispatch_abi: DispatchAbi::Sdk,
+        resource_abi: ResourceAbi::Sdk,
+        formats: Vec::new(),
+        resource_ids: Vec::new(),
+    }
+}
+
+/// Built-in profiles, in lookup order. Profiles from `oxr.ini` are searched
+/// first, so they can override these.
+pub fn builtin() -> Vec<GameProfile> {
+    vec![cyberpunk_2077(), sdk_v1()]
+}
+
+/// Name used when neither `[game] profile` nor the executable selects a
+/// profile. The proxy was reverse-engineered against Cyberpunk, so its
+/// layouts are the long-standing default.
+pub const DEFAULT_PROFILE: &str = "Cyberpunk 2077";
+
+/// `FfxSurfaceFormat` variant by name, case-insensitive.
+fn surface_format_from_name(name: &str) -> Option<FfxSurfaceFormat> {
+    let name = name.trim();
+    FfxSurfaceFormat::ALL
+        .into_iter()
+        .find(|f| format!("{f:?}").eq_ignore_ascii_case(name))
+}
+
+/// File name of the process executable.
+fn host_executable() -> Option<String> {
+    use windows::Win32::System::LibraryLoader::GetModuleFileNameW;
+
+    let mut buf = vec![0u16; 512];
+    let len = unsafe { GetModuleFileNameW(None, &mut buf) } as usize;
+    if len == 0 {
+        return None;
+    }
+    let path = PathBuf::from(String::from_utf16_lossy(&buf[..len]));
+    path.file_name().map(|n| n.to_string_lossy().into_owned())
+}
+
+/// Pick a profile: `forced` by name, else by `exe`, else [`DEFAULT_PROFILE`].
+pub fn select(
+    mut profiles: Vec<GameProfile>,
+    forced: Option<&str>,
+    exe: Option<&str>,
+) -> GameProfile {
+    let by_name = forced.and_then(|name| {
+        let found = profiles
+            .iter()
+            .position(|p| p.name.eq_ignore_ascii_case(name));
+        if found.is_none() {
+            warn!("game profile: [game] profile = {:?} not found", name);
+        }
+        found
+    });
+    let by_exe = || {
+        let exe = exe?;
+        profiles
+            .iter()
+            .position(|p| p.executables.iter().any(|e| e.eq_ignore_ascii_case(exe)))
+    };
+    let index = by_name
+        .or_else(by_exe)
+        .or_else(|| profiles.iter().position(|p| p.name == DEFAULT_PROFILE))
+        .unwrap_or(0);
+    profiles.swap_remove(index)
+}
+
+/// Profile for the running game, resolved once.
+pub fn active() -> &'static GameProfile {
+    static ACTIVE: OnceLock<GameProfile> = OnceLock::new();
+    ACTIVE.get_or_init(|| {
+        let s = settings::get();
+        let mut profiles = s.game_profiles.clone();
+        profiles.extend(builtin());
+        let exe = host_executable();
+        let profile = select(profiles, s.game_profile.as_deref(), exe.as_deref());
+        info!(
+            exe = ?exe,
+            dispatch_abi = ?profile.dispatch_abi,
+            resource_abi = ?profile.resource_abi,
+            "game profile: {}",
+            profile.name
+        );
+        profile
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: FfxDimensions2D = FfxDimensions2D {
+        width: 2560,
+        height: 1440,
+    };
+
+    fn wide(ptr: *const u16) -> String {
+        let mut len = 0;
+        while unsafe { *ptr.add(len) } != 0 {
+            len += 1;
+        }
+        String::from_utf16(unsafe { std::slice::from_raw_parts(ptr, len) }).unwrap()
+    }
+
+    #[test]
+    fn cyberpunk_shared_resources() {
+        let profile = cyberpunk_2077();
+        assert_eq!(profile.resource_abi, ResourceAbi::Packed);
+        // (resource, game format, id, usage)
+        let expected = [
+            (SharedResource::ReconstructedPrevNearestDepth, 7, 8, 2),
+            (SharedResource::DilatedDepth, 24, 10, 3),
+            (SharedResource::DilatedMotionVectors, 14, 9, 3),
+        ];
+        for (resource, format, id, usage) in expected {
+            let d = profile.packed_description(resource, SIZE);
+            assert_eq!(d.heap_type, 0);
+            assert_eq!(d.initial_state, 2);
+            assert_eq!(d.id, id);
+            assert_eq!(d.init_data.type_, 0);
+            assert_eq!(d.init_data.size, 0);
+            assert!(d.init_data.buffer.is_null());
+            assert_eq!(wide(d.name), resource.name());
+
+            let r = d.resource_description;
+            assert_eq!(r.type_, FfxResourceType::Texture2D as u32);
+            assert_eq!(r.format, format, "{resource:?}");
+            assert_eq!(
+                (r.width, r.height, r.depth, r.mip_count),
+                (2560, 1440, 1, 1)
+            );
+            assert_eq!(r.flags, FfxResourceFlags::Aliasable as u32);
+            assert_eq!(r.usage, usage);
+        }
+    }
+
+    #[test]
+    fn sdk_shared_resources_use_header_values() {
+        let profile = sdk_v1();
+        for resource in SharedResource::ALL {
+            let d = profile.sdk_description(resource, SIZE);
+            assert_eq!(d.resource_description.format, resource.format() as u32);
+            assert_eq!(d.id, resource.sdk_id());
+            assert_eq!(d.init_data.type_, 1);
+            assert_eq!(wide(d.name), resource.name());
+        }
+    }
+
+    /// Known games: (exe, profile, dispatch ABI, resource ABI,
+    /// [(header format, game value)]).
+    #[allow(clippy::type_complexity)]
+    const COMPATIBILITY: [(
+        &str,
+        &str,
+        DispatchAbi,
+        ResourceAbi,
+        &[(FfxSurfaceFormat, u32)],
+    ); 2] = [
+        (
+            "Cyberpunk2077.exe",
+            "Cyberpunk 2077",
+            DispatchAbi::NoUpscaleSize,
+            ResourceAbi::Packed,
+            &[
+                (FfxSurfaceFormat::R16G16B16A16Float, 4),
+                (FfxSurfaceFormat::R32G32Float, 5),
+                (FfxSurfaceFormat::R8Uint, 6),
+                (FfxSurfaceFormat::R32Uint, 7),
+                (FfxSurfaceFormat::R8G8B8A8Unorm, 9),
+                (FfxSurfaceFormat::R10G10B10A2Unorm, 13),
+                (FfxSurfaceFormat::R16G16Float, 14),
+                (FfxSurfaceFormat::R32Float, 24),
+                (FfxSurfaceFormat::R32Typeless, 33),
+                (FfxSurfaceFormat::R32G32B32Float, 0),
+                (FfxSurfaceFormat::B8G8R8A8Unorm, 0),
+            ],
+        ),
+        (
+            "SomeSdkTitle.exe",
+            "FidelityFX SDK v1.1.4",
+            DispatchAbi::Sdk,
+            ResourceAbi::Sdk,
+            &[
+                (FfxSurfaceFormat::R32Uint, 8),
+                (FfxSurfaceFormat::B8G8R8A8Unorm, 14),
+                (FfxSurfaceFormat::R16G16Float, 18),
+                (FfxSurfaceFormat::R32Float, 28),
+            ],
+        ),
+    ];
+
+    #[test]
+    fn compatibility_table() {
+        for (exe, name, dispatch_abi, resource_abi, formats) in COMPATIBILITY {
+            let forced = (exe == "SomeSdkTitle.exe").then_some(name);
+            let profile = select(builtin(), forced, Some(exe));
+            assert_eq!(profile.name, name);
+            assert_eq!(profile.dispatch_abi, dispatch_abi, "{name}");
+            assert_eq!(profile.resource_abi, resource_abi, "{name}");
+            for &(format, value) in formats {
+                assert_eq!(profile.game_format(format), value, "{name} {format:?}");
+                if value != 0 {
+                    assert_eq!(
+                        profile.header_format(value),
+                        format as u32,
+                        "{name} {value}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn executable_match_is_case_insensitive() {
+        let profile = select(builtin(), None, Some("CYBERPUNK2077.EXE"));
+        assert_eq!(profile.name, "Cyberpunk 2077");
+        let profile = select(builtin(), None, Some("unknown.exe"));
+        assert_eq!(profile.name, DEFAULT_PROFILE);
+    }
+
+    #[test]
+    fn cyberpunk_numbering_is_invertible() {
+        let profile = cyberpunk_2077();
+        let mut seen = Vec::new();
+        for value in 0..CYBERPUNK_2077_FORMATS.len() as u32 {
+            let header = profile.header_format(value);
+            assert!(!seen.contains(&header), "{value} reuses {header}");
+            seen.push(header);
+            let format = FfxSurfaceFormat::ALL[header as usize];
+            assert_eq!(profile.game_format(format), value);
+        }
+        // Past the end of the game's enum, nothing is known
+        for value in CYBERPUNK_2077_FORMATS.len() as u32..38 {
+            assert_eq!(profile.header_format(value), 0);
+        }
+    }
+
+    #[test]
+    fn profile_from_ini_section() {
+        let keys = [
+            ("executables", "A.exe, b.exe"),
+            ("dispatch_abi", "no_upscale_size"),
+            ("resource_abi", "packed"),
+            ("format.r32uint", "7"),
+            ("format.R8G8B8A8Typeless", "8"),
+            ("format.R16G16Float", "7"),
+            ("format.NotAFormat", "3"),
+            ("resource_id.dilated_depth", "11"),
+        ];
+        let names: Vec<&str> = keys.iter().map(|(k, _)| *k).collect();
+        let get = |k: &str| keys.iter().find(|(key, _)| *key == k).map(|(_, v)| *v);
+        let (profile, rejected) = GameProfile::parse("mine", &names, get);
+
+        assert_eq!(profile.executables, ["A.exe", "b.exe"]);
+        assert_eq!(profile.dispatch_abi, DispatchAbi::NoUpscaleSize);
+        assert_eq!(profile.resource_abi, ResourceAbi::Packed);
+        assert_eq!(
+            profile.formats,
+            [
+                (FfxSurfaceFormat::R32Uint, 7),
+                (FfxSurfaceFormat::R8G8B8A8Typeless, 8),
+            ]
+        );
+        assert_eq!(profile.resource_id(SharedResource::DilatedDepth), 11);
+        assert_eq!(profile.resource_id(SharedResource::DilatedMotionVectors), 9);
+        let rejected: Vec<&str> = rejected.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(rejected, ["format.R16G16Float", "format.NotAFormat"]);
+
+        // R8Uint's header value 7 is the game's R32Uint, so it has none
+        assert_eq!(profile.game_format(FfxSurfaceFormat::R8Uint), 0);
+        // 9 would be R8G8B8A8Typeless, which the game numbers 8
+        assert_eq!(profile.header_format(9), 0);
+        assert_eq!(profile.header_format(10), 10);
+    }
+
+    #[test]
+    fn format_names_come_from_the_enum() {
+        for format in FfxSurfaceFormat::ALL {
+            let name = format!("{format:?}").to_ascii_lowercase();
+            assert_eq!(surface_format_from_name(&name), Some(format));
+        }
+        assert_eq!(surface_format_from_name("R32_UINT"), None);
+    }
+
+    #[test]
+    fn names_outlive_the_call() {
+        let a = SharedResource::DilatedDepth.wide_name().as_ptr();
+        let b = SharedResource::DilatedDepth.wide_name().as_ptr();
+        assert_eq!(a, b);
+    }
+}
@@ -1,9 +1,11 @@
-use std::sync::OnceLock;
+use fsr_sys::imba::PASS_COUNT;
+use std::sync::{Arc, OnceLock};
 use tracing::{error, info, warn};
 use windows::Win32::Graphics::Direct3D::ID3DBlob;
 use windows::Win32::Graphics::Direct3D12::*;
 use windows::Win32::Graphics::Dxgi::Common::*;
 
+use crate::context::{ContextKey, PerContext};
 use crate::fsr3_types::FfxSurfaceFormat;
 
 const VS_DXIL: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/blit_vs.dxil"));
@@ -35,8 +37,20 @@ const SGSR2_3P_UPSCALE_PS_DXIL: &[u8] = include_bytes!(concat!(
     "/SGSRv2_3Pass_sgsr2_3p_upscale_ps.dxil"
 ));
 
-// AA compute shaders (17 PSOs, indexed by PassType)
-const AA_CS_DXIL: [&[u8]; 17] = [
+const REACTIVE_MASK_CS_DXIL: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/reactive_mask_cs.dxil"));
+const REACTIVE_BLEND_CS_DXIL: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/reactive_blend_cs.dxil"));
+const INPUT_NORMALIZE_CS_DXIL: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/input_normalize_cs.dxil"));
+const EXPOSURE_HISTOGRAM_CS_DXIL: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/exposure_histogram_cs.dxil"));
+const EXPOSURE_RESOLVE_CS_DXIL: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/exposure_resolve_cs.dxil"));
+const SCENE_STATS_CS_DXIL: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/scene_stats_cs.dxil"));
+
+// AA compute shaders (18 PSOs, indexed by PassType); the last is in-tree
+const AA_CS_DXIL: [&[u8]; PASS_COUNT] = [
     include_bytes!(concat!(env!("OUT_DIR"), "/aa_0_PixelUnshuffleCS.dxil")),
     include_bytes!(concat!(env!("OUT_DIR"), "/aa_1_ConvCS.dxil")),
     include_bytes!(concat!(env!("OUT_DIR"), "/aa_2_GNStatsCS.dxil")),
@@ -54,8 +68,19 @@ const AA_CS_DXIL: [&[u8]; 17] = [
     include_bytes!(concat!(env!("OUT_DIR"), "/aa_1d_Conv3x3_16x12CS.dxil")),
     include_bytes!(concat!(env!("OUT_DIR"), "/aa_1e_Conv3x3_12x12CS.dxil")),
     include_bytes!(concat!(env!("OUT_DIR"), "/aa_1f_Conv3x3S2_16x32CS.dxil")),
+    include_bytes!(concat!(env!("OUT_DIR"), "/imba_upscale_out_cs.dxil")),
+    include_bytes!(concat!(env!("OUT_DIR"), "/imba_debug_cs.dxil")),
+    include_bytes!(concat!(env!("OUT_DIR"), "/imba_unshuffle_padded_cs.dxil")),
+    include_bytes!(concat!(env!("OUT_DIR"), "/imba_scale_mv_padded_cs.dxil")),
+    include_bytes!(concat!(env!("OUT_DIR"), "/imba_history_mask_cs.dxil")),
 ];
 
+/// Descriptor slots per SRV/UAV heap; the slot map is in `try_init`.
+const SRV_HEAP_SIZE: u32 = 81;
+/// Render target slots per RTV heap.
+const RTV_HEAP_SIZE: u32 = 11;
+
+#[derive(Clone)]
 pub struct GpuState {
     pub device: ID3D12Device,
     pub root_signature: ID3D12RootSignature,
@@ -73,6 +98,15 @@ pub struct GpuState {
     pub pso_sgsr2_3p_upscale: ID3D12PipelineState,
     pub aa_root_signature: ID3D12RootSignature,
     pub aa_psos: Vec<ID3D12PipelineState>,
+    pub reactive_root_signature: ID3D12RootSignature,
+    pub pso_reactive_mask: ID3D12PipelineState,
+    pub pso_reactive_blend: ID3D12PipelineState,
+    pub compute_2uav_root_signature: ID3D12RootSignature,
+    pub pso_input_normalize: ID3D12PipelineState,
+    pub pso_exposure_histogram: ID3D12PipelineState,
+    pub pso_exposure_resolve: ID3D12PipelineState,
+    pub compute_3srv_root_signature: ID3D12RootSignature,
+    pub pso_scene_stats: ID3D12PipelineState,
     pub srv_heap: ID3D12DescriptorHeap,
     pub rtv_heap: ID3D12DescriptorHeap,
     pub srv_descriptor_size: u32,
@@ -126,6 +160,62 @@ pub unsafe fn get_or_init(
         .as_ref()
 }
 
+/// The GPU state if a dispatch has already initialized it.
+///
+/// Passes that cannot know the output format (reactive mask generation runs
+/// before the first upscale dispatch) use this instead of [`get_or_init`].
+pub fn get() -> Option<&'static GpuState> {
+    GPU_STATE.get().and_then(Option::as_ref)
+}
+
+// Every pass writes its descriptors into fixed slots while recording, and the
+// GPU reads them when the game executes the command list. Two contexts
+// recorded into the same frame (split-screen) would overwrite each other's
+// slots, so each context gets a copy of the shared state with its own
+// descriptor heaps; PSOs and root signatures stay shared. The overlay keeps
+// using the shared heaps, where its font descriptor lives.
+static CONTEXT_GPU: PerContext<Arc<GpuState>> = PerContext::new();
+
+/// `shared` with descriptor heaps owned by `context`, created on first use.
+pub unsafe fn for_context(context: ContextKey, shared: &GpuState) -> Option<Arc<GpuState>> {
+    let created = CONTEXT_GPU.get_or_create(
+        context,
+        |_| false,
+        || -> Result<_, String> {
+            let mut gpu = shared.clone();
+            gpu.srv_heap = create_descriptor_heap(
+                &shared.device,
+                D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
+                SRV_HEAP_SIZE,
+                true,
+            )?;
+            gpu.rtv_heap = create_descriptor_heap(
+                &shared.device,
+                D3D12_DESCRIPTOR_HEAP_TYPE_RTV,
+                RTV_HEAP_SIZE,
+                false,
+            )?;
+            info!(
+                "gpu_pipeline: context 0x{:x}: descriptor heaps created",
+                context
+            );
+            Ok(Arc::new(gpu))
+        },
+    );
+    match created {
+        Ok(gpu) => Some(Arc::clone(&gpu)),
+        Err(e) => {
+            error!("gpu_pipeline: context 0x{:x}: {}", context, e);
+            None
+        }
+    }
+}
+
+/// Release the descriptor heaps of a destroyed context.
+pub fn release(key: ContextKey) {
+    CONTEXT_GPU.remove(key);
+}
+
 unsafe fn try_init(
     cmd_list: &ID3D12GraphicsCommandList,
     output_format: DXGI_FORMAT,
@@ -201,7 +291,7 @@ unsafe fn try_init(
         typed_format
     );
 
-    // --- SGSRv2: separate root signature with 32 root constants + 3 SRVs ---
+    // --- SGSRv2: separate root signature with 32 root constants + 4 SRVs ---
     let sgsr2_root_signature = create_sgsr2_root_signature(&device)?;
     info!("gpu_pipeline: SGSRv2 root signature created");
 
@@ -263,32 +353,85 @@ unsafe fn try_init(
         typed_format
     );
 
-    // --- AA: compute root signature + 17 PSOs ---
+    // --- AA: compute root signature + one PSO per pass type ---
     let aa_root_signature = create_aa_root_signature(&device)?;
     info!("gpu_pipeline: AA root signature created");
 
-    let mut aa_psos = Vec::with_capacity(17);
+    let mut aa_psos = Vec::with_capacity(PASS_COUNT);
     for (i, cs_dxil) in AA_CS_DXIL.iter().enumerate() {
         let pso = create_compute_pso(&device, &aa_root_signature, cs_dxil)?;
         aa_psos.push(pso);
         if i == 0 {
-            info!("gpu_pipeline: AA PSOs creating (17 total)...");
+            info!("gpu_pipeline: AA PSOs creating ({} total)...", PASS_COUNT);
         }
     }
     info!("gpu_pipeline: all {} AA PSOs created", aa_psos.len());
 
+    // --- Reactive mask: compute root signature (8 constants + 2 SRVs + 1 UAV) ---
+    let reactive_root_signature = create_compute_root_signature(&device, 2, 1)?;
+    let pso_reactive_mask =
+        create_compute_pso(&device, &reactive_root_signature, REACTIVE_MASK_CS_DXIL)?;
+    let pso_reactive_blend =
+        create_compute_pso(&device, &reactive_root_signature, REACTIVE_BLEND_CS_DXIL)?;
+    info!("gpu_pipeline: reactive mask PSOs created");
+
+    // --- Input normalization and exposure: same layout with 2 UAVs ---
+    let compute_2uav_root_signature = create_compute_root_signature(&device, 2, 2)?;
+    let pso_input_normalize = create_compute_pso(
+        &device,
+        &compute_2uav_root_signature,
+        INPUT_NORMALIZE_CS_DXIL,
+    )?;
+    let pso_exposure_histogram = create_compute_pso(
+        &device,
+        &compute_2uav_root_signature,
+        EXPOSURE_HISTOGRAM_CS_DXIL,
+    )?;
+    let pso_exposure_resolve = create_compute_pso(
+        &device,
+        &compute_2uav_root_signature,
+        EXPOSURE_RESOLVE_CS_DXIL,
+    )?;
+    info!("gpu_pipeline: input normalization and exposure PSOs created");
+
+    // --- Scene-cut statistics: 3 SRVs + 1 UAV ---
+    let compute_3srv_root_signature = create_compute_root_signature(&device, 3, 1)?;
+    let pso_scene_stats =
+        create_compute_pso(&device, &compute_3srv_root_signature, SCENE_STATS_CS_DXIL)?;
+    info!("gpu_pipeline: scene-cut statistics PSO created");
+
     // Slot 0: blit color SRV, Slot 1: imgui font SRV, Slots 2-8: debug textures, Slot 9: RCAS
-    // Slots 10-11: SGSRv2 2-pass convert (depth, velocity), Slots 12-14: SGSRv2 2-pass upscale
+    // Slots 10-11: SGSRv2 2-pass convert (depth, velocity), Slot 12: IMBA upscaler blit
+    // Slots 13-14: unused
     // Slots 15-17: SGSRv2 3-pass convert (depth, velocity, color)
     // Slots 18-20: SGSRv2 3-pass activate (ycocg, mda, prev_luma)
-    // Slots 21-23: SGSRv2 3-pass upscale (prev_history, mdca, ycocg)
-    // Slots 25-31: AA SRV table (t0-t6), Slots 32-35: AA UAV table (u0-u3)
-    let srv_heap =
-        create_descriptor_heap(&device, D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV, 36, true)?;
+    // Slots 21-24: SGSRv2 3-pass upscale (prev_history, mdca, ycocg, reactive)
+    // Slots 25-33: AA SRV table (t0-t8), Slots 34-35: unused
+    // Slots 36-38: reactive mask generation (opaque, color, out UAV)
+    // Slots 39-41: AA reactive blend (color, reactive, out UAV)
+    // Slots 42-45: SGSRv2 2-pass upscale (prev_history, mdc, color, reactive)
+    // Slots 46-49: input normalization (depth, velocity, out depth UAV, out velocity UAV)
+    // Slots 50-53: exposure (color, game exposure, histogram UAV, state UAV)
+    // Slots 54-55: AA composition blend (color, composition)
+    // Slots 56-57: SGSRv2 2-pass / 3-pass composition mask
+    // Slots 58-61: scene-cut statistics (color, depth, velocity, stats UAV)
+    // Slots 62-70: AA comparison model SRV table (t0-t8), Slots 71-72: unused
+    // Slots 73-76: AA UAV table (u0-u3), Slots 77-80: the comparison model's
+    let srv_heap = create_descriptor_heap(
+        &device,
+        D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
+        SRV_HEAP_SIZE,
+        true,
+    )?;
     // Slots 0-1: existing, Slot 2-3: SGSRv2 2-pass
     // Slots 4-5: 3-pass convert MRT, Slots 6-7: 3-pass activate MRT, Slot 8: 3-pass upscale
-    // Slot 9: 3-pass history clear scratch
-    let rtv_heap = create_descriptor_heap(&device, D3D12_DESCRIPTOR_HEAP_TYPE_RTV, 10, false)?;
+    // Slot 9: 3-pass history clear scratch, Slot 10: IMBA upscaler blit
+    let rtv_heap = create_descriptor_heap(
+        &device,
+        D3D12_DESCRIPTOR_HEAP_TYPE_RTV,
+        RTV_HEAP_SIZE,
+        false,
+    )?;
     info!("gpu_pipeline: descriptor heaps created");
 
     let srv_descriptor_size =
@@ -313,6 +456,15 @@ unsafe fn try_init(
         pso_sgsr2_3p_upscale,
         aa_root_signature,
         aa_psos,
+        reactive_root_signature,
+        pso_reactive_mask,
+        pso_reactive_blend,
+        compute_2uav_root_signature,
+        pso_input_normalize,
+        pso_exposure_histogram,
+        pso_exposure_resolve,
+        compute_3srv_root_signature,
+        pso_scene_stats,
         srv_heap,
         rtv_heap,
         srv_descriptor_size,
@@ -322,10 +474,11 @@ unsafe fn try_init(
 }
 
 unsafe fn create_root_signature(device: &ID3D12Device) -> Result<ID3D12RootSignature, String> {
+    // 5 root constants (b0): uvScale (2) + inputSize (2) + hdr (1); RCAS uses 0 and 4
     let constants = D3D12_ROOT_CONSTANTS {
         ShaderRegister: 0,
         RegisterSpace: 0,
-        Num32BitValues: 4,
+        Num32BitValues: 5,
     };
 
     let srv_range = D3D12_DESCRIPTOR_RANGE {
@@ -444,11 +597,18 @@ unsafe fn create_sgsr2_root_signature(
 
     let srv_range = D3D12_DESCRIPTOR_RANGE {
         RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
-        NumDescriptors: 3,
+        NumDescriptors: 4,
         BaseShaderRegister: 0,
         RegisterSpace: 0,
         OffsetInDescriptorsFromTableStart: 0,
     };
+    let composition_range = D3D12_DESCRIPTOR_RANGE {
+        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+        NumDescriptors: 1,
+        BaseShaderRegister: 5,
+        RegisterSpace: 0,
+        OffsetInDescriptorsFromTableStart: 0,
+    };
 
     let params = [
         D3D12_ROOT_PARAMETER {
@@ -468,6 +628,28 @@ unsafe fn create_sgsr2_root_signature(
             },
             ShaderVisibility: D3D12_SHADER_VISIBILITY_PIXEL,
         },
+        // [2] Root SRV t4: per-context ExposureState (see exposure.rs)
+        D3D12_ROOT_PARAMETER {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_SRV,
+            Anonymous: D3D12_ROOT_PARAMETER_0 {
+                Descriptor: D3D12_ROOT_DESCRIPTOR {
+                    ShaderRegister: 4,
+                    RegisterSpace: 0,
+                },
+            },
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_PIXEL,
+        },
+        // [3] SRV table t5: transparency & composition mask
+        D3D12_ROOT_PARAMETER {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
+            Anonymous: D3D12_ROOT_PARAMETER_0 {
+                DescriptorTable: D3D12_ROOT_DESCRIPTOR_TABLE {
+                    NumDescriptorRanges: 1,
+                    pDescriptorRanges: &composition_range,
+                },
+            },
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_PIXEL,
+        },
     ];
 
     let static_sampler = D3D12_STATIC_SAMPLER_DESC {
@@ -538,11 +720,18 @@ unsafe fn create_sgsr2_3pass_root_signature(
 
     let srv_range = D3D12_DESCRIPTOR_RANGE {
         RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
-        NumDescriptors: 3,
+        NumDescriptors: 4,
         BaseShaderRegister: 0,
         RegisterSpace: 0,
         OffsetInDescriptorsFromTableStart: 0,
     };
+    let composition_range = D3D12_DESCRIPTOR_RANGE {
+        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+        NumDescriptors: 1,
+        BaseShaderRegister: 5,
+        RegisterSpace: 0,
+        OffsetInDescriptorsFromTableStart: 0,
+    };
 
     let params = [
         D3D12_ROOT_PARAMETER {
@@ -562,6 +751,28 @@ unsafe fn create_sgsr2_3pass_root_signature(
             },
             ShaderVisibility: D3D12_SHADER_VISIBILITY_PIXEL,
         },
+        // [2] Root SRV t4: per-context ExposureState (see exposure.rs)
+        D3D12_ROOT_PARAMETER {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_SRV,
+            Anonymous: D3D12_ROOT_PARAMETER_0 {
+                Descriptor: D3D12_ROOT_DESCRIPTOR {
+                    ShaderRegister: 4,
+                    RegisterSpace: 0,
+                },
+            },
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_PIXEL,
+        },
+        // [3] SRV table t5: transparency & composition mask
+        D3D12_ROOT_PARAMETER {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
+            Anonymous: D3D12_ROOT_PARAMETER_0 {
+                DescriptorTable: D3D12_ROOT_DESCRIPTOR_TABLE {
+                    NumDescriptorRanges: 1,
+                    pDescriptorRanges: &composition_range,
+                },
+            },
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_PIXEL,
+        },
     ];
 
     // Two static samplers: s0 = linear clamp, s1 = point clamp
@@ -650,10 +861,10 @@ unsafe fn create_aa_root_signature(device: &ID3D12Device) -> Result<ID3D12RootSi
         Num32BitValues: 28,
     };
 
-    // [1] SRV table: t0-t6
+    // [1] SRV table: t0-t8 (t7-t8: reactive and composition masks)
     let srv_range = D3D12_DESCRIPTOR_RANGE {
         RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
-        NumDescriptors: 7,
+        NumDescriptors: 9,
         BaseShaderRegister: 0,
         RegisterSpace: 0,
         OffsetInDescriptorsFromTableStart: 0,
@@ -754,6 +965,109 @@ unsafe fn create_aa_root_signature(device: &ID3D12Device) -> Result<ID3D12RootSi
         .map_err(|e| format!("AA CreateRootSignature failed: {}", e))
 }
 
+/// Compute layout shared by the reactive mask, reactive blend and input
+/// normalization passes: 8 constants, SRVs t0-t1, `num_uavs` UAVs from u0.
+unsafe fn create_compute_root_signature(
+    device: &ID3D12Device,
+    num_srvs: u32,
+    num_uavs: u32,
+) -> Result<ID3D12RootSignature, String> {
+    // [0] 8 root constants (b0)
+    let constants = D3D12_ROOT_CONSTANTS {
+        ShaderRegister: 0,
+        RegisterSpace: 0,
+        Num32BitValues: 8,
+    };
+
+    // [1] SRV table: t0..
+    let srv_range = D3D12_DESCRIPTOR_RANGE {
+        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+        NumDescriptors: num_srvs,
+        BaseShaderRegister: 0,
+        RegisterSpace: 0,
+        OffsetInDescriptorsFromTableStart: 0,
+    };
+
+    // [2] UAV table: u0..
+    let uav_range = D3D12_DESCRIPTOR_RANGE {
+        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_UAV,
+        NumDescriptors: num_uavs,
+        BaseShaderRegister: 0,
+        RegisterSpace: 0,
+        OffsetInDescriptorsFromTableStart: 0,
+    };
+
+    let params = [
+        D3D12_ROOT_PARAMETER {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_32BIT_CONSTANTS,
+            Anonymous: D3D12_ROOT_PARAMETER_0 {
+                Constants: constants,
+            },
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_ALL,
+        },
+        D3D12_ROOT_PARAMETER {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
+            Anonymous: D3D12_ROOT_PARAMETER_0 {
+                DescriptorTable: D3D12_ROOT_DESCRIPTOR_TABLE {
+                    NumDescriptorRanges: 1,
+                    pDescriptorRanges: &srv_range,
+                },
+            },
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_ALL,
+        },
+        D3D12_ROOT_PARAMETER {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
+            Anonymous: D3D12_ROOT_PARAMETER_0 {
+                DescriptorTable: D3D12_ROOT_DESCRIPTOR_TABLE {
+                    NumDescriptorRanges: 1,
+                    pDescriptorRanges: &uav_range,
+                },
+            },
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_ALL,
+        },
+    ];
+
+    let desc = D3D12_ROOT_SIGNATURE_DESC {
+        NumParameters: params.len() as u32,
+        pParameters: params.as_ptr(),
+        NumStaticSamplers: 0,
+        pStaticSamplers: std::ptr::null(),
+        Flags: D3D12_ROOT_SIGNATURE_FLAG_NONE,
+    };
+
+    let mut blob: Option<ID3DBlob> = None;
+    let mut error_blob: Option<ID3DBlob> = None;
+
+    if let Err(e) = D3D12SerializeRootSignature(
+        &desc,
+        D3D_ROOT_SIGNATURE_VERSION_1,
+        &mut blob,
+        Some(&mut error_blob),
+    ) {
+        if let Some(err_blob) = &error_blob {
+            let err_ptr = err_blob.GetBufferPointer() as *const u8;
+            let err_len = err_blob.GetBufferSize();
+            let err_msg =
+                std::str::from_utf8_unchecked(std::slice::from_raw_parts(err_ptr, err_len));
+            error!(
+                "Compute D3D12SerializeRootSignature error: {}",
+                err_msg.trim_end_matches('\0')
+            );
+        }
+        return Err(format!("Compute D3D12SerializeRootSignature failed: {}", e));
+    }
+
+    let blob =
+        blob.ok_or_else(|| "Compute D3D12SerializeRootSignature produced no blob".to_string())?;
+
+    device
+        .CreateRootSignature(
+            0,
+            std::slice::from_raw_parts(blob.GetBufferPointer() as *const u8, blob.GetBufferSize()),
+        )
+        .map_err(|e| format!("Compute CreateRootSignature failed: {}", e))
+}
+
 unsafe fn create_compute_pso(
     device: &ID3D12Device,
     root_sig: &ID3D12RootSignature,
@@ -0,0 +1,268 @@
+//! Depth and motion-vector normalization driven by the context creation flags.
+//!
+//! SGSRv2 and IMBA read reverse-Z depth and render-resolution UV motion vectors
+//! without jitter. Contexts created with standard depth, display-resolution
+//! vectors or jitter cancellation, and frames whose motion vector scale does
+//! not give UV units, get their inputs rewritten into per-context textures
+//! first, and the upscalers are handed those instead.
+//!
+//! Follows `fsr_sys::inputs::InputNormalization`; `input_normalize_cs.hlsl`
+//! is its GPU twin.
+
+use crate::context::{ContextKey, PerContext};
+use crate::dispatch::{apply_barriers, ffx_state_to_d3d12, transitions_if_needed};
+use crate::fsr3_types::FfxSurfaceFormat;
+use crate::gpu_pipeline::{self, GpuState};
+use crate::reactive_mask::create_uav;
+use crate::upscalers::{borrow_resource, create_typed_srv};
+use fsr_sys::inputs::{InputFlags, InputNormalization};
+use fsr_sys::{FfxApiResource, UpscaleFrame};
+use tracing::{error, info, warn};
+use windows::core::Interface;
+use windows::Win32::Graphics::Direct3D12::*;
+use windows::Win32::Graphics::Dxgi::Common::*;
+
+// --- SRV/UAV slots (each pair is one descriptor table) ---
+const SRV_DEPTH: u32 = 46;
+const SRV_VELOCITY: u32 = 47;
+const UAV_DEPTH: u32 = 48;
+const UAV_VELOCITY: u32 = 49;
+
+const GROUP_SIZE: u32 = 8;
+
+/// Matches `FLAG_FLIP_DEPTH` in the shader.
+const FLAG_FLIP_DEPTH: u32 = 1;
+
+struct NormalizedInputs {
+    depth: ID3D12Resource,
+    motion_vectors: ID3D12Resource,
+    width: u32,
+    height: u32,
+    prev_jitter: [f32; 2],
+}
+
+static INPUTS: PerContext<NormalizedInputs> = PerContext::new();
+
+/// Drop the normalized textures of a destroyed context.
+pub fn release(key: ContextKey) {
+    INPUTS.remove(key);
+}
+
+unsafe fn create_texture(
+    device: &ID3D12Device,
+    w: u32,
+    h: u32,
+    format: DXGI_FORMAT,
+) -> Result<ID3D12Resource, String> {
+    let heap_props = D3D12_HEAP_PROPERTIES {
+        Type: D3D12_HEAP_TYPE_DEFAULT,
+        ..Default::default()
+    };
+    let desc = D3D12_RESOURCE_DESC {
+        Dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+        Alignment: 0,
+        Width: w as u64,
+        Height: h,
+        DepthOrArraySize: 1,
+        MipLevels: 1,
+        Format: format,
+        SampleDesc: DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
+        Flags: D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS,
+    };
+
+    let mut resource: Option<ID3D12Resource> = None;
+    device
+        .CreateCommittedResource(
+            &heap_props,
+            D3D12_HEAP_FLAG_NONE,
+            &desc,
+            D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+            None,
+            &mut resource,
+        )
+        .map_err(|e| format!("CreateCommittedResource for {:?} failed: {}", format, e))?;
+    resource.ok_or_else(|| "CreateCommittedResource returned null".to_string())
+}
+
+/// Describe one of our textures as a frame input. It is left in
+/// UNORDERED_ACCESS, which differs from both states the upscalers read in, so
+/// their entry and exit transitions are never redundant.
+fn as_frame_input(
+    original: &FfxApiResource,
+    resource: &ID3D12Resource,
+    format: FfxSurfaceFormat,
+    w: u32,
+    h: u32,
+) -> FfxApiResource {
+    let mut input = *original;
+    input.resource = resource.as_raw();
+    input.description.type_ = fsr_sys::FFX_API_RESOURCE_TYPE_TEXTURE2D;
+    input.description.format = format as u32;
+    input.description.width = w;
+    input.description.height = h;
+    input.description.depth = 1;
+    input.description.mip_count = 1;
+    input.description.usage = fsr_sys::FFX_API_RESOURCE_USAGE_UAV;
+    input.state = fsr_sys::FFX_API_RESOURCE_STATE_UNORDERED_ACCESS;
+    input
+}
+
+/// Rewrite the frame's depth and motion vectors when `flags` say they differ
+/// from the upscalers' convention. Returns a copy of `d` pointing at the
+/// normalized textures, or `None` to use the game's inputs unchanged (nothing
+/// to do, missing inputs, or a failure that has been logged).
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn normalize(
+    context: ContextKey,
+    cmd_list: &ID3D12GraphicsCommandList,
+    gpu: &GpuState,
+    d: &UpscaleFrame,
+    flags: InputFlags,
+    render_w: u32,
+    render_h: u32,
+) -> Option<UpscaleFrame> {
+    let upscale_size = (d.upscale_size.width, d.upscale_size.height);
+    let mv_scale = [d.motion_vector_scale.x, d.motion_vector_scale.y];
+    if !flags.needs_normalization((render_w, render_h), upscale_size, mv_scale) {
+        return None;
+    }
+    let (Some(depth_res), Some(mv_res)) = (
+        borrow_resource(d.depth.resource),
+        borrow_resource(d.motion_vectors.resource),
+    ) else {
+        warn!("input_normalize: null depth or motion vectors, using inputs as-is");
+        return None;
+    };
+
+    let jitter = d.jitter_pixels();
+    let mut inputs = match INPUTS.get_or_create(
+        context,
+        |t| t.width != render_w || t.height != render_h,
+        || {
+            let depth = create_texture(&gpu.device, render_w, render_h, DXGI_FORMAT_R32_FLOAT)?;
+            let motion_vectors =
+                create_texture(&gpu.device, render_w, render_h, DXGI_FORMAT_R16G16_FLOAT)?;
+            info!(
+                ?flags,
+                "input_normalize: textures created ({}x{})", render_w, render_h
+            );
+            Ok::<_, String>(NormalizedInputs {
+                depth,
+                motion_vectors,
+                width: render_w,
+                height: render_h,
+                prev_jitter: jitter,
+            })
+        },
+    ) {
+        Ok(inputs) => inputs,
+        Err(e) => {
+            error!("input_normalize: {}, using inputs as-is", e);
+            return None;
+        }
+    };
+
+    let n = InputNormalization::new(
+        flags,
+        (render_w, render_h),
+        upscale_size,
+        mv_scale,
+        jitter,
+        inputs.prev_jitter,
+    );
+    inputs.prev_jitter = jitter;
+
+    let states = [
+        (
+            &depth_res,
+            ffx_state_to_d3d12(d.depth.state),
+            D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE,
+        ),
+        (
+            &mv_res,
+            ffx_state_to_d3d12(d.motion_vectors.state),
+            D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE,
+        ),
+    ];
+    apply_barriers(cmd_list, &transitions_if_needed(&states));
+
+    let srvs_ok = create_typed_srv(gpu, &depth_res, d.depth.description.format, SRV_DEPTH)
+        && create_typed_srv(
+            gpu,
+            &mv_res,
+            d.motion_vectors.description.format,
+            SRV_VELOCITY,
+        );
+    if srvs_ok {
+        create_uav(
+            gpu,
+            &inputs.depth,
+            FfxSurfaceFormat::R32Float as u32,
+            UAV_DEPTH,
+        );
+        create_uav(
+            gpu,
+            &inputs.motion_vectors,
+            FfxSurfaceFormat::R16G16Float as u32,
+            UAV_VELOCITY,
+        );
+
+        let constants = [
+            render_w | (render_h << 16),
+            if n.flip_depth { FLAG_FLIP_DEPTH } else { 0 },
+            n.mv_sample_scale[0].to_bits(),
+            n.mv_sample_scale[1].to_bits(),
+            n.mv_uv_scale[0].to_bits(),
+            n.mv_uv_scale[1].to_bits(),
+            n.jitter_cancellation[0].to_bits(),
+            n.jitter_cancellation[1].to_bits(),
+        ];
+        cmd_list.SetDescriptorHeaps(&[Some(gpu.srv_heap.clone())]);
+        cmd_list.SetComputeRootSignature(&gpu.compute_2uav_root_signature);
+        cmd_list.SetPipelineState(&gpu.pso_input_normalize);
+        cmd_list.SetComputeRoot32BitConstants(
+            0,
+            constants.len() as u32,
+            constants.as_ptr() as *const core::ffi::c_void,
+            0,
+        );
+        cmd_list.SetComputeRootDescriptorTable(1, gpu_pipeline::get_srv_gpu_handle(gpu, SRV_DEPTH));
+        cmd_list.SetComputeRootDescriptorTable(2, gpu_pipeline::get_srv_gpu_handle(gpu, UAV_DEPTH));
+        cmd_list.Dispatch(
+            render_w.div_ceil(GROUP_SIZE),
+            render_h.div_ceil(GROUP_SIZE),
+            1,
+        );
+    } else {
+        error!("input_normalize: unsupported depth or motion vector format, using inputs as-is");
+    }
+
+    let restore = states.map(|(res, before, after)| (res, after, before));
+    apply_barriers(cmd_list, &transitions_if_needed(&restore));
+    if !srvs_ok {
+        return None;
+    }
+
+    // The upscalers transition the textures out of UNORDERED_ACCESS, which
+    // orders their reads after these writes.
+    let mut frame = *d;
+    frame.depth = as_frame_input(
+        &d.depth,
+        &inputs.depth,
+        FfxSurfaceFormat::R32Float,
+        render_w,
+        render_h,
+    );
+    frame.motion_vectors = as_frame_input(
+        &d.motion_vectors,
+        &inputs.motion_vectors,
+        FfxSurfaceFormat::R16G16Float,
+        render_w,
+        render_h,
+    );
+    Some(frame)
+}
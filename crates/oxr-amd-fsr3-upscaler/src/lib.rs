@@ -1,21 +1,30 @@
 #![allow(non_snake_case)]
+mod context;
 mod dispatch;
+mod dynres;
+mod exposure;
 mod fsr3_types;
+mod game_profile;
 mod gpu_pipeline;
 mod imgui_renderer;
+mod input_normalize;
 mod logging;
 mod overlay;
 mod post_processing;
+mod reactive_mask;
 #[cfg(feature = "recording")]
 mod recording;
+mod scene_cut;
 mod settings;
 mod upscaler_type;
 mod upscalers;
 
 use core::ffi::c_void;
 use fsr3_types::*;
+use std::ffi::{CStr, CString};
+use std::sync::atomic::{AtomicPtr, Ordering};
 use std::sync::OnceLock;
-use tracing::info;
+use tracing::{error, info, warn};
 use windows::core::PCSTR;
 use windows::Win32::Foundation::HINSTANCE;
 use windows::Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryW};
@@ -27,29 +36,82 @@ struct FnTable {
         *const FfxFsr3UpscalerContextDescription,
     ) -> u32,
     ContextDestroy: unsafe extern "C" fn(*mut FfxFsr3UpscalerContext) -> u32,
-    ContextDispatch: unsafe extern "C" fn(
-        *mut FfxFsr3UpscalerContext,
-        *const FfxFsr3UpscalerDispatchDescription,
-    ) -> u32,
     GenReactiveMask: unsafe extern "C" fn(
         *mut FfxFsr3UpscalerContext,
         *const FfxFsr3UpscalerGenerateReactiveDescription,
     ) -> u32,
-    GetJitterOffset: unsafe extern "C" fn(*mut f32, *mut f32, i32, i32) -> u32,
-    GetJitterPhCount: unsafe extern "C" fn(i32, i32) -> i32,
     ResourceIsNull: unsafe extern "C" fn(FfxResource) -> u32,
-    SafeRelCopy: unsafe extern "C" fn(*mut c_void, *const c_void, u32),
-    SafeRelPipeline: unsafe extern "C" fn(*mut c_void, *mut c_void, u32),
-    SafeRelResource: unsafe extern "C" fn(*mut c_void, *const c_void, u32),
     AssertReport: unsafe extern "C" fn(*const i8, u32, *const i8, *const i8),
     AssertSetCb: unsafe extern "C" fn(*mut c_void),
+    // Run natively when the original lacks them
+    ContextDispatch: Option<
+        unsafe extern "C" fn(
+            *mut FfxFsr3UpscalerContext,
+            *const FfxFsr3UpscalerDispatchDescription,
+        ) -> u32,
+    >,
+    GetJitterOffset: Option<unsafe extern "C" fn(*mut f32, *mut f32, i32, i32) -> u32>,
+    GetJitterPhCount: Option<unsafe extern "C" fn(i32, i32) -> i32>,
 }
 
 static FN_TABLE: OnceLock<FnTable> = OnceLock::new();
-static MAX_RENDER_SIZE: OnceLock<FfxDimensions2D> = OnceLock::new();
 
-unsafe fn resolve(module: windows::Win32::Foundation::HMODULE, name: &[u8]) -> *const c_void {
-    GetProcAddress(module, PCSTR(name.as_ptr())).unwrap() as *const c_void
+/// Set only when `[original] forward` is on and the original DLL loaded with
+/// every required export; otherwise all exports run natively.
+fn original() -> Option<&'static FnTable> {
+    FN_TABLE.get()
+}
+
+unsafe fn resolve(
+    module: windows::Win32::Foundation::HMODULE,
+    name: &[u8],
+) -> Option<*const c_void> {
+    let f = resolve_optional(module, name);
+    if f.is_none() {
+        warn!(
+            "original DLL is missing export {}",
+            String::from_utf8_lossy(&name[..name.len() - 1])
+        );
+    }
+    f
+}
+
+unsafe fn resolve_optional(
+    module: windows::Win32::Foundation::HMODULE,
+    name: &[u8],
+) -> Option<*const c_void> {
+    GetProcAddress(module, PCSTR(name.as_ptr())).map(|f| f as *const c_void)
+}
+
+/// Load `ffx_fsr3upscaler_x64_original.dll` and resolve the forwarded exports.
+unsafe fn load_original() -> Option<FnTable> {
+    let wname: Vec<u16> = "ffx_fsr3upscaler_x64_original.dll\0"
+        .encode_utf16()
+        .collect();
+    let hmod = match LoadLibraryW(windows::core::PCWSTR(wname.as_ptr())) {
+        Ok(h) => h,
+        Err(e) => {
+            warn!("failed to load ffx_fsr3upscaler_x64_original.dll: {}", e);
+            return None;
+        }
+    };
+    Some(FnTable {
+        ContextCreate: std::mem::transmute(resolve(hmod, b"ffxFsr3UpscalerContextCreate\0")?),
+        ContextDestroy: std::mem::transmute(resolve(hmod, b"ffxFsr3UpscalerContextDestroy\0")?),
+        GenReactiveMask: std::mem::transmute(resolve(
+            hmod,
+            b"ffxFsr3UpscalerContextGenerateReactiveMask\0",
+        )?),
+        ResourceIsNull: std::mem::transmute(resolve(hmod, b"ffxFsr3UpscalerResourceIsNull\0")?),
+        AssertReport: std::mem::transmute(resolve(hmod, b"ffxAssertReport\0")?),
+        AssertSetCb: std::mem::transmute(resolve(hmod, b"ffxAssertSetPrintingCallback\0")?),
+        ContextDispatch: resolve_optional(hmod, b"ffxFsr3UpscalerContextDispatch\0")
+            .map(|f| std::mem::transmute(f)),
+        GetJitterOffset: resolve_optional(hmod, b"ffxFsr3UpscalerGetJitterOffset\0")
+            .map(|f| std::mem::transmute(f)),
+        GetJitterPhCount: resolve_optional(hmod, b"ffxFsr3UpscalerGetJitterPhaseCount\0")
+            .map(|f| std::mem::transmute(f)),
+    })
 }
 
 #[no_mangle]
@@ -59,61 +121,19 @@ unsafe extern "system" fn DllMain(_: HINSTANCE, reason: u32, _: *mut ()) -> bool
             logging::init();
             settings::init();
             info!("upscaler: {:?}", upscaler_type::get());
-            info!("oxr-amd-fsr3-upscaler: loading original");
-            let wname: Vec<u16> = "ffx_fsr3upscaler_x64_original.dll\0"
-                .encode_utf16()
-                .collect();
-            let hmod = LoadLibraryW(windows::core::PCWSTR(wname.as_ptr()))
-                .expect("failed to load ffx_fsr3upscaler_x64_original.dll");
-            FN_TABLE
-                .set(FnTable {
-                    ContextCreate: std::mem::transmute(resolve(
-                        hmod,
-                        b"ffxFsr3UpscalerContextCreate\0",
-                    )),
-                    ContextDestroy: std::mem::transmute(resolve(
-                        hmod,
-                        b"ffxFsr3UpscalerContextDestroy\0",
-                    )),
-                    ContextDispatch: std::mem::transmute(resolve(
-                        hmod,
-                        b"ffxFsr3UpscalerContextDispatch\0",
-                    )),
-                    GenReactiveMask: std::mem::transmute(resolve(
-                        hmod,
-                        b"ffxFsr3UpscalerContextGenerateReactiveMask\0",
-                    )),
-                    GetJitterOffset: std::mem::transmute(resolve(
-                        hmod,
-                        b"ffxFsr3UpscalerGetJitterOffset\0",
-                    )),
-                    GetJitterPhCount: std::mem::transmute(resolve(
-                        hmod,
-                        b"ffxFsr3UpscalerGetJitterPhaseCount\0",
-                    )),
-                    ResourceIsNull: std::mem::transmute(resolve(
-                        hmod,
-                        b"ffxFsr3UpscalerResourceIsNull\0",
-                    )),
-                    SafeRelCopy: std::mem::transmute(resolve(
-                        hmod,
-                        b"ffxSafeReleaseCopyResource\0",
-                    )),
-                    SafeRelPipeline: std::mem::transmute(resolve(
-                        hmod,
-                        b"ffxSafeReleasePipeline\0",
-                    )),
-                    SafeRelResource: std::mem::transmute(resolve(
-                        hmod,
-                        b"ffxSafeReleaseResource\0",
-                    )),
-                    AssertReport: std::mem::transmute(resolve(hmod, b"ffxAssertReport\0")),
-                    AssertSetCb: std::mem::transmute(resolve(
-                        hmod,
-                        b"ffxAssertSetPrintingCallback\0",
-                    )),
-                })
-                .ok();
+            if settings::get().forward_to_original {
+                info!("oxr-amd-fsr3-upscaler: loading original");
+                match load_original() {
+                    Some(table) => {
+                        let _ = FN_TABLE.set(table);
+                    }
+                    None => {
+                        warn!("oxr-amd-fsr3-upscaler: original unavailable, running standalone")
+                    }
+                }
+            } else {
+                info!("oxr-amd-fsr3-upscaler: standalone mode");
+            }
             info!("oxr-amd-fsr3-upscaler: ready");
             true
         }
@@ -137,17 +157,29 @@ pub unsafe extern "C" fn ffxFsr3UpscalerContextCreate(
             h = mrs.height,
             "ffxFsr3UpscalerContextCreate"
         );
-        let _ = MAX_RENDER_SIZE.set(mrs);
+        fsr_sys::message::set_callback(context::key(ctx) as u64, (*desc).fp_message);
+        context::create(context::key(ctx), &*desc);
     } else {
         info!("ffxFsr3UpscalerContextCreate (null desc)");
     }
-    (FN_TABLE.get().unwrap().ContextCreate)(ctx, desc)
+    match original() {
+        Some(t) => (t.ContextCreate)(ctx, desc),
+        // Native: all per-context state lives in `context`; the game's opaque
+        // context buffer is left untouched.
+        None if desc.is_null() => 0x8000_0000, // FFX_ERROR_INVALID_POINTER
+        None => 0,                             // FFX_OK
+    }
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn ffxFsr3UpscalerContextDestroy(ctx: *mut FfxFsr3UpscalerContext) -> u32 {
     info!("ffxFsr3UpscalerContextDestroy");
-    (FN_TABLE.get().unwrap().ContextDestroy)(ctx)
+    context::destroy(context::key(ctx));
+    fsr_sys::message::clear_callback(context::key(ctx) as u64);
+    match original() {
+        Some(t) => (t.ContextDestroy)(ctx),
+        None => 0, // FFX_OK
+    }
 }
 
 #[no_mangle]
@@ -156,27 +188,49 @@ pub unsafe extern "C" fn ffxFsr3UpscalerContextDispatch(
     desc: *const FfxFsr3UpscalerDispatchDescription,
 ) -> u32 {
     if desc.is_null() {
-        return (FN_TABLE.get().unwrap().ContextDispatch)(ctx, desc);
+        return match original().and_then(|t| t.ContextDispatch) {
+            Some(f) => f(ctx, desc),
+            None => 0x8000_0000, // FFX_ERROR_INVALID_POINTER
+        };
     }
 
-    let d = &*desc;
+    let frame = game_profile::active().read_dispatch(desc);
+    let d = &frame;
     let rw = d.render_size.width;
     let rh = d.render_size.height;
-    let uw = d.output.description.width;
-    let uh = d.output.description.height;
+    let uw = d.upscale_size.width;
+    let uh = d.upscale_size.height;
     info!(
         render = format_args!("{}x{}", rw, rh),
         upscale = format_args!("{}x{}", uw, uh),
         "ffxFsr3UpscalerContextDispatch"
     );
 
+    let key = context::key(ctx);
+    context::begin_frame(key);
+    // Logged after the context lock is released: warnings and errors reach
+    // the game's message callback, which may call back into the proxy.
+    let violations = context::with(key, |state| {
+        state.validator.as_mut().map(|validator| validator.check(d))
+    })
+    .flatten()
+    .unwrap_or_default();
+    for v in violations {
+        if v.code.is_warning() {
+            warn!(code = %v.code.id(), "ffxFsr3UpscalerContextDispatch: validation: {}", v.detail);
+        } else {
+            error!(code = %v.code.id(), "ffxFsr3UpscalerContextDispatch: validation: {}", v.detail);
+        }
+    }
+    dynres::update(key, d.frame_time_delta, uw, uh);
+
     #[cfg(feature = "recording")]
     recording::pre_dispatch(d);
 
     let result = if rw == uw && rh == uh {
-        dispatch::dispatch_anti_aliasing(d)
+        dispatch::dispatch_anti_aliasing(key, d)
     } else {
-        dispatch::dispatch_upscale(d)
+        dispatch::dispatch_upscale(key, d)
     };
 
     #[cfg(feature = "recording")]
@@ -191,7 +245,15 @@ pub unsafe extern "C" fn ffxFsr3UpscalerContextGenerateReactiveMask(
     desc: *const FfxFsr3UpscalerGenerateReactiveDescription,
 ) -> u32 {
     info!("ffxFsr3UpscalerContextGenerateReactiveMask called");
-    (FN_TABLE.get().unwrap().GenReactiveMask)(ctx, desc)
+    match original() {
+        Some(t) => (t.GenReactiveMask)(ctx, desc),
+        None if desc.is_null() => 0x8000_0000, // FFX_ERROR_INVALID_POINTER
+        None => {
+            let mut frame = (&*desc).into();
+            game_profile::active().normalize_reactive(&mut frame);
+            reactive_mask::generate(context::key(ctx), &frame)
+        }
+    }
 }
 
 #[no_mangle]
@@ -201,20 +263,37 @@ pub unsafe extern "C" fn ffxFsr3UpscalerGetJitterOffset(
     idx: i32,
     pc: i32,
 ) -> u32 {
-    // TODO: unconditionally disabled for testing
-    if !ox.is_null() {
-        *ox = 0.0;
+    if !settings::get().jitter {
+        if !ox.is_null() {
+            *ox = 0.0;
+        }
+        if !oy.is_null() {
+            *oy = 0.0;
+        }
+        return 0; // FFX_OK
     }
-    if !oy.is_null() {
-        *oy = 0.0;
+    if let Some(f) = original().and_then(|t| t.GetJitterOffset) {
+        return f(ox, oy, idx, pc);
     }
+    if ox.is_null() || oy.is_null() {
+        return 0x8000_0000; // FFX_ERROR_INVALID_POINTER
+    }
+    if pc <= 0 {
+        return 0x8000_0001; // FFX_ERROR_INVALID_ARGUMENT
+    }
+    (*ox, *oy) = fsr_sys::jitter::offset(idx, pc);
     0 // FFX_OK
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn ffxFsr3UpscalerGetJitterPhaseCount(rw: i32, dw: i32) -> i32 {
-    // TODO: unconditionally disabled for testing
-    1
+    if !settings::get().jitter {
+        return 1;
+    }
+    match original().and_then(|t| t.GetJitterPhCount) {
+        Some(f) => f(rw, dw),
+        None => fsr_sys::jitter::phase_count(rw, dw),
+    }
 }
 
 #[no_mangle]
@@ -225,12 +304,15 @@ pub unsafe extern "C" fn ffxFsr3UpscalerGetRenderResolutionFromQualityMode(
     dh: u32,
     qm: u32,
 ) -> u32 {
-    let ratio = ffxFsr3UpscalerGetUpscaleRatioFromQualityMode(qm);
-    if ratio == 0.0 {
+    let quality = &settings::get().quality;
+    let Some((mut render_w, mut render_h)) = quality.render_resolution(dw, dh, qm) else {
         return 0x8000_0001; // FFX_ERROR_INVALID_ARGUMENT
+    };
+    // A forced native / AA mode keeps the display size
+    if let Some(ratio) = dynres::active_ratio(dw, dh).filter(|_| !quality.is_native(qm)) {
+        render_w = ((dw as f32 / ratio) as u32).max(1);
+        render_h = ((dh as f32 / ratio) as u32).max(1);
     }
-    let render_w = (dw as f32 / ratio) as u32;
-    let render_h = (dh as f32 / ratio) as u32;
     if !ow.is_null() {
         *ow = render_w;
     }
@@ -242,123 +324,46 @@ pub unsafe extern "C" fn ffxFsr3UpscalerGetRenderResolutionFromQualityMode(
 
 #[no_mangle]
 pub unsafe extern "C" fn ffxFsr3UpscalerGetSharedResourceDescriptions(
-    _ctx: *mut FfxFsr3UpscalerContext,
+    ctx: *mut FfxFsr3UpscalerContext,
     desc: *mut FfxFsr3UpscalerSharedResourceDescriptions,
 ) -> u32 {
-    use std::ptr;
-
-    // Game ABI constants (differ from v1.1.4 SDK header values)
-    const FFX_HEAP_TYPE_DEFAULT: u32 = 0;
-    const FFX_RESOURCE_TYPE_TEXTURE2D: u32 = 2;
-    const FFX_RESOURCE_STATE_UNORDERED_ACCESS: u32 = 2;
-    const FFX_RESOURCE_FLAGS_ALIASABLE: u32 = 1;
-    const FFX_RESOURCE_USAGE_UAV: u32 = 2;
-    const FFX_RESOURCE_USAGE_RT: u32 = 1;
-    const FFX_RESOURCE_INIT_DATA_TYPE_UNINITIALIZED: u32 = 0; // game enum: 0=Uninitialized
-                                                              // Game's FfxSurfaceFormat enum values (older SDK, some formats missing → shifted values)
-    const R32_UINT: u32 = 7;
-    const R16G16_FLOAT: u32 = 14;
-    const R32_FLOAT: u32 = 24;
-    // Resource IDs
-    const ID_RECONSTRUCTED_PREV_NEAREST_DEPTH: u32 = 8;
-    const ID_DILATED_MOTION_VECTORS: u32 = 9;
-    const ID_DILATED_DEPTH: u32 = 10;
-
-    // Static wide-string names (null-terminated UTF-16)
-    static NAME_RECON_DEPTH: &[u16] = &{
-        const S: &str = "FSR3UPSCALER_ReconstructedPrevNearestDepth\0";
-        const N: usize = S.len();
-        let mut buf = [0u16; N];
-        let bytes = S.as_bytes();
-        let mut i = 0;
-        while i < N {
-            buf[i] = bytes[i] as u16;
-            i += 1;
-        }
-        buf
-    };
-    static NAME_DILATED_DEPTH: &[u16] = &{
-        const S: &str = "FSR3UPSCALER_DilatedDepth\0";
-        const N: usize = S.len();
-        let mut buf = [0u16; N];
-        let bytes = S.as_bytes();
-        let mut i = 0;
-        while i < N {
-            buf[i] = bytes[i] as u16;
-            i += 1;
-        }
-        buf
-    };
-    static NAME_DILATED_MVS: &[u16] = &{
-        const S: &str = "FSR3UPSCALER_DilatedVelocity\0";
-        const N: usize = S.len();
-        let mut buf = [0u16; N];
-        let bytes = S.as_bytes();
-        let mut i = 0;
-        while i < N {
-            buf[i] = bytes[i] as u16;
-            i += 1;
-        }
-        buf
-    };
+    use game_profile::{ResourceAbi, SharedResource};
 
-    let mrs = match MAX_RENDER_SIZE.get() {
+    if desc.is_null() {
+        return 0x8000_0000; // FFX_ERROR_INVALID_POINTER
+    }
+    let mrs = match context::with(context::key(ctx), |state| state.max_render_size) {
         Some(s) => s,
         None => {
-            info!("GetSharedResourceDescriptions: MAX_RENDER_SIZE not set");
+            info!("GetSharedResourceDescriptions: unknown context {:?}", ctx);
             return 0x8000_0001; // FFX_ERROR_INVALID_ARGUMENT
         }
     };
 
-    let make_desc =
-        |format: u32, usage: u32, name: &[u16], id: u32| -> FfxCreateResourceDescription {
-            FfxCreateResourceDescription {
-                heap_type: FFX_HEAP_TYPE_DEFAULT,
-                resource_description: FfxResourceDescription {
-                    type_: FFX_RESOURCE_TYPE_TEXTURE2D,
-                    format,
-                    width: mrs.width,
-                    height: mrs.height,
-                    depth: 1,
-                    mip_count: 1,
-                    flags: FFX_RESOURCE_FLAGS_ALIASABLE,
-                    usage,
-                },
-                initial_state: FFX_RESOURCE_STATE_UNORDERED_ACCESS,
-                init_data: FfxResourceInitData {
-                    type_: FFX_RESOURCE_INIT_DATA_TYPE_UNINITIALIZED,
-                    size: 0,
-                    buffer: ptr::null(),
-                },
-                name: name.as_ptr(),
-                id,
-                _pad: 0,
-            }
-        };
-
-    let out = &mut *desc;
-    out.reconstructed_prev_nearest_depth = make_desc(
-        R32_UINT,
-        FFX_RESOURCE_USAGE_UAV,
-        NAME_RECON_DEPTH,
-        ID_RECONSTRUCTED_PREV_NEAREST_DEPTH,
-    );
-    out.dilated_depth = make_desc(
-        R32_FLOAT,
-        FFX_RESOURCE_USAGE_RT | FFX_RESOURCE_USAGE_UAV,
-        NAME_DILATED_DEPTH,
-        ID_DILATED_DEPTH,
-    );
-    out.dilated_motion_vectors = make_desc(
-        R16G16_FLOAT,
-        FFX_RESOURCE_USAGE_RT | FFX_RESOURCE_USAGE_UAV,
-        NAME_DILATED_MVS,
-        ID_DILATED_MOTION_VECTORS,
-    );
+    let profile = game_profile::active();
+    match profile.resource_abi {
+        ResourceAbi::Packed => {
+            let make_desc = |resource| profile.packed_description(resource, mrs);
+            let out = &mut *desc;
+            out.reconstructed_prev_nearest_depth =
+                make_desc(SharedResource::ReconstructedPrevNearestDepth);
+            out.dilated_depth = make_desc(SharedResource::DilatedDepth);
+            out.dilated_motion_vectors = make_desc(SharedResource::DilatedMotionVectors);
+        }
+        ResourceAbi::Sdk => {
+            let make_desc = |resource| profile.sdk_description(resource, mrs);
+            let out = &mut *(desc as *mut FfxFsr3UpscalerSharedResourceDescriptionsSdk);
+            out.reconstructed_prev_nearest_depth =
+                make_desc(SharedResource::ReconstructedPrevNearestDepth);
+            out.dilated_depth = make_desc(SharedResource::DilatedDepth);
+            out.dilated_motion_vectors = make_desc(SharedResource::DilatedMotionVectors);
+        }
+    }
 
     info!(
         w = mrs.width,
         h = mrs.height,
+        profile = %profile.name,
         "GetSharedResourceDescriptions (standalone)"
     );
     0 // FFX_OK
@@ -366,38 +371,80 @@ pub unsafe extern "C" fn ffxFsr3UpscalerGetSharedResourceDescriptions(
 
 #[no_mangle]
 pub unsafe extern "C" fn ffxFsr3UpscalerGetUpscaleRatioFromQualityMode(qm: u32) -> f32 {
-    match qm {
-        0 => 1.0, // NativeAA
-        1 => 1.5, // Quality
-        2 => 1.7, // Balanced
-        3 => 2.0, // Performance
-        4 => 3.0, // UltraPerformance
-        _ => 0.0,
-    }
+    // Per mode; dynamic resolution only changes the render-resolution answer
+    settings::get().quality.ratio(qm).unwrap_or(0.0)
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn ffxFsr3UpscalerResourceIsNull(res: FfxResource) -> u32 {
-    (FN_TABLE.get().unwrap().ResourceIsNull)(res)
+    match original() {
+        Some(t) => (t.ResourceIsNull)(res),
+        None => res.resource.is_null() as u32,
+    }
 }
 
+// The SDK's `ffxSafeRelease*` helpers release through the caller's backend
+// interface; they never touch the original DLL.
+
 #[no_mangle]
 pub unsafe extern "C" fn ffxSafeReleaseCopyResource(
-    iface: *mut c_void,
-    res: *const c_void,
+    iface: *mut FfxInterface,
+    res: FfxResourceInternal,
     fid: u32,
 ) {
-    (FN_TABLE.get().unwrap().SafeRelCopy)(iface, res, fid)
+    destroy_resource("ffxSafeReleaseCopyResource", iface, res, fid);
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn ffxSafeReleasePipeline(iface: *mut c_void, pipe: *mut c_void, fid: u32) {
-    (FN_TABLE.get().unwrap().SafeRelPipeline)(iface, pipe, fid)
+pub unsafe extern "C" fn ffxSafeReleasePipeline(
+    iface: *mut FfxInterface,
+    pipe: *mut c_void,
+    fid: u32,
+) {
+    if pipe.is_null() {
+        return;
+    }
+    match iface.as_ref().and_then(|i| i.fp_destroy_pipeline) {
+        Some(f) => {
+            let rc = f(iface, pipe, fid);
+            if rc != 0 {
+                warn!(
+                    "ffxSafeReleasePipeline: fpDestroyPipeline returned 0x{:x}",
+                    rc
+                );
+            }
+        }
+        None => error!("ffxSafeReleasePipeline: no fpDestroyPipeline, pipeline leaked"),
+    }
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn ffxSafeReleaseResource(iface: *mut c_void, res: *const c_void, fid: u32) {
-    (FN_TABLE.get().unwrap().SafeRelResource)(iface, res, fid)
+pub unsafe extern "C" fn ffxSafeReleaseResource(
+    iface: *mut FfxInterface,
+    res: FfxResourceInternal,
+    fid: u32,
+) {
+    destroy_resource("ffxSafeReleaseResource", iface, res, fid);
+}
+
+unsafe fn destroy_resource(
+    export: &str,
+    iface: *mut FfxInterface,
+    res: FfxResourceInternal,
+    fid: u32,
+) {
+    match iface.as_ref().and_then(|i| i.fp_destroy_resource) {
+        Some(f) => {
+            let rc = f(iface, res, fid);
+            if rc != 0 {
+                warn!("{}: fpDestroyResource returned 0x{:x}", export, rc);
+            }
+        }
+        None => error!(
+            "{}: no fpDestroyResource, resource {} leaked",
+            export, res.internal_index
+        ),
+    }
 }
 
 #[no_mangle]
@@ -407,12 +454,49 @@ pub unsafe extern "C" fn ffxAssertReport(
     cond: *const i8,
     msg: *const i8,
 ) {
-    (FN_TABLE.get().unwrap().AssertReport)(file, line, cond, msg)
+    match original() {
+        Some(t) => (t.AssertReport)(file, line, cond, msg),
+        None => assert_report(file, line, cond, msg),
+    }
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn ffxAssertSetPrintingCallback(cb: *mut c_void) {
-    (FN_TABLE.get().unwrap().AssertSetCb)(cb)
+    match original() {
+        Some(t) => (t.AssertSetCb)(cb),
+        None => ASSERT_CALLBACK.store(cb, Ordering::Relaxed),
+    }
+}
+
+/// Printing callback registered through `ffxAssertSetPrintingCallback` (standalone).
+static ASSERT_CALLBACK: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+
+/// Native `ffxAssertReport`: log the failure and pass the formatted message to
+/// the registered printing callback (`void (*)(const char*)`).
+unsafe fn assert_report(file: *const i8, line: u32, cond: *const i8, msg: *const i8) {
+    let text = |p: *const i8| {
+        if p.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(p).to_string_lossy().into_owned()
+        }
+    };
+    let report = format!(
+        "{}({}): ASSERTION FAILED. {} {}",
+        text(file),
+        line,
+        text(cond),
+        text(msg)
+    );
+    error!("{}", report);
+
+    let cb = ASSERT_CALLBACK.load(Ordering::Relaxed);
+    if !cb.is_null() {
+        let cb: unsafe extern "C" fn(*const i8) = std::mem::transmute(cb);
+        if let Ok(c) = CString::new(report) {
+            cb(c.as_ptr());
+        }
+    }
 }
 
 #[no_mangle]
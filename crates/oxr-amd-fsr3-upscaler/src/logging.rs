@@ -1,3 +1,4 @@
+use fsr_sys::message::GameMessageLayer;
 use std::path::PathBuf;
 use std::sync::Once;
 use tracing_appender::non_blocking::WorkerGuard;
@@ -27,6 +28,7 @@ pub unsafe fn init() {
                     .with_ansi(false)
                     .with_target(false),
             )
+            .with(GameMessageLayer)
             .init();
         GUARD = Some(guard);
     });
@@ -9,9 +9,13 @@ use tracing::{error, info};
 use windows::Win32::Graphics::Direct3D12::*;
 use windows::Win32::UI::Input::KeyboardAndMouse::GetAsyncKeyState;
 
+use crate::dynres;
 use crate::gpu_pipeline::{self, get_srv_cpu_handle, get_srv_gpu_handle, GpuState};
 use crate::imgui_renderer::ImguiDx12Renderer;
+use crate::settings;
 use crate::upscaler_type::{self, AntiAliasingType, UpscalerType};
+use crate::upscalers::aa_models;
+use crate::upscalers::aa_pass::{self, LayerView};
 
 const VK_HOME: i32 = 0x24;
 const VK_END: i32 = 0x23;
@@ -43,14 +47,19 @@ fn key_down(vk: i32) -> bool {
     (unsafe { GetAsyncKeyState(vk) } as u16 & 0x8000) != 0
 }
 
-/// Call once per dispatch, after `DrawInstanced`, while the output RTV is still bound
-/// and the output resource is in RENDER_TARGET state.
+/// Call once per dispatch, after `DrawInstanced`, while the output resource is in
+/// RENDER_TARGET state; `rtv` is the dispatching context's output view.
+///
+/// Draws with the shared descriptor heap, which holds the font atlas SRV in slot 1.
 pub unsafe fn render_frame(
     cmd_list: &ID3D12GraphicsCommandList,
-    gpu: &GpuState,
+    rtv: D3D12_CPU_DESCRIPTOR_HANDLE,
     output_w: u32,
     output_h: u32,
 ) {
+    let Some(gpu) = gpu_pipeline::get() else {
+        return;
+    };
     let Ok(mut guard) = OVERLAY.lock() else {
         return; // poisoned
     };
@@ -136,12 +145,113 @@ pub unsafe fn render_frame(
                         upscaler_type::aa_set(aa_active);
                         info!("overlay: AA switched to {:?}", aa_active);
                     }
-                    if ui.radio_button("IMBA v0", &mut aa_active, AntiAliasingType::ImbaV0) {
+                    if ui.radio_button("IMBA", &mut aa_active, AntiAliasingType::Imba) {
                         upscaler_type::aa_set(aa_active);
                         info!("overlay: AA switched to {:?}", aa_active);
                     }
                 }
 
+                // IMBA models: the active one, and in AA one for the right half
+                let imba_aa = aa_active == AntiAliasingType::Imba;
+                let imba_active = imba_aa || upscaler_type::get() == UpscalerType::Imba;
+                if imba_active {
+                    let models = aa_models::models();
+                    let selected = aa_models::selected();
+                    let compare = aa_models::compare();
+
+                    ui.text("Model");
+                    if models.is_empty() {
+                        ui.text_disabled("no models found");
+                    }
+                    for (i, m) in models.iter().enumerate() {
+                        let on = selected.as_ref() == Some(m);
+                        if ui.radio_button_bool(format!("{}##model{}", m.label, i), on) {
+                            aa_models::select(&m.path);
+                        }
+                        ui.same_line();
+                        ui.text_disabled(format!("{} {:?}", m.file_name, m.dtype));
+                    }
+
+                    if imba_aa && models.len() > 1 {
+                        ui.text("Compare (right half)");
+                        if ui.radio_button_bool("Off##compare", compare.is_none()) {
+                            aa_models::set_compare(None);
+                        }
+                        for (i, m) in models.iter().enumerate() {
+                            if selected.as_ref() == Some(m) {
+                                continue;
+                            }
+                            let on = compare.as_ref() == Some(m);
+                            if ui.radio_button_bool(format!("{}##compare{}", m.label, i), on) {
+                                aa_models::set_compare(Some(&m.path));
+                            }
+                        }
+                    }
+
+                    if ui.button("Rescan models") {
+                        aa_models::rescan();
+                    }
+
+                    // Temporal history of the active model
+                    let history = aa_pass::history_status();
+                    if history.depth > 0 {
+                        ui.text(format!(
+                            "History {}/{} frames",
+                            history.frames, history.depth
+                        ));
+                    }
+                    if let Some((reason, at)) = history.last_reset {
+                        ui.text_disabled(format!(
+                            "{} resets, last: {} ({:.0}s ago)",
+                            history.resets,
+                            reason,
+                            at.elapsed().as_secs_f32()
+                        ));
+                    }
+
+                    // Layer view: one channel of a tensor instead of the output
+                    let layers = aa_pass::layers();
+                    if !layers.is_empty() {
+                        let current = aa_pass::layer_view();
+                        let mut layer = current
+                            .as_ref()
+                            .and_then(|v| layers.iter().position(|a| a.name == v.tensor))
+                            .map_or(0, |i| i as u32 + 1);
+                        let label = match layer.checked_sub(1) {
+                            Some(i) => {
+                                let a = &layers[i as usize];
+                                format!("{} {}ch {}x{}", a.name, a.channels, a.width, a.height)
+                            }
+                            None => "off".to_string(),
+                        };
+                        let mut changed = ui
+                            .slider_config("Layer", 0, layers.len() as u32)
+                            .display_format(label)
+                            .build(&mut layer);
+
+                        let mut view = current.unwrap_or(LayerView {
+                            tensor: String::new(),
+                            channel: 0,
+                            range: 1.0,
+                        });
+                        if let Some(a) = layer.checked_sub(1).map(|i| &layers[i as usize]) {
+                            view.tensor = a.name.clone();
+                            view.channel = view.channel.min(a.channels - 1);
+                            changed |= ui.slider("Channel", 0, a.channels - 1, &mut view.channel);
+                            changed |= ui
+                                .slider_config("Range", 0.01, 100.0)
+                                .flags(imgui::SliderFlags::LOGARITHMIC)
+                                .display_format("%.2f")
+                                .build(&mut view.range);
+                        }
+                        if changed {
+                            let view = (layer > 0).then_some(view);
+                            info!("overlay: layer view {:?}", view);
+                            aa_pass::set_layer_view(view);
+                        }
+                    }
+                }
+
                 ui.separator();
 
                 // Upscaler
@@ -175,6 +285,18 @@ pub unsafe fn render_frame(
                         upscaler_type::set(active);
                         info!("overlay: switched to {:?}", active);
                     }
+                    // AA then bilinear, not a full upscaler: only offered when
+                    // `[imba] upscaler` is set
+                    if settings::get().imba_upscaler
+                        && ui.radio_button(
+                            "IMBA AA + Bilinear##upscaler",
+                            &mut active,
+                            UpscalerType::Imba,
+                        )
+                    {
+                        upscaler_type::set(active);
+                        info!("overlay: switched to {:?}", active);
+                    }
 
                     // RCAS checkbox
                     let mut rcas_on = upscaler_type::rcas_get();
@@ -193,6 +315,27 @@ pub unsafe fn render_frame(
                     info!("overlay: debug_view={}", debug_on);
                 }
 
+                // Transparency & composition mask checkbox
+                let mut composition_on = upscaler_type::composition_mask_get();
+                if ui.checkbox("Composition Mask", &mut composition_on) {
+                    upscaler_type::composition_mask_set(composition_on);
+                    info!("overlay: composition_mask={}", composition_on);
+                }
+
+                // Dynamic resolution status
+                if let Some(status) = dynres::status() {
+                    ui.separator();
+                    ui.text("Dynamic Resolution");
+                    ui.text(format!(
+                        "{:.1} / {:.1} ms",
+                        status.decision.smoothed_frame_ms, status.target_frame_ms
+                    ));
+                    ui.text(format!(
+                        "{:.2}x  {}x{}",
+                        status.decision.ratio, status.render_size.0, status.render_size.1
+                    ));
+                }
+
                 // Recording status
                 #[cfg(feature = "recording")]
                 {
@@ -238,6 +381,17 @@ pub unsafe fn render_frame(
                         stride::set(current_stride);
                         info!("overlay: stride={:?}", current_stride);
                     }
+
+                    // One frame of IMBA activations for comparing with PyTorch
+                    if imba_active {
+                        use recording::activations;
+                        if activations::busy() {
+                            ui.text_disabled("Dumping IMBA activations...");
+                        } else if ui.button("Dump IMBA activations") {
+                            activations::request();
+                            info!("overlay: activation dump requested");
+                        }
+                    }
                 }
             });
 
@@ -250,10 +404,9 @@ pub unsafe fn render_frame(
 
     // --- Render ---
     let frame_idx = state.frame_idx;
-    let rtv_handle = gpu_pipeline::get_rtv_cpu_handle(gpu, 0);
     if let Err(e) = state
         .renderer
-        .render(draw_data, cmd_list, frame_idx, &gpu.srv_heap, rtv_handle)
+        .render(draw_data, cmd_list, frame_idx, &gpu.srv_heap, rtv)
     {
         error!("overlay: render failed: {}", e);
         gpu_pipeline::log_device_removed_reason(&gpu.device);
@@ -1,16 +1,26 @@
 pub mod debug_view;
 pub mod rcas;
 
-use crate::fsr3_types::*;
+use crate::context::ContextKey;
 use crate::gpu_pipeline::GpuState;
+use fsr_sys::inputs::InputFlags;
+use fsr_sys::UpscaleFrame;
 use windows::Win32::Graphics::Direct3D12::*;
 
 /// Shared context passed to post-processing effects.
 pub struct PostContext<'a> {
+    pub context: ContextKey,
     pub cmd_list: &'a ID3D12GraphicsCommandList,
     pub gpu: &'a GpuState,
-    pub d: &'a FfxFsr3UpscalerDispatchDescription,
+    pub d: &'a UpscaleFrame,
+    /// Creation flags of the dispatching context.
+    pub input_flags: InputFlags,
     pub output_res: &'a ID3D12Resource,
     pub output_w: u32,
     pub output_h: u32,
 }
+
+/// Release post-processing resources owned by a destroyed context.
+pub fn release(key: ContextKey) {
+    rcas::release(key);
+}
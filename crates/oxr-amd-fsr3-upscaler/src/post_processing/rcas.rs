@@ -1,5 +1,4 @@
-use std::sync::Mutex;
-
+use crate::context::{ContextKey, PerContext};
 use crate::gpu_pipeline;
 use crate::post_processing::PostContext;
 use crate::upscaler_type;
@@ -19,65 +18,69 @@ struct RcasResources {
     height: u32,
 }
 
-static RCAS_TEMP: Mutex<Option<RcasResources>> = Mutex::new(None);
+static RCAS_TEMP: PerContext<RcasResources> = PerContext::new();
+
+/// Drop the temp RT of a destroyed context.
+pub fn release(key: ContextKey) {
+    RCAS_TEMP.remove(key);
+}
 
 unsafe fn ensure_rcas_temp(
+    key: ContextKey,
     device: &ID3D12Device,
     w: u32,
     h: u32,
     format: DXGI_FORMAT,
 ) -> Result<ID3D12Resource, String> {
-    let mut guard = RCAS_TEMP.lock().map_err(|_| "RCAS_TEMP mutex poisoned")?;
-
-    if let Some(res) = guard.as_ref() {
-        if res.width == w && res.height == h {
-            return Ok(res.temp_rt.clone());
-        }
-    }
-
-    let heap_props = D3D12_HEAP_PROPERTIES {
-        Type: D3D12_HEAP_TYPE_DEFAULT,
-        ..Default::default()
-    };
-
-    let desc = D3D12_RESOURCE_DESC {
-        Dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
-        Alignment: 0,
-        Width: w as u64,
-        Height: h,
-        DepthOrArraySize: 1,
-        MipLevels: 1,
-        Format: format,
-        SampleDesc: DXGI_SAMPLE_DESC {
-            Count: 1,
-            Quality: 0,
+    let res = RCAS_TEMP.get_or_create(
+        key,
+        |res| res.width != w || res.height != h,
+        || {
+            let heap_props = D3D12_HEAP_PROPERTIES {
+                Type: D3D12_HEAP_TYPE_DEFAULT,
+                ..Default::default()
+            };
+
+            let desc = D3D12_RESOURCE_DESC {
+                Dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+                Alignment: 0,
+                Width: w as u64,
+                Height: h,
+                DepthOrArraySize: 1,
+                MipLevels: 1,
+                Format: format,
+                SampleDesc: DXGI_SAMPLE_DESC {
+                    Count: 1,
+                    Quality: 0,
+                },
+                Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
+                Flags: D3D12_RESOURCE_FLAG_ALLOW_RENDER_TARGET,
+            };
+
+            let mut resource: Option<ID3D12Resource> = None;
+            device
+                .CreateCommittedResource(
+                    &heap_props,
+                    D3D12_HEAP_FLAG_NONE,
+                    &desc,
+                    D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+                    None,
+                    &mut resource,
+                )
+                .map_err(|e| format!("CreateCommittedResource for RCAS temp RT failed: {}", e))?;
+
+            let resource = resource.ok_or("CreateCommittedResource returned null")?;
+            info!("RCAS temp RT created: {}x{} format={:?}", w, h, format);
+
+            Ok::<_, String>(RcasResources {
+                temp_rt: resource,
+                width: w,
+                height: h,
+            })
         },
-        Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
-        Flags: D3D12_RESOURCE_FLAG_ALLOW_RENDER_TARGET,
-    };
+    )?;
 
-    let mut resource: Option<ID3D12Resource> = None;
-    device
-        .CreateCommittedResource(
-            &heap_props,
-            D3D12_HEAP_FLAG_NONE,
-            &desc,
-            D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
-            None,
-            &mut resource,
-        )
-        .map_err(|e| format!("CreateCommittedResource for RCAS temp RT failed: {}", e))?;
-
-    let resource = resource.ok_or("CreateCommittedResource returned null")?;
-    info!("RCAS temp RT created: {}x{} format={:?}", w, h, format);
-
-    *guard = Some(RcasResources {
-        temp_rt: resource.clone(),
-        width: w,
-        height: h,
-    });
-
-    Ok(resource)
+    Ok(res.temp_rt.clone())
 }
 
 /// Returns true if RCAS should be applied for the current upscaler.
@@ -91,7 +94,13 @@ pub unsafe fn apply(ctx: &PostContext) {
     let gpu = ctx.gpu;
     let cmd_list = ctx.cmd_list;
 
-    let temp_rt = match ensure_rcas_temp(&gpu.device, ctx.output_w, ctx.output_h, gpu.rt_format) {
+    let temp_rt = match ensure_rcas_temp(
+        ctx.context,
+        &gpu.device,
+        ctx.output_w,
+        ctx.output_h,
+        gpu.rt_format,
+    ) {
         Ok(rt) => rt,
         Err(e) => {
             error!("RCAS: temp RT creation failed: {}, skipping", e);
@@ -178,6 +187,7 @@ pub unsafe fn apply(ctx: &PostContext) {
 
     let sharpness: f32 = 1.0;
     cmd_list.SetGraphicsRoot32BitConstant(0, sharpness.to_bits(), 0);
+    cmd_list.SetGraphicsRoot32BitConstant(0, ctx.input_flags.hdr as u32, 4);
 
     cmd_list.SetGraphicsRootDescriptorTable(1, gpu_pipeline::get_srv_gpu_handle(gpu, SRV_RCAS));
 
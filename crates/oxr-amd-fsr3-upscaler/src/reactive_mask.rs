@@ -0,0 +1,310 @@
+//! Native reactive mask generation (`ffxFsr3UpscalerContextGenerateReactiveMask`)
+//! and the reactive / composition blend applied after IMBA.
+//!
+//! The mask follows `fsr_sys::reactive::reactive_value`; `reactive_mask_cs.hlsl`
+//! is its GPU twin.
+
+use crate::context::ContextKey;
+use crate::dispatch::{apply_barriers, ffx_state_to_d3d12, transitions_if_needed};
+use crate::gpu_pipeline::{self, GpuState};
+use crate::upscaler_type;
+use crate::upscalers::{borrow_resource, create_typed_srv};
+use fsr_sys::reactive::{ReactiveMaskFrame, COMPOSITION_HISTORY_REJECTION};
+use fsr_sys::{FfxApiResource, UpscaleFrame};
+use tracing::{error, info, warn};
+use windows::Win32::Graphics::Direct3D12::*;
+use windows::Win32::Graphics::Dxgi::Common::*;
+
+// --- SRV/UAV slots ---
+const SRV_MASK_OPAQUE: u32 = 36;
+const SRV_MASK_COLOR: u32 = 37;
+const UAV_MASK_OUT: u32 = 38;
+const SRV_BLEND_COLOR: u32 = 39;
+const SRV_BLEND_REACTIVE: u32 = 40;
+const UAV_BLEND_OUT: u32 = 41;
+const SRV_BLEND_COMPOSITION_COLOR: u32 = 54;
+const SRV_BLEND_COMPOSITION: u32 = 55;
+
+const GROUP_SIZE: u32 = 8;
+
+/// Create a 2D UAV on `resource`, resolving typeless formats.
+pub(crate) unsafe fn create_uav(
+    gpu: &GpuState,
+    resource: &ID3D12Resource,
+    ffx_format: u32,
+    slot: u32,
+) {
+    let res_format = resource.GetDesc().Format;
+    let format = if res_format != DXGI_FORMAT_UNKNOWN {
+        res_format
+    } else {
+        gpu_pipeline::ffx_format_to_dxgi(ffx_format)
+    };
+    let uav = D3D12_UNORDERED_ACCESS_VIEW_DESC {
+        Format: gpu_pipeline::dxgi_typeless_to_typed(format),
+        ViewDimension: D3D12_UAV_DIMENSION_TEXTURE2D,
+        Anonymous: D3D12_UNORDERED_ACCESS_VIEW_DESC_0 {
+            Texture2D: D3D12_TEX2D_UAV {
+                MipSlice: 0,
+                PlaneSlice: 0,
+            },
+        },
+    };
+    gpu.device.CreateUnorderedAccessView(
+        resource,
+        None,
+        Some(&uav),
+        gpu_pipeline::get_srv_cpu_handle(gpu, slot),
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe fn dispatch_compute(
+    cmd_list: &ID3D12GraphicsCommandList,
+    gpu: &GpuState,
+    pso: &ID3D12PipelineState,
+    constants: &[u32; 8],
+    srv_table: u32,
+    uav_table: u32,
+    w: u32,
+    h: u32,
+) {
+    cmd_list.SetDescriptorHeaps(&[Some(gpu.srv_heap.clone())]);
+    cmd_list.SetComputeRootSignature(&gpu.reactive_root_signature);
+    cmd_list.SetPipelineState(pso);
+    cmd_list.SetComputeRoot32BitConstants(
+        0,
+        constants.len() as u32,
+        constants.as_ptr() as *const core::ffi::c_void,
+        0,
+    );
+    cmd_list.SetComputeRootDescriptorTable(1, gpu_pipeline::get_srv_gpu_handle(gpu, srv_table));
+    cmd_list.SetComputeRootDescriptorTable(2, gpu_pipeline::get_srv_gpu_handle(gpu, uav_table));
+    cmd_list.Dispatch(w.div_ceil(GROUP_SIZE), h.div_ceil(GROUP_SIZE), 1);
+}
+
+/// Record `context`'s reactive mask pass into the game's command list.
+/// Returns an FFX error code.
+pub unsafe fn generate(context: ContextKey, d: &ReactiveMaskFrame) -> u32 {
+    let Some(cmd_list) =
+        <ID3D12GraphicsCommandList as windows::core::Interface>::from_raw_borrowed(&d.command_list)
+            .cloned()
+    else {
+        warn!("reactive_mask: null command list");
+        return 0x8000_0000; // FFX_ERROR_INVALID_POINTER
+    };
+    let (Some(opaque_res), Some(color_res), Some(out_res)) = (
+        borrow_resource(d.color_opaque_only.resource),
+        borrow_resource(d.color_pre_upscale.resource),
+        borrow_resource(d.out_reactive.resource),
+    ) else {
+        warn!("reactive_mask: null opaque, color or output resource");
+        return 0x8000_0000; // FFX_ERROR_INVALID_POINTER
+    };
+
+    // The pipeline is created by the first upscale dispatch, which knows the
+    // output format; games generate the mask before that dispatch, so the very
+    // first frame goes without.
+    let Some(shared) = gpu_pipeline::get() else {
+        info!("reactive_mask: GPU pipeline not initialized yet, skipping");
+        return 0;
+    };
+    let Some(gpu) = gpu_pipeline::for_context(context, shared) else {
+        return 0x8000_000d; // FFX_ERROR_BACKEND_API_ERROR
+    };
+    let gpu = &*gpu;
+
+    let (w, h) = d.effective_render_size();
+    if w == 0 || h == 0 {
+        warn!("reactive_mask: zero render size");
+        return 0x8000_0001; // FFX_ERROR_INVALID_ARGUMENT
+    }
+
+    let states = [
+        (
+            &opaque_res,
+            ffx_state_to_d3d12(d.color_opaque_only.state),
+            D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE,
+        ),
+        (
+            &color_res,
+            ffx_state_to_d3d12(d.color_pre_upscale.state),
+            D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE,
+        ),
+        (
+            &out_res,
+            ffx_state_to_d3d12(d.out_reactive.state),
+            D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+        ),
+    ];
+    apply_barriers(&cmd_list, &transitions_if_needed(&states));
+
+    let srvs_ok = create_typed_srv(
+        gpu,
+        &opaque_res,
+        d.color_opaque_only.description.format,
+        SRV_MASK_OPAQUE,
+    ) && create_typed_srv(
+        gpu,
+        &color_res,
+        d.color_pre_upscale.description.format,
+        SRV_MASK_COLOR,
+    );
+    if srvs_ok {
+        create_uav(
+            gpu,
+            &out_res,
+            d.out_reactive.description.format,
+            UAV_MASK_OUT,
+        );
+
+        let p = &d.params;
+        let constants = [
+            w,
+            h,
+            p.scale.to_bits(),
+            p.cutoff_threshold.to_bits(),
+            p.binary_value.to_bits(),
+            p.flags,
+            0,
+            0,
+        ];
+        dispatch_compute(
+            &cmd_list,
+            gpu,
+            &gpu.pso_reactive_mask,
+            &constants,
+            SRV_MASK_OPAQUE,
+            UAV_MASK_OUT,
+            w,
+            h,
+        );
+    } else {
+        error!("reactive_mask: unsupported input format, mask left untouched");
+    }
+
+    let restore = states.map(|(res, before, after)| (res, after, before));
+    apply_barriers(&cmd_list, &transitions_if_needed(&restore));
+
+    info!(
+        size = format_args!("{}x{}", w, h),
+        scale = d.params.scale,
+        cutoff = d.params.cutoff_threshold,
+        binary = d.params.binary_value,
+        flags = d.params.flags,
+        "reactive_mask: generated"
+    );
+    0 // FFX_OK
+}
+
+/// Pull `output` (an R16G16B16A16_FLOAT UAV texture in UNORDERED_ACCESS) toward
+/// the frame's color where its reactive mask is set, then partly where its
+/// transparency & composition mask is (unless the overlay turned that off).
+/// `color_res` must already be readable from compute; the masks are
+/// transitioned here and restored. Follows
+/// `fsr_sys::reactive::blend_toward_current`.
+pub unsafe fn blend(
+    cmd_list: &ID3D12GraphicsCommandList,
+    gpu: &GpuState,
+    d: &UpscaleFrame,
+    color_res: &ID3D12Resource,
+    output: &ID3D12Resource,
+    w: u32,
+    h: u32,
+) {
+    // Each pass has its own descriptors: the heap is read when the list executes
+    blend_mask(
+        cmd_list,
+        gpu,
+        d,
+        color_res,
+        output,
+        &d.reactive,
+        1.0,
+        [SRV_BLEND_COLOR, SRV_BLEND_REACTIVE],
+        w,
+        h,
+    );
+    if upscaler_type::composition_mask_get() {
+        blend_mask(
+            cmd_list,
+            gpu,
+            d,
+            color_res,
+            output,
+            &d.transparency_and_composition,
+            COMPOSITION_HISTORY_REJECTION,
+            [SRV_BLEND_COMPOSITION_COLOR, SRV_BLEND_COMPOSITION],
+            w,
+            h,
+        );
+    }
+}
+
+/// One blend pass: `output = lerp(output, color, saturate(mask) * strength)`.
+/// No-op when the game supplies no `mask`.
+#[allow(clippy::too_many_arguments)]
+unsafe fn blend_mask(
+    cmd_list: &ID3D12GraphicsCommandList,
+    gpu: &GpuState,
+    d: &UpscaleFrame,
+    color_res: &ID3D12Resource,
+    output: &ID3D12Resource,
+    mask: &FfxApiResource,
+    strength: f32,
+    [srv_color, srv_mask]: [u32; 2],
+    w: u32,
+    h: u32,
+) {
+    let Some(mask_res) = borrow_resource(mask.resource) else {
+        return;
+    };
+
+    let states = [(
+        &mask_res,
+        ffx_state_to_d3d12(mask.state),
+        D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE,
+    )];
+    apply_barriers(cmd_list, &transitions_if_needed(&states));
+
+    if create_typed_srv(gpu, color_res, d.color.description.format, srv_color)
+        && create_typed_srv(gpu, &mask_res, mask.description.format, srv_mask)
+    {
+        create_uav(
+            gpu,
+            output,
+            fsr_sys::FFX_API_SURFACE_FORMAT_R16G16B16A16_FLOAT,
+            UAV_BLEND_OUT,
+        );
+        apply_barriers(cmd_list, &[uav_barrier(output)]);
+        let constants = [w, h, strength.to_bits(), 0, 0, 0, 0, 0];
+        dispatch_compute(
+            cmd_list,
+            gpu,
+            &gpu.pso_reactive_blend,
+            &constants,
+            srv_color,
+            UAV_BLEND_OUT,
+            w,
+            h,
+        );
+        apply_barriers(cmd_list, &[uav_barrier(output)]);
+    } else {
+        warn!("reactive_blend: unsupported color or mask format, skipped");
+    }
+
+    let restore = states.map(|(res, before, after)| (res, after, before));
+    apply_barriers(cmd_list, &transitions_if_needed(&restore));
+}
+
+fn uav_barrier(resource: &ID3D12Resource) -> D3D12_RESOURCE_BARRIER {
+    D3D12_RESOURCE_BARRIER {
+        Type: D3D12_RESOURCE_BARRIER_TYPE_UAV,
+        Flags: D3D12_RESOURCE_BARRIER_FLAG_NONE,
+        Anonymous: D3D12_RESOURCE_BARRIER_0 {
+            UAV: std::mem::ManuallyDrop::new(D3D12_RESOURCE_UAV_BARRIER {
+                pResource: unsafe { std::mem::transmute_copy(resource) },
+            }),
+        },
+    }
+}
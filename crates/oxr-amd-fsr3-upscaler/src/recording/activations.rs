@@ -0,0 +1,409 @@
+//! One-frame dump of the IMBA network's activations, for comparing against
+//! PyTorch on the same frame.
+//!
+//! The overlay requests a dump; the next primary AA dispatch (`aa_pass`) runs
+//! the whole table without the temporal cache and copies every tensor into a
+//! readback buffer right after the dispatch that completes it, before its slot
+//! is reused. A GPU marker after the last copy tells `poll` (from
+//! `pre_dispatch`) when the data can be mapped; a thread then writes
+//! `activations_<secs>/` under the recording path: one `NNN_<name>.npy`
+//! (float32, C×H×W) per tensor, the network inputs of each pixel-unshuffle
+//! tensor un-shuffled back to render resolution (`<name>_inputs.npy`, for v0
+//! 8×H×W: RGB, depth, motion in render pixels, jitter), and `manifest.json`.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use fsr_sys::imba::graph::Activation;
+use fsr_sys::imba::{AAConstants, PassType};
+use fsr_sys::weights::WeightDtype;
+use tracing::{error, info, warn};
+use windows::core::Interface;
+use windows::Win32::Graphics::Direct3D12::*;
+use windows::Win32::Graphics::Dxgi::Common::*;
+
+use crate::dispatch::{apply_barriers, resource_barrier_transition_d3d12};
+
+/// Polls before an unmarked capture is assumed complete and dropped.
+const MAX_POLLS: u32 = 120;
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+static IN_FLIGHT: Mutex<Option<Capture>> = Mutex::new(None);
+
+/// What the manifest records about the dumped frame.
+pub struct DumpInfo {
+    pub model: String,
+    pub dtype: WeightDtype,
+    pub render: (u32, u32),
+    pub output: (u32, u32),
+    /// Jitter of the current and previous frame, in render pixels.
+    pub jitter: [f32; 2],
+    pub prev_jitter: [f32; 2],
+}
+
+struct Tensor {
+    activation: Activation,
+    /// Byte offset in the readback buffer.
+    offset: u64,
+    /// Produced by a pixel unshuffle of the frame inputs.
+    unshuffled_inputs: bool,
+}
+
+/// The readback of one frame's activations, recorded into a command list.
+pub struct Capture {
+    buffer: ID3D12Resource,
+    tensors: Vec<Tensor>,
+    marker_offset: u64,
+    marked: bool,
+    polls: u32,
+    info: DumpInfo,
+}
+
+/// Dump the activations of the next IMBA frame.
+pub fn request() {
+    REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// A dump is requested or not yet written.
+pub fn busy() -> bool {
+    REQUESTED.load(Ordering::Relaxed) || IN_FLIGHT.lock().map_or(true, |c| c.is_some())
+}
+
+/// Start the requested dump, if any, for a table with `dispatches` and
+/// `activations`. Returns `None` when none is requested or the buffer cannot
+/// be created.
+pub unsafe fn begin(
+    device: &ID3D12Device,
+    dispatches: &[AAConstants],
+    activations: &[Activation],
+    info: DumpInfo,
+) -> Option<Capture> {
+    if IN_FLIGHT.lock().map_or(true, |c| c.is_some()) || !REQUESTED.swap(false, Ordering::Relaxed) {
+        return None;
+    }
+
+    let mut offset = 0u64;
+    let tensors: Vec<Tensor> = activations
+        .iter()
+        .map(|a| {
+            let t = Tensor {
+                activation: a.clone(),
+                offset,
+                unshuffled_inputs: dispatches[a.dispatch as usize].pass_type
+                    == PassType::PixelUnshuffle as u32,
+            };
+            offset += a.len() as u64 * 4;
+            t
+        })
+        .collect();
+    let marker_offset = offset;
+
+    let heap = D3D12_HEAP_PROPERTIES {
+        Type: D3D12_HEAP_TYPE_READBACK,
+        ..Default::default()
+    };
+    let desc = D3D12_RESOURCE_DESC {
+        Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
+        Width: marker_offset + 4,
+        Height: 1,
+        DepthOrArraySize: 1,
+        MipLevels: 1,
+        Format: DXGI_FORMAT_UNKNOWN,
+        SampleDesc: DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+        ..Default::default()
+    };
+    let mut buffer: Option<ID3D12Resource> = None;
+    if let Err(e) = device.CreateCommittedResource(
+        &heap,
+        D3D12_HEAP_FLAG_NONE,
+        &desc,
+        D3D12_RESOURCE_STATE_COPY_DEST,
+        None,
+        &mut buffer,
+    ) {
+        error!("activations: readback buffer ({} bytes): {}", desc.Width, e);
+        return None;
+    }
+    let buffer = buffer?;
+
+    // Clear the marker; readback memory has no defined contents
+    let mut mapped: *mut u8 = std::ptr::null_mut();
+    if let Err(e) = buffer.Map(0, None, Some(&mut mapped as *mut *mut u8 as *mut *mut _)) {
+        error!("activations: Map failed: {}", e);
+        return None;
+    }
+    std::ptr::write_unaligned(mapped.add(marker_offset as usize) as *mut u32, 0);
+    let written = D3D12_RANGE {
+        Begin: marker_offset as usize,
+        End: marker_offset as usize + 4,
+    };
+    buffer.Unmap(0, Some(&written));
+
+    info!(
+        "activations: capturing {} tensors ({:.1} MiB)",
+        tensors.len(),
+        marker_offset as f64 / (1024.0 * 1024.0)
+    );
+    Some(Capture {
+        buffer,
+        tensors,
+        marker_offset,
+        marked: false,
+        polls: 0,
+        info,
+    })
+}
+
+impl Capture {
+    /// Copy the tensors completed by dispatch `index` out of `features` (in
+    /// UNORDERED_ACCESS, where it stays).
+    pub unsafe fn copy_after(
+        &self,
+        cmd_list: &ID3D12GraphicsCommandList,
+        features: &ID3D12Resource,
+        buf_stride: u32,
+        index: u32,
+    ) {
+        let done: Vec<&Tensor> = self
+            .tensors
+            .iter()
+            .filter(|t| t.activation.dispatch == index)
+            .collect();
+        if done.is_empty() {
+            return;
+        }
+
+        apply_barriers(
+            cmd_list,
+            &[resource_barrier_transition_d3d12(
+                features,
+                D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+                D3D12_RESOURCE_STATE_COPY_SOURCE,
+            )],
+        );
+        for t in done {
+            cmd_list.CopyBufferRegion(
+                &self.buffer,
+                t.offset,
+                features,
+                t.activation.slot as u64 * buf_stride as u64 * 4,
+                t.activation.len() as u64 * 4,
+            );
+        }
+        apply_barriers(
+            cmd_list,
+            &[resource_barrier_transition_d3d12(
+                features,
+                D3D12_RESOURCE_STATE_COPY_SOURCE,
+                D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+            )],
+        );
+    }
+
+    /// Mark the end of the copies and hand the capture to `poll`.
+    pub unsafe fn finish(mut self, cmd_list: &ID3D12GraphicsCommandList) {
+        match cmd_list.cast::<ID3D12GraphicsCommandList2>() {
+            Ok(cmd_list2) => {
+                let param = D3D12_WRITEBUFFERIMMEDIATE_PARAMETER {
+                    Dest: self.buffer.GetGPUVirtualAddress() + self.marker_offset,
+                    Value: 1,
+                };
+                let mode = D3D12_WRITEBUFFERIMMEDIATE_MODE_MARKER_IN;
+                cmd_list2.WriteBufferImmediate(1, &param, Some(&mode));
+                self.marked = true;
+            }
+            Err(e) => {
+                warn!(
+                    "activations: QI to ID3D12GraphicsCommandList2 failed: {} — writing after {} frames",
+                    e, MAX_POLLS
+                );
+            }
+        }
+        if let Ok(mut in_flight) = IN_FLIGHT.lock() {
+            *in_flight = Some(self);
+        }
+    }
+
+    unsafe fn complete(&mut self) -> bool {
+        self.polls += 1;
+        if !self.marked {
+            return self.polls >= MAX_POLLS;
+        }
+        let mut mapped: *mut u8 = std::ptr::null_mut();
+        let marker = D3D12_RANGE {
+            Begin: self.marker_offset as usize,
+            End: self.marker_offset as usize + 4,
+        };
+        if let Err(e) = self.buffer.Map(
+            0,
+            Some(&marker),
+            Some(&mut mapped as *mut *mut u8 as *mut *mut _),
+        ) {
+            error!("activations: marker Map failed: {}", e);
+            return false;
+        }
+        let value = std::ptr::read_unaligned(mapped.add(self.marker_offset as usize) as *const u32);
+        self.buffer.Unmap(0, Some(&D3D12_RANGE::default()));
+        value == 1
+    }
+}
+
+/// Write out a capture the GPU has finished. Called once per dispatch.
+pub unsafe fn poll() {
+    let capture = {
+        let Ok(mut in_flight) = IN_FLIGHT.lock() else {
+            return;
+        };
+        let complete = in_flight.as_mut().is_some_and(|c| c.complete());
+        if !complete {
+            return;
+        }
+        in_flight.take()
+    };
+    let Some(capture) = capture else {
+        return;
+    };
+
+    let mut mapped: *mut u8 = std::ptr::null_mut();
+    if let Err(e) = capture
+        .buffer
+        .Map(0, None, Some(&mut mapped as *mut *mut u8 as *mut *mut _))
+    {
+        error!("activations: Map failed: {}", e);
+        return;
+    }
+    let floats = capture.marker_offset as usize / 4;
+    let mut data = vec![0f32; floats];
+    std::ptr::copy_nonoverlapping(mapped as *const f32, data.as_mut_ptr(), floats);
+    capture.buffer.Unmap(0, Some(&D3D12_RANGE::default()));
+
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let dir = crate::settings::get()
+        .recording_path
+        .join(format!("activations_{}", secs));
+    let Capture { tensors, info, .. } = capture;
+
+    let spawned = std::thread::Builder::new()
+        .name("activations-writer".into())
+        .spawn(move || match write_dump(&dir, &tensors, &data, &info) {
+            Ok(()) => info!(
+                "activations: wrote {} tensors → {}",
+                tensors.len(),
+                dir.display()
+            ),
+            Err(e) => error!("activations: {}", e),
+        });
+    if let Err(e) = spawned {
+        error!("activations: failed to spawn writer thread: {}", e);
+    }
+}
+
+fn write_dump(dir: &Path, tensors: &[Tensor], data: &[f32], info: &DumpInfo) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("create {}: {}", dir.display(), e))?;
+
+    let mut entries = Vec::with_capacity(tensors.len());
+    for (i, t) in tensors.iter().enumerate() {
+        let a = &t.activation;
+        let start = t.offset as usize / 4;
+        let values = &data[start..start + a.len() as usize];
+        let file = format!("{:03}_{}.npy", i, a.name);
+        write_npy(&dir.join(&file), &[a.channels, a.height, a.width], values)?;
+        entries.push(format!(
+            "    {{\"file\": \"{}\", \"name\": \"{}\", \"dispatch\": {}, \"shape\": [{}, {}, {}]}}",
+            file, a.name, a.dispatch, a.channels, a.height, a.width
+        ));
+
+        if t.unshuffled_inputs {
+            let file = format!("{}_inputs.npy", a.name);
+            let (c, w, h) = (a.channels / 4, a.width * 2, a.height * 2);
+            write_npy(
+                &dir.join(&file),
+                &[c, h, w],
+                &pixel_shuffle(values, c, a.width, a.height),
+            )?;
+            entries.push(format!(
+                "    {{\"file\": \"{}\", \"name\": \"{}\", \"inputs\": true, \"shape\": [{}, {}, {}]}}",
+                file, a.name, c, h, w
+            ));
+        }
+    }
+
+    let manifest = format!(
+        r#"{{
+  "model": "{}",
+  "weight_dtype": "{:?}",
+  "render_size": [{}, {}],
+  "output_size": [{}, {}],
+  "jitter_px": [{}, {}],
+  "prev_jitter_px": [{}, {}],
+  "layout": "float32 CHW; *_inputs are RGB, depth, motion (render px), jitter (px); past render_size the edge pixels repeat",
+  "tensors": [
+{}
+  ]
+}}"#,
+        info.model.replace('\\', "\\\\").replace('"', "\\\""),
+        info.dtype,
+        info.render.0,
+        info.render.1,
+        info.output.0,
+        info.output.1,
+        info.jitter[0],
+        info.jitter[1],
+        info.prev_jitter[0],
+        info.prev_jitter[1],
+        entries.join(",\n"),
+    );
+    std::fs::write(dir.join("manifest.json"), manifest)
+        .map_err(|e| format!("write manifest: {}", e))
+}
+
+/// Inverse of the network's 2× pixel unshuffle: `channels * 4` planes of
+/// `w × h` back to `channels` planes of `2w × 2h`.
+fn pixel_shuffle(values: &[f32], channels: u32, w: u32, h: u32) -> Vec<f32> {
+    let (w, h) = (w as usize, h as usize);
+    let mut out = vec![0f32; channels as usize * 4 * w * h];
+    for ch in 0..channels as usize {
+        for dy in 0..2 {
+            for dx in 0..2 {
+                let plane = &values[(ch * 4 + dy * 2 + dx) * w * h..][..w * h];
+                for y in 0..h {
+                    for x in 0..w {
+                        out[(ch * 2 * h + 2 * y + dy) * 2 * w + 2 * x + dx] = plane[y * w + x];
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+/// NumPy `.npy` v1.0, little-endian float32, C order.
+fn write_npy(path: &Path, shape: &[u32], values: &[f32]) -> Result<(), String> {
+    let dims: Vec<String> = shape.iter().map(|d| d.to_string()).collect();
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}), }}",
+        dims.join(", ")
+    );
+    // magic (6) + version (2) + length (2) + header, padded to 64 with '\n' last
+    while (10 + header.len() + 1) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+
+    let mut bytes = Vec::with_capacity(10 + header.len() + values.len() * 4);
+    bytes.extend_from_slice(b"\x93NUMPY\x01\x00");
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    for v in values {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+    std::fs::write(path, bytes).map_err(|e| format!("write {}: {}", path.display(), e))
+}
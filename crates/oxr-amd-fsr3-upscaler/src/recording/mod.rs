@@ -1,3 +1,4 @@
+pub mod activations;
 mod extractor;
 mod readback;
 pub mod stride;
@@ -13,8 +14,8 @@ use windows::Win32::Graphics::Direct3D12::{
 };
 use windows::Win32::UI::Input::KeyboardAndMouse::GetAsyncKeyState;
 
-use crate::fsr3_types::FfxFsr3UpscalerDispatchDescription;
 use extractor::{estimate_slot_bytes, DeferredFramePacket, DeferredTextureData, ExtractorMessage};
+use fsr_sys::UpscaleFrame;
 use readback::{is_depth_stencil_format, ReadbackPool, Slot};
 use writer::{FrameMetadata, FramePacket, TextureData, WriterMessage};
 
@@ -84,7 +85,9 @@ impl RecorderState {
 static RECORDER: Mutex<Option<RecorderState>> = Mutex::new(None);
 
 /// Called before dispatch. Checks hotkey, maps previous frame's readback, sends to writer.
-pub unsafe fn pre_dispatch(d: &FfxFsr3UpscalerDispatchDescription) {
+pub unsafe fn pre_dispatch(d: &UpscaleFrame) {
+    activations::poll();
+
     let f10_down = (GetAsyncKeyState(VK_F10) as u16 & 0x8000) != 0;
     let prev = PREV_F10.swap(f10_down, Ordering::Relaxed);
     let toggled = f10_down && !prev;
@@ -624,7 +627,7 @@ pub unsafe fn pre_dispatch(d: &FfxFsr3UpscalerDispatchDescription) {
 }
 
 /// Called after dispatch. Enqueues GPU copies from source textures to readback buffers.
-pub unsafe fn post_dispatch(d: &FfxFsr3UpscalerDispatchDescription) {
+pub unsafe fn post_dispatch(d: &UpscaleFrame) {
     if !RECORDING_ACTIVE.load(Ordering::Relaxed) {
         return;
     }
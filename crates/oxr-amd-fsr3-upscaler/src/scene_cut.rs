@@ -0,0 +1,342 @@
+//! Camera-cut detection for the temporal upscalers and IMBA AA.
+//!
+//! Each frame a compute pass gathers `FrameStats` (luma and depth histograms,
+//! motion vector magnitudes) into a buffer that is copied to one of a few
+//! readback buffers. Later dispatches read the finished ones back and feed
+//! `fsr_sys::scene_cut::SceneCutDetector`; when it sees a cut the game did not
+//! flag, the next dispatch runs with `reset` set. The readback latency means
+//! history is dropped a frame or two after the cut rather than on it.
+//!
+//! Follows `fsr_sys::scene_cut`; `scene_stats_cs.hlsl` is its GPU twin.
+
+use crate::context::{ContextKey, PerContext};
+use crate::dispatch::{
+    apply_barriers, ffx_state_to_d3d12, resource_barrier_transition_d3d12, transitions_if_needed,
+};
+use crate::exposure::{create_buffer, create_structured_uav};
+use crate::gpu_pipeline::{self, GpuState};
+use crate::settings;
+use crate::upscalers::{borrow_resource, create_typed_srv};
+use fsr_sys::exposure::sanitize_pre_exposure;
+use fsr_sys::scene_cut::{FrameStats, SceneCutDetector, SCENE_CUT_STATS_WORDS};
+use fsr_sys::UpscaleFrame;
+use tracing::{error, info, warn};
+use windows::Win32::Graphics::Direct3D12::*;
+use windows::Win32::Graphics::Dxgi::Common::*;
+
+// --- SRV/UAV slots (SRV table t0-t2, then the UAV table) ---
+const SRV_COLOR: u32 = 58;
+const SRV_DEPTH: u32 = 59;
+const SRV_VELOCITY: u32 = 60;
+const UAV_STATS: u32 = 61;
+
+const GROUP_SIZE: u32 = 8;
+
+/// Match `MODE_*` in the shader.
+const MODE_GATHER: u32 = 0;
+const MODE_CLEAR: u32 = 1;
+
+/// Frames of statistics in flight; the GPU rarely runs further behind.
+const READBACK_SLOTS: usize = 3;
+
+const STATS_BYTES: u64 = (SCENE_CUT_STATS_WORDS * 4) as u64;
+
+struct SceneCutState {
+    stats: ID3D12Resource,
+    readback: [ID3D12Resource; READBACK_SLOTS],
+    /// Frame index and game `reset` of the statistics copied into each
+    /// readback slot, until they are consumed.
+    pending: [Option<(u32, bool)>; READBACK_SLOTS],
+    /// Next frame index. Starts at 1 so zeroed readback memory never matches.
+    frame: u32,
+    /// Whether the stats buffer has been cleared since creation.
+    initialized: bool,
+    detector: SceneCutDetector,
+    reset_requested: bool,
+}
+
+static SCENE_CUT: PerContext<SceneCutState> = PerContext::new();
+
+/// Drop the detector and buffers of a destroyed context.
+pub fn release(key: ContextKey) {
+    SCENE_CUT.remove(key);
+}
+
+unsafe fn create_readback_buffer(device: &ID3D12Device) -> Result<ID3D12Resource, String> {
+    let heap_props = D3D12_HEAP_PROPERTIES {
+        Type: D3D12_HEAP_TYPE_READBACK,
+        ..Default::default()
+    };
+    let desc = D3D12_RESOURCE_DESC {
+        Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
+        Width: STATS_BYTES,
+        Height: 1,
+        DepthOrArraySize: 1,
+        MipLevels: 1,
+        SampleDesc: DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+        ..Default::default()
+    };
+
+    let mut resource: Option<ID3D12Resource> = None;
+    device
+        .CreateCommittedResource(
+            &heap_props,
+            D3D12_HEAP_FLAG_NONE,
+            &desc,
+            D3D12_RESOURCE_STATE_COPY_DEST,
+            None,
+            &mut resource,
+        )
+        .map_err(|e| format!("CreateCommittedResource for readback failed: {}", e))?;
+    resource.ok_or_else(|| "CreateCommittedResource returned null".to_string())
+}
+
+fn uav_barrier(resource: &ID3D12Resource) -> D3D12_RESOURCE_BARRIER {
+    D3D12_RESOURCE_BARRIER {
+        Type: D3D12_RESOURCE_BARRIER_TYPE_UAV,
+        Flags: D3D12_RESOURCE_BARRIER_FLAG_NONE,
+        Anonymous: D3D12_RESOURCE_BARRIER_0 {
+            UAV: std::mem::ManuallyDrop::new(D3D12_RESOURCE_UAV_BARRIER {
+                pResource: unsafe { std::mem::transmute_copy(resource) },
+            }),
+        },
+    }
+}
+
+/// Read a readback slot; `None` while the GPU has not copied `frame` into it.
+unsafe fn read_stats(readback: &ID3D12Resource, frame: u32) -> Option<FrameStats> {
+    let range = D3D12_RANGE {
+        Begin: 0,
+        End: STATS_BYTES as usize,
+    };
+    let mut mapped: *mut core::ffi::c_void = std::ptr::null_mut();
+    if let Err(e) = readback.Map(0, Some(&range), Some(&mut mapped)) {
+        warn!("scene_cut: readback Map failed: {}", e);
+        return None;
+    }
+    let words = std::slice::from_raw_parts(mapped as *const u32, SCENE_CUT_STATS_WORDS);
+    let stats = FrameStats::from_words(words);
+    readback.Unmap(0, Some(&D3D12_RANGE { Begin: 0, End: 0 }));
+    stats.filter(|s| s.frame_index == frame)
+}
+
+impl SceneCutState {
+    /// Feed every finished readback to the detector, oldest first.
+    unsafe fn poll(&mut self, context: ContextKey) {
+        let mut order: Vec<usize> = (0..READBACK_SLOTS)
+            .filter(|&i| self.pending[i].is_some())
+            .collect();
+        order.sort_by_key(|&i| self.pending[i].map(|(frame, _)| frame));
+        for i in order {
+            let Some((frame, game_reset)) = self.pending[i] else {
+                continue;
+            };
+            let Some(stats) = read_stats(&self.readback[i], frame) else {
+                // Later slots were submitted after this one; wait for it
+                break;
+            };
+            self.pending[i] = None;
+            if let Some(reason) = self.detector.update(stats, game_reset) {
+                info!(
+                    "scene_cut: context 0x{:x} frame {}: cut detected, {}",
+                    context, frame, reason
+                );
+                self.reset_requested = true;
+            }
+        }
+    }
+}
+
+/// Record this frame's statistics pass, and return a copy of `d` with `reset`
+/// set when an earlier frame showed a cut the game did not flag. `None` keeps
+/// `d` as is (no cut, detection disabled in `oxr.ini`, or a logged failure).
+/// `d` should carry normalized depth and motion vectors; `color_res` must be
+/// in the game's state and is restored before returning.
+pub unsafe fn detect(
+    context: ContextKey,
+    cmd_list: &ID3D12GraphicsCommandList,
+    gpu: &GpuState,
+    d: &UpscaleFrame,
+    color_res: &ID3D12Resource,
+    render_w: u32,
+    render_h: u32,
+) -> Option<UpscaleFrame> {
+    let config = settings::get().scene_cut?;
+    let mut state = match SCENE_CUT.get_or_create(
+        context,
+        |_| false,
+        || {
+            let stats = create_buffer(&gpu.device, STATS_BYTES)?;
+            let readback = [
+                create_readback_buffer(&gpu.device)?,
+                create_readback_buffer(&gpu.device)?,
+                create_readback_buffer(&gpu.device)?,
+            ];
+            info!(
+                ?config,
+                "scene_cut: context 0x{:x} detector created", context
+            );
+            Ok::<_, String>(SceneCutState {
+                stats,
+                readback,
+                pending: [None; READBACK_SLOTS],
+                frame: 1,
+                initialized: false,
+                detector: SceneCutDetector::new(config),
+                reset_requested: false,
+            })
+        },
+    ) {
+        Ok(state) => state,
+        Err(e) => {
+            error!("scene_cut: {}, detection disabled for this frame", e);
+            return None;
+        }
+    };
+
+    state.poll(context);
+    record(&mut state, cmd_list, gpu, d, color_res, render_w, render_h);
+
+    if !std::mem::take(&mut state.reset_requested) || d.reset {
+        return None;
+    }
+    info!("scene_cut: context 0x{:x}: resetting history", context);
+    let mut frame = *d;
+    frame.reset = true;
+    Some(frame)
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe fn record(
+    state: &mut SceneCutState,
+    cmd_list: &ID3D12GraphicsCommandList,
+    gpu: &GpuState,
+    d: &UpscaleFrame,
+    color_res: &ID3D12Resource,
+    render_w: u32,
+    render_h: u32,
+) {
+    let (Some(depth_res), Some(mv_res)) = (
+        borrow_resource(d.depth.resource),
+        borrow_resource(d.motion_vectors.resource),
+    ) else {
+        return;
+    };
+
+    let states = [
+        (
+            color_res,
+            ffx_state_to_d3d12(d.color.state),
+            D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE,
+        ),
+        (
+            &depth_res,
+            ffx_state_to_d3d12(d.depth.state),
+            D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE,
+        ),
+        (
+            &mv_res,
+            ffx_state_to_d3d12(d.motion_vectors.state),
+            D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE,
+        ),
+    ];
+    apply_barriers(cmd_list, &transitions_if_needed(&states));
+
+    let srvs_ok = create_typed_srv(gpu, color_res, d.color.description.format, SRV_COLOR)
+        && create_typed_srv(gpu, &depth_res, d.depth.description.format, SRV_DEPTH)
+        && create_typed_srv(
+            gpu,
+            &mv_res,
+            d.motion_vectors.description.format,
+            SRV_VELOCITY,
+        );
+    if srvs_ok {
+        create_structured_uav(
+            gpu,
+            &state.stats,
+            SCENE_CUT_STATS_WORDS as u32,
+            4,
+            UAV_STATS,
+        );
+
+        cmd_list.SetDescriptorHeaps(&[Some(gpu.srv_heap.clone())]);
+        cmd_list.SetComputeRootSignature(&gpu.compute_3srv_root_signature);
+        cmd_list.SetPipelineState(&gpu.pso_scene_stats);
+        cmd_list.SetComputeRootDescriptorTable(1, gpu_pipeline::get_srv_gpu_handle(gpu, SRV_COLOR));
+        cmd_list.SetComputeRootDescriptorTable(2, gpu_pipeline::get_srv_gpu_handle(gpu, UAV_STATS));
+
+        let frame = state.frame;
+        let motion_threshold = state.detector.config.motion_threshold;
+        let set_constants = |mode: u32| {
+            let constants = [
+                render_w | (render_h << 16),
+                mode,
+                sanitize_pre_exposure(d.pre_exposure).to_bits(),
+                motion_threshold.to_bits(),
+                frame,
+                0,
+                0,
+                0,
+            ];
+            cmd_list.SetComputeRoot32BitConstants(
+                0,
+                constants.len() as u32,
+                constants.as_ptr() as *const core::ffi::c_void,
+                0,
+            );
+        };
+
+        // Fresh buffers hold garbage
+        if !state.initialized {
+            set_constants(MODE_CLEAR);
+            cmd_list.Dispatch(1, 1, 1);
+            apply_barriers(cmd_list, &[uav_barrier(&state.stats)]);
+            state.initialized = true;
+        }
+
+        set_constants(MODE_GATHER);
+        cmd_list.Dispatch(
+            render_w.div_ceil(GROUP_SIZE),
+            render_h.div_ceil(GROUP_SIZE),
+            1,
+        );
+
+        let slot = frame as usize % READBACK_SLOTS;
+        apply_barriers(
+            cmd_list,
+            &[resource_barrier_transition_d3d12(
+                &state.stats,
+                D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+                D3D12_RESOURCE_STATE_COPY_SOURCE,
+            )],
+        );
+        cmd_list.CopyBufferRegion(&state.readback[slot], 0, &state.stats, 0, STATS_BYTES);
+        apply_barriers(
+            cmd_list,
+            &[resource_barrier_transition_d3d12(
+                &state.stats,
+                D3D12_RESOURCE_STATE_COPY_SOURCE,
+                D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+            )],
+        );
+
+        // Leave the buffer zeroed for the next frame
+        set_constants(MODE_CLEAR);
+        cmd_list.Dispatch(1, 1, 1);
+        apply_barriers(cmd_list, &[uav_barrier(&state.stats)]);
+
+        // An unread slot means the GPU is more than READBACK_SLOTS frames
+        // behind; the detector does not compare across the gap
+        state.pending[slot] = Some((frame, d.reset));
+        state.frame = frame.wrapping_add(1).max(1);
+    } else {
+        warn!("scene_cut: unsupported color, depth or motion vector format, skipped");
+    }
+
+    let restore = states.map(|(res, before, after)| (res, after, before));
+    apply_barriers(cmd_list, &transitions_if_needed(&restore));
+}
@@ -1,13 +1,41 @@
 use std::path::PathBuf;
 use std::sync::OnceLock;
 
+use fsr_sys::dynres::DynResConfig;
+use fsr_sys::quality::QualityTable;
+use fsr_sys::scene_cut::SceneCutConfig;
 use ini::Ini;
-use tracing::info;
+use tracing::{info, warn};
 
+use crate::game_profile::GameProfile;
 use crate::logging;
 
 pub struct Settings {
     pub recording_path: PathBuf,
+    /// `[validation] enabled` — validate dispatch descriptors even when the game
+    /// did not request debug checking.
+    pub validation: bool,
+    /// `[original] forward` — load `ffx_fsr3upscaler_x64_original.dll` and forward
+    /// context lifetime, reactive mask and helper exports to it. Off by default.
+    pub forward_to_original: bool,
+    /// `[imba] upscaler` — offer IMBA as an upscaler: the AA network at render
+    /// resolution followed by a bilinear resample. Reactive / composition masks
+    /// and exposure are not applied. Off by default.
+    pub imba_upscaler: bool,
+    /// `[jitter] enabled` — answer the jitter exports with FSR's Halton
+    /// sequence. Off by default: they report no jitter (offset 0, one phase).
+    pub jitter: bool,
+    /// `[quality]` — per-mode upscale ratios, `custom_scale` and `force_native`.
+    pub quality: QualityTable,
+    /// `[dynamic_resolution]` — controller config, `None` unless `enabled`.
+    pub dynamic_resolution: Option<DynResConfig>,
+    /// `[scene_cut]` — camera-cut detector thresholds, `None` when disabled.
+    pub scene_cut: Option<SceneCutConfig>,
+    /// `[game] profile` — force a game profile by name instead of detecting it.
+    pub game_profile: Option<String>,
+    /// `[profile.<name>]` sections — extra game profiles, searched before the
+    /// built-in ones.
+    pub game_profiles: Vec<GameProfile>,
 }
 
 static SETTINGS: OnceLock<Settings> = OnceLock::new();
@@ -25,25 +53,96 @@ fn load() -> Settings {
     let ini_path = dll_dir.join("oxr.ini");
     let default_recording = dll_dir.join("recordings");
 
-    let recording_path = match Ini::load_from_file_opt(
+    let ini = match Ini::load_from_file_opt(
         &ini_path,
         ini::ParseOption {
             enabled_escape: false,
             ..Default::default()
         },
     ) {
-        Ok(ini) => ini
-            .section(Some("recording"))
-            .and_then(|s| s.get("path"))
-            .filter(|p| !p.is_empty())
-            .map(PathBuf::from)
-            .unwrap_or(default_recording),
+        Ok(ini) => Some(ini),
         Err(_) => {
             info!("settings: oxr.ini not found, using defaults");
-            default_recording
+            None
         }
     };
+    let get = |section: &str, key: &str| {
+        ini.as_ref()
+            .and_then(|ini| ini.section(Some(section)))
+            .and_then(|s| s.get(key))
+            .filter(|v| !v.is_empty())
+    };
+
+    let recording_path = get("recording", "path")
+        .map(PathBuf::from)
+        .unwrap_or(default_recording);
+    let validation = get("validation", "enabled").is_some_and(parse_bool);
+    let forward_to_original = get("original", "forward").is_some_and(parse_bool);
+    let imba_upscaler = get("imba", "upscaler").is_some_and(parse_bool);
+    let jitter = get("jitter", "enabled").is_some_and(parse_bool);
+    let (quality, rejected) = QualityTable::parse(|key| get("quality", key));
+    for (key, value) in rejected {
+        warn!("settings: ignoring [quality] {} = {:?}", key, value);
+    }
+    let (dynamic_resolution, rejected) = DynResConfig::parse(|key| get("dynamic_resolution", key));
+    for (key, value) in rejected {
+        warn!(
+            "settings: ignoring [dynamic_resolution] {} = {:?}",
+            key, value
+        );
+    }
+
+    let (scene_cut, rejected) = SceneCutConfig::parse(|key| get("scene_cut", key));
+    for (key, value) in rejected {
+        warn!("settings: ignoring [scene_cut] {} = {:?}", key, value);
+    }
 
     info!("settings: recording_path = {:?}", recording_path);
-    Settings { recording_path }
+    info!("settings: validation = {}", validation);
+    info!("settings: forward_to_original = {}", forward_to_original);
+    info!("settings: imba_upscaler = {}", imba_upscaler);
+    info!("settings: jitter = {}", jitter);
+    let game_profile = get("game", "profile").map(|v| v.trim().to_string());
+    let mut game_profiles = Vec::new();
+    for (section, props) in ini.iter().flat_map(|ini| ini.iter()) {
+        let Some(name) = section.and_then(|s| s.strip_prefix("profile.")) else {
+            continue;
+        };
+        let keys: Vec<&str> = props.iter().map(|(k, _)| k).collect();
+        let (profile, rejected) = GameProfile::parse(name, &keys, |key| props.get(key));
+        for (key, value) in rejected {
+            warn!(
+                "settings: ignoring [profile.{}] {} = {:?}",
+                name, key, value
+            );
+        }
+        game_profiles.push(profile);
+    }
+
+    info!("settings: quality = {:?}", quality);
+    info!("settings: dynamic_resolution = {:?}", dynamic_resolution);
+    info!("settings: scene_cut = {:?}", scene_cut);
+    info!("settings: game_profile = {:?}", game_profile);
+    for p in &game_profiles {
+        info!("settings: game profile from oxr.ini: {:?}", p);
+    }
+    Settings {
+        recording_path,
+        validation,
+        forward_to_original,
+        imba_upscaler,
+        jitter,
+        quality,
+        dynamic_resolution,
+        scene_cut,
+        game_profile,
+        game_profiles,
+    }
+}
+
+fn parse_bool(v: &str) -> bool {
+    matches!(
+        v.trim().to_ascii_lowercase().as_str(),
+        "1" | "true" | "yes" | "on"
+    )
 }
@@ -7,6 +7,10 @@ pub enum UpscalerType {
     SGSR = 2,
     SGSRv2TwoPass = 3,
     SGSRv2 = 4,
+    /// IMBA AA at render resolution, then a bilinear resample to the output;
+    /// the reactive / composition masks and exposure are not applied. Only
+    /// offered with `[imba] upscaler` set.
+    Imba = 5,
 }
 
 static ACTIVE: AtomicU8 = AtomicU8::new(UpscalerType::SGSRv2 as u8);
@@ -17,6 +21,7 @@ pub fn get() -> UpscalerType {
         2 => UpscalerType::SGSR,
         3 => UpscalerType::SGSRv2TwoPass,
         4 => UpscalerType::SGSRv2,
+        5 => UpscalerType::Imba,
         _ => UpscalerType::Lanczos,
     }
 }
@@ -38,14 +43,15 @@ pub fn rcas_set(on: bool) {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AntiAliasingType {
     None = 0,
-    ImbaV0 = 1,
+    /// IMBA with the model picked in `upscalers::aa_models`.
+    Imba = 1,
 }
 
-static AA_TYPE: AtomicU8 = AtomicU8::new(AntiAliasingType::ImbaV0 as u8);
+static AA_TYPE: AtomicU8 = AtomicU8::new(AntiAliasingType::Imba as u8);
 
 pub fn aa_get() -> AntiAliasingType {
     match AA_TYPE.load(Ordering::Relaxed) {
-        1 => AntiAliasingType::ImbaV0,
+        1 => AntiAliasingType::Imba,
         _ => AntiAliasingType::None,
     }
 }
@@ -63,3 +69,15 @@ pub fn debug_view_get() -> bool {
 pub fn debug_view_set(on: bool) {
     DEBUG_VIEW.store(on, Ordering::Relaxed);
 }
+
+/// Whether the temporal upscalers and IMBA AA honour the game's transparency &
+/// composition mask; the overlay toggles it for comparison.
+static COMPOSITION_MASK: AtomicBool = AtomicBool::new(true);
+
+pub fn composition_mask_get() -> bool {
+    COMPOSITION_MASK.load(Ordering::Relaxed)
+}
+
+pub fn composition_mask_set(on: bool) {
+    COMPOSITION_MASK.store(on, Ordering::Relaxed);
+}
@@ -0,0 +1,189 @@
+//! IMBA model registry.
+//!
+//! Weight files in `models/` next to the DLL, plus the historical
+//! `aa_weights.bin` there, are scanned on first use and again when the overlay
+//! asks. Only files that parse and match the network (`aa_graph.txt` or the
+//! built-in v0) are listed; the others are logged with the reason. The overlay
+//! picks the active model and optionally a second one drawn on the right half
+//! of the frame; `aa_pass` rebuilds a context's state when its model changes.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use fsr_sys::imba::graph::Graph;
+use fsr_sys::imba::{pack_weight_words, parse_weights};
+use fsr_sys::weights::{WeightDtype, WeightFile};
+use tracing::{info, warn};
+
+use crate::logging;
+
+/// A listed weight file. Two entries are equal when they name the same file
+/// with the same modification time, so a retrained file rebuilds the state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Model {
+    /// Model name and version from the header.
+    pub label: String,
+    pub path: PathBuf,
+    pub file_name: String,
+    pub dtype: WeightDtype,
+    modified: Option<SystemTime>,
+}
+
+struct Registry {
+    models: Vec<Model>,
+    scanned: bool,
+    selected: Option<PathBuf>,
+    compare: Option<PathBuf>,
+}
+
+impl Registry {
+    fn selected(&self) -> Option<Model> {
+        find(&self.models, self.selected.as_deref()).or_else(|| self.models.first().cloned())
+    }
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    models: Vec::new(),
+    scanned: false,
+    selected: None,
+    compare: None,
+});
+
+fn lock() -> std::sync::MutexGuard<'static, Registry> {
+    let mut reg = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    if !reg.scanned {
+        reg.models = scan();
+        reg.scanned = true;
+    }
+    reg
+}
+
+/// Scan the model directory again; selections survive when their file is
+/// still listed.
+pub fn rescan() {
+    let mut reg = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    reg.models = scan();
+    reg.scanned = true;
+}
+
+/// Every valid model, `aa_weights.bin` first, then `models/` by file name.
+pub fn models() -> Vec<Model> {
+    lock().models.clone()
+}
+
+/// The model the AA pass runs: the selected one, else the first listed.
+pub fn selected() -> Option<Model> {
+    lock().selected()
+}
+
+pub fn select(path: &Path) {
+    lock().selected = Some(path.to_path_buf());
+    info!("aa_models: selected {}", path.display());
+}
+
+/// The model shown on the right half for comparison, unless it is the
+/// selected one.
+pub fn compare() -> Option<Model> {
+    let reg = lock();
+    let selected = reg.selected();
+    find(&reg.models, reg.compare.as_deref()).filter(|m| Some(m) != selected.as_ref())
+}
+
+pub fn set_compare(path: Option<&Path>) {
+    lock().compare = path.map(Path::to_path_buf);
+    match path {
+        Some(p) => info!("aa_models: comparing against {}", p.display()),
+        None => info!("aa_models: comparison off"),
+    }
+}
+
+fn find(models: &[Model], path: Option<&Path>) -> Option<Model> {
+    let path = path?;
+    models.iter().find(|m| m.path == path).cloned()
+}
+
+/// Read `path` and check it against `graph`; returns the header and the
+/// buffer in the graph's layout as f32 words, whatever the file's dtype. A
+/// legacy raw blob is accepted when its size matches.
+pub fn load(path: &Path, graph: &Graph) -> Result<(WeightFile, Vec<u32>), String> {
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    if !WeightFile::is_container(&bytes) {
+        warn!(
+            "aa_models: {} is a legacy raw weight blob; only its size can be checked \
+             (`oxr-weights convert` turns it into a checked file)",
+            path.display()
+        );
+    }
+    parse_weights(&bytes, graph)
+        .and_then(|f| {
+            let words = pack_weight_words(&f, graph)?;
+            Ok((f, words))
+        })
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn scan() -> Vec<Model> {
+    let dll_dir = logging::dll_directory().unwrap_or_else(|| PathBuf::from("."));
+    let graph = match super::aa_pass::load_graph() {
+        Ok(g) => g,
+        Err(e) => {
+            warn!("aa_models: {}", e);
+            return Vec::new();
+        }
+    };
+
+    let mut paths = Vec::new();
+    let legacy_path = dll_dir.join("aa_weights.bin");
+    if legacy_path.is_file() {
+        paths.push(legacy_path);
+    }
+    let models_dir = dll_dir.join("models");
+    if let Ok(entries) = std::fs::read_dir(&models_dir) {
+        let mut found: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file() && p.extension().is_some_and(|e| e.eq_ignore_ascii_case("bin")))
+            .collect();
+        found.sort();
+        paths.extend(found);
+    }
+
+    let mut models = Vec::new();
+    for path in paths {
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let (file, _) = match load(&path, &graph) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                warn!("aa_models: skipping {}", e);
+                continue;
+            }
+        };
+        let label = format!("{} {}", file.model_name, file.model_version);
+        info!(
+            "aa_models: {} '{}' ({:?})",
+            path.display(),
+            label,
+            file.dtype
+        );
+        models.push(Model {
+            label,
+            modified: std::fs::metadata(&path).and_then(|m| m.modified()).ok(),
+            path,
+            file_name,
+            dtype: file.dtype,
+        });
+    }
+    if models.is_empty() {
+        warn!(
+            "aa_models: no weight files in {} or {}",
+            models_dir.display(),
+            dll_dir.join("aa_weights.bin").display()
+        );
+    }
+    models
+}
@@ -1,328 +1,36 @@
 //! AA model inference via compute shaders.
 //!
-//! Port of AAPass.cpp from imba. 17 compute shaders, ~57K params,
-//! 26 dispatches per frame (prev encoder cached after frame 1).
-
+//! Port of AAPass.cpp from imba. 21 compute shaders; the network is described
+//! by `fsr_sys::imba::graph` (built-in v0, or `aa_graph.txt` next to the DLL)
+//! and compiled to a dispatch table per render and output size. The output
+//! texture is at output size, larger than the inputs when IMBA upscales
+//! (`upscalers::imba`). The previous-frame encoder is skipped once its output
+//! is cached; the last `Graph::history_depth` frames of it are kept for the
+//! graph's `history` tensors. A reset, a long frame gap or a new state
+//! (resolution, colour format or model change) drops them, see
+//! `history_status` for the overlay. Weights come from the model picked in `aa_models`; a second
+//! state per context runs the comparison model with its own descriptor tables.
+//! The overlay can replace the output with one channel of a tensor
+//! (`set_layer_view`); with the `recording` feature a frame's tensors can be
+//! dumped (`recording::activations`).
+
+use std::cell::Cell;
+use std::fmt;
 use std::sync::Mutex;
+use std::time::Instant;
 
+use fsr_sys::imba::graph::{Activation, Graph, HistoryRead, TemporalCache, AA_GN_MAX_GROUPS};
+use fsr_sys::imba::{AAConstants, PassType, AA_GN_TILES_PER_GROUP, PASS_COUNT};
+use fsr_sys::UpscaleFrame;
 use tracing::{error, info};
 use windows::Win32::Graphics::Direct3D12::*;
 use windows::Win32::Graphics::Dxgi::Common::*;
 
+use crate::context::{ContextKey, PerContext, PerContextGuard};
 use crate::gpu_pipeline::{self, GpuState};
 use crate::logging;
 
-// ── Constants ────────────────────────────────────────────────────────────────
-
-const AA_TOTAL_WEIGHTS: u32 = 57352;
-const AA_NO_OFFSET: u32 = 0xFFFF_FFFF;
-
-// Pass type indices — must match shader file order in gpu_pipeline AA_PSO array
-#[repr(u32)]
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub enum PassType {
-    PixelUnshuffle = 0,
-    Conv = 1,
-    GNStats = 2,
-    GNApply = 3,
-    BackwardWarp = 4,
-    Attention = 5,
-    NearestUpsample = 6,
-    SkipConcatConv = 7,
-    PixelShuffleOut = 8,
-    ScaleMV = 9,
-    GNStatsReduce = 10,
-    Conv3x3_16x16 = 11,
-    Conv3x3_32x32 = 12,
-    Conv3x3_32x16 = 13,
-    Conv3x3_16x12 = 14,
-    Conv3x3_12x12 = 15,
-    Conv3x3S2_16x32 = 16,
-}
-
-pub const PASS_COUNT: usize = 17;
-
-const ACT_NONE: u32 = 0;
-const ACT_SILU: u32 = 1;
-const ACT_TANH: u32 = 3;
-
-const FLAG_HAS_SKIP: u32 = 1;
-const FLAG_IS_PREV: u32 = 2;
-
-// ── Weight offsets (from AAWeightOffsets.h) ──────────────────────────────────
-
-struct WeightEntry {
-    offset: u32,
-    _count: u32,
-}
-
-#[allow(dead_code)]
-#[repr(usize)]
-enum W {
-    EncCompressConv = 0,
-    EncCompressGnGamma,
-    EncCompressGnBeta,
-    EncRes1C1Conv,
-    EncRes1C1GnGamma,
-    EncRes1C1GnBeta,
-    EncRes1C2Conv,
-    EncRes1Gn2Gamma,
-    EncRes1Gn2Beta,
-    EncRes2C1Conv,
-    EncRes2C1GnGamma,
-    EncRes2C1GnBeta,
-    EncRes2C2Conv,
-    EncRes2Gn2Gamma,
-    EncRes2Gn2Beta,
-    EncDownConv,
-    EncDownGnGamma,
-    EncDownGnBeta,
-    EncRes3C1Conv,
-    EncRes3C1GnGamma,
-    EncRes3C1GnBeta,
-    EncRes3C2Conv,
-    EncRes3Gn2Gamma,
-    EncRes3Gn2Beta,
-    TempAttnConv,
-    TempAttnBias,
-    TempMergeConv,
-    TempMergeGnGamma,
-    TempMergeGnBeta,
-    DecUpConv,
-    DecUpGnGamma,
-    DecUpGnBeta,
-    DecSkipConv,
-    DecResC1Conv,
-    DecResC1GnGamma,
-    DecResC1GnBeta,
-    DecResC2Conv,
-    DecResGn2Gamma,
-    DecResGn2Beta,
-    HeadExpandConv,
-    HeadExpandBias,
-    HeadRefineConv,
-    HeadRefineBias,
-}
-
-static WEIGHTS: &[WeightEntry] = &[
-    WeightEntry {
-        offset: 0,
-        _count: 512,
-    }, // EncCompressConv [16,32,1,1]
-    WeightEntry {
-        offset: 512,
-        _count: 16,
-    }, // EncCompressGnGamma
-    WeightEntry {
-        offset: 528,
-        _count: 16,
-    }, // EncCompressGnBeta
-    WeightEntry {
-        offset: 544,
-        _count: 2304,
-    }, // EncRes1C1Conv [16,16,3,3]
-    WeightEntry {
-        offset: 2848,
-        _count: 16,
-    }, // EncRes1C1GnGamma
-    WeightEntry {
-        offset: 2864,
-        _count: 16,
-    }, // EncRes1C1GnBeta
-    WeightEntry {
-        offset: 2880,
-        _count: 2304,
-    }, // EncRes1C2Conv
-    WeightEntry {
-        offset: 5184,
-        _count: 16,
-    }, // EncRes1Gn2Gamma
-    WeightEntry {
-        offset: 5200,
-        _count: 16,
-    }, // EncRes1Gn2Beta
-    WeightEntry {
-        offset: 5216,
-        _count: 2304,
-    }, // EncRes2C1Conv
-    WeightEntry {
-        offset: 7520,
-        _count: 16,
-    }, // EncRes2C1GnGamma
-    WeightEntry {
-        offset: 7536,
-        _count: 16,
-    }, // EncRes2C1GnBeta
-    WeightEntry {
-        offset: 7552,
-        _count: 2304,
-    }, // EncRes2C2Conv
-    WeightEntry {
-        offset: 9856,
-        _count: 16,
-    }, // EncRes2Gn2Gamma
-    WeightEntry {
-        offset: 9872,
-        _count: 16,
-    }, // EncRes2Gn2Beta
-    WeightEntry {
-        offset: 9888,
-        _count: 4608,
-    }, // EncDownConv [32,16,3,3]
-    WeightEntry {
-        offset: 14496,
-        _count: 32,
-    }, // EncDownGnGamma
-    WeightEntry {
-        offset: 14528,
-        _count: 32,
-    }, // EncDownGnBeta
-    WeightEntry {
-        offset: 14560,
-        _count: 9216,
-    }, // EncRes3C1Conv [32,32,3,3]
-    WeightEntry {
-        offset: 23776,
-        _count: 32,
-    }, // EncRes3C1GnGamma
-    WeightEntry {
-        offset: 23808,
-        _count: 32,
-    }, // EncRes3C1GnBeta
-    WeightEntry {
-        offset: 23840,
-        _count: 9216,
-    }, // EncRes3C2Conv
-    WeightEntry {
-        offset: 33056,
-        _count: 32,
-    }, // EncRes3Gn2Gamma
-    WeightEntry {
-        offset: 33088,
-        _count: 32,
-    }, // EncRes3Gn2Beta
-    WeightEntry {
-        offset: 33120,
-        _count: 2048,
-    }, // TempAttnConv [32,64,1,1]
-    WeightEntry {
-        offset: 35168,
-        _count: 32,
-    }, // TempAttnBias
-    WeightEntry {
-        offset: 35200,
-        _count: 9216,
-    }, // TempMergeConv [32,32,3,3]
-    WeightEntry {
-        offset: 44416,
-        _count: 32,
-    }, // TempMergeGnGamma
-    WeightEntry {
-        offset: 44448,
-        _count: 32,
-    }, // TempMergeGnBeta
-    WeightEntry {
-        offset: 44480,
-        _count: 4608,
-    }, // DecUpConv [16,32,3,3]
-    WeightEntry {
-        offset: 49088,
-        _count: 16,
-    }, // DecUpGnGamma
-    WeightEntry {
-        offset: 49104,
-        _count: 16,
-    }, // DecUpGnBeta
-    WeightEntry {
-        offset: 49120,
-        _count: 512,
-    }, // DecSkipConv [16,32,1,1]
-    WeightEntry {
-        offset: 49632,
-        _count: 2304,
-    }, // DecResC1Conv [16,16,3,3]
-    WeightEntry {
-        offset: 51936,
-        _count: 16,
-    }, // DecResC1GnGamma
-    WeightEntry {
-        offset: 51952,
-        _count: 16,
-    }, // DecResC1GnBeta
-    WeightEntry {
-        offset: 51968,
-        _count: 2304,
-    }, // DecResC2Conv
-    WeightEntry {
-        offset: 54272,
-        _count: 16,
-    }, // DecResGn2Gamma
-    WeightEntry {
-        offset: 54288,
-        _count: 16,
-    }, // DecResGn2Beta
-    WeightEntry {
-        offset: 54304,
-        _count: 1728,
-    }, // HeadExpandConv [12,16,3,3]
-    WeightEntry {
-        offset: 56032,
-        _count: 12,
-    }, // HeadExpandBias
-    WeightEntry {
-        offset: 56044,
-        _count: 1296,
-    }, // HeadRefineConv [12,12,3,3]
-    WeightEntry {
-        offset: 57340,
-        _count: 12,
-    }, // HeadRefineBias
-];
-
-fn w(idx: W) -> u32 {
-    WEIGHTS[idx as usize].offset
-}
-
-// ── AAConstants (root constants, 28 DWORDs = 112 bytes) ─────────────────────
-
-#[repr(C)]
-#[derive(Clone, Copy)]
-pub struct AAConstants {
-    pub pass_type: u32,
-    pub in_buf: u32,
-    pub out_buf: u32,
-    pub aux_buf: u32,
-    pub width: u32,
-    pub height: u32,
-    pub in_channels: u32,
-    pub out_channels: u32,
-    pub kernel_size: u32,
-    pub stride: u32,
-    pub weight_off: u32,
-    pub bias_off: u32,
-    pub gamma_off: u32,
-    pub beta_off: u32,
-    pub num_groups: u32,
-    pub activation: u32,
-    pub flags: u32,
-    pub in_width: u32,
-    pub in_height: u32,
-    pub buf_stride: u32,
-    pub jitter_x: f32,
-    pub jitter_y: f32,
-    pub prev_jitter_x: f32,
-    pub prev_jitter_y: f32,
-    pub debug_mode: u32,
-    pub _pad: [u32; 3],
-}
-
-const _: () = assert!(std::mem::size_of::<AAConstants>() == 112);
-
-impl Default for AAConstants {
-    fn default() -> Self {
-        unsafe { std::mem::zeroed() }
-    }
-}
+use super::aa_models::{self, Model};
 
 // ── Persistent AA state ─────────────────────────────────────────────────────
 
@@ -341,73 +49,330 @@ pub struct AAState {
     buf_stride: u32,
     feature_buf_total_elements: u32,
     gn_partials_total_elements: u32,
-    temporal_cache_bytes: u64,
+    weight_count: u32,
 
     prev_encoder_start: u32,
     prev_encoder_end: u32,
-    temporal_slot: u32,
-    prev_temporal_slot: u32,
-
-    pub has_cached_temporal: bool,
+    temporal: Option<TemporalCache>,
+    history: Vec<HistoryRead>,
+    activations: Vec<Activation>,
+
+    /// Frames in `cached_temporal_buffer`, a ring of `temporal.depth`
+    /// entries with the newest at `history_head`.
+    history_frames: u32,
+    history_head: u32,
     pub prev_frame_valid: bool,
     pub prev_jitter_x: f32,
     pub prev_jitter_y: f32,
     render_w: u32,
     render_h: u32,
+    output_w: u32,
+    output_h: u32,
+    color_format: DXGI_FORMAT,
+    /// The weights this state was built from.
+    pub model: Model,
+    srv_start: u32,
+    uav_start: u32,
 }
 
-static AA_STATE: Mutex<Option<AAState>> = Mutex::new(None);
+static AA_STATE: PerContext<AAState> = PerContext::new();
+static AA_COMPARE_STATE: PerContext<AAState> = PerContext::new();
+
+/// Why the temporal history was dropped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HistoryReset {
+    /// First state of the context.
+    Created,
+    RenderSize,
+    OutputSize,
+    ColorFormat,
+    Model,
+    /// The frame's `reset` flag.
+    GameReset,
+    /// `scene_cut` found a cut the game did not flag.
+    SceneCut,
+    /// `frame_time_delta` above `HISTORY_MAX_FRAME_GAP_MS`, in milliseconds.
+    FrameGap(f32),
+}
 
-/// SRV/UAV heap slot assignments for AA pass.
-pub const AA_SRV_START: u32 = 25; // t0-t6: slots 25-31
-pub const AA_UAV_START: u32 = 32; // u0-u3: slots 32-35
+impl fmt::Display for HistoryReset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Created => write!(f, "new context"),
+            Self::RenderSize => write!(f, "render size changed"),
+            Self::OutputSize => write!(f, "output size changed"),
+            Self::ColorFormat => write!(f, "colour format changed"),
+            Self::Model => write!(f, "model changed"),
+            Self::GameReset => write!(f, "game reset"),
+            Self::SceneCut => write!(f, "scene cut"),
+            Self::FrameGap(ms) => write!(f, "{:.0} ms frame gap", ms),
+        }
+    }
+}
+
+/// Longest `frame_time_delta` the history survives. Slower than this is a
+/// pause or a loading screen rather than gameplay, and the cached frames no
+/// longer match what is on screen.
+pub const HISTORY_MAX_FRAME_GAP_MS: f32 = 500.0;
+
+/// Why frame `d` cannot build on the history, if it cannot: its `reset` flag
+/// (`cut` when `scene_cut` set it), or a long gap since the last frame.
+pub fn history_break(d: &UpscaleFrame, cut: bool) -> Option<HistoryReset> {
+    if d.reset {
+        Some(if cut {
+            HistoryReset::SceneCut
+        } else {
+            HistoryReset::GameReset
+        })
+    } else if d.frame_time_delta > HISTORY_MAX_FRAME_GAP_MS {
+        Some(HistoryReset::FrameGap(d.frame_time_delta))
+    } else {
+        None
+    }
+}
 
-/// Get or create AA state. Returns `None` on error.
+/// The primary state's temporal history, for the overlay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistoryStatus {
+    /// Frames cached, up to `depth`.
+    pub frames: u32,
+    /// Frames the network keeps; 0 without temporal features.
+    pub depth: u32,
+    /// Resets since start, state creation included.
+    pub resets: u32,
+    pub last_reset: Option<(HistoryReset, Instant)>,
+}
+
+static HISTORY: Mutex<HistoryStatus> = Mutex::new(HistoryStatus {
+    frames: 0,
+    depth: 0,
+    resets: 0,
+    last_reset: None,
+});
+
+pub fn history_status() -> HistoryStatus {
+    *HISTORY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl AAState {
+    fn is_primary(&self) -> bool {
+        self.srv_start == AA_SRV_START
+    }
+
+    /// Heap slot of t7, the first of the two mask SRVs.
+    pub fn mask_srv_slot(&self) -> u32 {
+        self.srv_start + 7
+    }
+
+    /// Start over like a first frame: the previous-frame inputs and the cached
+    /// temporal features are dropped.
+    pub fn reset_history(&mut self, reason: HistoryReset) {
+        self.prev_frame_valid = false;
+        self.history_frames = 0;
+        if self.is_primary() {
+            info!("aa_pass: history reset ({})", reason);
+            let mut status = HISTORY.lock().unwrap_or_else(|e| e.into_inner());
+            status.resets += 1;
+            status.last_reset = Some((reason, Instant::now()));
+        }
+        self.publish_history();
+    }
+
+    fn publish_history(&self) {
+        if self.is_primary() {
+            let mut status = HISTORY.lock().unwrap_or_else(|e| e.into_inner());
+            status.frames = self.history_frames;
+            status.depth = self.temporal.map_or(0, |t| t.depth);
+        }
+    }
+
+    /// Why this state cannot serve a frame of these sizes, format and model.
+    fn stale(
+        &self,
+        render: (u32, u32),
+        output: (u32, u32),
+        color_format: DXGI_FORMAT,
+        model: &Model,
+    ) -> Option<HistoryReset> {
+        if (self.render_w, self.render_h) != render {
+            Some(HistoryReset::RenderSize)
+        } else if (self.output_w, self.output_h) != output {
+            Some(HistoryReset::OutputSize)
+        } else if self.color_format != color_format {
+            Some(HistoryReset::ColorFormat)
+        } else if self.model != *model {
+            Some(HistoryReset::Model)
+        } else {
+            None
+        }
+    }
+
+    /// Byte offset of the cached frame `age` frames back (1 the newest).
+    fn history_offset(&self, t: TemporalCache, age: u32) -> u64 {
+        let entry = (self.history_head + age - 1) % t.depth;
+        entry as u64 * t.len as u64 * 4
+    }
+}
+
+/// A tensor channel drawn instead of the network's output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerView {
+    pub tensor: String,
+    pub channel: u32,
+    /// Magnitude drawn at full intensity.
+    pub range: f32,
+}
+
+static LAYER_VIEW: Mutex<Option<LayerView>> = Mutex::new(None);
+/// Tensors of the last primary state created, for the overlay.
+static LAYERS: Mutex<Vec<Activation>> = Mutex::new(Vec::new());
+
+pub fn layer_view() -> Option<LayerView> {
+    LAYER_VIEW.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Draw `view` instead of the output, or the output again with `None`. The
+/// network stops after the viewed tensor and its temporal cache is rebuilt
+/// afterwards.
+pub fn set_layer_view(view: Option<LayerView>) {
+    *LAYER_VIEW.lock().unwrap_or_else(|e| e.into_inner()) = view;
+}
+
+/// The tensors a layer view can pick, in dispatch order.
+pub fn layers() -> Vec<Activation> {
+    LAYERS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// SRV/UAV heap slot assignments for AA pass.
+pub const AA_SRV_START: u32 = 25; // t0-t8: slots 25-33
+pub const AA_UAV_START: u32 = 73; // u0-u3: slots 73-76
+/// The comparison model's tables; both models run in one command list.
+pub const AA_COMPARE_SRV_START: u32 = 62; // t0-t8: slots 62-70
+pub const AA_COMPARE_UAV_START: u32 = 77; // u0-u3: slots 77-80
+
+/// Get or create the AA state of context `key` for `model`, writing an
+/// `output_w × output_h` output (the render size for AA). Returns `None` on
+/// error.
+#[allow(clippy::too_many_arguments)]
 pub unsafe fn get_or_create(
+    key: ContextKey,
     device: &ID3D12Device,
     render_w: u32,
     render_h: u32,
+    output_w: u32,
+    output_h: u32,
     color_format: DXGI_FORMAT,
-) -> std::sync::MutexGuard<'static, Option<AAState>> {
-    let mut guard = AA_STATE.lock().unwrap();
+    model: &Model,
+) -> Option<PerContextGuard<'static, AAState>> {
+    get_or_create_in(
+        &AA_STATE,
+        (AA_SRV_START, AA_UAV_START),
+        key,
+        device,
+        (render_w, render_h),
+        (output_w, output_h),
+        color_format,
+        model,
+    )
+}
 
-    let need_create = match guard.as_ref() {
-        None => true,
-        Some(s) => s.render_w != render_w || s.render_h != render_h,
-    };
+/// Like `get_or_create` at render resolution, for the model shown on the
+/// right half.
+pub unsafe fn get_or_create_compare(
+    key: ContextKey,
+    device: &ID3D12Device,
+    render_w: u32,
+    render_h: u32,
+    color_format: DXGI_FORMAT,
+    model: &Model,
+) -> Option<PerContextGuard<'static, AAState>> {
+    get_or_create_in(
+        &AA_COMPARE_STATE,
+        (AA_COMPARE_SRV_START, AA_COMPARE_UAV_START),
+        key,
+        device,
+        (render_w, render_h),
+        (render_w, render_h),
+        color_format,
+        model,
+    )
+}
 
-    if need_create {
-        match create_state(device, render_w, render_h, color_format) {
-            Ok(state) => {
-                info!(
-                    "aa_pass: created resources for {}x{} (features={} floats)",
-                    render_w, render_h, state.feature_buf_total_elements
-                );
-                *guard = Some(state);
-            }
-            Err(e) => {
-                error!("aa_pass: failed to create resources: {}", e);
-                *guard = None;
-            }
+#[allow(clippy::too_many_arguments)]
+unsafe fn get_or_create_in(
+    map: &'static PerContext<AAState>,
+    slots: (u32, u32),
+    key: ContextKey,
+    device: &ID3D12Device,
+    render: (u32, u32),
+    output: (u32, u32),
+    color_format: DXGI_FORMAT,
+    model: &Model,
+) -> Option<PerContextGuard<'static, AAState>> {
+    let reason = Cell::new(HistoryReset::Created);
+    let result = map.get_or_create(
+        key,
+        |s| {
+            let stale = s.stale(render, output, color_format, model);
+            reason.set(stale.unwrap_or(HistoryReset::Created));
+            stale.is_some()
+        },
+        || {
+            let mut state = create_state(device, render, output, color_format, model, slots)?;
+            state.reset_history(reason.get());
+            info!(
+                "aa_pass: created resources for {}x{} -> {}x{} with '{}' (features={} floats)",
+                render.0,
+                render.1,
+                output.0,
+                output.1,
+                model.label,
+                state.feature_buf_total_elements
+            );
+            Ok::<_, String>(state)
+        },
+    );
+    match result {
+        Ok(guard) => Some(guard),
+        Err(e) => {
+            error!("aa_pass: failed to create resources: {}", e);
+            None
         }
     }
+}
+
+/// Make the comparison state of `key`, if any, start over like a first frame.
+pub fn reset_compare(key: ContextKey) {
+    if let Some(mut state) = AA_COMPARE_STATE.get(key) {
+        state.prev_frame_valid = false;
+        state.history_frames = 0;
+    }
+}
+
+/// Drop the comparison model's buffers once comparison is off.
+pub fn release_compare(key: ContextKey) {
+    AA_COMPARE_STATE.remove(key);
+}
 
-    guard
+/// Drop the buffers and temporal history of a destroyed context.
+pub fn release(key: ContextKey) {
+    AA_STATE.remove(key);
+    AA_COMPARE_STATE.remove(key);
 }
 
 unsafe fn create_state(
     device: &ID3D12Device,
-    render_w: u32,
-    render_h: u32,
+    (render_w, render_h): (u32, u32),
+    (output_w, output_h): (u32, u32),
     color_format: DXGI_FORMAT,
+    model: &Model,
+    (srv_start, uav_start): (u32, u32),
 ) -> Result<AAState, String> {
-    let half_w = render_w / 2;
-    let half_h = render_h / 2;
-    let qtr_w = half_w / 2;
-    let qtr_h = half_h / 2;
-
-    let buf_stride = 32 * half_w * half_h;
-    let feature_buf_total_elements = 6 * buf_stride;
+    let graph = load_graph()?;
+    let weight_data = load_weights(&graph, model)?;
+    let table = graph.dispatch_table(render_w, render_h, output_w, output_h);
+    let buf_stride = table.buf_stride;
+    let feature_buf_total_elements = table.slots * buf_stride;
 
     let default_heap = D3D12_HEAP_PROPERTIES {
         Type: D3D12_HEAP_TYPE_DEFAULT,
@@ -420,7 +385,6 @@ unsafe fn create_state(
 
     // ── Weight buffer (upload heap) ──
     let weight_buffer = {
-        let weight_data = load_weights()?;
         let desc = D3D12_RESOURCE_DESC {
             Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
             Width: weight_data.len() as u64,
@@ -454,7 +418,7 @@ unsafe fn create_state(
         buf.Unmap(0, None);
 
         info!(
-            "aa_pass: loaded {} weight floats ({} bytes)",
+            "aa_pass: loaded {} weight words ({} bytes)",
             weight_data.len() / 4,
             weight_data.len()
         );
@@ -493,12 +457,15 @@ unsafe fn create_state(
 
     let feature_buffer = make_buf(feature_buf_total_elements as u64 * 4, "feature buffer")?;
 
-    let gn_stats_buffer = make_buf(64 * 4, "gn_stats buffer")?;
+    let gn_stats_buffer = make_buf(2 * AA_GN_MAX_GROUPS as u64 * 4, "gn_stats buffer")?;
 
-    let gn_partials_total_elements = 4 * 64 * 2; // NumGroups * TILES_PER_GROUP * 2
+    // NumGroups * TILES_PER_GROUP * 2
+    let gn_partials_total_elements = table.gn_max_groups.max(1) * AA_GN_TILES_PER_GROUP * 2;
     let gn_partials_buffer = make_buf(gn_partials_total_elements as u64 * 4, "gn_partials buffer")?;
 
-    let temporal_cache_bytes = 32u64 * qtr_w as u64 * qtr_h as u64 * 4;
+    let temporal_cache_bytes = table
+        .temporal
+        .map_or(4, |t| t.depth as u64 * t.len as u64 * 4);
     let cached_temporal_buffer = make_buf(temporal_cache_bytes, "cached_temporal buffer")?;
 
     // Helper: create texture
@@ -537,8 +504,8 @@ unsafe fn create_state(
     };
 
     let output_texture = make_tex(
-        render_w,
-        render_h,
+        output_w,
+        output_h,
         DXGI_FORMAT_R16G16B16A16_FLOAT,
         D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS,
         "aa output",
@@ -566,16 +533,16 @@ unsafe fn create_state(
         "aa prev_motion",
     )?;
 
-    // Build dispatch table
-    let (dispatch_table, prev_encoder_start, prev_encoder_end) =
-        build_dispatch_table(render_w, render_h, buf_stride);
-
     info!(
-        "aa_pass: {} dispatches, prev_encoder=[{}..{})",
-        dispatch_table.len(),
-        prev_encoder_start,
-        prev_encoder_end
+        "aa_pass: {} dispatches, {} feature slots, prev_encoder=[{}..{})",
+        table.dispatches.len(),
+        table.slots,
+        table.prev_encoder.start,
+        table.prev_encoder.end
     );
+    if srv_start == AA_SRV_START {
+        *LAYERS.lock().unwrap_or_else(|e| e.into_inner()) = table.activations.clone();
+    }
 
     Ok(AAState {
         weight_buffer,
@@ -587,32 +554,69 @@ unsafe fn create_state(
         prev_color,
         prev_depth,
         prev_motion,
-        dispatch_table,
+        dispatch_table: table.dispatches,
         buf_stride,
         feature_buf_total_elements,
         gn_partials_total_elements,
-        temporal_cache_bytes,
-        prev_encoder_start,
-        prev_encoder_end,
-        temporal_slot: 3,
-        prev_temporal_slot: 4,
-        has_cached_temporal: false,
+        weight_count: graph.weight_count(),
+        prev_encoder_start: table.prev_encoder.start,
+        prev_encoder_end: table.prev_encoder.end,
+        temporal: table.temporal,
+        history: table.history,
+        activations: table.activations,
+        history_frames: 0,
+        history_head: 0,
         prev_frame_valid: false,
         prev_jitter_x: 0.0,
         prev_jitter_y: 0.0,
         render_w,
         render_h,
+        output_w,
+        output_h,
+        color_format,
+        model: model.clone(),
+        srv_start,
+        uav_start,
     })
 }
 
-fn load_weights() -> Result<Vec<u8>, String> {
+/// The network: `aa_graph.txt` next to the DLL when present, else the
+/// built-in v0.
+pub(crate) fn load_graph() -> Result<Graph, String> {
     let dll_dir = logging::dll_directory().unwrap_or_else(|| std::path::PathBuf::from("."));
-    let path = dll_dir.join("aa_weights.bin");
-    std::fs::read(&path).map_err(|e| format!("failed to read {}: {}", path.display(), e))
+    let path = dll_dir.join("aa_graph.txt");
+    if !path.exists() {
+        return Ok(Graph::builtin());
+    }
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let graph = Graph::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    info!(
+        "aa_pass: network '{}' from {}",
+        graph.model_name,
+        path.display()
+    );
+    Ok(graph)
+}
+
+/// Read `model`'s file, check it against `graph` and return the buffer in the
+/// graph's layout, F16 / I8 kernels decoded to f32.
+fn load_weights(graph: &Graph, model: &Model) -> Result<Vec<u8>, String> {
+    let (file, words) = aa_models::load(&model.path, graph)?;
+    info!(
+        "aa_pass: weights '{}' version '{}', {} tensors, {:?}",
+        file.model_name,
+        file.model_version,
+        file.tensors.len(),
+        file.dtype
+    );
+    Ok(words.iter().flat_map(|w| w.to_le_bytes()).collect())
 }
 
 // ── Descriptor setup ────────────────────────────────────────────────────────
 
+/// t0-t6 and u0-u3; the caller binds the masks at
+/// [`AAState::mask_srv_slot`] (`upscalers::bind_compute_mask_srvs`).
 pub unsafe fn setup_descriptors(
     gpu: &GpuState,
     state: &AAState,
@@ -623,7 +627,7 @@ pub unsafe fn setup_descriptors(
 ) {
     let device = &gpu.device;
 
-    // t0: weights (StructuredBuffer<float>)
+    // t0: weights (f32 words)
     {
         let srv = D3D12_SHADER_RESOURCE_VIEW_DESC {
             Format: DXGI_FORMAT_UNKNOWN,
@@ -632,7 +636,7 @@ pub unsafe fn setup_descriptors(
             Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
                 Buffer: D3D12_BUFFER_SRV {
                     FirstElement: 0,
-                    NumElements: AA_TOTAL_WEIGHTS,
+                    NumElements: state.weight_count,
                     StructureByteStride: 4,
                     Flags: D3D12_BUFFER_SRV_FLAG_NONE,
                 },
@@ -641,19 +645,19 @@ pub unsafe fn setup_descriptors(
         device.CreateShaderResourceView(
             &state.weight_buffer,
             Some(&srv),
-            gpu_pipeline::get_srv_cpu_handle(gpu, AA_SRV_START),
+            gpu_pipeline::get_srv_cpu_handle(gpu, state.srv_start),
         );
     }
 
     // t1: currColor
-    create_texture_srv(device, color_res, output_format, gpu, AA_SRV_START + 1);
+    create_texture_srv(device, color_res, output_format, gpu, state.srv_start + 1);
     // t2: currDepth
     create_texture_srv(
         device,
         depth_res,
         DXGI_FORMAT_R32_FLOAT,
         gpu,
-        AA_SRV_START + 2,
+        state.srv_start + 2,
     );
     // t3: currMotion
     create_texture_srv(
@@ -661,7 +665,7 @@ pub unsafe fn setup_descriptors(
         mv_res,
         DXGI_FORMAT_R16G16_FLOAT,
         gpu,
-        AA_SRV_START + 3,
+        state.srv_start + 3,
     );
     // t4: prevColor
     create_texture_srv(
@@ -669,7 +673,7 @@ pub unsafe fn setup_descriptors(
         &state.prev_color,
         output_format,
         gpu,
-        AA_SRV_START + 4,
+        state.srv_start + 4,
     );
     // t5: prevDepth
     create_texture_srv(
@@ -677,7 +681,7 @@ pub unsafe fn setup_descriptors(
         &state.prev_depth,
         DXGI_FORMAT_R32_FLOAT,
         gpu,
-        AA_SRV_START + 5,
+        state.srv_start + 5,
     );
     // t6: prevMotion
     create_texture_srv(
@@ -685,7 +689,7 @@ pub unsafe fn setup_descriptors(
         &state.prev_motion,
         DXGI_FORMAT_R16G16_FLOAT,
         gpu,
-        AA_SRV_START + 6,
+        state.srv_start + 6,
     );
 
     // u0: features (RWStructuredBuffer<float>)
@@ -694,10 +698,10 @@ pub unsafe fn setup_descriptors(
         &state.feature_buffer,
         state.feature_buf_total_elements,
         gpu,
-        AA_UAV_START,
+        state.uav_start,
     );
     // u1: gnStats
-    create_buffer_uav(device, &state.gn_stats_buffer, 64, gpu, AA_UAV_START + 1);
+    create_buffer_uav(device, &state.gn_stats_buffer, 64, gpu, state.uav_start + 1);
     // u2: output (RWTexture2D<float4>)
     {
         let uav = D3D12_UNORDERED_ACCESS_VIEW_DESC {
@@ -714,7 +718,7 @@ pub unsafe fn setup_descriptors(
             &state.output_texture,
             None,
             Some(&uav),
-            gpu_pipeline::get_srv_cpu_handle(gpu, AA_UAV_START + 2),
+            gpu_pipeline::get_srv_cpu_handle(gpu, state.uav_start + 2),
         );
     }
     // u3: gnPartials
@@ -723,7 +727,7 @@ pub unsafe fn setup_descriptors(
         &state.gn_partials_buffer,
         state.gn_partials_total_elements,
         gpu,
-        AA_UAV_START + 3,
+        state.uav_start + 3,
     );
 }
 
@@ -809,33 +813,68 @@ pub unsafe fn execute(
     }
 
     cmd_list.SetComputeRootSignature(&gpu.aa_root_signature);
-    cmd_list.SetComputeRootDescriptorTable(1, gpu_pipeline::get_srv_gpu_handle(gpu, AA_SRV_START));
-    cmd_list.SetComputeRootDescriptorTable(2, gpu_pipeline::get_srv_gpu_handle(gpu, AA_UAV_START));
-
-    let use_cache = state.has_cached_temporal;
-
-    // If using cache, copy cached prev_temporal → buf4
-    if use_cache {
-        let dst_offset = state.prev_temporal_slot as u64 * state.buf_stride as u64 * 4;
-
-        let barrier_pre = make_uav_to_copy_src(&state.cached_temporal_buffer);
-        cmd_list.ResourceBarrier(&[barrier_pre]);
-
-        cmd_list.CopyBufferRegion(
-            &state.feature_buffer,
-            dst_offset,
-            &state.cached_temporal_buffer,
-            0,
-            state.temporal_cache_bytes,
+    cmd_list
+        .SetComputeRootDescriptorTable(1, gpu_pipeline::get_srv_gpu_handle(gpu, state.srv_start));
+    cmd_list
+        .SetComputeRootDescriptorTable(2, gpu_pipeline::get_srv_gpu_handle(gpu, state.uav_start));
+
+    // A dump (primary state only) runs the whole table and copies every tensor out
+    #[cfg(feature = "recording")]
+    let capture = if state.srv_start == AA_SRV_START {
+        use crate::recording::activations::{self, DumpInfo};
+        let info = DumpInfo {
+            model: state.model.label.clone(),
+            dtype: state.model.dtype,
+            render: (state.render_w, state.render_h),
+            output: (state.output_w, state.output_h),
+            jitter: [jitter_x, jitter_y],
+            prev_jitter: [prev_jitter_x, prev_jitter_y],
+        };
+        activations::begin(&gpu.device, &state.dispatch_table, &state.activations, info)
+    } else {
+        None
+    };
+    #[cfg(feature = "recording")]
+    let dumping = capture.is_some();
+    #[cfg(not(feature = "recording"))]
+    let dumping = false;
+
+    // A layer view stops after its tensor's dispatch, before the slot is reused
+    let view = layer_view().filter(|_| !dumping).and_then(|v| {
+        let a = state.activations.iter().find(|a| a.name == v.tensor)?;
+        let c = a.view(
+            v.channel,
+            v.range,
+            state.output_w,
+            state.output_h,
+            state.buf_stride,
         );
-
-        let barrier_post = make_copy_src_to_common(&state.cached_temporal_buffer);
-        cmd_list.ResourceBarrier(&[barrier_post]);
+        Some((a.dispatch as usize + 1, c))
+    });
+    let end = view
+        .as_ref()
+        .map_or(state.dispatch_table.len(), |&(end, _)| end);
+
+    let cache = state.temporal;
+    let cached = state.history_frames;
+    let use_cache = cached > 0 && cache.is_some() && view.is_none() && !dumping;
+
+    // Cached frames → prev_temporal (when the encoder is skipped) and history slots
+    if let Some(t) = cache.filter(|_| cached > 0) {
+        let mut loads: Vec<(u32, u32)> = state
+            .history
+            .iter()
+            .map(|h| (h.frames.min(cached), h.slot))
+            .collect();
+        if use_cache {
+            loads.push((1, t.prev_slot));
+        }
+        load_history(cmd_list, state, t, &loads, false);
     }
 
     let mut current_pso = u32::MAX;
 
-    for i in 0..state.dispatch_table.len() {
+    for i in 0..end {
         // Skip prev encoder dispatches when using cached result
         if use_cache && i as u32 >= state.prev_encoder_start && (i as u32) < state.prev_encoder_end
         {
@@ -872,7 +911,7 @@ pub unsafe fn execute(
 
         // Calculate thread groups
         let (groups_x, groups_y) = if pass_type == PassType::GNStats as u32 {
-            (d.num_groups * 64, 1) // TILES_PER_GROUP = 64
+            (d.num_groups * AA_GN_TILES_PER_GROUP, 1)
         } else if pass_type == PassType::GNStatsReduce as u32 {
             (d.num_groups, 1)
         } else {
@@ -898,50 +937,145 @@ pub unsafe fn execute(
             make_uav(&state.gn_stats_buffer),
             make_uav(&state.gn_partials_buffer),
         ]);
+
+        #[cfg(feature = "recording")]
+        if let Some(capture) = &capture {
+            capture.copy_after(cmd_list, &state.feature_buffer, state.buf_stride, i as u32);
+        }
+
+        // Nothing cached: the encoded previous frame is the oldest history
+        if let Some(t) = cache.filter(|_| cached == 0 && !state.history.is_empty()) {
+            if i as u32 + 1 == state.prev_encoder_end {
+                push_history(cmd_list, state, t, t.prev_slot);
+                let loads: Vec<(u32, u32)> = state.history.iter().map(|h| (1, h.slot)).collect();
+                load_history(cmd_list, state, t, &loads, true);
+            }
+        }
     }
 
-    // Save curr_temporal (buf3) → cache for next frame
-    {
-        let src_offset = state.temporal_slot as u64 * state.buf_stride as u64 * 4;
-
-        let barriers_pre = [
-            make_barrier(
-                &state.feature_buffer,
-                D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
-                D3D12_RESOURCE_STATE_COPY_SOURCE,
-            ),
-            make_barrier(
-                &state.cached_temporal_buffer,
-                D3D12_RESOURCE_STATE_COMMON,
-                D3D12_RESOURCE_STATE_COPY_DEST,
-            ),
-        ];
-        cmd_list.ResourceBarrier(&barriers_pre);
+    if let Some((_, c)) = &view {
+        cmd_list.SetPipelineState(&gpu.aa_psos[PassType::DebugView as usize]);
+        cmd_list.SetComputeRoot32BitConstants(
+            0,
+            28,
+            c as *const AAConstants as *const std::ffi::c_void,
+            0,
+        );
+        cmd_list.Dispatch(c.width.div_ceil(8), c.height.div_ceil(8), 1);
+        // The current temporal features were not computed
+        state.history_frames = 0;
+        state.publish_history();
+        return;
+    }
 
-        cmd_list.CopyBufferRegion(
+    #[cfg(feature = "recording")]
+    if let Some(capture) = capture {
+        capture.finish(cmd_list);
+    }
+
+    // Save curr_temporal → cache for next frame
+    if let Some(t) = cache {
+        push_history(cmd_list, state, t, t.slot);
+    }
+    state.publish_history();
+}
+
+/// Copy feature slot `slot` into the ring of cached frames as the newest one.
+/// The feature buffer is in UNORDERED_ACCESS.
+unsafe fn push_history(
+    cmd_list: &ID3D12GraphicsCommandList,
+    state: &mut AAState,
+    t: TemporalCache,
+    slot: u32,
+) {
+    state.history_head = (state.history_head + t.depth - 1) % t.depth;
+    state.history_frames = (state.history_frames + 1).min(t.depth);
+    let src_offset = slot as u64 * state.buf_stride as u64 * 4;
+
+    let barriers_pre = [
+        make_barrier(
+            &state.feature_buffer,
+            D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+            D3D12_RESOURCE_STATE_COPY_SOURCE,
+        ),
+        make_barrier(
             &state.cached_temporal_buffer,
-            0,
+            D3D12_RESOURCE_STATE_COMMON,
+            D3D12_RESOURCE_STATE_COPY_DEST,
+        ),
+    ];
+    cmd_list.ResourceBarrier(&barriers_pre);
+
+    cmd_list.CopyBufferRegion(
+        &state.cached_temporal_buffer,
+        state.history_offset(t, 1),
+        &state.feature_buffer,
+        src_offset,
+        t.len as u64 * 4,
+    );
+
+    let barriers_post = [
+        make_barrier(
             &state.feature_buffer,
-            src_offset,
-            state.temporal_cache_bytes,
-        );
+            D3D12_RESOURCE_STATE_COPY_SOURCE,
+            D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+        ),
+        make_barrier(
+            &state.cached_temporal_buffer,
+            D3D12_RESOURCE_STATE_COPY_DEST,
+            D3D12_RESOURCE_STATE_COMMON,
+        ),
+    ];
+    cmd_list.ResourceBarrier(&barriers_post);
+}
 
-        let barriers_post = [
-            make_barrier(
-                &state.feature_buffer,
-                D3D12_RESOURCE_STATE_COPY_SOURCE,
-                D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
-            ),
-            make_barrier(
-                &state.cached_temporal_buffer,
-                D3D12_RESOURCE_STATE_COPY_DEST,
-                D3D12_RESOURCE_STATE_COMMON,
-            ),
-        ];
-        cmd_list.ResourceBarrier(&barriers_post);
+/// Copy cached frames into feature slots, `(age, slot)` with age 1 the
+/// newest. Before the first dispatch the feature buffer is promoted to
+/// COPY_DEST; `after_dispatches` it is in UNORDERED_ACCESS.
+unsafe fn load_history(
+    cmd_list: &ID3D12GraphicsCommandList,
+    state: &AAState,
+    t: TemporalCache,
+    loads: &[(u32, u32)],
+    after_dispatches: bool,
+) {
+    let mut barriers = vec![make_barrier(
+        &state.cached_temporal_buffer,
+        D3D12_RESOURCE_STATE_COMMON,
+        D3D12_RESOURCE_STATE_COPY_SOURCE,
+    )];
+    if after_dispatches {
+        barriers.push(make_barrier(
+            &state.feature_buffer,
+            D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+            D3D12_RESOURCE_STATE_COPY_DEST,
+        ));
+    }
+    cmd_list.ResourceBarrier(&barriers);
 
-        state.has_cached_temporal = true;
+    for &(age, slot) in loads {
+        cmd_list.CopyBufferRegion(
+            &state.feature_buffer,
+            slot as u64 * state.buf_stride as u64 * 4,
+            &state.cached_temporal_buffer,
+            state.history_offset(t, age),
+            t.len as u64 * 4,
+        );
+    }
+
+    let mut barriers = vec![make_barrier(
+        &state.cached_temporal_buffer,
+        D3D12_RESOURCE_STATE_COPY_SOURCE,
+        D3D12_RESOURCE_STATE_COMMON,
+    )];
+    if after_dispatches {
+        barriers.push(make_barrier(
+            &state.feature_buffer,
+            D3D12_RESOURCE_STATE_COPY_DEST,
+            D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+        ));
     }
+    cmd_list.ResourceBarrier(&barriers);
 }
 
 // ── Barrier helpers ─────────────────────────────────────────────────────────
@@ -964,603 +1098,3 @@ unsafe fn make_barrier(
     });
     barrier
 }
-
-unsafe fn make_uav_to_copy_src(resource: &ID3D12Resource) -> D3D12_RESOURCE_BARRIER {
-    make_barrier(
-        resource,
-        D3D12_RESOURCE_STATE_COMMON,
-        D3D12_RESOURCE_STATE_COPY_SOURCE,
-    )
-}
-
-unsafe fn make_copy_src_to_common(resource: &ID3D12Resource) -> D3D12_RESOURCE_BARRIER {
-    make_barrier(
-        resource,
-        D3D12_RESOURCE_STATE_COPY_SOURCE,
-        D3D12_RESOURCE_STATE_COMMON,
-    )
-}
-
-// ── Dispatch table builder ──────────────────────────────────────────────────
-
-fn build_dispatch_table(
-    render_w: u32,
-    render_h: u32,
-    buf_stride: u32,
-) -> (Vec<AAConstants>, u32, u32) {
-    let half_w = render_w / 2;
-    let half_h = render_h / 2;
-    let qtr_w = half_w / 2;
-    let qtr_h = half_h / 2;
-
-    let mut dispatches: Vec<AAConstants> = Vec::with_capacity(64);
-
-    // ── Helper closures ──
-
-    let add_pixel_unshuffle = |dispatches: &mut Vec<AAConstants>, is_prev: bool, out_buf: u32| {
-        let mut c = AAConstants::default();
-        c.pass_type = PassType::PixelUnshuffle as u32;
-        c.out_buf = out_buf;
-        c.width = half_w;
-        c.height = half_h;
-        c.in_width = render_w;
-        c.in_height = render_h;
-        c.flags = if is_prev { FLAG_IS_PREV } else { 0 };
-        c.buf_stride = buf_stride;
-        dispatches.push(c);
-    };
-
-    let select_conv_pass = |ks: u32, stride: u32, in_ch: u32, out_ch: u32| -> u32 {
-        if ks == 3 && stride == 1 && in_ch == 16 && out_ch == 16 {
-            PassType::Conv3x3_16x16 as u32
-        } else if ks == 3 && stride == 1 && in_ch == 32 && out_ch == 32 {
-            PassType::Conv3x3_32x32 as u32
-        } else if ks == 3 && stride == 1 && in_ch == 32 && out_ch == 16 {
-            PassType::Conv3x3_32x16 as u32
-        } else if ks == 3 && stride == 1 && in_ch == 16 && out_ch == 12 {
-            PassType::Conv3x3_16x12 as u32
-        } else if ks == 3 && stride == 1 && in_ch == 12 && out_ch == 12 {
-            PassType::Conv3x3_12x12 as u32
-        } else if ks == 3 && stride == 2 && in_ch == 16 && out_ch == 32 {
-            PassType::Conv3x3S2_16x32 as u32
-        } else {
-            PassType::Conv as u32
-        }
-    };
-
-    #[allow(clippy::too_many_arguments)]
-    let add_conv = |dispatches: &mut Vec<AAConstants>,
-                    in_buf: u32,
-                    out_buf: u32,
-                    in_ch: u32,
-                    out_ch: u32,
-                    ks: u32,
-                    stride: u32,
-                    in_w: u32,
-                    in_h: u32,
-                    w_idx: W,
-                    bias_off: u32,
-                    activation: u32| {
-        let mut c = AAConstants::default();
-        c.pass_type = select_conv_pass(ks, stride, in_ch, out_ch);
-        c.in_buf = in_buf;
-        c.out_buf = out_buf;
-        c.in_channels = in_ch;
-        c.out_channels = out_ch;
-        c.kernel_size = ks;
-        c.stride = stride;
-        c.in_width = in_w;
-        c.in_height = in_h;
-        c.width = if stride == 2 { in_w / 2 } else { in_w };
-        c.height = if stride == 2 { in_h / 2 } else { in_h };
-        c.weight_off = w(w_idx);
-        c.bias_off = bias_off;
-        c.activation = activation;
-        c.buf_stride = buf_stride;
-        dispatches.push(c);
-    };
-
-    let add_gn_stats = |dispatches: &mut Vec<AAConstants>,
-                        buf: u32,
-                        ch: u32,
-                        groups: u32,
-                        gn_w: u32,
-                        gn_h: u32| {
-        // Pass A: partial reduction
-        let mut c = AAConstants::default();
-        c.pass_type = PassType::GNStats as u32;
-        c.in_buf = buf;
-        c.out_channels = ch;
-        c.num_groups = groups;
-        c.width = gn_w;
-        c.height = gn_h;
-        c.buf_stride = buf_stride;
-        dispatches.push(c);
-
-        // Pass B: final reduction
-        let mut r = AAConstants::default();
-        r.pass_type = PassType::GNStatsReduce as u32;
-        r.in_buf = buf;
-        r.out_channels = ch;
-        r.num_groups = groups;
-        r.width = gn_w;
-        r.height = gn_h;
-        r.buf_stride = buf_stride;
-        dispatches.push(r);
-    };
-
-    #[allow(clippy::too_many_arguments)]
-    let add_gn_apply = |dispatches: &mut Vec<AAConstants>,
-                        in_buf: u32,
-                        out_buf: u32,
-                        ch: u32,
-                        groups: u32,
-                        gn_w: u32,
-                        gn_h: u32,
-                        gamma_idx: W,
-                        beta_idx: W,
-                        activation: u32,
-                        has_skip: bool,
-                        skip_buf: u32| {
-        let mut c = AAConstants::default();
-        c.pass_type = PassType::GNApply as u32;
-        c.in_buf = in_buf;
-        c.out_buf = out_buf;
-        c.out_channels = ch;
-        c.num_groups = groups;
-        c.width = gn_w;
-        c.height = gn_h;
-        c.gamma_off = w(gamma_idx);
-        c.beta_off = w(beta_idx);
-        c.activation = activation;
-        c.flags = if has_skip { FLAG_HAS_SKIP } else { 0 };
-        c.aux_buf = skip_buf;
-        c.buf_stride = buf_stride;
-        dispatches.push(c);
-    };
-
-    // ── Macro helpers for common patterns ──
-
-    // ConvBlock: Conv → GN → SiLU
-    macro_rules! conv_block {
-        ($d:expr, $in_buf:expr, $out_buf:expr, $in_ch:expr, $out_ch:expr,
-         $ks:expr, $stride:expr, $iw:expr, $ih:expr,
-         $conv_w:expr, $gn_gamma:expr, $gn_beta:expr) => {{
-            let ow = if $stride == 2 { $iw / 2 } else { $iw };
-            let oh = if $stride == 2 { $ih / 2 } else { $ih };
-            add_conv(
-                $d,
-                $in_buf,
-                $out_buf,
-                $in_ch,
-                $out_ch,
-                $ks,
-                $stride,
-                $iw,
-                $ih,
-                $conv_w,
-                AA_NO_OFFSET,
-                ACT_NONE,
-            );
-            add_gn_stats($d, $out_buf, $out_ch, 4, ow, oh);
-            add_gn_apply(
-                $d, $out_buf, $out_buf, $out_ch, 4, ow, oh, $gn_gamma, $gn_beta, ACT_SILU, false, 0,
-            );
-        }};
-    }
-
-    // ResBlock: ConvBlock → Conv → GN → SiLU+skip
-    macro_rules! res_block {
-        ($d:expr, $in_buf:expr, $temp_buf:expr, $out_buf:expr, $skip_buf:expr,
-         $ch:expr, $bw:expr, $bh:expr,
-         $c1_conv:expr, $c1_gamma:expr, $c1_beta:expr,
-         $c2_conv:expr, $gn2_gamma:expr, $gn2_beta:expr) => {{
-            conv_block!(
-                $d, $in_buf, $temp_buf, $ch, $ch, 3, 1, $bw, $bh, $c1_conv, $c1_gamma, $c1_beta
-            );
-            add_conv(
-                $d,
-                $temp_buf,
-                $out_buf,
-                $ch,
-                $ch,
-                3,
-                1,
-                $bw,
-                $bh,
-                $c2_conv,
-                AA_NO_OFFSET,
-                ACT_NONE,
-            );
-            add_gn_stats($d, $out_buf, $ch, 4, $bw, $bh);
-            add_gn_apply(
-                $d, $out_buf, $out_buf, $ch, 4, $bw, $bh, $gn2_gamma, $gn2_beta, ACT_SILU, true,
-                $skip_buf,
-            );
-        }};
-    }
-
-    // =====================================================
-    // ENCODE CURRENT FRAME
-    // =====================================================
-
-    // PixelUnshuffle curr → buf0 [32, halfW, halfH]
-    add_pixel_unshuffle(&mut dispatches, false, 0);
-
-    // encoder.compress: Conv1x1 32→16 + GN + SiLU → buf1
-    conv_block!(
-        &mut dispatches,
-        0,
-        1,
-        32,
-        16,
-        1,
-        1,
-        half_w,
-        half_h,
-        W::EncCompressConv,
-        W::EncCompressGnGamma,
-        W::EncCompressGnBeta
-    );
-
-    // encoder.res1: buf1→buf2(temp), buf0(out), skip=buf1
-    res_block!(
-        &mut dispatches,
-        1,
-        2,
-        0,
-        1,
-        16,
-        half_w,
-        half_h,
-        W::EncRes1C1Conv,
-        W::EncRes1C1GnGamma,
-        W::EncRes1C1GnBeta,
-        W::EncRes1C2Conv,
-        W::EncRes1Gn2Gamma,
-        W::EncRes1Gn2Beta
-    );
-
-    // encoder.res2: buf0→buf1(temp), buf2(out), skip=buf0
-    res_block!(
-        &mut dispatches,
-        0,
-        1,
-        2,
-        0,
-        16,
-        half_w,
-        half_h,
-        W::EncRes2C1Conv,
-        W::EncRes2C1GnGamma,
-        W::EncRes2C1GnBeta,
-        W::EncRes2C2Conv,
-        W::EncRes2Gn2Gamma,
-        W::EncRes2Gn2Beta
-    );
-    // buf2 = curr_spatial [16, halfH, halfW]
-
-    // encoder.downsample: Conv3x3 stride=2 16→32 + GN + SiLU → buf0
-    conv_block!(
-        &mut dispatches,
-        2,
-        0,
-        16,
-        32,
-        3,
-        2,
-        half_w,
-        half_h,
-        W::EncDownConv,
-        W::EncDownGnGamma,
-        W::EncDownGnBeta
-    );
-
-    // encoder.res3: buf0→buf1(temp), buf3(out), skip=buf0
-    res_block!(
-        &mut dispatches,
-        0,
-        1,
-        3,
-        0,
-        32,
-        qtr_w,
-        qtr_h,
-        W::EncRes3C1Conv,
-        W::EncRes3C1GnGamma,
-        W::EncRes3C1GnBeta,
-        W::EncRes3C2Conv,
-        W::EncRes3Gn2Gamma,
-        W::EncRes3Gn2Beta
-    );
-    // buf2 = curr_spatial, buf3 = curr_temporal
-
-    // =====================================================
-    // ENCODE PREVIOUS FRAME
-    // =====================================================
-    let prev_encoder_start = dispatches.len() as u32;
-
-    add_pixel_unshuffle(&mut dispatches, true, 0);
-
-    conv_block!(
-        &mut dispatches,
-        0,
-        1,
-        32,
-        16,
-        1,
-        1,
-        half_w,
-        half_h,
-        W::EncCompressConv,
-        W::EncCompressGnGamma,
-        W::EncCompressGnBeta
-    );
-
-    res_block!(
-        &mut dispatches,
-        1,
-        4,
-        0,
-        1,
-        16,
-        half_w,
-        half_h,
-        W::EncRes1C1Conv,
-        W::EncRes1C1GnGamma,
-        W::EncRes1C1GnBeta,
-        W::EncRes1C2Conv,
-        W::EncRes1Gn2Gamma,
-        W::EncRes1Gn2Beta
-    );
-
-    res_block!(
-        &mut dispatches,
-        0,
-        1,
-        4,
-        0,
-        16,
-        half_w,
-        half_h,
-        W::EncRes2C1Conv,
-        W::EncRes2C1GnGamma,
-        W::EncRes2C1GnBeta,
-        W::EncRes2C2Conv,
-        W::EncRes2Gn2Gamma,
-        W::EncRes2Gn2Beta
-    );
-
-    conv_block!(
-        &mut dispatches,
-        4,
-        0,
-        16,
-        32,
-        3,
-        2,
-        half_w,
-        half_h,
-        W::EncDownConv,
-        W::EncDownGnGamma,
-        W::EncDownGnBeta
-    );
-
-    res_block!(
-        &mut dispatches,
-        0,
-        1,
-        4,
-        0,
-        32,
-        qtr_w,
-        qtr_h,
-        W::EncRes3C1Conv,
-        W::EncRes3C1GnGamma,
-        W::EncRes3C1GnBeta,
-        W::EncRes3C2Conv,
-        W::EncRes3Gn2Gamma,
-        W::EncRes3Gn2Beta
-    );
-
-    let prev_encoder_end = dispatches.len() as u32;
-
-    // =====================================================
-    // TEMPORAL FUSION
-    // =====================================================
-
-    // Scale motion vectors
-    {
-        let mut c = AAConstants::default();
-        c.pass_type = PassType::ScaleMV as u32;
-        c.out_buf = 0;
-        c.width = qtr_w;
-        c.height = qtr_h;
-        c.in_width = render_w;
-        c.in_height = render_h;
-        c.buf_stride = buf_stride;
-        dispatches.push(c);
-    }
-
-    // Backward warp: warp buf4 (prev temporal) using buf0 (scaled MV) → buf1
-    {
-        let mut c = AAConstants::default();
-        c.pass_type = PassType::BackwardWarp as u32;
-        c.in_buf = 4;
-        c.out_buf = 1;
-        c.aux_buf = 0;
-        c.width = qtr_w;
-        c.height = qtr_h;
-        c.in_channels = 32;
-        c.buf_stride = buf_stride;
-        dispatches.push(c);
-    }
-
-    // Attention: concat(buf3=curr, buf1=warped) → blend → buf0
-    {
-        let mut c = AAConstants::default();
-        c.pass_type = PassType::Attention as u32;
-        c.in_buf = 3;
-        c.aux_buf = 1;
-        c.out_buf = 0;
-        c.width = qtr_w;
-        c.height = qtr_h;
-        c.in_channels = 32;
-        c.weight_off = w(W::TempAttnConv);
-        c.bias_off = w(W::TempAttnBias);
-        c.buf_stride = buf_stride;
-        dispatches.push(c);
-    }
-
-    // Merge conv + GN + SiLU: buf0 → buf1
-    add_conv(
-        &mut dispatches,
-        0,
-        1,
-        32,
-        32,
-        3,
-        1,
-        qtr_w,
-        qtr_h,
-        W::TempMergeConv,
-        AA_NO_OFFSET,
-        ACT_NONE,
-    );
-    add_gn_stats(&mut dispatches, 1, 32, 4, qtr_w, qtr_h);
-    add_gn_apply(
-        &mut dispatches,
-        1,
-        1,
-        32,
-        4,
-        qtr_w,
-        qtr_h,
-        W::TempMergeGnGamma,
-        W::TempMergeGnBeta,
-        ACT_SILU,
-        false,
-        0,
-    );
-
-    // =====================================================
-    // DECODER
-    // =====================================================
-
-    // Nearest upsample 2×: buf1 [32, qtrH, qtrW] → buf0 [32, halfH, halfW]
-    {
-        let mut c = AAConstants::default();
-        c.pass_type = PassType::NearestUpsample as u32;
-        c.in_buf = 1;
-        c.out_buf = 0;
-        c.width = half_w;
-        c.height = half_h;
-        c.in_width = qtr_w;
-        c.in_height = qtr_h;
-        c.in_channels = 32;
-        c.buf_stride = buf_stride;
-        dispatches.push(c);
-    }
-
-    // decoder.upsample_conv: Conv3x3 32→16 + GN + SiLU → buf3
-    conv_block!(
-        &mut dispatches,
-        0,
-        3,
-        32,
-        16,
-        3,
-        1,
-        half_w,
-        half_h,
-        W::DecUpConv,
-        W::DecUpGnGamma,
-        W::DecUpGnBeta
-    );
-
-    // Skip concat conv: concat(buf3, buf2) → 1×1 conv 32→16 → buf0
-    {
-        let mut c = AAConstants::default();
-        c.pass_type = PassType::SkipConcatConv as u32;
-        c.in_buf = 3;
-        c.aux_buf = 2;
-        c.out_buf = 0;
-        c.width = half_w;
-        c.height = half_h;
-        c.in_channels = 32;
-        c.out_channels = 16;
-        c.weight_off = w(W::DecSkipConv);
-        c.bias_off = AA_NO_OFFSET;
-        c.buf_stride = buf_stride;
-        dispatches.push(c);
-    }
-
-    // decoder.res: buf0→buf1(temp), buf2(out), skip=buf0
-    res_block!(
-        &mut dispatches,
-        0,
-        1,
-        2,
-        0,
-        16,
-        half_w,
-        half_h,
-        W::DecResC1Conv,
-        W::DecResC1GnGamma,
-        W::DecResC1GnBeta,
-        W::DecResC2Conv,
-        W::DecResGn2Gamma,
-        W::DecResGn2Beta
-    );
-
-    // =====================================================
-    // AA HEAD
-    // =====================================================
-
-    // Conv3x3 16→12 + bias + SiLU → buf0
-    add_conv(
-        &mut dispatches,
-        2,
-        0,
-        16,
-        12,
-        3,
-        1,
-        half_w,
-        half_h,
-        W::HeadExpandConv,
-        w(W::HeadExpandBias),
-        ACT_SILU,
-    );
-
-    // Conv3x3 12→12 + bias + Tanh → buf1
-    add_conv(
-        &mut dispatches,
-        0,
-        1,
-        12,
-        12,
-        3,
-        1,
-        half_w,
-        half_h,
-        W::HeadRefineConv,
-        w(W::HeadRefineBias),
-        ACT_TANH,
-    );
-
-    // PixelShuffle + add to input color → output texture
-    {
-        let mut c = AAConstants::default();
-        c.pass_type = PassType::PixelShuffleOut as u32;
-        c.in_buf = 1;
-        c.width = render_w;
-        c.height = render_h;
-        c.in_width = half_w;
-        c.in_height = half_h;
-        c.buf_stride = buf_stride;
-        dispatches.push(c);
-    }
-
-    (dispatches, prev_encoder_start, prev_encoder_end)
-}
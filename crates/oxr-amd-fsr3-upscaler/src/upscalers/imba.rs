@@ -0,0 +1,297 @@
+//! IMBA AA followed by a bilinear resample, offered as an upscaler when
+//! `[imba] upscaler` is set in oxr.ini.
+//!
+//! This is not a temporal upscaler. The network runs at render resolution on
+//! the AA pass's state (`aa_pass`, with the output texture at output size) and
+//! reconstructs an anti-aliased frame at that resolution; its last pass,
+//! `PixelShuffleUpscale`, samples that frame bilinearly under each output
+//! pixel. The result is drawn into the game's output with the bilinear blit,
+//! which converts it to the output's format. On the first frame and after a
+//! reset or a long frame gap (`aa_pass::history_break`) the current frame
+//! stands in for the previous one. Without a model, or without depth and
+//! motion vectors, this is the bilinear upscaler.
+//!
+//! The reactive and composition masks only reject history inside the network
+//! (`PassType::HistoryMask`); the post-blend toward the current frame works on
+//! same-size images and is not applied. Neither is the exposure.
+
+use tracing::{error, warn};
+use windows::Win32::Graphics::Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST;
+use windows::Win32::Graphics::Direct3D12::*;
+use windows::Win32::Graphics::Dxgi::Common::*;
+
+use crate::dispatch::{
+    apply_barriers, ffx_state_to_d3d12, resource_barrier_transition,
+    resource_barrier_transition_d3d12, transitions_if_needed,
+};
+use crate::gpu_pipeline;
+use crate::upscalers::aa_pass::{self, AAState};
+use crate::upscalers::{
+    aa_models, bind_compute_mask_srvs, borrow_resource, create_native_srv, simple, DispatchContext,
+};
+
+/// SRV slot the network's output is blitted from.
+const SRV_OUTPUT: u32 = 12;
+/// RTV slot the blit draws through; slot 0 is recreated for the overlay.
+const RTV_OUTPUT: u32 = 10;
+
+/// Run the selected IMBA model at render size and resample its output to the
+/// output size. Receives resources
+/// in their FFX states and leaves the output in RENDER_TARGET with its RTV at
+/// slot 0. Returns FFX_OK (0) on success.
+pub unsafe fn dispatch(ctx: &DispatchContext) -> u32 {
+    let gpu = ctx.gpu;
+    let cmd_list = ctx.cmd_list;
+    let d = ctx.d;
+    let (render_w, render_h) = (ctx.render_w, ctx.render_h);
+
+    let Some(model) = aa_models::selected() else {
+        return simple::dispatch(ctx);
+    };
+    let (Some(depth_res), Some(mv_res)) = (
+        borrow_resource(d.depth.resource),
+        borrow_resource(d.motion_vectors.resource),
+    ) else {
+        warn!("dispatch_imba: no depth or motion vectors, using bilinear");
+        return simple::dispatch(ctx);
+    };
+
+    let color_format = gpu_pipeline::dxgi_typeless_to_typed(gpu_pipeline::ffx_format_to_dxgi(
+        d.color.description.format,
+    ));
+    let color_format = if color_format == DXGI_FORMAT_UNKNOWN {
+        ctx.color_res.GetDesc().Format
+    } else {
+        color_format
+    };
+
+    let Some(mut state) = aa_pass::get_or_create(
+        ctx.context,
+        &gpu.device,
+        render_w,
+        render_h,
+        ctx.output_w,
+        ctx.output_h,
+        color_format,
+        &model,
+    ) else {
+        error!("dispatch_imba: no AA state, using bilinear");
+        return simple::dispatch(ctx);
+    };
+    let state = &mut *state;
+
+    // Inputs → NON_PIXEL_SHADER_RESOURCE for the network and the history copies
+    let inputs = [ctx.color_res, &depth_res, &mv_res];
+    let input_states = [
+        ffx_state_to_d3d12(d.color.state),
+        ffx_state_to_d3d12(d.depth.state),
+        ffx_state_to_d3d12(d.motion_vectors.state),
+    ];
+    let to_read: Vec<_> = inputs
+        .iter()
+        .zip(input_states)
+        .map(|(&res, s)| (res, s, D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE))
+        .collect();
+    apply_barriers(cmd_list, &transitions_if_needed(&to_read));
+
+    let [jitter_x, jitter_y] = d.jitter_pixels();
+
+    // No previous frame yet: the current one stands in, cached features too
+    if let Some(reason) = aa_pass::history_break(d, ctx.scene_cut) {
+        state.reset_history(reason);
+    }
+    if !state.prev_frame_valid {
+        copy_to_prev(cmd_list, state, inputs, render_w, render_h);
+        state.prev_jitter_x = jitter_x;
+        state.prev_jitter_y = jitter_y;
+        state.prev_frame_valid = true;
+    }
+
+    cmd_list.SetDescriptorHeaps(&[Some(gpu.srv_heap.clone())]);
+    aa_pass::setup_descriptors(gpu, state, ctx.color_res, &depth_res, &mv_res, color_format);
+    let mask_restore = bind_compute_mask_srvs(cmd_list, gpu, d, state.mask_srv_slot());
+    let (prev_jitter_x, prev_jitter_y) = (state.prev_jitter_x, state.prev_jitter_y);
+    aa_pass::execute(
+        cmd_list,
+        gpu,
+        state,
+        jitter_x,
+        jitter_y,
+        prev_jitter_x,
+        prev_jitter_y,
+    );
+    apply_barriers(cmd_list, &mask_restore);
+
+    // Current frame → prev for the next dispatch, then give the inputs back
+    copy_to_prev(cmd_list, state, inputs, render_w, render_h);
+    state.prev_jitter_x = jitter_x;
+    state.prev_jitter_y = jitter_y;
+    let restore: Vec<_> = to_read
+        .iter()
+        .map(|&(res, before, after)| (res, after, before))
+        .collect();
+    apply_barriers(cmd_list, &transitions_if_needed(&restore));
+
+    blit_output(ctx, state);
+    0 // FFX_OK
+}
+
+/// Draw the network's output 1:1 into the game's output, which is left in
+/// RENDER_TARGET with its RTV at slot 0.
+unsafe fn blit_output(ctx: &DispatchContext, state: &AAState) {
+    let gpu = ctx.gpu;
+    let cmd_list = ctx.cmd_list;
+
+    apply_barriers(
+        cmd_list,
+        &[
+            resource_barrier_transition_d3d12(
+                &state.output_texture,
+                D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+                D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+            ),
+            resource_barrier_transition(
+                ctx.output_res,
+                ctx.d.output.state,
+                D3D12_RESOURCE_STATE_RENDER_TARGET,
+            ),
+        ],
+    );
+
+    create_native_srv(gpu, &state.output_texture, SRV_OUTPUT);
+    gpu.device
+        .CreateRenderTargetView(ctx.output_res, None, ctx.rtv_cpu(RTV_OUTPUT));
+
+    let viewport = D3D12_VIEWPORT {
+        TopLeftX: 0.0,
+        TopLeftY: 0.0,
+        Width: ctx.output_w as f32,
+        Height: ctx.output_h as f32,
+        MinDepth: 0.0,
+        MaxDepth: 1.0,
+    };
+    let scissor = windows::Win32::Foundation::RECT {
+        left: 0,
+        top: 0,
+        right: ctx.output_w as i32,
+        bottom: ctx.output_h as i32,
+    };
+
+    cmd_list.SetGraphicsRootSignature(&gpu.root_signature);
+    cmd_list.SetPipelineState(&gpu.pso_bilinear);
+    cmd_list.SetDescriptorHeaps(&[Some(gpu.srv_heap.clone())]);
+
+    // Root constants: uvScale + inputSize + hdr; texel centres map 1:1
+    cmd_list.SetGraphicsRoot32BitConstant(0, 1.0f32.to_bits(), 0);
+    cmd_list.SetGraphicsRoot32BitConstant(0, 1.0f32.to_bits(), 1);
+    cmd_list.SetGraphicsRoot32BitConstant(0, (ctx.output_w as f32).to_bits(), 2);
+    cmd_list.SetGraphicsRoot32BitConstant(0, (ctx.output_h as f32).to_bits(), 3);
+    cmd_list.SetGraphicsRoot32BitConstant(0, ctx.input_flags.hdr as u32, 4);
+    cmd_list.SetGraphicsRootDescriptorTable(1, ctx.srv_gpu(SRV_OUTPUT));
+
+    cmd_list.RSSetViewports(&[viewport]);
+    cmd_list.RSSetScissorRects(&[scissor]);
+    let rtv_handle = ctx.rtv_cpu(RTV_OUTPUT);
+    cmd_list.OMSetRenderTargets(1, Some(&rtv_handle), false, None);
+    cmd_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+    cmd_list.DrawInstanced(3, 1, 0, 0);
+    gpu_pipeline::log_device_removed_reason(&gpu.device);
+
+    apply_barriers(
+        cmd_list,
+        &[resource_barrier_transition_d3d12(
+            &state.output_texture,
+            D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+            D3D12_RESOURCE_STATE_COMMON,
+        )],
+    );
+
+    // Output stays in RENDER_TARGET — create RTV at slot 0 for overlay and post-fx
+    gpu.device
+        .CreateRenderTargetView(ctx.output_res, None, ctx.rtv_cpu(0));
+}
+
+/// Copy the render-size region of `inputs` (colour, depth, motion vectors, in
+/// NON_PIXEL_SHADER_RESOURCE, where they stay) into the previous-frame
+/// textures. Inputs of exactly the render size are copied whole, the only
+/// copy depth-stencil resources allow.
+unsafe fn copy_to_prev(
+    cmd_list: &ID3D12GraphicsCommandList,
+    state: &AAState,
+    inputs: [&ID3D12Resource; 3],
+    render_w: u32,
+    render_h: u32,
+) {
+    let prev = [&state.prev_color, &state.prev_depth, &state.prev_motion];
+    let pairs = || inputs.iter().copied().zip(prev);
+
+    let to_copy: Vec<_> = pairs()
+        .flat_map(|(src, dst)| {
+            [
+                resource_barrier_transition_d3d12(
+                    src,
+                    D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE,
+                    D3D12_RESOURCE_STATE_COPY_SOURCE,
+                ),
+                resource_barrier_transition_d3d12(
+                    dst,
+                    D3D12_RESOURCE_STATE_COMMON,
+                    D3D12_RESOURCE_STATE_COPY_DEST,
+                ),
+            ]
+        })
+        .collect();
+    apply_barriers(cmd_list, &to_copy);
+
+    let region = D3D12_BOX {
+        left: 0,
+        top: 0,
+        front: 0,
+        right: render_w,
+        bottom: render_h,
+        back: 1,
+    };
+    for (src, dst) in pairs() {
+        let desc = src.GetDesc();
+        if desc.Width == render_w as u64 && desc.Height == render_h {
+            cmd_list.CopyResource(dst, src);
+        } else {
+            cmd_list.CopyTextureRegion(
+                &copy_location(dst),
+                0,
+                0,
+                0,
+                &copy_location(src),
+                Some(&region),
+            );
+        }
+    }
+
+    let back: Vec<_> = pairs()
+        .flat_map(|(src, dst)| {
+            [
+                resource_barrier_transition_d3d12(
+                    src,
+                    D3D12_RESOURCE_STATE_COPY_SOURCE,
+                    D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE,
+                ),
+                resource_barrier_transition_d3d12(
+                    dst,
+                    D3D12_RESOURCE_STATE_COPY_DEST,
+                    D3D12_RESOURCE_STATE_COMMON,
+                ),
+            ]
+        })
+        .collect();
+    apply_barriers(cmd_list, &back);
+}
+
+unsafe fn copy_location(resource: &ID3D12Resource) -> D3D12_TEXTURE_COPY_LOCATION {
+    D3D12_TEXTURE_COPY_LOCATION {
+        pResource: std::mem::transmute_copy(resource),
+        Type: D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
+        Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+            SubresourceIndex: 0,
+        },
+    }
+}
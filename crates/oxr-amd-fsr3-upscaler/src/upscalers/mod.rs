@@ -1,18 +1,32 @@
+pub mod aa_models;
 pub mod aa_pass;
+pub mod imba;
 pub mod sgsr2_three_pass;
 pub mod sgsr2_two_pass;
 pub mod simple;
 
-use crate::fsr3_types::*;
+use crate::context::ContextKey;
+use crate::dispatch::{apply_barriers, ffx_state_to_d3d12, transitions_if_needed};
 use crate::gpu_pipeline::{self, GpuState};
+use crate::upscaler_type;
+use fsr_sys::inputs::InputFlags;
+use fsr_sys::{FfxApiResource, UpscaleFrame};
 use windows::Win32::Graphics::Direct3D12::*;
 use windows::Win32::Graphics::Dxgi::Common::*;
 
 /// Shared context passed to every upscaler dispatch.
 pub struct DispatchContext<'a> {
+    pub context: ContextKey,
     pub cmd_list: &'a ID3D12GraphicsCommandList,
     pub gpu: &'a GpuState,
-    pub d: &'a FfxFsr3UpscalerDispatchDescription,
+    pub d: &'a UpscaleFrame,
+    /// `d.reset` was set by `scene_cut`, not the game.
+    pub scene_cut: bool,
+    /// Creation flags of the dispatching context.
+    pub input_flags: InputFlags,
+    /// GPU address of the frame's `ExposureState` (SGSRv2 root SRV t4); 0 for
+    /// upscalers that do not accumulate history.
+    pub exposure: u64,
     pub color_res: &'a ID3D12Resource,
     pub output_res: &'a ID3D12Resource,
     pub render_w: u32,
@@ -35,6 +49,13 @@ impl<'a> DispatchContext<'a> {
     }
 }
 
+/// Release upscaler history owned by a destroyed context.
+pub fn release(key: ContextKey) {
+    aa_pass::release(key);
+    sgsr2_two_pass::release(key);
+    sgsr2_three_pass::release(key);
+}
+
 /// Create an SRV with an explicit typed format descriptor.
 /// Converts FFX format -> DXGI, then typeless -> typed, so resources like R32_TYPELESS
 /// (depth buffers) get a valid SRV format instead of relying on D3D12 auto-inference.
@@ -121,6 +142,95 @@ pub unsafe fn create_native_srv(gpu: &GpuState, resource: &ID3D12Resource, slot:
     true
 }
 
+/// Bind the frame's reactive mask at `slot` for pixel shaders, or a null SRV
+/// (reads as 0: no reactive pixels) when the game supplies none.
+/// Returns the barriers that restore the mask to the game's state.
+pub unsafe fn bind_reactive_srv(ctx: &DispatchContext, slot: u32) -> Vec<D3D12_RESOURCE_BARRIER> {
+    bind_mask_srv(
+        ctx.cmd_list,
+        ctx.gpu,
+        Some(&ctx.d.reactive),
+        slot,
+        D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+    )
+}
+
+/// Bind the frame's transparency & composition mask at `slot` for pixel
+/// shaders, or a null SRV when the game supplies none or the overlay has it
+/// turned off. Returns the barriers that restore the mask to the game's state.
+pub unsafe fn bind_composition_srv(
+    ctx: &DispatchContext,
+    slot: u32,
+) -> Vec<D3D12_RESOURCE_BARRIER> {
+    let mask = upscaler_type::composition_mask_get().then_some(&ctx.d.transparency_and_composition);
+    bind_mask_srv(
+        ctx.cmd_list,
+        ctx.gpu,
+        mask,
+        slot,
+        D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+    )
+}
+
+/// Bind both masks for compute shaders: reactive at `slot`, transparency &
+/// composition at `slot + 1`, null SRVs where missing (as above). Returns the
+/// barriers that restore them to the game's state.
+pub unsafe fn bind_compute_mask_srvs(
+    cmd_list: &ID3D12GraphicsCommandList,
+    gpu: &GpuState,
+    d: &UpscaleFrame,
+    slot: u32,
+) -> Vec<D3D12_RESOURCE_BARRIER> {
+    let state = D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE;
+    let composition =
+        upscaler_type::composition_mask_get().then_some(&d.transparency_and_composition);
+    let mut restore = bind_mask_srv(cmd_list, gpu, Some(&d.reactive), slot, state);
+    restore.extend(bind_mask_srv(cmd_list, gpu, composition, slot + 1, state));
+    restore
+}
+
+unsafe fn bind_mask_srv(
+    cmd_list: &ID3D12GraphicsCommandList,
+    gpu: &GpuState,
+    mask: Option<&FfxApiResource>,
+    slot: u32,
+    state: D3D12_RESOURCE_STATES,
+) -> Vec<D3D12_RESOURCE_BARRIER> {
+    if let Some((mask, mask_res)) =
+        mask.and_then(|m| borrow_resource(m.resource).map(|res| (m, res)))
+    {
+        let states = [(&mask_res, ffx_state_to_d3d12(mask.state), state)];
+        apply_barriers(cmd_list, &transitions_if_needed(&states));
+        if create_typed_srv(gpu, &mask_res, mask.description.format, slot) {
+            let restore = states.map(|(res, before, after)| (res, after, before));
+            return transitions_if_needed(&restore);
+        }
+        tracing::warn!("bind_mask_srv: unsupported mask format, ignoring mask");
+        let restore = states.map(|(res, before, after)| (res, after, before));
+        apply_barriers(cmd_list, &transitions_if_needed(&restore));
+    }
+
+    let null_desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
+        Format: DXGI_FORMAT_R8_UNORM,
+        ViewDimension: D3D12_SRV_DIMENSION_TEXTURE2D,
+        Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+        Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+            Texture2D: D3D12_TEX2D_SRV {
+                MostDetailedMip: 0,
+                MipLevels: 1,
+                PlaneSlice: 0,
+                ResourceMinLODClamp: 0.0,
+            },
+        },
+    };
+    gpu.device.CreateShaderResourceView(
+        None,
+        Some(&null_desc),
+        gpu_pipeline::get_srv_cpu_handle(gpu, slot),
+    );
+    Vec::new()
+}
+
 /// Borrow a COM resource from a raw FFX pointer, returning None if null.
 pub unsafe fn borrow_resource(raw: *mut core::ffi::c_void) -> Option<ID3D12Resource> {
     if raw.is_null() {
@@ -1,7 +1,8 @@
-use std::sync::Mutex;
-
+use crate::context::{ContextKey, PerContext, PerContextGuard};
 use crate::gpu_pipeline;
-use crate::upscalers::{borrow_resource, create_typed_srv, DispatchContext};
+use crate::upscalers::{
+    bind_composition_srv, bind_reactive_srv, borrow_resource, create_typed_srv, DispatchContext,
+};
 use tracing::{error, info};
 use windows::Win32::Graphics::Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST;
 use windows::Win32::Graphics::Direct3D12::*;
@@ -28,63 +29,64 @@ pub struct Sgsr2ThreePassState {
     pub output_h: u32,
 }
 
-static STATE: Mutex<Option<Sgsr2ThreePassState>> = Mutex::new(None);
+static STATE: PerContext<Sgsr2ThreePassState> = PerContext::new();
+
+/// Drop the history textures of a destroyed context.
+pub fn release(key: ContextKey) {
+    STATE.remove(key);
+}
 
 unsafe fn get_or_create_state(
+    key: ContextKey,
     device: &ID3D12Device,
     render_w: u32,
     render_h: u32,
     output_w: u32,
     output_h: u32,
     _output_format: DXGI_FORMAT,
-) -> Result<std::sync::MutexGuard<'static, Option<Sgsr2ThreePassState>>, String> {
-    let mut guard = STATE
-        .lock()
-        .map_err(|_| "sgsr2 3-pass state mutex poisoned")?;
-
-    let needs_recreate = match guard.as_ref() {
-        Some(s) => {
+) -> Result<PerContextGuard<'static, Sgsr2ThreePassState>, String> {
+    STATE.get_or_create(
+        key,
+        |s| {
             s.render_w != render_w
                 || s.render_h != render_h
                 || s.output_w != output_w
                 || s.output_h != output_h
-        }
-        None => true,
-    };
-
-    if needs_recreate {
-        let ycocg_color = create_texture(device, render_w, render_h, DXGI_FORMAT_R32_UINT)?;
-        let motion_depth_alpha =
-            create_texture(device, render_w, render_h, DXGI_FORMAT_R16G16B16A16_FLOAT)?;
-        let motion_depth_clip_alpha =
-            create_texture(device, render_w, render_h, DXGI_FORMAT_R16G16B16A16_FLOAT)?;
-        let luma_history_0 = create_texture(device, render_w, render_h, DXGI_FORMAT_R32_UINT)?;
-        let luma_history_1 = create_texture(device, render_w, render_h, DXGI_FORMAT_R32_UINT)?;
-
-        let history0 = create_texture(device, output_w, output_h, DXGI_FORMAT_R16G16B16A16_FLOAT)?;
-        let history1 = create_texture(device, output_w, output_h, DXGI_FORMAT_R16G16B16A16_FLOAT)?;
-
-        info!(
-            "sgsr2_3pass: created textures render={}x{} output={}x{}",
-            render_w, render_h, output_w, output_h
-        );
-
-        *guard = Some(Sgsr2ThreePassState {
-            ycocg_color,
-            motion_depth_alpha,
-            motion_depth_clip_alpha,
-            luma_history: [luma_history_0, luma_history_1],
-            history: [history0, history1],
-            frame_idx: 0,
-            initialized: false,
-            render_w,
-            render_h,
-            output_w,
-            output_h,
-        });
-    }
+        },
+        || {
+            let ycocg_color = create_texture(device, render_w, render_h, DXGI_FORMAT_R32_UINT)?;
+            let motion_depth_alpha =
+                create_texture(device, render_w, render_h, DXGI_FORMAT_R16G16B16A16_FLOAT)?;
+            let motion_depth_clip_alpha =
+                create_texture(device, render_w, render_h, DXGI_FORMAT_R16G16B16A16_FLOAT)?;
+            let luma_history_0 = create_texture(device, render_w, render_h, DXGI_FORMAT_R32_UINT)?;
+            let luma_history_1 = create_texture(device, render_w, render_h, DXGI_FORMAT_R32_UINT)?;
+
+            let history0 =
+                create_texture(device, output_w, output_h, DXGI_FORMAT_R16G16B16A16_FLOAT)?;
+            let history1 =
+                create_texture(device, output_w, output_h, DXGI_FORMAT_R16G16B16A16_FLOAT)?;
+
+            info!(
+                "sgsr2_3pass: created textures render={}x{} output={}x{}",
+                render_w, render_h, output_w, output_h
+            );
 
-    Ok(guard)
+            Ok(Sgsr2ThreePassState {
+                ycocg_color,
+                motion_depth_alpha,
+                motion_depth_clip_alpha,
+                luma_history: [luma_history_0, luma_history_1],
+                history: [history0, history1],
+                frame_idx: 0,
+                initialized: false,
+                render_w,
+                render_h,
+                output_w,
+                output_h,
+            })
+        },
+    )
 }
 
 unsafe fn create_texture(
@@ -139,6 +141,8 @@ const SRV_ACTIVATE_PREV_LUMA: u32 = 20;
 const SRV_UPSCALE_PREV_HISTORY: u32 = 21;
 const SRV_UPSCALE_MDCA: u32 = 22;
 const SRV_UPSCALE_YCOCG: u32 = 23;
+const SRV_UPSCALE_REACTIVE: u32 = 24;
+const SRV_UPSCALE_COMPOSITION: u32 = 57;
 const RTV_CONVERT_YCOCG: u32 = 4;
 const RTV_CONVERT_MDA: u32 = 5;
 const RTV_ACTIVATE_MDCA: u32 = 6;
@@ -157,6 +161,7 @@ pub unsafe fn dispatch(ctx: &DispatchContext) -> u32 {
     let output_h = ctx.output_h;
 
     let mut state_guard = match get_or_create_state(
+        ctx.context,
         &gpu.device,
         render_w,
         render_h,
@@ -170,7 +175,7 @@ pub unsafe fn dispatch(ctx: &DispatchContext) -> u32 {
             return 1;
         }
     };
-    let state = state_guard.as_mut().unwrap();
+    let state = &mut *state_guard;
 
     let is_reset = d.reset || !state.initialized;
 
@@ -281,6 +286,7 @@ pub unsafe fn dispatch(ctx: &DispatchContext) -> u32 {
         0,
     );
     cmd_list.SetGraphicsRootDescriptorTable(1, ctx.srv_gpu(SRV_CONVERT_DEPTH));
+    cmd_list.SetGraphicsRootShaderResourceView(2, ctx.exposure);
 
     let render_viewport = D3D12_VIEWPORT {
         TopLeftX: 0.0,
@@ -531,6 +537,8 @@ pub unsafe fn dispatch(ctx: &DispatchContext) -> u32 {
             ctx.srv_cpu(SRV_UPSCALE_YCOCG),
         );
     }
+    let reactive_restore = bind_reactive_srv(ctx, SRV_UPSCALE_REACTIVE);
+    let composition_restore = bind_composition_srv(ctx, SRV_UPSCALE_COMPOSITION);
 
     // RTVs for upscale MRT
     gpu.device.CreateRenderTargetView(
@@ -553,6 +561,7 @@ pub unsafe fn dispatch(ctx: &DispatchContext) -> u32 {
 
     cmd_list.SetPipelineState(&gpu.pso_sgsr2_3p_upscale);
     cmd_list.SetGraphicsRootDescriptorTable(1, ctx.srv_gpu(SRV_UPSCALE_PREV_HISTORY));
+    cmd_list.SetGraphicsRootDescriptorTable(3, ctx.srv_gpu(SRV_UPSCALE_COMPOSITION));
 
     let upscale_viewport = D3D12_VIEWPORT {
         TopLeftX: 0.0,
@@ -577,6 +586,8 @@ pub unsafe fn dispatch(ctx: &DispatchContext) -> u32 {
     cmd_list.OMSetRenderTargets(2, Some(up_rtvs.as_ptr()), false, None);
 
     cmd_list.DrawInstanced(3, 1, 0, 0);
+    apply_barriers(cmd_list, &reactive_restore);
+    apply_barriers(cmd_list, &composition_restore);
 
     // ============================================================
     // Post-upscale: transition history, prepare output for overlay
@@ -638,7 +649,7 @@ pub unsafe fn dispatch(ctx: &DispatchContext) -> u32 {
 }
 
 fn build_root_constants(
-    d: &crate::fsr3_types::FfxFsr3UpscalerDispatchDescription,
+    d: &fsr_sys::UpscaleFrame,
     render_w: u32,
     render_h: u32,
     output_w: u32,
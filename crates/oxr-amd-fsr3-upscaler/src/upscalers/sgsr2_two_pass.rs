@@ -1,7 +1,8 @@
-use std::sync::Mutex;
-
+use crate::context::{ContextKey, PerContext, PerContextGuard};
 use crate::gpu_pipeline;
-use crate::upscalers::{borrow_resource, create_typed_srv, DispatchContext};
+use crate::upscalers::{
+    bind_composition_srv, bind_reactive_srv, borrow_resource, create_typed_srv, DispatchContext,
+};
 use tracing::{error, info};
 use windows::Win32::Graphics::Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST;
 use windows::Win32::Graphics::Direct3D12::*;
@@ -25,52 +26,53 @@ pub struct Sgsr2State {
     pub output_h: u32,
 }
 
-static STATE: Mutex<Option<Sgsr2State>> = Mutex::new(None);
+static STATE: PerContext<Sgsr2State> = PerContext::new();
+
+/// Drop the history textures of a destroyed context.
+pub fn release(key: ContextKey) {
+    STATE.remove(key);
+}
 
 unsafe fn get_or_create_state(
+    key: ContextKey,
     device: &ID3D12Device,
     render_w: u32,
     render_h: u32,
     output_w: u32,
     output_h: u32,
     output_format: DXGI_FORMAT,
-) -> Result<std::sync::MutexGuard<'static, Option<Sgsr2State>>, String> {
-    let mut guard = STATE.lock().map_err(|_| "sgsr2 state mutex poisoned")?;
-
-    let needs_recreate = match guard.as_ref() {
-        Some(s) => {
+) -> Result<PerContextGuard<'static, Sgsr2State>, String> {
+    STATE.get_or_create(
+        key,
+        |s| {
             s.render_w != render_w
                 || s.render_h != render_h
                 || s.output_w != output_w
                 || s.output_h != output_h
-        }
-        None => true,
-    };
-
-    if needs_recreate {
-        let motion_depth_clip =
-            create_texture(device, render_w, render_h, DXGI_FORMAT_R16G16B16A16_FLOAT)?;
-        let history0 = create_texture(device, output_w, output_h, output_format)?;
-        let history1 = create_texture(device, output_w, output_h, output_format)?;
-
-        info!(
-            "sgsr2: created textures render={}x{} output={}x{} format={:?}",
-            render_w, render_h, output_w, output_h, output_format
-        );
-
-        *guard = Some(Sgsr2State {
-            motion_depth_clip,
-            history: [history0, history1],
-            frame_idx: 0,
-            initialized: false,
-            render_w,
-            render_h,
-            output_w,
-            output_h,
-        });
-    }
-
-    Ok(guard)
+        },
+        || {
+            let motion_depth_clip =
+                create_texture(device, render_w, render_h, DXGI_FORMAT_R16G16B16A16_FLOAT)?;
+            let history0 = create_texture(device, output_w, output_h, output_format)?;
+            let history1 = create_texture(device, output_w, output_h, output_format)?;
+
+            info!(
+                "sgsr2: created textures render={}x{} output={}x{} format={:?}",
+                render_w, render_h, output_w, output_h, output_format
+            );
+
+            Ok(Sgsr2State {
+                motion_depth_clip,
+                history: [history0, history1],
+                frame_idx: 0,
+                initialized: false,
+                render_w,
+                render_h,
+                output_w,
+                output_h,
+            })
+        },
+    )
 }
 
 unsafe fn create_texture(
@@ -118,9 +120,11 @@ unsafe fn create_texture(
 // --- SRV/RTV slots ---
 const SRV_DEPTH: u32 = 10;
 const SRV_VELOCITY: u32 = 11;
-const SRV_PREV_HISTORY: u32 = 12;
-const SRV_MDC: u32 = 13;
-const SRV_COLOR: u32 = 14;
+const SRV_PREV_HISTORY: u32 = 42;
+const SRV_MDC: u32 = 43;
+const SRV_COLOR: u32 = 44;
+const SRV_REACTIVE: u32 = 45;
+const SRV_COMPOSITION: u32 = 56;
 const RTV_MDC: u32 = 2;
 const RTV_HISTORY: u32 = 3;
 
@@ -136,6 +140,7 @@ pub unsafe fn dispatch(ctx: &DispatchContext) -> u32 {
 
     // Get/create persistent SGSRv2 textures
     let mut state_guard = match get_or_create_state(
+        ctx.context,
         &gpu.device,
         render_w,
         render_h,
@@ -149,7 +154,7 @@ pub unsafe fn dispatch(ctx: &DispatchContext) -> u32 {
             return 1;
         }
     };
-    let state = state_guard.as_mut().unwrap();
+    let state = &mut *state_guard;
 
     let is_reset = d.reset || !state.initialized;
 
@@ -220,6 +225,7 @@ pub unsafe fn dispatch(ctx: &DispatchContext) -> u32 {
     );
 
     cmd_list.SetGraphicsRootDescriptorTable(1, ctx.srv_gpu(SRV_DEPTH));
+    cmd_list.SetGraphicsRootShaderResourceView(2, ctx.exposure);
 
     let convert_viewport = D3D12_VIEWPORT {
         TopLeftX: 0.0,
@@ -337,6 +343,8 @@ pub unsafe fn dispatch(ctx: &DispatchContext) -> u32 {
         );
     }
     create_typed_srv(gpu, ctx.color_res, d.color.description.format, SRV_COLOR);
+    let reactive_restore = bind_reactive_srv(ctx, SRV_REACTIVE);
+    let composition_restore = bind_composition_srv(ctx, SRV_COMPOSITION);
 
     // Create RTV for history write
     gpu.device
@@ -344,6 +352,7 @@ pub unsafe fn dispatch(ctx: &DispatchContext) -> u32 {
 
     cmd_list.SetPipelineState(&gpu.pso_sgsr2_upscale);
     cmd_list.SetGraphicsRootDescriptorTable(1, ctx.srv_gpu(SRV_PREV_HISTORY));
+    cmd_list.SetGraphicsRootDescriptorTable(3, ctx.srv_gpu(SRV_COMPOSITION));
 
     let upscale_viewport = D3D12_VIEWPORT {
         TopLeftX: 0.0,
@@ -366,6 +375,8 @@ pub unsafe fn dispatch(ctx: &DispatchContext) -> u32 {
     cmd_list.OMSetRenderTargets(1, Some(&rtv_history), false, None);
 
     cmd_list.DrawInstanced(3, 1, 0, 0);
+    apply_barriers(cmd_list, &reactive_restore);
+    apply_barriers(cmd_list, &composition_restore);
 
     // === Copy history[curr] -> output ===
     apply_barriers(
@@ -450,7 +461,7 @@ pub unsafe fn dispatch(ctx: &DispatchContext) -> u32 {
 }
 
 fn build_root_constants(
-    d: &crate::fsr3_types::FfxFsr3UpscalerDispatchDescription,
+    d: &fsr_sys::UpscaleFrame,
     render_w: u32,
     render_h: u32,
     output_w: u32,
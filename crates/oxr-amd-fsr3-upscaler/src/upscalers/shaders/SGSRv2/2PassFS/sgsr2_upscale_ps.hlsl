@@ -35,8 +35,23 @@ cbuffer Params : register(b0)
 Texture2D<float4> PrevOutput                  : register(t0);
 Texture2D<float4> MotionDepthClipAlphaBuffer  : register(t1);
 Texture2D<float4> InputColor                  : register(t2);
+Texture2D<float>  Reactive                    : register(t3);  // null SRV reads 0
+Texture2D<float>  Composition                 : register(t5);  // null SRV reads 0
 SamplerState      samp                        : register(s0);
 
+// Current-frame weight of a fully set composition mask
+#define COMPOSITION_HISTORY_REJECTION 0.5   // fsr_sys::reactive::COMPOSITION_HISTORY_REJECTION
+
+// Exposure for the frame (fsr_sys::exposure::ExposureState), bound as a root SRV
+struct ExposureState
+{
+    float exposure;
+    float prepare;                  // game colour -> accumulation space
+    float unprepare;                // accumulation space -> game colour
+    float adaptedLog2Luminance;
+};
+StructuredBuffer<ExposureState> Exposure : register(t4);
+
 struct VSOut
 {
     float4 pos : SV_POSITION;
@@ -66,7 +81,9 @@ float4 PS(VSOut input) : SV_Target
 
     float depthfactor = mda.z;
 
-    float3 HistoryColor = PrevOutput.SampleLevel(samp, PrevUV, 0).xyz;
+    // History is stored as game colour; blend in the current exposure space
+    float prepare = Exposure[0].prepare;
+    float3 HistoryColor = PrevOutput.SampleLevel(samp, PrevUV, 0).xyz * prepare;
 
     // Upsample and compute bounding box
     float4 Upsampledcw = float4(0, 0, 0, 0);
@@ -89,7 +106,7 @@ float4 PS(VSOut input) : SV_Target
     float3 rectboxmax;
 
     // Sample 5 cross-pattern pixels
-    float3 topMid = InputColor.Load(int3(InputPos + int2(0, 1), 0)).xyz;
+    float3 topMid = InputColor.Load(int3(InputPos + int2(0, 1), 0)).xyz * prepare;
     {
         float3 samplecolor = topMid;
         float2 baseoffset = srcpos_srcOutputPos + float2(0.0, 1.0);
@@ -106,7 +123,7 @@ float4 PS(VSOut input) : SV_Target
         rectboxweight += boxweight;
     }
 
-    float3 rightMid = InputColor.Load(int3(InputPos + int2(1, 0), 0)).xyz;
+    float3 rightMid = InputColor.Load(int3(InputPos + int2(1, 0), 0)).xyz * prepare;
     {
         float3 samplecolor = rightMid;
         float2 baseoffset = srcpos_srcOutputPos + float2(1.0, 0.0);
@@ -123,7 +140,7 @@ float4 PS(VSOut input) : SV_Target
         rectboxweight += boxweight;
     }
 
-    float3 leftMid = InputColor.Load(int3(InputPos + int2(-1, 0), 0)).xyz;
+    float3 leftMid = InputColor.Load(int3(InputPos + int2(-1, 0), 0)).xyz * prepare;
     {
         float3 samplecolor = leftMid;
         float2 baseoffset = srcpos_srcOutputPos + float2(-1.0, 0.0);
@@ -140,7 +157,7 @@ float4 PS(VSOut input) : SV_Target
         rectboxweight += boxweight;
     }
 
-    float3 centerMid = InputColor.Load(int3(InputPos, 0)).xyz;
+    float3 centerMid = InputColor.Load(int3(InputPos, 0)).xyz * prepare;
     {
         float3 samplecolor = centerMid;
         float2 baseoffset = srcpos_srcOutputPos;
@@ -157,7 +174,7 @@ float4 PS(VSOut input) : SV_Target
         rectboxweight += boxweight;
     }
 
-    float3 btmMid = InputColor.Load(int3(InputPos + int2(0, -1), 0)).xyz;
+    float3 btmMid = InputColor.Load(int3(InputPos + int2(0, -1), 0)).xyz * prepare;
     {
         float3 samplecolor = btmMid;
         float2 baseoffset = srcpos_srcOutputPos + float2(0.0, -1.0);
@@ -227,6 +244,11 @@ float4 PS(VSOut input) : SV_Target
     // Blend current frame with history
     float alphasum = max(EPSILON, basealpha + Upsampledcw.w);
     float alpha = clamp(Upsampledcw.w / alphasum + reset, 0.0, 1.0);
+    // Reactive pixels (particles, transparencies) favour the current frame, composited
+    // ones (UI, alpha-blended surfaces) partly: fsr_sys::reactive::temporal_blend_alpha
+    float reactive = saturate(Reactive.Load(int3(InputPos, 0)));
+    float composition = saturate(Composition.Load(int3(InputPos, 0))) * COMPOSITION_HISTORY_REJECTION;
+    alpha = max(alpha, 1.0 - (1.0 - reactive) * (1.0 - composition));
 
     Upsampledcw.xyz = lerp(HistoryColor, Upsampledcw.xyz, alpha);
 
@@ -234,5 +256,5 @@ float4 PS(VSOut input) : SV_Target
     if (any(isnan(Upsampledcw.xyz)) || any(isinf(Upsampledcw.xyz)))
         Upsampledcw.xyz = centerMid;
 
-    return float4(Upsampledcw.xyz, 1.0);
+    return float4(Upsampledcw.xyz * Exposure[0].unprepare, 1.0);
 }
@@ -31,6 +31,16 @@ Texture2D<float4> InputColor    : register(t2);
 SamplerState      samp          : register(s0);   // linear clamp
 SamplerState      pointSamp     : register(s1);   // point clamp
 
+// Exposure for the frame (fsr_sys::exposure::ExposureState), bound as a root SRV
+struct ExposureState
+{
+    float exposure;
+    float prepare;                  // game colour -> accumulation space
+    float unprepare;                // accumulation space -> game colour
+    float adaptedLog2Luminance;
+};
+StructuredBuffer<ExposureState> Exposure : register(t4);
+
 struct VSOut
 {
     float4 pos : SV_POSITION;
@@ -90,10 +100,11 @@ PSOut PS(VSOut input)
     }
 
     // Read scene color and tonemap
-    half3 Colorrgb = (half3)InputColor.Load(int3(InputPos, 0)).xyz;
+    half3 Colorrgb = (half3)(InputColor.Load(int3(InputPos, 0)).xyz * Exposure[0].prepare);
 
-    // Simple tonemap: divide by max component + exposure reciprocal
-    float Exposure_co_rcp = preExposure;
+    // Simple tonemap: divide by max component + exposure reciprocal. Colour is
+    // already in exposure space, so the reciprocal is 1 (preExposure is folded in).
+    float Exposure_co_rcp = 1.0;
     half ColorMax = max(max(Colorrgb.x, Colorrgb.y), Colorrgb.z) + (half)Exposure_co_rcp;
     Colorrgb /= ColorMax;
 
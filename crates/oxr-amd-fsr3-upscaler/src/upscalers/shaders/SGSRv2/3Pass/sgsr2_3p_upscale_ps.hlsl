@@ -28,9 +28,24 @@ cbuffer Params : register(b0)
 Texture2D<float4> PrevHistoryOutput            : register(t0);
 Texture2D<float4> MotionDepthClipAlphaBuffer   : register(t1);
 Texture2D<uint>   YCoCgColor                   : register(t2);
+Texture2D<float>  Reactive                     : register(t3);   // null SRV reads 0
+Texture2D<float>  Composition                  : register(t5);   // null SRV reads 0
 SamplerState      samp                         : register(s0);   // linear clamp
 SamplerState      pointSamp                    : register(s1);   // point clamp
 
+// Current-frame weight of a fully set composition mask
+#define COMPOSITION_HISTORY_REJECTION 0.5   // fsr_sys::reactive::COMPOSITION_HISTORY_REJECTION
+
+// Exposure for the frame (fsr_sys::exposure::ExposureState), bound as a root SRV
+struct ExposureState
+{
+    float exposure;
+    float prepare;                  // game colour -> accumulation space
+    float unprepare;                // accumulation space -> game colour
+    float adaptedLog2Luminance;
+};
+StructuredBuffer<ExposureState> Exposure : register(t4);
+
 struct VSOut
 {
     float4 pos : SV_POSITION;
@@ -69,7 +84,7 @@ PSOut PS(VSOut input)
     // Compute derived params from scale ratio
     float Biasmax_viewportXScale = min(scaleRatio.x, 1.99);
     float Scalefactor = min(20.0, pow(scaleRatio.x * scaleRatio.y, 3.0));
-    float Exposure_co_rcp = preExposure;
+    float Exposure_co_rcp = 1.0;   // must match convert; colour is in exposure space
     float ValidReset = reset;
 
     float2 Hruv = input.uv;
@@ -328,6 +343,11 @@ PSOut PS(VSOut input)
     // Blend current frame with history (both in tonemapped-RGB space)
     half alphasum = max(EPS, basealpha + Upsampledcw.w);
     half alpha = saturate(Upsampledcw.w / alphasum + (half)ValidReset);
+    // Reactive pixels (particles, transparencies) favour the current frame, composited
+    // ones (UI, alpha-blended surfaces) partly: fsr_sys::reactive::temporal_blend_alpha
+    float reactive = saturate(Reactive.Load(int3(InputPos, 0)));
+    float composition = saturate(Composition.Load(int3(InputPos, 0))) * COMPOSITION_HISTORY_REJECTION;
+    alpha = max(alpha, (half)(1.0 - (1.0 - reactive) * (1.0 - composition)));
     half3 blended = lerp(HistoryColor, Upsampledcw.xyz, alpha);
 
     // NaN guard on blended
@@ -352,7 +372,7 @@ PSOut PS(VSOut input)
         compMax = clamp(compMax, 0.0, 254.0 / 255.0);
         scale = Exposure_co_rcp / ((1.0 + 1.0 / 65504.0) - compMax);
     }
-    float3 rgb = blended_f * scale;
+    float3 rgb = blended_f * scale * Exposure[0].unprepare;
     if (any(isnan(rgb)) || any(isinf(rgb)))
         rgb = float3(0, 0, 0);
     o.scene = float4(rgb, 1.0);
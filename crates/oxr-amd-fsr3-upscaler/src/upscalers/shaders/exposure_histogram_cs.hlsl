@@ -0,0 +1,55 @@
+//============================================================================================================
+//
+//  Auto-exposure, step 1 — log2 luminance histogram of the pre-upscale color.
+//
+//  Mirrors fsr_sys::exposure::{luminance, histogram_bin, build_histogram}; keep them in sync.
+//  Constants match exposure_resolve_cs.hlsl.
+//
+//============================================================================================================
+
+#define BINS            64
+#define MIN_LOG2_LUM    -12.0
+#define MAX_LOG2_LUM    4.0
+
+cbuffer Params : register(b0)
+{
+    uint  packedRenderSize;         // width | height << 16
+    uint  mode;
+    float preExposure;
+    float frameTimeMs;
+    uint  reset;
+    uint3 pad;                      // 8 DWORDs total
+};
+
+Texture2D<float4>         InputColor : register(t0);
+RWStructuredBuffer<uint>  Histogram  : register(u0);
+
+groupshared uint localBins[BINS];
+
+uint HistogramBin(float lum)
+{
+    if (!(lum > exp2(MIN_LOG2_LUM)))
+        return 0;
+    float t = saturate((log2(lum) - MIN_LOG2_LUM) / (MAX_LOG2_LUM - MIN_LOG2_LUM));
+    return min(1u + (uint)(t * (BINS - 1)), BINS - 1u);
+}
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID, uint gi : SV_GroupIndex)
+{
+    localBins[gi] = 0;
+    GroupMemoryBarrierWithGroupSync();
+
+    uint2 renderSize = uint2(packedRenderSize & 0xFFFFu, packedRenderSize >> 16);
+    if (all(id.xy < renderSize))
+    {
+        float3 c = InputColor.Load(int3(id.xy, 0)).rgb / preExposure;
+        float lum = dot(c, float3(0.2126, 0.7152, 0.0722));
+        InterlockedAdd(localBins[HistogramBin(lum)], 1u);
+    }
+    GroupMemoryBarrierWithGroupSync();
+
+    // 8x8 threads == BINS: one bin per thread
+    if (localBins[gi] != 0)
+        InterlockedAdd(Histogram[gi], localBins[gi]);
+}
@@ -0,0 +1,110 @@
+//============================================================================================================
+//
+//  Auto-exposure, step 2 — turn the histogram (or the game's exposure texture) into the
+//  ExposureState the temporal upscalers read, then clear the histogram for the next frame.
+//
+//  Mirrors fsr_sys::exposure::{average_log2_luminance, AutoExposure, ExposureState}; keep them in sync.
+//
+//============================================================================================================
+
+#define BINS                64
+#define MIN_LOG2_LUM        -12.0
+#define MAX_LOG2_LUM        4.0
+#define LOW_PERCENTILE      0.1
+#define HIGH_PERCENTILE     0.9
+#define ADAPTATION_RATE     1.5
+
+#define MODE_AUTO           0u
+#define MODE_GAME_TEXTURE   1u
+#define MODE_FIXED          2u
+#define MODE_CLEAR          3u
+
+cbuffer Params : register(b0)
+{
+    uint  packedRenderSize;
+    uint  mode;
+    float preExposure;              // already sanitized (> 0)
+    float frameTimeMs;
+    uint  reset;                    // also set on the first frame of a context
+    uint3 pad;                      // 8 DWORDs total
+};
+
+struct ExposureState
+{
+    float exposure;
+    float prepare;                  // exposure / preExposure
+    float unprepare;                // preExposure / exposure
+    float adaptedLog2Luminance;
+};
+
+Texture2D<float>                   GameExposure : register(t1);
+RWStructuredBuffer<uint>           Histogram    : register(u0);
+RWStructuredBuffer<ExposureState>  State        : register(u1);
+
+float BinCenterLog2(uint bin)
+{
+    return MIN_LOG2_LUM + (bin - 0.5) / (BINS - 1) * (MAX_LOG2_LUM - MIN_LOG2_LUM);
+}
+
+[numthreads(1, 1, 1)]
+void main()
+{
+    float adapted = State[0].adaptedLog2Luminance;
+    float exposure = 1.0;
+
+    if (mode == MODE_AUTO)
+    {
+        float total = 0.0;
+        for (uint i = 1; i < BINS; ++i)
+            total += (float)Histogram[i];
+
+        if (total > 0.0)
+        {
+            float lo = total * LOW_PERCENTILE;
+            float hi = total * HIGH_PERCENTILE;
+            float below = 0.0, sum = 0.0, weight = 0.0;
+            for (uint b = 1; b < BINS; ++b)
+            {
+                float end = below + (float)Histogram[b];
+                float take = max(min(end, hi) - max(below, lo), 0.0);
+                sum += take * BinCenterLog2(b);
+                weight += take;
+                below = end;
+            }
+            if (weight > 0.0)
+            {
+                float target = sum / weight;
+                float dt = min(max(frameTimeMs, 0.0) / 1000.0, 1.0);
+                adapted = reset ? target
+                                : adapted + (target - adapted) * (1.0 - exp(-ADAPTATION_RATE * dt));
+            }
+        }
+        else if (reset)
+        {
+            adapted = 0.0;
+        }
+
+        float ev100 = adapted + log2(100.0 / 12.5);
+        exposure = 1.0 / (1.2 * exp2(ev100));
+    }
+    else if (mode == MODE_GAME_TEXTURE)
+    {
+        exposure = GameExposure.Load(int3(0, 0, 0));
+    }
+
+    if (!(exposure > 0.0) || isinf(exposure))
+        exposure = 1.0;
+
+    if (mode != MODE_CLEAR)
+    {
+        ExposureState s;
+        s.exposure = exposure;
+        s.prepare = exposure / preExposure;
+        s.unprepare = preExposure / exposure;
+        s.adaptedLog2Luminance = adapted;
+        State[0] = s;
+    }
+
+    for (uint c = 0; c < BINS; ++c)
+        Histogram[c] = 0;
+}
@@ -0,0 +1,59 @@
+//============================================================================================================
+//
+//  IMBA feature view (PassType::DebugView) — one channel of a feature slot drawn over the output,
+//  nearest-sampled. t = clamp(v / range, -1, 1); positive values are red-orange (t, t/2, 0), negative
+//  ones blue-cyan (0, -t/2, -t). Alpha is 1. Chosen from the overlay; not part of a network.
+//
+//  Runs on the AA root signature and reads the AAConstants layout. Mirrors
+//  fsr_sys::imba::cpu::AAReference::debug_view; keep the two in sync.
+//
+//============================================================================================================
+
+cbuffer AAConstants : register(b0)
+{
+    uint  passType;
+    uint  inBuf;                    // slot holding the tensor
+    uint  outBuf;
+    uint  auxBuf;
+    uint  width;                    // output size
+    uint  height;
+    uint  inChannels;               // channel drawn
+    uint  outChannels;
+    uint  kernelSize;
+    uint  stride;
+    uint  weightOff;
+    uint  biasOff;
+    uint  gammaOff;
+    uint  betaOff;
+    uint  numGroups;
+    uint  activation;
+    uint  flags;
+    uint  inWidth;                  // tensor size
+    uint  inHeight;
+    uint  bufStride;
+    float jitterX;
+    float jitterY;
+    float prevJitterX;
+    float prevJitterY;
+    uint  debugMode;                // range, as float bits
+    uint  pad;
+    uint  renderWidth;
+    uint  renderHeight;
+};
+
+RWStructuredBuffer<float>  Features : register(u0);
+RWTexture2D<float4>        Output   : register(u2);
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID)
+{
+    if (id.x >= width || id.y >= height)
+        return;
+
+    uint2 s = min(id.xy * uint2(inWidth, inHeight) / uint2(width, height), uint2(inWidth - 1, inHeight - 1));
+    uint plane = inBuf * bufStride + inChannels * inWidth * inHeight;
+    float range = max(asfloat(debugMode), 1.175494351e-38);
+    float t = clamp(Features[plane + s.y * inWidth + s.x] / range, -1.0, 1.0);
+
+    Output[id.xy] = t >= 0.0 ? float4(t, 0.5 * t, 0.0, 1.0) : float4(0.0, -0.5 * t, -t, 1.0);
+}
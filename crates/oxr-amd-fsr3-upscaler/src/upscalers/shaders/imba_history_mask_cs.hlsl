@@ -0,0 +1,74 @@
+//============================================================================================================
+//
+//  IMBA history rejection (PassType::HistoryMask) — runs before each attention and pulls the warped
+//  history (auxBuf, rewritten in place) toward the current features (inBuf) where the game's masks
+//  reject history, sampled at the centre of each stride x stride render-pixel cell. A fully set mask
+//  leaves the attention nothing but the current frame, so particles and UI do not smear along
+//  history the network would otherwise keep.
+//
+//  Runs on the AA root signature and reads the AAConstants layout. Mirrors
+//  fsr_sys::imba::cpu::AAReference::history_mask and fsr_sys::reactive::history_rejection; keep
+//  them in sync.
+//
+//============================================================================================================
+
+cbuffer AAConstants : register(b0)
+{
+    uint  passType;
+    uint  inBuf;                    // current features
+    uint  outBuf;                   // == auxBuf
+    uint  auxBuf;                   // warped history
+    uint  width;                    // padded size / stride
+    uint  height;
+    uint  inChannels;
+    uint  outChannels;
+    uint  kernelSize;
+    uint  stride;                   // cell size
+    uint  weightOff;
+    uint  biasOff;
+    uint  gammaOff;
+    uint  betaOff;
+    uint  numGroups;
+    uint  activation;
+    uint  flags;
+    uint  inWidth;                  // render size
+    uint  inHeight;
+    uint  bufStride;
+    float jitterX;
+    float jitterY;
+    float prevJitterX;
+    float prevJitterY;
+    uint  debugMode;
+    uint  pad;
+    uint  renderWidth;
+    uint  renderHeight;
+};
+
+// fsr_sys::reactive::COMPOSITION_HISTORY_REJECTION
+static const float COMPOSITION_HISTORY_REJECTION = 0.5;
+
+Texture2D<float>           Reactive    : register(t7);   // null SRV (0) when absent
+Texture2D<float>           Composition : register(t8);
+RWStructuredBuffer<float>  Features    : register(u0);
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID)
+{
+    if (id.x >= width || id.y >= height)
+        return;
+
+    float cell = float(max(stride, 1u));
+    int3 s = int3(min(uint2((float2(id.xy) + 0.5) * cell), uint2(inWidth - 1, inHeight - 1)), 0);
+    float reactive = saturate(Reactive.Load(s));
+    float composition = saturate(Composition.Load(s)) * COMPOSITION_HISTORY_REJECTION;
+    float t = 1.0 - (1.0 - reactive) * (1.0 - composition);
+    if (t <= 0.0)
+        return;
+
+    uint plane = width * height;
+    uint o = id.y * width + id.x;
+    uint warped = auxBuf * bufStride + o;
+    uint current = inBuf * bufStride + o;
+    for (uint c = 0; c < inChannels; c++)
+        Features[warped + c * plane] = Features[warped + c * plane] * (1.0 - t) + Features[current + c * plane] * t;
+}
@@ -0,0 +1,61 @@
+//============================================================================================================
+//
+//  IMBA motion vectors of a padded table (PassType::ScaleMVPadded) — for each pixel of a 1 / stride
+//  tensor, the motion vector at the centre of its stride x stride render-pixel cell, clamped to the
+//  render size, in render pixels over stride, as 2 channels.
+//
+//  Runs on the AA root signature and reads the AAConstants layout. Mirrors
+//  fsr_sys::imba::cpu::AAReference::scale_mv_padded; keep the two in sync.
+//
+//============================================================================================================
+
+cbuffer AAConstants : register(b0)
+{
+    uint  passType;
+    uint  inBuf;
+    uint  outBuf;                   // 2 channels at 1 / stride
+    uint  auxBuf;
+    uint  width;                    // padded size / stride
+    uint  height;
+    uint  inChannels;
+    uint  outChannels;
+    uint  kernelSize;
+    uint  stride;                   // cell size
+    uint  weightOff;
+    uint  biasOff;
+    uint  gammaOff;
+    uint  betaOff;
+    uint  numGroups;
+    uint  activation;
+    uint  flags;
+    uint  inWidth;                  // render size
+    uint  inHeight;
+    uint  bufStride;
+    float jitterX;
+    float jitterY;
+    float prevJitterX;
+    float prevJitterY;
+    uint  debugMode;
+    uint  pad;
+    uint  renderWidth;
+    uint  renderHeight;
+};
+
+Texture2D<float2>          CurrMotion : register(t3);
+RWStructuredBuffer<float>  Features   : register(u0);
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID)
+{
+    if (id.x >= width || id.y >= height)
+        return;
+
+    float cell = float(max(stride, 1u));
+    uint2 s = min(uint2((float2(id.xy) + 0.5) * cell), uint2(inWidth - 1, inHeight - 1));
+    float2 mv = CurrMotion.Load(int3(s, 0)) * float2(inWidth, inHeight) / cell;
+
+    uint base = outBuf * bufStride;
+    uint o = id.y * width + id.x;
+    Features[base + o] = mv.x;
+    Features[base + width * height + o] = mv.y;
+}
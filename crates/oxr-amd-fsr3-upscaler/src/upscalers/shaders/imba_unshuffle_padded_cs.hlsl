@@ -0,0 +1,85 @@
+//============================================================================================================
+//
+//  IMBA pixel unshuffle of a padded table (PassType::PixelUnshufflePadded) — PyTorch's pixel_unshuffle(2)
+//  of the frame inputs (RGB, depth, motion vector in render pixels, jitter) over a tensor larger than
+//  the render size: reads past the render size repeat the edge pixels. Output channel c * 4 + dy * 2 + dx
+//  at (x, y) is input channel c at (2x + dx, 2y + dy).
+//
+//  Runs on the AA root signature and reads the AAConstants layout. Mirrors
+//  fsr_sys::imba::cpu::AAReference::pixel_unshuffle; keep the two in sync.
+//
+//============================================================================================================
+
+cbuffer AAConstants : register(b0)
+{
+    uint  passType;
+    uint  inBuf;
+    uint  outBuf;                   // 32 channels at half the padded size
+    uint  auxBuf;
+    uint  width;                    // half the padded size
+    uint  height;
+    uint  inChannels;
+    uint  outChannels;
+    uint  kernelSize;
+    uint  stride;
+    uint  weightOff;
+    uint  biasOff;
+    uint  gammaOff;
+    uint  betaOff;
+    uint  numGroups;
+    uint  activation;
+    uint  flags;                    // FLAG_IS_PREV (2): previous frame
+    uint  inWidth;                  // render size
+    uint  inHeight;
+    uint  bufStride;
+    float jitterX;
+    float jitterY;
+    float prevJitterX;
+    float prevJitterY;
+    uint  debugMode;
+    uint  pad;
+    uint  renderWidth;
+    uint  renderHeight;
+};
+
+Texture2D<float4>          CurrColor  : register(t1);
+Texture2D<float>           CurrDepth  : register(t2);
+Texture2D<float2>          CurrMotion : register(t3);
+Texture2D<float4>          PrevColor  : register(t4);
+Texture2D<float>           PrevDepth  : register(t5);
+Texture2D<float2>          PrevMotion : register(t6);
+RWStructuredBuffer<float>  Features   : register(u0);
+
+#define FLAG_IS_PREV 2u
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID)
+{
+    if (id.x >= width || id.y >= height)
+        return;
+
+    bool prev = (flags & FLAG_IS_PREV) != 0;
+    float2 jitter = prev ? float2(prevJitterX, prevJitterY) : float2(jitterX, jitterY);
+    float2 mvScale = float2(inWidth, inHeight);
+    uint base = outBuf * bufStride;
+    uint plane = width * height;
+    uint o = id.y * width + id.x;
+
+    [unroll]
+    for (uint dy = 0; dy < 2; dy++)
+    {
+        [unroll]
+        for (uint dx = 0; dx < 2; dx++)
+        {
+            int3 p = int3(min(id.xy * 2 + uint2(dx, dy), uint2(inWidth - 1, inHeight - 1)), 0);
+            float3 color = prev ? PrevColor.Load(p).rgb : CurrColor.Load(p).rgb;
+            float depth = prev ? PrevDepth.Load(p) : CurrDepth.Load(p);
+            float2 mv = (prev ? PrevMotion.Load(p) : CurrMotion.Load(p)) * mvScale;
+            float inputs[8] = { color.r, color.g, color.b, depth, mv.x, mv.y, jitter.x, jitter.y };
+
+            [unroll]
+            for (uint ch = 0; ch < 8; ch++)
+                Features[base + (ch * 4 + dy * 2 + dx) * plane + o] = inputs[ch];
+        }
+    }
+}
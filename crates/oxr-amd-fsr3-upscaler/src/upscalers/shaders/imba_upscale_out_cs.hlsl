@@ -0,0 +1,85 @@
+//============================================================================================================
+//
+//  IMBA upscale output (PassType::PixelShuffleUpscale) — the PixelShuffleOut reconstruction followed
+//  by a resample to the output size. The network runs at render resolution; each output pixel
+//  bilinearly samples the render-resolution frame (current colour plus the pixel-shuffled residual
+//  of the head) under its centre, edges clamped. Alpha is 1. The head of a padded table covers more
+//  than the frame; the rest is never read.
+//
+//  Runs on the AA root signature and reads the AAConstants layout. Mirrors
+//  fsr_sys::imba::cpu::AAReference::pixel_shuffle_upscale; keep the two in sync.
+//
+//============================================================================================================
+
+cbuffer AAConstants : register(b0)
+{
+    uint  passType;
+    uint  inBuf;                    // head output, 3 * stride^2 channels at 1 / stride
+    uint  outBuf;
+    uint  auxBuf;
+    uint  width;                    // output size
+    uint  height;
+    uint  inChannels;
+    uint  outChannels;
+    uint  kernelSize;
+    uint  stride;                   // pixel-shuffle factor of the head
+    uint  weightOff;
+    uint  biasOff;
+    uint  gammaOff;
+    uint  betaOff;
+    uint  numGroups;
+    uint  activation;
+    uint  flags;
+    uint  inWidth;                  // head size
+    uint  inHeight;
+    uint  bufStride;
+    float jitterX;
+    float jitterY;
+    float prevJitterX;
+    float prevJitterY;
+    uint  debugMode;
+    uint  pad;
+    uint  renderWidth;
+    uint  renderHeight;
+};
+
+Texture2D<float4>          CurrColor : register(t1);
+RWStructuredBuffer<float>  Features  : register(u0);
+RWTexture2D<float4>        Output    : register(u2);
+
+// Render-resolution reconstruction at integer pixel p
+float3 Reconstruct(int2 p)
+{
+    uint r = max(stride, 1u);
+    uint2 head = uint2(inWidth, inHeight);
+    uint2 s = min(uint2(p) / r, head - 1);
+    uint2 d = uint2(p) % r;
+    uint base = inBuf * bufStride;
+
+    float3 color = CurrColor.Load(int3(p, 0)).rgb;
+    [unroll]
+    for (uint ch = 0; ch < 3; ch++)
+    {
+        uint ic = ch * r * r + d.y * r + d.x;
+        color[ch] += Features[base + (ic * head.y + s.y) * head.x + s.x];
+    }
+    return color;
+}
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID)
+{
+    if (id.x >= width || id.y >= height)
+        return;
+
+    uint2 render = uint2(renderWidth, renderHeight);
+    float2 scale = float2(render) / float2(width, height);
+    float2 pos = clamp((float2(id.xy) + 0.5) * scale - 0.5, 0.0, float2(render - 1));
+    int2 p0 = int2(floor(pos));
+    int2 p1 = min(p0 + 1, int2(render - 1));
+    float2 f = pos - float2(p0);
+
+    float3 top = lerp(Reconstruct(p0), Reconstruct(int2(p1.x, p0.y)), f.x);
+    float3 bottom = lerp(Reconstruct(int2(p0.x, p1.y)), Reconstruct(p1), f.x);
+    Output[id.xy] = float4(lerp(top, bottom, f.y), 1.0);
+}
@@ -0,0 +1,41 @@
+//============================================================================================================
+//
+//  Input normalization — game depth / motion vectors to the upscalers' convention
+//  (reverse-Z depth, render-resolution UV motion vectors without jitter).
+//
+//  Mirrors fsr_sys::inputs::InputNormalization; keep the two in sync.
+//
+//============================================================================================================
+
+#define FLAG_FLIP_DEPTH 1u
+
+cbuffer Params : register(b0)
+{
+    uint   packedRenderSize;        // width | height << 16
+    uint   flags;
+    float2 mvSampleScale;           // MV texels per render pixel
+    float2 mvUvScale;               // raw MV -> UV
+    float2 jitterCancellation;      // UV, subtracted
+};
+
+Texture2D<float>    InputDepth    : register(t0);
+Texture2D<float4>   InputVelocity : register(t1);
+RWTexture2D<float>  OutDepth      : register(u0);
+RWTexture2D<float2> OutVelocity   : register(u1);
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID)
+{
+    uint2 renderSize = uint2(packedRenderSize & 0xFFFFu, packedRenderSize >> 16);
+    if (any(id.xy >= renderSize))
+        return;
+
+    float depth = InputDepth.Load(int3(id.xy, 0));
+    if (flags & FLAG_FLIP_DEPTH)
+        depth = 1.0 - depth;
+    OutDepth[id.xy] = depth;
+
+    uint2 mvPos = uint2((float2(id.xy) + 0.5) * mvSampleScale);
+    float2 mv = InputVelocity.Load(int3(mvPos, 0)).xy;
+    OutVelocity[id.xy] = mv * mvUvScale - jitterCancellation;
+}
@@ -8,6 +8,7 @@ SamplerState samp : register(s0);
 cbuffer Params : register(b0) {
     float2 uvScale;    // constants 0,1 — consumed by VS
     float2 inputSize;  // constants 2,3 — color texture dimensions (width, height)
+    uint hdr;          // constant 4 — filter in tonemapped space (FFX HDR flag)
 };
 
 static const float ARStrength = 0.5;
@@ -17,6 +18,11 @@ static const float ARStrength = 0.5;
 #define min4(a, b, c, d) min(min(a, b), min(c, d))
 #define max4(a, b, c, d) max(max(a, b), max(c, d))
 
+// Reversible tonemap from fsr_sys::inputs::to_filter_space / from_filter_space.
+float Max3(float3 c) { return max(c.r, max(c.g, c.b)); }
+float3 Tonemap(float3 c) { return c / (max(Max3(c), 0.0) + 1.0); }
+float3 InverseTonemap(float3 c) { return c / max(1.0 / 65503.0, 1.0 - Max3(c)); }
+
 float3 weight3(float x) {
     const float rcpRadius = 1.0f / 3.0f;
     float3 s = FIX(2.0 * PI * float3(x - 1.5, x - 0.5, x + 0.5));
@@ -68,6 +74,15 @@ float4 PS(float4 sv_pos : SV_POSITION, float2 uv : TEXCOORD0) : SV_Target {
         }
     }
 
+    if (hdr) {
+        [unroll]
+        for (i = 0; i < 6; ++i) {
+            [unroll]
+            for (j = 0; j < 6; ++j)
+                tap[i][j] = Tonemap(tap[i][j]);
+        }
+    }
+
     float3 color = float3(0, 0, 0);
     [unroll]
     for (i = 0; i <= 4; i += 2) {
@@ -83,5 +98,8 @@ float4 PS(float4 sv_pos : SV_POSITION, float2 uv : TEXCOORD0) : SV_Target {
     float3 max_sample = max4(tap[2][2], tap[3][2], tap[2][3], tap[3][3]);
     color = lerp(color, clamp(color, min_sample, max_sample), ARStrength);
 
+    if (hdr)
+        color = InverseTonemap(color);
+
     return float4(color, 1);
 }
@@ -3,7 +3,16 @@
 
 Texture2D<float4> src : register(t0);
 
-cbuffer Params : register(b0) { float sharpness; };
+cbuffer Params : register(b0) {
+    float sharpness;    // constant 0
+    float3 pad;         // constants 1-3 (uvScale / inputSize in the shared layout)
+    uint hdr;           // constant 4 — sharpen in tonemapped space (FFX HDR flag)
+};
+
+// Reversible tonemap from fsr_sys::inputs::to_filter_space / from_filter_space.
+float Max3(float3 c) { return max(c.r, max(c.g, c.b)); }
+float3 Tonemap(float3 c) { return c / (max(Max3(c), 0.0) + 1.0); }
+float3 InverseTonemap(float3 c) { return c / max(1.0 / 65503.0, 1.0 - Max3(c)); }
 
 #define FSR_RCAS_LIMIT (0.25 - 1.0/16.0)
 
@@ -20,6 +29,15 @@ float4 PS(float4 sv_pos : SV_POSITION, float2 uv : TEXCOORD0) : SV_Target {
     float3 f = src.Load(int3(sp + int2( 1, 0), 0)).rgb;
     float3 h = src.Load(int3(sp + int2( 0, 1), 0)).rgb;
 
+    // RCAS limits assume [0, 1]; HDR input is tonemapped first (luma included)
+    if (hdr) {
+        b = Tonemap(b);
+        d = Tonemap(d);
+        e = Tonemap(e);
+        f = Tonemap(f);
+        h = Tonemap(h);
+    }
+
     float bR = b.r, bG = b.g, bB = b.b;
     float dR = d.r, dG = d.g, dB = d.b;
     float eR = e.r, eG = e.g, eB = e.b;
@@ -68,5 +86,9 @@ float4 PS(float4 sv_pos : SV_POSITION, float2 uv : TEXCOORD0) : SV_Target {
     float pixG = (lobe * bG + lobe * dG + lobe * hG + lobe * fG + eG) * rcpL;
     float pixB = (lobe * bB + lobe * dB + lobe * hB + lobe * fB + eB) * rcpL;
 
-    return float4(pixR, pixG, pixB, 1.0);
+    float3 pix = float3(pixR, pixG, pixB);
+    if (hdr)
+        pix = InverseTonemap(pix);
+
+    return float4(pix, 1.0);
 }
@@ -0,0 +1,32 @@
+//============================================================================================================
+//
+//  Reactive blend — pulls an upscaler/AA result toward the current frame where one of the
+//  game's masks is set: reactive (particles, transparencies, animated textures) at full
+//  strength, transparency & composition (UI, alpha-blended surfaces) partly. One pass per mask.
+//
+//  Mirrors fsr_sys::reactive::blend_toward_current; keep the two in sync.
+//
+//============================================================================================================
+
+cbuffer Params : register(b0)
+{
+    uint2 size;
+    float strength;                 // current-frame weight of a fully set mask
+    uint  pad;
+};
+
+Texture2D<float4>   InputColor : register(t0);
+Texture2D<float>    Mask       : register(t1);
+RWTexture2D<float4> Output     : register(u0);
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID)
+{
+    if (any(id.xy >= size))
+        return;
+
+    float weight = saturate(Mask.Load(int3(id.xy, 0))) * strength;
+    float4 result = Output[id.xy];
+    float3 current = InputColor.Load(int3(id.xy, 0)).rgb;
+    Output[id.xy] = float4(lerp(result.rgb, current, weight), result.a);
+}
@@ -0,0 +1,71 @@
+//============================================================================================================
+//
+//  Reactive mask generation — opaque-only vs pre-upscale color difference.
+//
+//  Mirrors fsr_sys::reactive::reactive_value; keep the two in sync.
+//
+//============================================================================================================
+
+#define FLAG_APPLY_TONEMAP        1u
+#define FLAG_APPLY_INVERSETONEMAP 2u
+#define FLAG_APPLY_THRESHOLD      4u
+#define FLAG_USE_COMPONENTS_MAX   8u
+
+cbuffer Params : register(b0)
+{
+    uint2 renderSize;
+    float scale;
+    float cutoffThreshold;
+    float binaryValue;
+    uint  flags;
+    uint2 pad;                  // 8 DWORDs total
+};
+
+Texture2D<float4>   ColorOpaqueOnly : register(t0);
+Texture2D<float4>   ColorPreUpscale : register(t1);
+RWTexture2D<float>  OutReactive     : register(u0);
+
+float Max3(float3 c)
+{
+    return max(c.r, max(c.g, c.b));
+}
+
+float3 Tonemap(float3 c)
+{
+    return c / (max(Max3(c), 0.0) + 1.0);
+}
+
+float3 InverseTonemap(float3 c)
+{
+    return c / max(1.0 / 65503.0, 1.0 - Max3(c));
+}
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID)
+{
+    if (any(id.xy >= renderSize))
+        return;
+
+    float3 opaque = ColorOpaqueOnly.Load(int3(id.xy, 0)).rgb;
+    float3 color = ColorPreUpscale.Load(int3(id.xy, 0)).rgb;
+
+    if (flags & FLAG_APPLY_TONEMAP)
+    {
+        opaque = Tonemap(opaque);
+        color = Tonemap(color);
+    }
+    if (flags & FLAG_APPLY_INVERSETONEMAP)
+    {
+        opaque = InverseTonemap(opaque);
+        color = InverseTonemap(color);
+    }
+
+    float3 delta = abs(color - opaque);
+    float out_value = (flags & FLAG_USE_COMPONENTS_MAX) ? Max3(delta) : length(delta);
+    out_value *= scale;
+
+    if (flags & FLAG_APPLY_THRESHOLD)
+        out_value = out_value < cutoffThreshold ? 0.0 : binaryValue;
+
+    OutReactive[id.xy] = out_value;
+}
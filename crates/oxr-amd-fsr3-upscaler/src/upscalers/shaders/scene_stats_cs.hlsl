@@ -0,0 +1,116 @@
+//============================================================================================================
+//
+//  Scene-cut statistics — luma / depth histograms and motion vector magnitudes of one frame,
+//  read back by scene_cut.rs and compared with the previous frame's on the CPU.
+//
+//  Mirrors fsr_sys::scene_cut::{frame_stats, depth_bin} and fsr_sys::exposure::histogram_bin;
+//  keep them in sync. The buffer layout is fsr_sys::scene_cut::FrameStats.
+//
+//============================================================================================================
+
+#define LUMA_BINS       64
+#define DEPTH_BINS      32
+#define MIN_LOG2_LUM    -12.0
+#define MAX_LOG2_LUM    4.0
+#define MIN_LOG2_DEPTH  -16.0
+#define MOTION_SCALE    256.0
+
+// FrameStats word offsets
+#define OFFSET_DEPTH        LUMA_BINS
+#define OFFSET_FAR_MOTION   (LUMA_BINS + DEPTH_BINS)
+#define OFFSET_MOTION_SUM   (OFFSET_FAR_MOTION + 1)
+#define OFFSET_PIXELS       (OFFSET_FAR_MOTION + 2)
+#define OFFSET_FRAME        (OFFSET_FAR_MOTION + 3)
+#define STATS_WORDS         (OFFSET_FAR_MOTION + 4)
+
+#define MODE_GATHER 0u
+#define MODE_CLEAR  1u
+
+cbuffer Params : register(b0)
+{
+    uint  packedRenderSize;         // width | height << 16
+    uint  mode;
+    float preExposure;
+    float motionThreshold;          // UV length of a "far" motion vector
+    uint  frameIndex;
+    uint3 pad;                      // 8 DWORDs total
+};
+
+Texture2D<float4>        InputColor    : register(t0);
+Texture2D<float>         InputDepth    : register(t1);
+Texture2D<float4>        InputVelocity : register(t2);
+RWStructuredBuffer<uint> Stats         : register(u0);
+
+groupshared uint localLuma[LUMA_BINS];
+groupshared uint localDepth[DEPTH_BINS];
+groupshared uint localFar;
+groupshared uint localMotion;
+groupshared uint localPixels;
+
+uint LumaBin(float lum)
+{
+    if (!(lum > exp2(MIN_LOG2_LUM)))
+        return 0;
+    float t = saturate((log2(lum) - MIN_LOG2_LUM) / (MAX_LOG2_LUM - MIN_LOG2_LUM));
+    return min(1u + (uint)(t * (LUMA_BINS - 1)), LUMA_BINS - 1u);
+}
+
+uint DepthBin(float depth)
+{
+    if (!(depth > exp2(MIN_LOG2_DEPTH)))
+        return 0;
+    float t = saturate(1.0 - log2(min(depth, 1.0)) / MIN_LOG2_DEPTH);
+    return min(1u + (uint)(t * (DEPTH_BINS - 1)), DEPTH_BINS - 1u);
+}
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID, uint gi : SV_GroupIndex)
+{
+    if (mode == MODE_CLEAR)
+    {
+        for (uint i = gi; i < STATS_WORDS; i += 64)
+            Stats[i] = 0;
+        return;
+    }
+
+    // 8x8 threads == LUMA_BINS: one luma bin per thread
+    localLuma[gi] = 0;
+    if (gi < DEPTH_BINS)
+        localDepth[gi] = 0;
+    if (gi == 0)
+    {
+        localFar = 0;
+        localMotion = 0;
+        localPixels = 0;
+    }
+    GroupMemoryBarrierWithGroupSync();
+
+    uint2 renderSize = uint2(packedRenderSize & 0xFFFFu, packedRenderSize >> 16);
+    if (all(id.xy < renderSize))
+    {
+        float3 c = InputColor.Load(int3(id.xy, 0)).rgb / preExposure;
+        InterlockedAdd(localLuma[LumaBin(dot(c, float3(0.2126, 0.7152, 0.0722)))], 1u);
+        InterlockedAdd(localDepth[DepthBin(InputDepth.Load(int3(id.xy, 0)))], 1u);
+
+        float len = length(InputVelocity.Load(int3(id.xy, 0)).xy);
+        if (len > motionThreshold)
+            InterlockedAdd(localFar, 1u);
+        if (!isnan(len))
+            InterlockedAdd(localMotion, (uint)(min(len, 1.0) * MOTION_SCALE));
+        InterlockedAdd(localPixels, 1u);
+    }
+    GroupMemoryBarrierWithGroupSync();
+
+    if (localLuma[gi] != 0)
+        InterlockedAdd(Stats[gi], localLuma[gi]);
+    if (gi < DEPTH_BINS && localDepth[gi] != 0)
+        InterlockedAdd(Stats[OFFSET_DEPTH + gi], localDepth[gi]);
+    if (gi == 0)
+    {
+        InterlockedAdd(Stats[OFFSET_FAR_MOTION], localFar);
+        InterlockedAdd(Stats[OFFSET_MOTION_SUM], localMotion);
+        InterlockedAdd(Stats[OFFSET_PIXELS], localPixels);
+        if (all(id.xy == 0))
+            Stats[OFFSET_FRAME] = frameIndex;
+    }
+}
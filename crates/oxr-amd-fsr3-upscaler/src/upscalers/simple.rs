@@ -53,7 +53,10 @@ pub unsafe fn dispatch(ctx: &DispatchContext) -> u32 {
     // Select PSO based on current upscaler type
     let current_upscaler = upscaler_type::get();
     let pso = match current_upscaler {
-        upscaler_type::UpscalerType::Bilinear => &gpu.pso_bilinear,
+        // IMBA falls back to bilinear while no model is available
+        upscaler_type::UpscalerType::Bilinear | upscaler_type::UpscalerType::Imba => {
+            &gpu.pso_bilinear
+        }
         upscaler_type::UpscalerType::Lanczos => &gpu.pso_lanczos,
         upscaler_type::UpscalerType::SGSR => &gpu.pso_sgsr,
         _ => unreachable!("simple::dispatch called for non-simple upscaler"),
@@ -80,11 +83,13 @@ pub unsafe fn dispatch(ctx: &DispatchContext) -> u32 {
     cmd_list.SetPipelineState(pso);
     cmd_list.SetDescriptorHeaps(&[Some(gpu.srv_heap.clone())]);
 
-    // Root constants: uvScale + inputSize
+    // Root constants: uvScale + inputSize + hdr
     cmd_list.SetGraphicsRoot32BitConstant(0, uv_scale_x.to_bits(), 0);
     cmd_list.SetGraphicsRoot32BitConstant(0, uv_scale_y.to_bits(), 1);
     cmd_list.SetGraphicsRoot32BitConstant(0, (color_tex_w as f32).to_bits(), 2);
     cmd_list.SetGraphicsRoot32BitConstant(0, (color_tex_h as f32).to_bits(), 3);
+    // Lanczos filters HDR colour in tonemapped space; the other shaders ignore it
+    cmd_list.SetGraphicsRoot32BitConstant(0, ctx.input_flags.hdr as u32, 4);
 
     cmd_list.SetGraphicsRootDescriptorTable(1, gpu.srv_heap.GetGPUDescriptorHandleForHeapStart());
 
@@ -0,0 +1,103 @@
+// Checkpoint readers. Everything is converted to f32 on load; the name
+// mapping and shape checks in `import` work on the result.
+
+use std::path::Path;
+
+use fsr_sys::weights::f16_to_f32;
+
+use crate::{onnx, safetensors};
+
+/// A named tensor from a checkpoint.
+#[derive(Debug, Clone)]
+pub struct Tensor {
+    pub name: String,
+    pub shape: Vec<u32>,
+    pub data: Vec<f32>,
+}
+
+/// A loaded checkpoint: tensors in file order plus free-form metadata.
+#[derive(Debug, Default)]
+pub struct Checkpoint {
+    pub tensors: Vec<Tensor>,
+    pub metadata: Vec<(String, String)>,
+}
+
+impl Checkpoint {
+    pub fn metadata(&self, key: &str) -> Option<&str> {
+        self.metadata
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Load a `.safetensors` or `.onnx` file, chosen by extension and falling
+/// back to sniffing the contents.
+pub fn load(path: &Path) -> Result<Checkpoint, String> {
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let result = match ext.as_deref() {
+        Some("safetensors") => safetensors::parse(&bytes),
+        Some("onnx") => onnx::parse(&bytes),
+        _ if safetensors::sniff(&bytes) => safetensors::parse(&bytes),
+        _ => onnx::parse(&bytes).map_err(|e| {
+            format!(
+                "not a safetensors file, and reading it as ONNX failed: {}",
+                e
+            )
+        }),
+    };
+    result.map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Element types a checkpoint may store; all widen to f32.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dtype {
+    F32,
+    F16,
+    BF16,
+    F64,
+}
+
+impl Dtype {
+    pub fn size(self) -> usize {
+        match self {
+            Self::F16 | Self::BF16 => 2,
+            Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    /// Decode little-endian `bytes` (a whole number of elements).
+    pub fn decode(self, bytes: &[u8]) -> Vec<f32> {
+        match self {
+            Self::F32 => bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+                .collect(),
+            Self::F16 => bytes
+                .chunks_exact(2)
+                .map(|b| f16_to_f32(u16::from_le_bytes([b[0], b[1]])))
+                .collect(),
+            Self::BF16 => bytes
+                .chunks_exact(2)
+                .map(|b| f32::from_bits((u16::from_le_bytes([b[0], b[1]]) as u32) << 16))
+                .collect(),
+            Self::F64 => bytes
+                .chunks_exact(8)
+                .map(|b| f64::from_le_bytes(b.try_into().unwrap()) as f32)
+                .collect(),
+        }
+    }
+}
+
+/// Element count of `shape`, rejecting overflow.
+pub fn element_count(shape: &[u32]) -> Option<usize> {
+    shape
+        .iter()
+        .try_fold(1usize, |n, &d| n.checked_mul(d as usize))
+}
@@ -0,0 +1,178 @@
+// Frames from a recording session (the upscaler's `recording` feature):
+// `[burst_]frame_NNNNNN_{color,depth,mv}.exr` plus `_meta.json`, turned into
+// AA inputs for the CPU reference.
+//
+// The recording does not keep the context flags, so depth is taken as
+// reverse-Z and motion vectors as render-resolution, scaled by the recorded
+// `motion_vector_scale` like `fsr_sys::inputs` does. A centre crop keeps
+// calibration runs short; motion vectors are rescaled to the crop's UVs.
+
+use std::path::{Path, PathBuf};
+
+use exr::prelude::traits::*;
+use fsr_sys::imba::cpu::AAFrameInputs;
+use fsr_sys::inputs::{InputFlags, InputNormalization};
+
+use crate::json::{self, Value};
+
+/// One frame's AA inputs at the crop size.
+pub struct Frame {
+    pub name: String,
+    pub color: Vec<[f32; 3]>,
+    pub depth: Vec<f32>,
+    pub motion_vectors: Vec<[f32; 2]>,
+    pub jitter: [f32; 2],
+}
+
+impl Frame {
+    pub fn inputs(&self) -> AAFrameInputs<'_> {
+        AAFrameInputs {
+            color: &self.color,
+            depth: &self.depth,
+            motion_vectors: &self.motion_vectors,
+            jitter: self.jitter,
+            history_mask: None,
+        }
+    }
+}
+
+/// Up to `max_frames` consecutive frames of the session in `dir`, cropped to
+/// at most `crop` (rounded down to a multiple of 4). Returns the frames and
+/// their size.
+pub fn load_session(
+    dir: &Path,
+    max_frames: usize,
+    crop: (u32, u32),
+) -> Result<(Vec<Frame>, (u32, u32)), String> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("failed to read {}: {}", dir.display(), e))?;
+    let mut prefixes: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().into_string().ok()?;
+            name.strip_suffix("_color.exr").map(str::to_string)
+        })
+        .collect();
+    prefixes.sort();
+    prefixes.truncate(max_frames);
+    if prefixes.is_empty() {
+        return Err(format!("no *_color.exr frames in {}", dir.display()));
+    }
+
+    let mut frames = Vec::with_capacity(prefixes.len());
+    let mut size = None;
+    let mut prev_jitter = None;
+    for prefix in &prefixes {
+        let file = |suffix: &str| -> PathBuf { dir.join(format!("{}_{}", prefix, suffix)) };
+        let frame = load_frame(prefix, &file, crop, &mut size, &mut prev_jitter)
+            .map_err(|e| format!("{}: {}", prefix, e))?;
+        frames.push(frame);
+    }
+    Ok((frames, size.unwrap()))
+}
+
+fn load_frame(
+    name: &str,
+    file: &dyn Fn(&str) -> PathBuf,
+    crop: (u32, u32),
+    size: &mut Option<(u32, u32)>,
+    prev_jitter: &mut Option<[f32; 2]>,
+) -> Result<Frame, String> {
+    let meta_path = file("meta.json");
+    let meta = std::fs::read_to_string(&meta_path)
+        .map_err(|e| format!("failed to read {}: {}", meta_path.display(), e))?;
+    let meta = json::parse(&meta)?;
+    let pair = |key: &str| -> Result<[f32; 2], String> {
+        match meta.get(key).and_then(Value::as_array) {
+            Some([Value::Number(a), Value::Number(b)]) => Ok([*a as f32, *b as f32]),
+            _ => Err(format!("meta.json: missing {}", key)),
+        }
+    };
+    let jitter = pair("jitter")?;
+    let render = pair("render_size")?;
+    let output = pair("output_size")?;
+    let mv_scale = pair("motion_vector_scale")?;
+
+    let (color, w, h) = read_exr(&file("color.exr"), &["R", "G", "B"])?;
+    let (depth, dw, dh) = read_exr(&file("depth.exr"), &["Y"])?;
+    let (mv, mw, mh) = read_exr(&file("mv.exr"), &["X", "Y"])?;
+    if (dw, dh) != (w, h) || (mw, mh) != (w, h) {
+        return Err(format!(
+            "texture sizes differ: color {}x{}, depth {}x{}, mv {}x{}",
+            w, h, dw, dh, mw, mh
+        ));
+    }
+
+    let (cw, ch) = *size.get_or_insert_with(|| {
+        let fit = |c: u32, full: usize| (c.min(full as u32) / 4) * 4;
+        (fit(crop.0, w), fit(crop.1, h))
+    });
+    if cw < 4 || ch < 4 || cw as usize > w || ch as usize > h {
+        return Err(format!(
+            "frame {}x{} smaller than the crop {}x{}",
+            w, h, cw, ch
+        ));
+    }
+    let (x0, y0) = ((w - cw as usize) / 2, (h - ch as usize) / 2);
+
+    let norm = InputNormalization::new(
+        InputFlags {
+            depth_inverted: true,
+            ..Default::default()
+        },
+        (render[0] as u32, render[1] as u32),
+        (output[0] as u32, output[1] as u32),
+        mv_scale,
+        jitter,
+        prev_jitter.unwrap_or(jitter),
+    );
+    *prev_jitter = Some(jitter);
+    // UV of the full frame to UV of the crop.
+    let to_crop = [w as f32 / cw as f32, h as f32 / ch as f32];
+
+    let pixels = (cw * ch) as usize;
+    let mut frame = Frame {
+        name: name.to_string(),
+        color: Vec::with_capacity(pixels),
+        depth: Vec::with_capacity(pixels),
+        motion_vectors: Vec::with_capacity(pixels),
+        jitter,
+    };
+    for y in 0..ch as usize {
+        for x in 0..cw as usize {
+            let i = (y0 + y) * w + x0 + x;
+            frame.color.push([color[0][i], color[1][i], color[2][i]]);
+            frame.depth.push(norm.depth(depth[0][i]));
+            let [u, v] = norm.motion_vector([mv[0][i], mv[1][i]]);
+            frame.motion_vectors.push([u * to_crop[0], v * to_crop[1]]);
+        }
+    }
+    Ok(frame)
+}
+
+/// The named channels of the first layer, as f32 planes.
+fn read_exr(path: &Path, names: &[&str]) -> Result<(Vec<Vec<f32>>, usize, usize), String> {
+    let image = read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .all_channels()
+        .first_valid_layer()
+        .all_attributes()
+        .from_file(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let layer = &image.layer_data;
+    let (w, h) = (layer.size.width(), layer.size.height());
+    let planes = names
+        .iter()
+        .map(|&name| {
+            layer
+                .channel_data
+                .list
+                .iter()
+                .find(|c| c.name == *name)
+                .map(|c| c.sample_data.values_as_f32().collect::<Vec<f32>>())
+                .ok_or(format!("{}: no channel {}", path.display(), name))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((planes, w, h))
+}
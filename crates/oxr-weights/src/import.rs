@@ -0,0 +1,487 @@
+// Map a training checkpoint onto the tensors a network description expects.
+//
+// Names go through, in order: prefix stripping (`module.` from DataParallel,
+// `_orig_mod.` from torch.compile), user renames, and the legacy
+// AAWeightOffsets.h layer names. Weight-normalised convolutions are fused
+// back into a plain weight, and shapes that only differ by size-1 dimensions
+// (a Linear used as a 1x1 conv, a GroupNorm affine saved as [C, 1, 1]) are
+// reshaped. Anything else that does not match is an error.
+
+use fsr_sys::imba::graph::Graph;
+use fsr_sys::weights::{layout_len, WeightDtype, WeightFile};
+
+use crate::checkpoint::{Checkpoint, Tensor};
+
+/// Prefixes stripped from every checkpoint name by default.
+pub const DEFAULT_STRIP: &[&str] = &["module.", "_orig_mod."];
+
+/// The layer list of the original AAWeightOffsets.h, for checkpoints
+/// exported with those names.
+pub const LEGACY_NAMES: &[(&str, &str)] = &[
+    ("EncCompressConv", "encoder.compress.conv.weight"),
+    ("EncCompressGnGamma", "encoder.compress.gn.weight"),
+    ("EncCompressGnBeta", "encoder.compress.gn.bias"),
+    ("EncRes1C1Conv", "encoder.res1.c1.conv.weight"),
+    ("EncRes1C1GnGamma", "encoder.res1.c1.gn.weight"),
+    ("EncRes1C1GnBeta", "encoder.res1.c1.gn.bias"),
+    ("EncRes1C2Conv", "encoder.res1.c2.weight"),
+    ("EncRes1Gn2Gamma", "encoder.res1.gn2.weight"),
+    ("EncRes1Gn2Beta", "encoder.res1.gn2.bias"),
+    ("EncRes2C1Conv", "encoder.res2.c1.conv.weight"),
+    ("EncRes2C1GnGamma", "encoder.res2.c1.gn.weight"),
+    ("EncRes2C1GnBeta", "encoder.res2.c1.gn.bias"),
+    ("EncRes2C2Conv", "encoder.res2.c2.weight"),
+    ("EncRes2Gn2Gamma", "encoder.res2.gn2.weight"),
+    ("EncRes2Gn2Beta", "encoder.res2.gn2.bias"),
+    ("EncDownConv", "encoder.downsample.conv.weight"),
+    ("EncDownGnGamma", "encoder.downsample.gn.weight"),
+    ("EncDownGnBeta", "encoder.downsample.gn.bias"),
+    ("EncRes3C1Conv", "encoder.res3.c1.conv.weight"),
+    ("EncRes3C1GnGamma", "encoder.res3.c1.gn.weight"),
+    ("EncRes3C1GnBeta", "encoder.res3.c1.gn.bias"),
+    ("EncRes3C2Conv", "encoder.res3.c2.weight"),
+    ("EncRes3Gn2Gamma", "encoder.res3.gn2.weight"),
+    ("EncRes3Gn2Beta", "encoder.res3.gn2.bias"),
+    ("TempAttnConv", "temporal.attention.weight"),
+    ("TempAttnBias", "temporal.attention.bias"),
+    ("TempMergeConv", "temporal.merge.conv.weight"),
+    ("TempMergeGnGamma", "temporal.merge.gn.weight"),
+    ("TempMergeGnBeta", "temporal.merge.gn.bias"),
+    ("DecUpConv", "decoder.upsample_conv.conv.weight"),
+    ("DecUpGnGamma", "decoder.upsample_conv.gn.weight"),
+    ("DecUpGnBeta", "decoder.upsample_conv.gn.bias"),
+    ("DecSkipConv", "decoder.skip_conv.weight"),
+    ("DecResC1Conv", "decoder.res.c1.conv.weight"),
+    ("DecResC1GnGamma", "decoder.res.c1.gn.weight"),
+    ("DecResC1GnBeta", "decoder.res.c1.gn.bias"),
+    ("DecResC2Conv", "decoder.res.c2.weight"),
+    ("DecResGn2Gamma", "decoder.res.gn2.weight"),
+    ("DecResGn2Beta", "decoder.res.gn2.bias"),
+    ("HeadExpandConv", "head.expand.weight"),
+    ("HeadExpandBias", "head.expand.bias"),
+    ("HeadRefineConv", "head.refine.weight"),
+    ("HeadRefineBias", "head.refine.bias"),
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub strip: Vec<String>,
+    /// Prefix renames, applied after stripping; the first match wins.
+    pub renames: Vec<(String, String)>,
+}
+
+/// Result of an import.
+pub struct Import {
+    pub file: WeightFile,
+    /// Checkpoint tensors the network does not use (optimizer state, EMA
+    /// copies, ...).
+    pub ignored: Vec<String>,
+    /// Renames, fusions and reshapes that were applied.
+    pub notes: Vec<String>,
+}
+
+pub fn import(
+    ckpt: Checkpoint,
+    graph: &Graph,
+    model_version: &str,
+    opts: &Options,
+) -> Result<Import, String> {
+    let mut notes = Vec::new();
+    let mut tensors: Vec<Tensor> = Vec::with_capacity(ckpt.tensors.len());
+    for mut t in ckpt.tensors {
+        let original = t.name.clone();
+        t.name = map_name(&t.name, opts);
+        if let Some(prev) = tensors.iter().find(|p| p.name == t.name) {
+            return Err(format!(
+                "'{}' and '{}' both map to '{}'",
+                prev.name, original, t.name
+            ));
+        }
+        if t.name != original {
+            notes.push(format!("{} -> {}", original, t.name));
+        }
+        tensors.push(t);
+    }
+    fuse_weight_norm(&mut tensors, &mut notes)?;
+
+    let mut data = vec![0.0; layout_len(&graph.weights)];
+    let mut used = vec![false; tensors.len()];
+    let mut errors = Vec::new();
+    for e in &graph.weights {
+        let Some(i) = tensors.iter().position(|t| t.name == e.name) else {
+            errors.push(format!("missing tensor '{}' {:?}", e.name, e.shape));
+            continue;
+        };
+        let t = &tensors[i];
+        used[i] = true;
+        if t.shape != e.shape {
+            if squeeze(&t.shape) != squeeze(&e.shape) {
+                errors.push(format!(
+                    "tensor '{}' has shape {:?}, expected {:?}",
+                    e.name, t.shape, e.shape
+                ));
+                continue;
+            }
+            notes.push(format!(
+                "reshaped '{}' {:?} -> {:?}",
+                e.name, t.shape, e.shape
+            ));
+        }
+        data[e.offset as usize..e.offset as usize + e.len()].copy_from_slice(&t.data);
+    }
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+
+    let file = WeightFile {
+        model_name: graph.model_name.clone(),
+        model_version: model_version.to_string(),
+        dtype: WeightDtype::F32,
+        tensors: graph.weights.clone(),
+        data,
+        scales: Vec::new(),
+    };
+    // What the DLL will do with it.
+    file.pack(&graph.model_name, &graph.weights)?;
+
+    let ignored = tensors
+        .iter()
+        .zip(&used)
+        .filter(|(_, &u)| !u)
+        .map(|(t, _)| t.name.clone())
+        .collect();
+    Ok(Import {
+        file,
+        ignored,
+        notes,
+    })
+}
+
+fn map_name(name: &str, opts: &Options) -> String {
+    let mut name = name;
+    while let Some(rest) = opts
+        .strip
+        .iter()
+        .find_map(|p| name.strip_prefix(p.as_str()))
+    {
+        name = rest;
+    }
+    let name = opts
+        .renames
+        .iter()
+        .find_map(|(from, to)| {
+            name.strip_prefix(from.as_str())
+                .map(|rest| format!("{}{}", to, rest))
+        })
+        .unwrap_or_else(|| name.to_string());
+    match LEGACY_NAMES.iter().find(|(legacy, _)| *legacy == name) {
+        Some((_, mapped)) => mapped.to_string(),
+        None => name,
+    }
+}
+
+/// Replace `x.weight_g` / `x.weight_v` (torch.nn.utils.weight_norm) and
+/// `x.parametrizations.weight.original0` / `original1` (its parametrize
+/// form) with `x.weight = g * v / ||v||`, the norm taken per output channel.
+fn fuse_weight_norm(tensors: &mut Vec<Tensor>, notes: &mut Vec<String>) -> Result<(), String> {
+    const FORMS: &[(&str, &str)] = &[
+        (".weight_g", ".weight_v"),
+        (
+            ".parametrizations.weight.original0",
+            ".parametrizations.weight.original1",
+        ),
+    ];
+    for (g_suffix, v_suffix) in FORMS {
+        while let Some(gi) = tensors.iter().position(|t| t.name.ends_with(g_suffix)) {
+            let base = tensors[gi].name[..tensors[gi].name.len() - g_suffix.len()].to_string();
+            let v_name = format!("{}{}", base, v_suffix);
+            let vi = tensors
+                .iter()
+                .position(|t| t.name == v_name)
+                .ok_or(format!(
+                    "'{}' has no matching '{}'",
+                    tensors[gi].name, v_name
+                ))?;
+            let weight = format!("{}.weight", base);
+            if tensors.iter().any(|t| t.name == weight) {
+                return Err(format!("'{}' is both stored and weight-normalised", weight));
+            }
+
+            let (g, v) = (&tensors[gi], &tensors[vi]);
+            let out = v.shape.first().copied().unwrap_or(1) as usize;
+            if g.data.len() != out || out == 0 || v.data.len() % out != 0 {
+                return Err(format!(
+                    "weight norm '{}': g {:?} does not match v {:?}",
+                    base, g.shape, v.shape
+                ));
+            }
+            let per = v.data.len() / out;
+            let mut data = Vec::with_capacity(v.data.len());
+            for (o, row) in v.data.chunks_exact(per).enumerate() {
+                let norm = row.iter().map(|x| x * x).sum::<f32>().sqrt();
+                let scale = if norm > 0.0 { g.data[o] / norm } else { 0.0 };
+                data.extend(row.iter().map(|x| x * scale));
+            }
+            let fused = Tensor {
+                name: weight,
+                shape: v.shape.clone(),
+                data,
+            };
+            notes.push(format!("fused weight norm into '{}'", fused.name));
+
+            let (hi, lo) = (gi.max(vi), gi.min(vi));
+            tensors.remove(hi);
+            tensors.remove(lo);
+            tensors.insert(lo, fused);
+        }
+    }
+    Ok(())
+}
+
+fn squeeze(shape: &[u32]) -> Vec<u32> {
+    shape.iter().copied().filter(|&d| d != 1).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Conv, GroupNorm and a biased head, named like v0's first and last
+    /// layers so the legacy names apply.
+    const GRAPH: &str = "\
+model import-test
+x = unshuffle
+c = conv x k=1 out=4 weight=encoder.compress.conv.weight
+n = groupnorm c groups=2 act=silu gamma=encoder.compress.gn.weight beta=encoder.compress.gn.bias
+head = conv n k=1 out=12 weight=head.expand.weight bias=head.expand.bias
+output head
+";
+
+    fn tensor(name: &str, shape: &[u32]) -> Tensor {
+        let len = shape.iter().product::<u32>() as usize;
+        Tensor {
+            name: name.to_string(),
+            shape: shape.to_vec(),
+            data: (0..len).map(|i| i as f32 * 0.25 - 1.0).collect(),
+        }
+    }
+
+    fn checkpoint(tensors: Vec<Tensor>) -> Checkpoint {
+        Checkpoint {
+            tensors,
+            metadata: Vec::new(),
+        }
+    }
+
+    fn default_options() -> Options {
+        Options {
+            strip: DEFAULT_STRIP.iter().map(|p| p.to_string()).collect(),
+            renames: Vec::new(),
+        }
+    }
+
+    /// The imported values of the graph's tensor `name`.
+    fn imported<'a>(import: &'a Import, name: &str) -> &'a [f32] {
+        let t = import.file.tensors.iter().find(|t| t.name == name).unwrap();
+        &import.file.data[t.range().unwrap()]
+    }
+
+    /// Every tensor `GRAPH` reads, under its own name.
+    fn full_checkpoint() -> Vec<Tensor> {
+        vec![
+            tensor("encoder.compress.conv.weight", &[4, 32, 1, 1]),
+            tensor("encoder.compress.gn.weight", &[4]),
+            tensor("encoder.compress.gn.bias", &[4]),
+            tensor("head.expand.weight", &[12, 4, 1, 1]),
+            tensor("head.expand.bias", &[12]),
+        ]
+    }
+
+    #[test]
+    fn imports_in_the_graph_layout() {
+        let graph = Graph::parse(GRAPH).unwrap();
+        let mut tensors = full_checkpoint();
+        tensors.push(tensor("optimizer.step", &[1]));
+        let result = import(checkpoint(tensors.clone()), &graph, "3", &default_options()).unwrap();
+
+        assert_eq!(result.file.model_name, "import-test");
+        assert_eq!(result.file.model_version, "3");
+        assert_eq!(result.file.dtype, WeightDtype::F32);
+        assert_eq!(result.file.tensors, graph.weights);
+        for t in &tensors[..5] {
+            assert_eq!(imported(&result, &t.name), t.data, "{}", t.name);
+        }
+        assert_eq!(result.ignored, ["optimizer.step"]);
+        assert!(result.notes.is_empty(), "{:?}", result.notes);
+    }
+
+    #[test]
+    fn maps_prefixes_renames_and_legacy_names() {
+        let graph = Graph::parse(GRAPH).unwrap();
+        let expected = full_checkpoint();
+        let names = [
+            "module.EncCompressConv",
+            "_orig_mod.module.EncCompressGnGamma",
+            "EncCompressGnBeta",
+            "net.expand.weight",
+            "HeadExpandBias",
+        ];
+        let tensors = expected
+            .iter()
+            .zip(names)
+            .map(|(t, name)| Tensor {
+                name: name.to_string(),
+                ..t.clone()
+            })
+            .collect();
+        let opts = Options {
+            renames: vec![("net.".into(), "head.".into())],
+            ..default_options()
+        };
+        let result = import(checkpoint(tensors), &graph, "1", &opts).unwrap();
+        for t in &expected {
+            assert_eq!(imported(&result, &t.name), t.data, "{}", t.name);
+        }
+        assert!(result
+            .notes
+            .contains(&"module.EncCompressConv -> encoder.compress.conv.weight".to_string()));
+        assert!(result
+            .notes
+            .contains(&"net.expand.weight -> head.expand.weight".to_string()));
+
+        // Every legacy name maps onto a distinct tensor of the built-in network
+        let v0 = Graph::parse(fsr_sys::imba::IMBA_V0_GRAPH).unwrap();
+        let mut mapped: Vec<_> = LEGACY_NAMES.iter().map(|&(_, name)| name).collect();
+        mapped.sort_unstable();
+        let mut weights: Vec<_> = v0.weights.iter().map(|w| w.name.as_str()).collect();
+        weights.sort_unstable();
+        assert_eq!(mapped, weights);
+    }
+
+    #[test]
+    fn fuses_weight_norm() {
+        let graph = Graph::parse(GRAPH).unwrap();
+        let mut tensors = full_checkpoint();
+        tensors.retain(|t| t.name != "head.expand.weight");
+        let g = Tensor {
+            name: "head.expand.weight_g".into(),
+            shape: vec![12, 1, 1, 1],
+            data: (0..12).map(|o| o as f32 + 1.0).collect(),
+        };
+        let v = tensor("head.expand.weight_v", &[12, 4, 1, 1]);
+        tensors.push(v.clone());
+        tensors.push(g.clone());
+        let result = import(checkpoint(tensors), &graph, "1", &default_options()).unwrap();
+
+        let fused = imported(&result, "head.expand.weight");
+        for (o, row) in v.data.chunks_exact(4).enumerate() {
+            let norm = row.iter().map(|x| x * x).sum::<f32>().sqrt();
+            for (i, x) in row.iter().enumerate() {
+                let want = g.data[o] * x / norm;
+                assert!((fused[o * 4 + i] - want).abs() < 1e-6, "{} {}", o, i);
+            }
+            // Each output channel's norm is its g
+            let got = fused[o * 4..][..4]
+                .iter()
+                .map(|x| x * x)
+                .sum::<f32>()
+                .sqrt();
+            assert!((got - g.data[o]).abs() < 1e-5);
+        }
+        assert!(result
+            .notes
+            .contains(&"fused weight norm into 'head.expand.weight'".to_string()));
+
+        // The parametrize form
+        let mut tensors = full_checkpoint();
+        tensors.retain(|t| t.name != "encoder.compress.conv.weight");
+        let g = Tensor {
+            name: "encoder.compress.conv.parametrizations.weight.original0".into(),
+            shape: vec![4, 1, 1, 1],
+            data: vec![2.0; 4],
+        };
+        let v = Tensor {
+            name: "encoder.compress.conv.parametrizations.weight.original1".into(),
+            ..tensor("", &[4, 32, 1, 1])
+        };
+        tensors.extend([g, v]);
+        let result = import(checkpoint(tensors), &graph, "1", &default_options()).unwrap();
+        let fused = imported(&result, "encoder.compress.conv.weight");
+        for row in fused.chunks_exact(32) {
+            let norm = row.iter().map(|x| x * x).sum::<f32>().sqrt();
+            assert!((norm - 2.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn rejects_broken_weight_norm() {
+        let graph = Graph::parse(GRAPH).unwrap();
+        let opts = default_options();
+
+        let mut tensors = full_checkpoint();
+        tensors.push(tensor("head.expand.weight_g", &[12, 1, 1, 1]));
+        assert_eq!(
+            import(checkpoint(tensors.clone()), &graph, "1", &opts).err(),
+            Some("'head.expand.weight_g' has no matching 'head.expand.weight_v'".to_string())
+        );
+        tensors.push(tensor("head.expand.weight_v", &[12, 4, 1, 1]));
+        assert_eq!(
+            import(checkpoint(tensors), &graph, "1", &opts).err(),
+            Some("'head.expand.weight' is both stored and weight-normalised".to_string())
+        );
+
+        let mut tensors = full_checkpoint();
+        tensors.retain(|t| t.name != "head.expand.weight");
+        tensors.push(tensor("head.expand.weight_g", &[4, 1, 1, 1]));
+        tensors.push(tensor("head.expand.weight_v", &[12, 4, 1, 1]));
+        let e = import(checkpoint(tensors), &graph, "1", &opts)
+            .err()
+            .unwrap();
+        assert!(e.contains("does not match v"), "{}", e);
+    }
+
+    #[test]
+    fn reshapes_only_size_one_dimensions() {
+        let graph = Graph::parse(GRAPH).unwrap();
+        let opts = default_options();
+
+        // A GroupNorm affine saved as [C, 1, 1], a 1x1 conv saved as a Linear
+        let mut tensors = full_checkpoint();
+        tensors[1].shape = vec![4, 1, 1];
+        tensors[3].shape = vec![12, 4];
+        let result = import(checkpoint(tensors), &graph, "1", &opts).unwrap();
+        assert!(result
+            .notes
+            .contains(&"reshaped 'encoder.compress.gn.weight' [4, 1, 1] -> [4]".to_string()));
+        assert!(result
+            .notes
+            .contains(&"reshaped 'head.expand.weight' [12, 4] -> [12, 4, 1, 1]".to_string()));
+
+        // Anything else is an error, reported with the missing tensors
+        let mut tensors = full_checkpoint();
+        tensors[3].shape = vec![4, 12, 1, 1];
+        tensors.remove(4);
+        let e = import(checkpoint(tensors), &graph, "1", &opts)
+            .err()
+            .unwrap();
+        assert_eq!(
+            e,
+            "tensor 'head.expand.weight' has shape [4, 12, 1, 1], expected [12, 4, 1, 1]\n\
+             missing tensor 'head.expand.bias' [12]"
+        );
+    }
+
+    #[test]
+    fn rejects_names_that_collide() {
+        let graph = Graph::parse(GRAPH).unwrap();
+        let mut tensors = full_checkpoint();
+        tensors.push(tensor("module.head.expand.bias", &[12]));
+        assert_eq!(
+            import(checkpoint(tensors), &graph, "1", &default_options()).err(),
+            Some(
+                "'head.expand.bias' and 'module.head.expand.bias' both map to 'head.expand.bias'"
+                    .to_string()
+            )
+        );
+    }
+}
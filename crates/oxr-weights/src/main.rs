@@ -0,0 +1,307 @@
+//! oxr-weights: build the `aa_weights.bin` the IMBA AA pass loads.
+//!
+//! Reads a PyTorch checkpoint exported as safetensors (or a restricted ONNX
+//! model), maps its tensors onto the network description the DLL compiles
+//! (`fsr_sys::imba::graph`, built-in v0 unless `--graph` names an
+//! `aa_graph.txt`) and writes a checked weight container. `quantize` turns a
+//! container into f16 or per-channel int8, calibrated and checked against
+//! recorded frames on the CPU reference.
+
+mod checkpoint;
+mod frames;
+mod import;
+mod json;
+mod onnx;
+mod quantize;
+mod safetensors;
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use fsr_sys::imba::graph::Graph;
+use fsr_sys::imba::{pack_weights, parse_weights};
+use fsr_sys::weights::{WeightDtype, WeightFile};
+
+const USAGE: &str = "\
+usage:
+  oxr-weights import <checkpoint.safetensors|.onnx> [-o aa_weights.bin]
+                     [--model-version V] [--strip PREFIX]... [--rename FROM=TO]...
+  oxr-weights convert <legacy.bin> [-o aa_weights.bin] [--model-version V]
+  oxr-weights quantize <aa_weights.bin> --dtype f16|i8 [-o out.bin]
+                     [--frames DIR] [--max-frames N] [--crop WxH]
+  oxr-weights inspect <weights.bin>
+  oxr-weights layers
+
+common options:
+  --graph FILE   network description (default: built-in v0)
+  -v             list renames, fusions, reshapes and ignored tensors
+
+quantize options:
+  --frames DIR       recording session to calibrate int8 clipping on and to
+                     measure the output error with (default: no calibration)
+  --max-frames N     frames used from the session (default 8)
+  --crop WxH         centre crop of each frame (default 256x144)";
+
+struct Args {
+    command: String,
+    input: Option<PathBuf>,
+    output: PathBuf,
+    graph: Option<PathBuf>,
+    model_version: Option<String>,
+    opts: import::Options,
+    verbose: bool,
+    dtype: Option<WeightDtype>,
+    frames: Option<PathBuf>,
+    max_frames: usize,
+    crop: (u32, u32),
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut it = std::env::args().skip(1);
+    let command = it.next().ok_or("missing command")?;
+    let mut args = Args {
+        command,
+        input: None,
+        output: PathBuf::from("aa_weights.bin"),
+        graph: None,
+        model_version: None,
+        opts: import::Options {
+            strip: import::DEFAULT_STRIP
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            renames: Vec::new(),
+        },
+        verbose: false,
+        dtype: None,
+        frames: None,
+        max_frames: 8,
+        crop: (256, 144),
+    };
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "-o" | "--output" => args.output = value()?.into(),
+            "--graph" => args.graph = Some(value()?.into()),
+            "--model-version" => args.model_version = Some(value()?),
+            "--strip" => args.opts.strip.push(value()?),
+            "--rename" => {
+                let v = value()?;
+                let (from, to) = v
+                    .split_once('=')
+                    .ok_or(format!("--rename expects FROM=TO, got '{}'", v))?;
+                args.opts.renames.push((from.to_string(), to.to_string()));
+            }
+            "--dtype" => {
+                let v = value()?;
+                args.dtype =
+                    Some(WeightDtype::from_name(&v).ok_or(format!("unknown dtype '{}'", v))?);
+            }
+            "--frames" => args.frames = Some(value()?.into()),
+            "--max-frames" => {
+                let v = value()?;
+                args.max_frames = v
+                    .parse()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or(format!("--max-frames expects a count, got '{}'", v))?;
+            }
+            "--crop" => {
+                let v = value()?;
+                args.crop = v
+                    .split_once('x')
+                    .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                    .ok_or(format!("--crop expects WxH, got '{}'", v))?;
+            }
+            "-v" | "--verbose" => args.verbose = true,
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+            _ if args.input.is_none() => args.input = Some(arg.into()),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+    Ok(args)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(a) => a,
+        Err(e) => {
+            if !e.is_empty() {
+                eprintln!("error: {}\n", e);
+            }
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    let graph = match &args.graph {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+            Graph::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?
+        }
+        None => Graph::builtin(),
+    };
+    let input = || {
+        args.input
+            .as_deref()
+            .ok_or("missing input file".to_string())
+    };
+
+    match args.command.as_str() {
+        "import" => {
+            let path = input()?;
+            let ckpt = checkpoint::load(path)?;
+            let version = args
+                .model_version
+                .clone()
+                .or_else(|| ckpt.metadata("model_version").map(str::to_string))
+                .or_else(|| ckpt.metadata("version").map(str::to_string))
+                .unwrap_or_else(|| file_stem(path));
+            let count = ckpt.tensors.len();
+            let result = import::import(ckpt, &graph, &version, &args.opts)?;
+            if args.verbose {
+                for n in &result.notes {
+                    println!("  {}", n);
+                }
+                for n in &result.ignored {
+                    println!("  ignored '{}'", n);
+                }
+            }
+            println!(
+                "{}: {} tensors, {} used, {} ignored",
+                path.display(),
+                count,
+                result.file.tensors.len(),
+                result.ignored.len()
+            );
+            write(&args.output, &result.file)
+        }
+        "convert" => {
+            let path = input()?;
+            let raw = std::fs::read(path)
+                .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+            if WeightFile::is_container(&raw) {
+                return Err(format!("{} is already a weight container", path.display()));
+            }
+            let version = args
+                .model_version
+                .clone()
+                .unwrap_or_else(|| "legacy".into());
+            let file = WeightFile::from_legacy(&raw, &graph.weights, &graph.model_name, &version)?;
+            write(&args.output, &file)
+        }
+        "quantize" => {
+            let path = input()?;
+            let dtype = args.dtype.ok_or("quantize needs --dtype f16|i8")?;
+            if dtype == WeightDtype::F32 {
+                return Err("nothing to do for --dtype f32".into());
+            }
+            let bytes = std::fs::read(path)
+                .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+            let file = parse_weights(&bytes, &graph)?;
+            let session = match &args.frames {
+                Some(dir) => {
+                    let (frames, size) = frames::load_session(dir, args.max_frames, args.crop)?;
+                    println!(
+                        "{}: {} frames, cropped to {}x{}",
+                        dir.display(),
+                        frames.len(),
+                        size.0,
+                        size.1
+                    );
+                    Some((frames, size))
+                }
+                None => None,
+            };
+            let frames = session.as_ref().map(|(frames, size)| quantize::Frames {
+                frames,
+                size: *size,
+            });
+            if frames.is_some() && dtype == WeightDtype::I8 {
+                println!("calibrating int8 clipping:");
+            } else if dtype == WeightDtype::I8 {
+                println!("no --frames given, int8 scales use each channel's full range");
+            }
+            let quantized =
+                quantize::quantize(&file, &graph, dtype, frames.as_ref(), &mut |line| {
+                    println!("{}", line)
+                })?;
+            for line in quantize::report(&file, &quantized, &graph, frames.as_ref())? {
+                println!("{}", line);
+            }
+            write(&args.output, &quantized)
+        }
+        "inspect" => {
+            let path = input()?;
+            let bytes = std::fs::read(path)
+                .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+            let file = parse_weights(&bytes, &graph)?;
+            println!(
+                "model '{}' version '{}', {:?}, {} values",
+                file.model_name,
+                file.model_version,
+                file.dtype,
+                file.data.len()
+            );
+            for t in &file.tensors {
+                println!("  {:>8}  {:<40} {:?}", t.offset, t.name, t.shape);
+            }
+            match pack_weights(&file, &graph) {
+                Ok(_) => println!("matches network '{}'", graph.model_name),
+                Err(e) => println!("does not match network '{}': {}", graph.model_name, e),
+            }
+            Ok(())
+        }
+        "layers" => {
+            println!("network '{}'", graph.model_name);
+            for t in &graph.weights {
+                let legacy = import::LEGACY_NAMES
+                    .iter()
+                    .find(|(_, name)| *name == t.name)
+                    .map_or("", |(legacy, _)| legacy);
+                println!(
+                    "  {:>8}  {:<40} {:<18} {}",
+                    t.offset,
+                    t.name,
+                    format!("{:?}", t.shape),
+                    legacy
+                );
+            }
+            println!("{} values", graph.weight_count());
+            Ok(())
+        }
+        other => Err(format!("unknown command '{}'", other)),
+    }
+}
+
+fn write(path: &Path, file: &WeightFile) -> Result<(), String> {
+    std::fs::write(path, file.to_bytes())
+        .map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+    println!(
+        "wrote {} ('{}' version '{}', {} tensors, {} values)",
+        path.display(),
+        file.model_name,
+        file.model_version,
+        file.tensors.len(),
+        file.data.len()
+    );
+    Ok(())
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("unknown")
+        .to_string()
+}
@@ -0,0 +1,410 @@
+// Restricted ONNX: only the graph's initializers are read, which is where
+// `torch.onnx.export` puts parameters. Supported are float, float16, bfloat16
+// and double tensors stored inline (raw_data or the typed repeated fields);
+// external data and sparse initializers are rejected. Nodes are ignored, so a
+// model whose exporter folded parameters into other constants will show up as
+// missing tensors.
+
+use fsr_sys::weights::f16_to_f32;
+
+use crate::checkpoint::{element_count, Checkpoint, Dtype, Tensor};
+
+// Field numbers from onnx.proto3.
+const MODEL_PRODUCER_NAME: u32 = 2;
+const MODEL_PRODUCER_VERSION: u32 = 3;
+const MODEL_GRAPH: u32 = 7;
+const MODEL_METADATA_PROPS: u32 = 14;
+const GRAPH_NAME: u32 = 2;
+const GRAPH_INITIALIZER: u32 = 5;
+const GRAPH_SPARSE_INITIALIZER: u32 = 15;
+const STRING_ENTRY_KEY: u32 = 1;
+const STRING_ENTRY_VALUE: u32 = 2;
+const TENSOR_DIMS: u32 = 1;
+const TENSOR_DATA_TYPE: u32 = 2;
+const TENSOR_FLOAT_DATA: u32 = 4;
+const TENSOR_INT32_DATA: u32 = 5;
+const TENSOR_NAME: u32 = 8;
+const TENSOR_RAW_DATA: u32 = 9;
+const TENSOR_DOUBLE_DATA: u32 = 10;
+const TENSOR_EXTERNAL_DATA: u32 = 13;
+const TENSOR_DATA_LOCATION: u32 = 14;
+
+// TensorProto.DataType
+const TYPE_FLOAT: u64 = 1;
+const TYPE_FLOAT16: u64 = 10;
+const TYPE_DOUBLE: u64 = 11;
+const TYPE_BFLOAT16: u64 = 16;
+
+pub fn parse(bytes: &[u8]) -> Result<Checkpoint, String> {
+    let mut ckpt = Checkpoint::default();
+    let mut graph = None;
+    for field in Fields::new(bytes) {
+        let (num, value) = field?;
+        match (num, value) {
+            (MODEL_GRAPH, Wire::Bytes(b)) => graph = Some(b),
+            (MODEL_PRODUCER_NAME, Wire::Bytes(b)) => {
+                ckpt.metadata.push(("producer".into(), utf8(b)?));
+            }
+            (MODEL_PRODUCER_VERSION, Wire::Bytes(b)) => {
+                ckpt.metadata.push(("producer_version".into(), utf8(b)?));
+            }
+            (MODEL_METADATA_PROPS, Wire::Bytes(b)) => {
+                let (mut key, mut val) = (String::new(), String::new());
+                for field in Fields::new(b) {
+                    match field? {
+                        (STRING_ENTRY_KEY, Wire::Bytes(k)) => key = utf8(k)?,
+                        (STRING_ENTRY_VALUE, Wire::Bytes(v)) => val = utf8(v)?,
+                        _ => {}
+                    }
+                }
+                ckpt.metadata.push((key, val));
+            }
+            _ => {}
+        }
+    }
+    let graph = graph.ok_or("not an ONNX model (no graph)")?;
+
+    for field in Fields::new(graph) {
+        match field? {
+            (GRAPH_INITIALIZER, Wire::Bytes(b)) => ckpt.tensors.push(tensor(b)?),
+            (GRAPH_SPARSE_INITIALIZER, _) => {
+                return Err("sparse initializers are not supported".into())
+            }
+            (GRAPH_NAME, Wire::Bytes(b)) => ckpt.metadata.push(("graph".into(), utf8(b)?)),
+            _ => {}
+        }
+    }
+    Ok(ckpt)
+}
+
+fn tensor(bytes: &[u8]) -> Result<Tensor, String> {
+    let mut name = String::new();
+    let mut dims = Vec::new();
+    let mut data_type = 0;
+    let mut raw = None;
+    let mut floats = Vec::new();
+    let mut halves = Vec::new();
+    let mut doubles = Vec::new();
+    let mut external = false;
+
+    for field in Fields::new(bytes) {
+        match field? {
+            (TENSOR_NAME, Wire::Bytes(b)) => name = utf8(b)?,
+            (TENSOR_DATA_TYPE, Wire::Varint(v)) => data_type = v,
+            (TENSOR_DIMS, Wire::Varint(v)) => dims.push(v),
+            (TENSOR_DIMS, Wire::Bytes(b)) => dims.extend(packed_varints(b)?),
+            (TENSOR_RAW_DATA, Wire::Bytes(b)) => raw = Some(b),
+            (TENSOR_FLOAT_DATA, Wire::Fixed32(v)) => floats.push(f32::from_bits(v)),
+            (TENSOR_FLOAT_DATA, Wire::Bytes(b)) => floats.extend(Dtype::F32.decode(b)),
+            (TENSOR_DOUBLE_DATA, Wire::Fixed64(v)) => doubles.push(f64::from_bits(v) as f32),
+            (TENSOR_DOUBLE_DATA, Wire::Bytes(b)) => doubles.extend(Dtype::F64.decode(b)),
+            // float16 / bfloat16 are stored one per int32 when not raw.
+            (TENSOR_INT32_DATA, Wire::Varint(v)) => halves.push(v as u16),
+            (TENSOR_INT32_DATA, Wire::Bytes(b)) => {
+                halves.extend(packed_varints(b)?.into_iter().map(|v| v as u16))
+            }
+            (TENSOR_EXTERNAL_DATA, _) => external = true,
+            (TENSOR_DATA_LOCATION, Wire::Varint(1)) => external = true,
+            _ => {}
+        }
+    }
+
+    let fail = |e: String| format!("initializer '{}': {}", name, e);
+    if external {
+        return Err(fail(
+            "external data is not supported; re-export with the weights embedded".into(),
+        ));
+    }
+    let shape = dims
+        .iter()
+        .map(|&d| u32::try_from(d).ok())
+        .collect::<Option<Vec<u32>>>()
+        .ok_or_else(|| fail("invalid dims".into()))?;
+    let count = element_count(&shape).ok_or_else(|| fail("shape overflows".into()))?;
+
+    let dtype = match data_type {
+        TYPE_FLOAT => Dtype::F32,
+        TYPE_FLOAT16 => Dtype::F16,
+        TYPE_BFLOAT16 => Dtype::BF16,
+        TYPE_DOUBLE => Dtype::F64,
+        other => return Err(fail(format!("unsupported data type {}", other))),
+    };
+    let data = match (raw, dtype) {
+        (Some(raw), _) => {
+            if count.checked_mul(dtype.size()) != Some(raw.len()) {
+                return Err(fail(format!(
+                    "{} bytes of raw data for shape {:?}",
+                    raw.len(),
+                    shape
+                )));
+            }
+            dtype.decode(raw)
+        }
+        (None, Dtype::F32) => floats,
+        (None, Dtype::F64) => doubles,
+        (None, Dtype::F16) => halves.into_iter().map(f16_to_f32).collect(),
+        (None, Dtype::BF16) => halves
+            .into_iter()
+            .map(|h| f32::from_bits((h as u32) << 16))
+            .collect(),
+    };
+    if data.len() != count {
+        return Err(fail(format!("{} values for shape {:?}", data.len(), shape)));
+    }
+    Ok(Tensor { name, shape, data })
+}
+
+fn utf8(b: &[u8]) -> Result<String, String> {
+    String::from_utf8(b.to_vec()).map_err(|_| "invalid UTF-8 string".to_string())
+}
+
+fn packed_varints(mut b: &[u8]) -> Result<Vec<u64>, String> {
+    let mut out = Vec::new();
+    while !b.is_empty() {
+        out.push(varint(&mut b)?);
+    }
+    Ok(out)
+}
+
+fn varint(b: &mut &[u8]) -> Result<u64, String> {
+    let mut v = 0u64;
+    for i in 0..10 {
+        let (&byte, rest) = b.split_first().ok_or("truncated varint")?;
+        *b = rest;
+        v |= ((byte & 0x7F) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    Err("varint too long".into())
+}
+
+/// A protobuf field's payload by wire type.
+enum Wire<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+/// Iterator over the top-level fields of a protobuf message.
+struct Fields<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { rest: bytes }
+    }
+
+    fn next_field(&mut self) -> Result<(u32, Wire<'a>), String> {
+        let key = varint(&mut self.rest)?;
+        let num = (key >> 3) as u32;
+        let value = match key & 7 {
+            0 => Wire::Varint(varint(&mut self.rest)?),
+            1 => Wire::Fixed64(u64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+            2 => {
+                let len = varint(&mut self.rest)?;
+                let len = usize::try_from(len).map_err(|_| "length overflows".to_string())?;
+                Wire::Bytes(self.take(len)?)
+            }
+            5 => Wire::Fixed32(u32::from_le_bytes(self.take(4)?.try_into().unwrap())),
+            w => return Err(format!("unsupported wire type {} (field {})", w, num)),
+        };
+        Ok((num, value))
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if n > self.rest.len() {
+            return Err("truncated message".into());
+        }
+        let (head, tail) = self.rest.split_at(n);
+        self.rest = tail;
+        Ok(head)
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<(u32, Wire<'a>), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        let field = self.next_field();
+        if field.is_err() {
+            self.rest = &[];
+        }
+        Some(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_varint(mut v: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        loop {
+            let byte = (v & 0x7F) as u8;
+            v >>= 7;
+            if v == 0 {
+                out.push(byte);
+                return out;
+            }
+            out.push(byte | 0x80);
+        }
+    }
+
+    /// A length-delimited field.
+    fn bytes_field(num: u32, payload: &[u8]) -> Vec<u8> {
+        let mut out = encode_varint((num as u64) << 3 | 2);
+        out.extend(encode_varint(payload.len() as u64));
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn varint_field(num: u32, v: u64) -> Vec<u8> {
+        let mut out = encode_varint((num as u64) << 3);
+        out.extend(encode_varint(v));
+        out
+    }
+
+    /// A ModelProto with `initializers` (TensorProto bytes) in its graph.
+    fn model(initializers: &[Vec<u8>]) -> Vec<u8> {
+        let mut graph = bytes_field(GRAPH_NAME, b"main");
+        for t in initializers {
+            graph.extend(bytes_field(GRAPH_INITIALIZER, t));
+        }
+        let mut props = bytes_field(STRING_ENTRY_KEY, b"epoch");
+        props.extend(bytes_field(STRING_ENTRY_VALUE, b"12"));
+        let mut out = bytes_field(MODEL_PRODUCER_NAME, b"pytorch");
+        out.extend(bytes_field(MODEL_PRODUCER_VERSION, b"2.1"));
+        out.extend(bytes_field(MODEL_METADATA_PROPS, &props));
+        out.extend(bytes_field(MODEL_GRAPH, &graph));
+        out
+    }
+
+    /// A TensorProto header: name, dims (unpacked) and data type.
+    fn tensor_header(name: &str, dims: &[u64], data_type: u64) -> Vec<u8> {
+        let mut out = bytes_field(TENSOR_NAME, name.as_bytes());
+        for &d in dims {
+            out.extend(varint_field(TENSOR_DIMS, d));
+        }
+        out.extend(varint_field(TENSOR_DATA_TYPE, data_type));
+        out
+    }
+
+    #[test]
+    fn reads_initializers_of_every_dtype() {
+        // float as raw_data
+        let mut raw = tensor_header("raw", &[2, 1], TYPE_FLOAT);
+        let floats: Vec<u8> = [1.0f32, -2.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        raw.extend(bytes_field(TENSOR_RAW_DATA, &floats));
+
+        // float as packed float_data, dims packed
+        let mut packed = bytes_field(TENSOR_NAME, b"packed");
+        packed.extend(bytes_field(TENSOR_DIMS, &[3]));
+        packed.extend(varint_field(TENSOR_DATA_TYPE, TYPE_FLOAT));
+        let floats: Vec<u8> = [0.5f32, 1.5, 2.5]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        packed.extend(bytes_field(TENSOR_FLOAT_DATA, &floats));
+
+        // float16 one per int32_data, unpacked and packed
+        let mut half = tensor_header("half", &[2], TYPE_FLOAT16);
+        half.extend(varint_field(TENSOR_INT32_DATA, 0x3C00));
+        half.extend(bytes_field(TENSOR_INT32_DATA, &encode_varint(0xC000)));
+
+        // bfloat16 in int32_data
+        let mut bf16 = tensor_header("bf16", &[1], TYPE_BFLOAT16);
+        bf16.extend(varint_field(TENSOR_INT32_DATA, 0x4040));
+
+        // double as packed double_data
+        let mut double = tensor_header("double", &[], TYPE_DOUBLE);
+        double.extend(bytes_field(TENSOR_DOUBLE_DATA, &0.25f64.to_le_bytes()));
+
+        let ckpt = parse(&model(&[raw, packed, half, bf16, double])).unwrap();
+        let tensors: Vec<_> = ckpt
+            .tensors
+            .iter()
+            .map(|t| (t.name.as_str(), t.shape.clone(), t.data.clone()))
+            .collect();
+        assert_eq!(
+            tensors,
+            [
+                ("raw", vec![2, 1], vec![1.0, -2.0]),
+                ("packed", vec![3], vec![0.5, 1.5, 2.5]),
+                ("half", vec![2], vec![1.0, -2.0]),
+                ("bf16", vec![1], vec![3.0]),
+                ("double", vec![], vec![0.25]),
+            ]
+        );
+        assert_eq!(ckpt.metadata("producer"), Some("pytorch"));
+        assert_eq!(ckpt.metadata("producer_version"), Some("2.1"));
+        assert_eq!(ckpt.metadata("epoch"), Some("12"));
+        assert_eq!(ckpt.metadata("graph"), Some("main"));
+    }
+
+    #[test]
+    fn rejects_unsupported_initializers() {
+        let mut external = tensor_header("ext", &[1], TYPE_FLOAT);
+        external.extend(varint_field(TENSOR_DATA_LOCATION, 1));
+        let mut short = tensor_header("short", &[3], TYPE_FLOAT);
+        short.extend(bytes_field(TENSOR_RAW_DATA, &[0; 8]));
+        let mut missing = tensor_header("missing", &[2], TYPE_FLOAT16);
+        missing.extend(varint_field(TENSOR_INT32_DATA, 0x3C00));
+        let int32 = tensor_header("int32", &[1], 6);
+        let wide = tensor_header("wide", &[1 << 32], TYPE_FLOAT);
+        for (tensor, error) in [
+            (
+                external,
+                "initializer 'ext': external data is not supported",
+            ),
+            (
+                short,
+                "initializer 'short': 8 bytes of raw data for shape [3]",
+            ),
+            (missing, "initializer 'missing': 1 values for shape [2]"),
+            (int32, "initializer 'int32': unsupported data type 6"),
+            (wide, "initializer 'wide': invalid dims"),
+        ] {
+            let e = parse(&model(&[tensor])).unwrap_err();
+            assert!(e.starts_with(error), "{}", e);
+        }
+
+        let sparse = bytes_field(MODEL_GRAPH, &bytes_field(GRAPH_SPARSE_INITIALIZER, &[]));
+        assert_eq!(
+            parse(&sparse).unwrap_err(),
+            "sparse initializers are not supported"
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_messages() {
+        assert_eq!(
+            parse(&bytes_field(MODEL_PRODUCER_NAME, b"x")).unwrap_err(),
+            "not an ONNX model (no graph)"
+        );
+
+        let mut raw = tensor_header("w", &[1], TYPE_FLOAT);
+        raw.extend(bytes_field(TENSOR_RAW_DATA, &1.0f32.to_le_bytes()));
+        let bytes = model(&[raw]);
+        assert!(parse(&bytes).is_ok());
+        assert_eq!(
+            parse(&bytes[..bytes.len() - 1]).unwrap_err(),
+            "truncated message"
+        );
+        assert_eq!(parse(&[0x80]).unwrap_err(), "truncated varint");
+        assert_eq!(parse(&[0xFF; 11]).unwrap_err(), "varint too long");
+        // Wire type 3 (start group)
+        assert_eq!(
+            parse(&[0x0B]).unwrap_err(),
+            "unsupported wire type 3 (field 1)"
+        );
+    }
+}
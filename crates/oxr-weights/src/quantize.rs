@@ -0,0 +1,187 @@
+// F16 / I8 weights, calibrated and measured with the CPU reference.
+//
+// I8 kernels use one symmetric scale per output channel. Calibration picks,
+// kernel by kernel in buffer order, the clip ratio of that range (a fraction
+// of the channel's largest magnitude) that minimises the output error on the
+// recorded frames, the other kernels quantised with their current choice.
+// The accuracy report runs the frames through the f32 and quantised weights
+// and compares the outputs.
+
+use fsr_sys::imba::cpu::AAReference;
+use fsr_sys::imba::graph::Graph;
+use fsr_sys::imba::pack_weights;
+use fsr_sys::weights::{WeightDtype, WeightFile};
+
+use crate::frames::Frame;
+
+/// Clip ratios tried per I8 kernel.
+pub const CLIP_RATIOS: &[f32] = &[1.0, 0.95, 0.9, 0.85, 0.8, 0.7];
+
+/// Recorded frames at one size.
+pub struct Frames<'a> {
+    pub frames: &'a [Frame],
+    pub size: (u32, u32),
+}
+
+/// Quantise `file` to `dtype`; with frames, I8 clip ratios are calibrated and
+/// each choice is passed to `log`.
+pub fn quantize(
+    file: &WeightFile,
+    graph: &Graph,
+    dtype: WeightDtype,
+    frames: Option<&Frames>,
+    log: &mut dyn FnMut(String),
+) -> Result<WeightFile, String> {
+    let mut ratios = vec![1.0f32; file.tensors.len()];
+    let quantize_with = |ratios: &[f32]| file.quantize(dtype, |i, max| max * ratios[i]);
+
+    if let (WeightDtype::I8, Some(frames)) = (dtype, frames) {
+        let reference = run(graph, pack_weights(file, graph)?, frames)?;
+        for (i, t) in file.tensors.iter().enumerate() {
+            if dtype.storage(&t.shape) != WeightDtype::I8 {
+                continue;
+            }
+            let mut best = (f64::INFINITY, 1.0);
+            for &r in CLIP_RATIOS {
+                ratios[i] = r;
+                let q = quantize_with(&ratios)?;
+                let err = mse(&reference, &run(graph, pack_weights(&q, graph)?, frames)?);
+                if err < best.0 {
+                    best = (err, r);
+                }
+            }
+            ratios[i] = best.1;
+            log(format!(
+                "  {:<40} clip {:.2}  mse {:.3e}",
+                t.name, best.1, best.0
+            ));
+        }
+    }
+    quantize_with(&ratios)
+}
+
+/// Outputs (RGB per pixel) of every frame, each run with the one before as
+/// the previous frame; the first is its own previous.
+pub fn run(
+    graph: &Graph,
+    weights: Vec<f32>,
+    frames: &Frames,
+) -> Result<Vec<Vec<[f32; 3]>>, String> {
+    let mut aa = AAReference::new(frames.size.0, frames.size.1, graph, weights)?;
+    let mut outputs = Vec::with_capacity(frames.frames.len());
+    for (i, frame) in frames.frames.iter().enumerate() {
+        let prev = &frames.frames[i.saturating_sub(1)];
+        let out = aa.run(&frame.inputs(), &prev.inputs())?;
+        outputs.push(out.iter().map(|p| [p[0], p[1], p[2]]).collect());
+    }
+    Ok(outputs)
+}
+
+fn mse(a: &[Vec<[f32; 3]>], b: &[Vec<[f32; 3]>]) -> f64 {
+    let (mut sum, mut n) = (0.0f64, 0usize);
+    for (fa, fb) in a.iter().zip(b) {
+        for (pa, pb) in fa.iter().zip(fb) {
+            for c in 0..3 {
+                let d = (pa[c] - pb[c]) as f64;
+                sum += d * d;
+            }
+            n += 3;
+        }
+    }
+    sum / n.max(1) as f64
+}
+
+/// Output error of `quantized` against `reference` over `frames`, one line
+/// per frame and a total, plus the weight error of each stored kernel.
+pub fn report(
+    reference: &WeightFile,
+    quantized: &WeightFile,
+    graph: &Graph,
+    frames: Option<&Frames>,
+) -> Result<Vec<String>, String> {
+    let mut lines = Vec::new();
+    lines.push(format!("weights ({:?} kernels):", quantized.dtype));
+    for t in &quantized.tensors {
+        if quantized.dtype.storage(&t.shape) == WeightDtype::F32 {
+            continue;
+        }
+        let (a, b) = (
+            reference.tensor(&t.name).unwrap_or_default(),
+            quantized.tensor(&t.name).unwrap_or_default(),
+        );
+        let max = a
+            .iter()
+            .zip(b)
+            .map(|(x, y)| (x - y).abs())
+            .fold(0.0, f32::max);
+        let rms = |v: &mut dyn Iterator<Item = f32>| {
+            let (s, n) = v.fold((0.0f64, 0), |(s, n), x| (s + (x as f64).powi(2), n + 1));
+            (s / n.max(1) as f64).sqrt()
+        };
+        let err = rms(&mut a.iter().zip(b).map(|(x, y)| x - y));
+        let mag = rms(&mut a.iter().copied());
+        lines.push(format!(
+            "  {:<40} max err {:.3e}  rel rms {:.3e}",
+            t.name,
+            max,
+            if mag > 0.0 { err / mag } else { 0.0 }
+        ));
+    }
+
+    let Some(frames) = frames else {
+        lines.push("no frames given, output accuracy not measured".into());
+        return Ok(lines);
+    };
+    let a = run(graph, pack_weights(reference, graph)?, frames)?;
+    let b = run(graph, pack_weights(quantized, graph)?, frames)?;
+    lines.push(format!(
+        "output vs f32 ({} frames at {}x{}; PSNR for a peak of 1.0):",
+        frames.frames.len(),
+        frames.size.0,
+        frames.size.1
+    ));
+    lines.push(format!(
+        "  {:<28} {:>10} {:>10} {:>8}",
+        "frame", "max abs", "mean abs", "PSNR"
+    ));
+    let (mut max_all, mut abs_all, mut sq_all, mut n_all) = (0.0f32, 0.0f64, 0.0f64, 0usize);
+    for ((fa, fb), frame) in a.iter().zip(&b).zip(frames.frames) {
+        let (mut max, mut abs, mut sq, mut n) = (0.0f32, 0.0f64, 0.0f64, 0usize);
+        for (pa, pb) in fa.iter().zip(fb) {
+            for c in 0..3 {
+                let d = (pa[c] - pb[c]).abs();
+                max = max.max(d);
+                abs += d as f64;
+                sq += (d as f64).powi(2);
+                n += 1;
+            }
+        }
+        lines.push(format!(
+            "  {:<28} {:>10.3e} {:>10.3e} {:>8.2}",
+            frame.name,
+            max,
+            abs / n as f64,
+            psnr(sq / n as f64)
+        ));
+        max_all = max_all.max(max);
+        abs_all += abs;
+        sq_all += sq;
+        n_all += n;
+    }
+    lines.push(format!(
+        "  {:<28} {:>10.3e} {:>10.3e} {:>8.2}",
+        "all",
+        max_all,
+        abs_all / n_all.max(1) as f64,
+        psnr(sq_all / n_all.max(1) as f64)
+    ));
+    Ok(lines)
+}
+
+fn psnr(mse: f64) -> f64 {
+    if mse > 0.0 {
+        -10.0 * mse.log10()
+    } else {
+        f64::INFINITY
+    }
+}
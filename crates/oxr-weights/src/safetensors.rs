@@ -0,0 +1,224 @@
+// safetensors: u64 little-endian header length, a JSON header mapping tensor
+// names to {dtype, shape, data_offsets}, then the raw data. Offsets are
+// relative to the start of the data. `__metadata__` is a string map.
+
+use crate::checkpoint::{element_count, Checkpoint, Dtype, Tensor};
+use crate::json::{self, Value};
+
+/// Header sizes above this are treated as "not a safetensors file".
+const MAX_HEADER: u64 = 100 << 20;
+
+/// Whether `bytes` plausibly starts with a safetensors header.
+pub fn sniff(bytes: &[u8]) -> bool {
+    bytes.len() >= 9
+        && u64::from_le_bytes(bytes[..8].try_into().unwrap()) <= MAX_HEADER
+        && bytes[8] == b'{'
+}
+
+pub fn parse(bytes: &[u8]) -> Result<Checkpoint, String> {
+    if bytes.len() < 8 {
+        return Err("truncated safetensors header".into());
+    }
+    let header_len = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+    if header_len > MAX_HEADER || header_len > (bytes.len() - 8) as u64 {
+        return Err(format!("invalid safetensors header length {}", header_len));
+    }
+    let (header, data) = bytes[8..].split_at(header_len as usize);
+    let header = std::str::from_utf8(header).map_err(|_| "header is not UTF-8".to_string())?;
+    let Value::Object(entries) = json::parse(header)? else {
+        return Err("header is not a JSON object".into());
+    };
+
+    let mut ckpt = Checkpoint::default();
+    for (name, entry) in entries {
+        if name == "__metadata__" {
+            if let Value::Object(fields) = entry {
+                for (k, v) in fields {
+                    if let Some(v) = v.as_str() {
+                        ckpt.metadata.push((k, v.to_string()));
+                    }
+                }
+            }
+            continue;
+        }
+        let tensor =
+            tensor(&name, &entry, data).map_err(|e| format!("tensor '{}': {}", name, e))?;
+        ckpt.tensors.push(tensor);
+    }
+    Ok(ckpt)
+}
+
+fn tensor(name: &str, entry: &Value, data: &[u8]) -> Result<Tensor, String> {
+    let dtype = entry
+        .get("dtype")
+        .and_then(Value::as_str)
+        .ok_or("missing dtype")?;
+    let dtype = match dtype {
+        "F32" => Dtype::F32,
+        "F16" => Dtype::F16,
+        "BF16" => Dtype::BF16,
+        "F64" => Dtype::F64,
+        other => return Err(format!("unsupported dtype {}", other)),
+    };
+    let shape = entry
+        .get("shape")
+        .and_then(Value::as_array)
+        .ok_or("missing shape")?
+        .iter()
+        .map(|d| d.as_u64().and_then(|d| u32::try_from(d).ok()))
+        .collect::<Option<Vec<u32>>>()
+        .ok_or("invalid shape")?;
+    let offsets = entry
+        .get("data_offsets")
+        .and_then(Value::as_array)
+        .ok_or("missing data_offsets")?;
+    let [begin, end] = offsets else {
+        return Err("data_offsets must have two entries".into());
+    };
+    let (Some(begin), Some(end)) = (begin.as_u64(), end.as_u64()) else {
+        return Err("invalid data_offsets".into());
+    };
+    if begin > end || end > data.len() as u64 {
+        return Err(format!(
+            "data [{}..{}) outside the {}-byte buffer",
+            begin,
+            end,
+            data.len()
+        ));
+    }
+    let count = element_count(&shape).ok_or("shape overflows")?;
+    if count.checked_mul(dtype.size()) != Some((end - begin) as usize) {
+        return Err(format!(
+            "{} bytes of data for shape {:?} ({:?})",
+            end - begin,
+            shape,
+            dtype
+        ));
+    }
+    Ok(Tensor {
+        name: name.to_string(),
+        shape,
+        data: dtype.decode(&data[begin as usize..end as usize]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A safetensors file: the header's length, the header, then `data`.
+    fn file(header: &str, data: &[u8]) -> Vec<u8> {
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn le<T: Copy, const N: usize>(values: &[T], to_bytes: fn(T) -> [u8; N]) -> Vec<u8> {
+        values.iter().flat_map(|&v| to_bytes(v)).collect()
+    }
+
+    #[test]
+    fn maps_dtypes_and_shapes() {
+        let mut data = le(&[1.0f32, -2.0], f32::to_le_bytes);
+        data.extend(le(&[0x3C00u16, 0xC000], u16::to_le_bytes)); // 1, -2
+        data.extend(le(&[0x3F80u16, 0x4040], u16::to_le_bytes)); // 1, 3
+        data.extend(le(&[0.5f64], f64::to_le_bytes));
+        let header = r#"{
+            "__metadata__": {"format": "pt", "epochs": 3},
+            "a": {"dtype": "F32", "shape": [2, 1], "data_offsets": [0, 8]},
+            "b": {"dtype": "F16", "shape": [2], "data_offsets": [8, 12]},
+            "c": {"dtype": "BF16", "shape": [1, 2], "data_offsets": [12, 16]},
+            "d": {"dtype": "F64", "shape": [], "data_offsets": [16, 24]}
+        }"#;
+        let bytes = file(header, &data);
+        assert!(sniff(&bytes));
+
+        let ckpt = parse(&bytes).unwrap();
+        let tensors: Vec<_> = ckpt
+            .tensors
+            .iter()
+            .map(|t| (t.name.as_str(), t.shape.clone(), t.data.clone()))
+            .collect();
+        assert_eq!(
+            tensors,
+            [
+                ("a", vec![2, 1], vec![1.0, -2.0]),
+                ("b", vec![2], vec![1.0, -2.0]),
+                ("c", vec![1, 2], vec![1.0, 3.0]),
+                ("d", vec![], vec![0.5]),
+            ]
+        );
+        // Only string metadata is kept
+        assert_eq!(ckpt.metadata, [("format".into(), "pt".into())]);
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        assert_eq!(parse(&[0; 7]).unwrap_err(), "truncated safetensors header");
+        assert!(!sniff(&[0; 7]));
+
+        // Longer than the file, and past the limit
+        let mut bytes = file("{}", &[]);
+        bytes[0] = 3;
+        assert!(parse(&bytes).unwrap_err().contains("header length 3"));
+        let huge = (MAX_HEADER + 1).to_le_bytes();
+        assert!(parse(&huge)
+            .unwrap_err()
+            .contains("invalid safetensors header length"));
+        assert!(!sniff(&file("[]", &[])));
+
+        assert_eq!(
+            parse(&file("[]", &[])).unwrap_err(),
+            "header is not a JSON object"
+        );
+        assert!(parse(&file("{\"a\":", &[]))
+            .unwrap_err()
+            .starts_with("JSON:"));
+        let mut bytes = file("{\"\"}", &[]);
+        bytes[10] = 0xFF;
+        assert_eq!(parse(&bytes).unwrap_err(), "header is not UTF-8");
+    }
+
+    #[test]
+    fn rejects_bad_tensors() {
+        let data = [0u8; 8];
+        for (entry, error) in [
+            (r#"{"shape": [2], "data_offsets": [0, 8]}"#, "missing dtype"),
+            (
+                r#"{"dtype": "I8", "shape": [8], "data_offsets": [0, 8]}"#,
+                "unsupported dtype I8",
+            ),
+            (
+                r#"{"dtype": "F32", "data_offsets": [0, 8]}"#,
+                "missing shape",
+            ),
+            (
+                r#"{"dtype": "F32", "shape": [-2], "data_offsets": [0, 8]}"#,
+                "invalid shape",
+            ),
+            (r#"{"dtype": "F32", "shape": [2]}"#, "missing data_offsets"),
+            (
+                r#"{"dtype": "F32", "shape": [2], "data_offsets": [0]}"#,
+                "two entries",
+            ),
+            (
+                r#"{"dtype": "F32", "shape": [2], "data_offsets": [0, 12]}"#,
+                "data [0..12) outside the 8-byte buffer",
+            ),
+            (
+                r#"{"dtype": "F32", "shape": [3], "data_offsets": [0, 8]}"#,
+                "8 bytes of data for shape [3] (F32)",
+            ),
+            (
+                r#"{"dtype": "F32", "shape": [4294967295, 4294967295, 4294967295], "data_offsets": [0, 8]}"#,
+                "shape overflows",
+            ),
+        ] {
+            let header = format!("{{\"w\": {}}}", entry);
+            let e = parse(&file(&header, &data)).unwrap_err();
+            assert!(e.starts_with("tensor 'w': "), "{}", e);
+            assert!(e.contains(error), "{}: {}", entry, e);
+        }
+    }
+}